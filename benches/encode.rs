@@ -0,0 +1,61 @@
+use criterion::{criterion_group, criterion_main, Criterion, Throughput};
+use ipp_print::pwgraster::*;
+use std::fs::File;
+use std::io;
+
+/// A4 page at 300dpi with colored bands, so rows both repeat and differ.
+fn page(header: &PageHeader) -> Vec<SrgbColor> {
+    let (width, height) = (header.width() as usize, header.height() as usize);
+    let mut pixels = vec![SrgbColor::new(255, 255, 255); width * height];
+    for y in (0..height).step_by(7) {
+        for x in (y % width..width).step_by(3) {
+            pixels[y * width + x] = SrgbColor::from((x * y) as u32);
+        }
+    }
+    pixels
+}
+
+fn encode_page(c: &mut Criterion) {
+    let header = PageHeader::default();
+    let pixels = page(&header);
+
+    let mut group = c.benchmark_group("encode_page");
+    group.throughput(Throughput::Bytes(
+        header.bytes_per_line() as u64 * header.height() as u64,
+    ));
+    group.sample_size(20);
+
+    group.bench_function("sink", |b| {
+        b.iter(|| {
+            let mut writer =
+                RasterDocumentWriter::new(io::sink(), header.clone(), SheetBack::Normal).unwrap();
+            writer.write_page(&pixels).unwrap();
+        })
+    });
+
+    #[cfg(feature = "rayon")]
+    group.bench_function("sink_parallel", |b| {
+        b.iter(|| {
+            let mut writer =
+                RasterDocumentWriter::new(io::sink(), header.clone(), SheetBack::Normal).unwrap();
+            writer.write_page_parallel(&pixels).unwrap();
+        })
+    });
+
+    // Unbuffered writes to a file cost a system call each.
+    let path = std::env::temp_dir().join(format!("ipp-print-bench-{}.pwg", std::process::id()));
+    group.bench_function("file", |b| {
+        b.iter(|| {
+            let file = File::create(&path).unwrap();
+            let mut writer =
+                RasterDocumentWriter::new(file, header.clone(), SheetBack::Normal).unwrap();
+            writer.write_page(&pixels).unwrap();
+        })
+    });
+    let _ = std::fs::remove_file(&path);
+
+    group.finish();
+}
+
+criterion_group!(benches, encode_page);
+criterion_main!(benches);
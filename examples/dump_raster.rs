@@ -0,0 +1,44 @@
+use ipp_print::pwgraster::PwgDocument;
+use std::error::Error;
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let path = std::env::args()
+        .nth(1)
+        .ok_or("usage: dump_raster FILE.pwg|FILE.ras [PNG_PREFIX]")?;
+    let mut f = std::io::BufReader::new(std::fs::File::open(path)?);
+    let doc = PwgDocument::read_from_stream(&mut f)?;
+
+    println!(
+        "SyncWord={}",
+        String::from_utf8_lossy(doc.sync_word.bytes())
+    );
+    for (i, page) in doc.pages.iter().enumerate() {
+        let header = &page.header;
+        let [x_dpi, y_dpi] = header.resolution();
+        println!("page {}:", i + 1);
+        println!("  PageSizeName={}", header.page_size_name());
+        println!("  MediaType={}", header.media_type());
+        println!("  MediaColor={}", header.media_color());
+        println!("  PrintContentOptimize={}", header.print_content_optimize());
+        println!("  RenderingIntent={}", header.rendering_intent());
+        println!("  HWResolution={}x{}", x_dpi, y_dpi);
+        println!("  Width={} Height={}", header.width(), header.height());
+        println!("  BitsPerPixel={}", header.bits_per_pixel());
+        println!("  BytesPerLine={}", header.bytes_per_line());
+        println!("  ColorSpace={:?}", header.color_space());
+        let (vendor_id, vendor_data) = header.vendor_data();
+        println!(
+            "  VendorIdentifier={:#06x} VendorLength={}",
+            vendor_id,
+            vendor_data.len()
+        );
+        println!("  rows={}", page.rows.len());
+
+        // With the image feature, pages are also saved as PREFIX-N.png.
+        #[cfg(feature = "image")]
+        if let Some(prefix) = std::env::args().nth(2) {
+            page.to_png(format!("{}-{}.png", prefix, i + 1))?;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,8 @@
+#![no_main]
+
+use ipp_print::ipp::IPPResponse;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = IPPResponse::read_from_stream(&mut &data[..]);
+});
@@ -0,0 +1,8 @@
+#![no_main]
+
+use ipp_print::pwgraster::PwgDocument;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = PwgDocument::read_from_stream(&mut &data[..]);
+});
@@ -0,0 +1,85 @@
+use crate::error::IppPrintError;
+
+/// Supplies credentials for every request sent to the printer.
+pub trait AuthProvider: Send {
+    /// Value of the Authorization header, like "Bearer TOKEN".
+    fn authorization(&mut self) -> Result<String, IppPrintError>;
+
+    /// Called when the printer rejected the credentials with HTTP 401.
+    /// Returns true if new credentials are available and the request should be retried.
+    fn refresh(&mut self) -> Result<bool, IppPrintError> {
+        Ok(false)
+    }
+}
+
+/// A token which never changes.
+#[derive(Debug, Clone)]
+pub struct BearerToken(pub String);
+
+impl AuthProvider for BearerToken {
+    fn authorization(&mut self) -> Result<String, IppPrintError> {
+        Ok(format!("Bearer {}", self.0))
+    }
+}
+
+/// A token obtained from a callback, e.g. an OAuth 2.0 refresh token grant.
+/// The callback is called for the first request and whenever the token is rejected.
+pub struct RefreshingToken<F> {
+    fetch_token: F,
+    token: Option<String>,
+}
+
+impl<F> RefreshingToken<F>
+where
+    F: FnMut() -> Result<String, IppPrintError> + Send,
+{
+    pub fn new(fetch_token: F) -> Self {
+        Self {
+            fetch_token,
+            token: None,
+        }
+    }
+}
+
+impl<F> AuthProvider for RefreshingToken<F>
+where
+    F: FnMut() -> Result<String, IppPrintError> + Send,
+{
+    fn authorization(&mut self) -> Result<String, IppPrintError> {
+        if self.token.is_none() {
+            self.token = Some((self.fetch_token)()?);
+        }
+        Ok(format!("Bearer {}", self.token.as_ref().unwrap()))
+    }
+
+    fn refresh(&mut self) -> Result<bool, IppPrintError> {
+        self.token = Some((self.fetch_token)()?);
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bearer_token() {
+        let mut auth = BearerToken("abc".to_string());
+        assert_eq!("Bearer abc", auth.authorization().unwrap());
+        assert!(!auth.refresh().unwrap());
+    }
+
+    #[test]
+    fn refreshing_token() {
+        let mut count = 0;
+        let mut auth = RefreshingToken::new(move || {
+            count += 1;
+            Ok(format!("token{}", count))
+        });
+
+        assert_eq!("Bearer token1", auth.authorization().unwrap());
+        assert_eq!("Bearer token1", auth.authorization().unwrap());
+        assert!(auth.refresh().unwrap());
+        assert_eq!("Bearer token2", auth.authorization().unwrap());
+    }
+}
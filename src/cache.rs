@@ -0,0 +1,190 @@
+//! Responses of Get-Printer-Attributes kept for a while, so that printing
+//! doesn't wait for all attributes of a slow printer every time.
+//!
+//! Entries are keyed by printer-uuid, so the same printer reached by another
+//! address shares them, and another printer at the same address doesn't get
+//! them. They are also dropped when printer-config-change-time changes; see
+//! [`crate::ipp::PrintClient::set_attribute_cache`].
+
+use crate::error::IppPrintError;
+use crate::ipp::IPPResponse;
+use std::collections::HashMap;
+use std::io;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
+/// Encoded responses by printer-uuid, in memory and optionally in files.
+#[derive(Debug, Clone)]
+pub struct AttributeCache {
+    ttl: Duration,
+    /// Directory of files named after the UUID, with the time they were
+    /// fetched as the modification time.
+    dir: Option<PathBuf>,
+    entries: HashMap<String, (SystemTime, Vec<u8>)>,
+}
+
+impl AttributeCache {
+    /// Keeps responses in memory for ttl.
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            dir: None,
+            entries: HashMap::new(),
+        }
+    }
+
+    /// Also keeps responses in files in dir, so that they outlive the process.
+    pub fn with_dir(ttl: Duration, dir: PathBuf) -> Self {
+        Self {
+            dir: Some(dir),
+            ..Self::new(ttl)
+        }
+    }
+
+    /// $XDG_CACHE_HOME/ipp-print/printers, or ~/.cache/ipp-print/printers.
+    pub fn default_dir() -> Option<PathBuf> {
+        let cache_home = match std::env::var_os("XDG_CACHE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+        };
+        Some(cache_home.join("ipp-print").join("printers"))
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    /// File of the printer, if the UUID is safe to use as a file name.
+    fn path(&self, uuid: &str) -> Option<PathBuf> {
+        let name = uuid.strip_prefix("urn:uuid:").unwrap_or(uuid);
+        if name.is_empty() || !name.bytes().all(|e| e.is_ascii_alphanumeric() || e == b'-') {
+            return None;
+        }
+        Some(self.dir.as_ref()?.join(format!("{}.ipp", name)))
+    }
+
+    /// The response for the printer, unless it was fetched ttl or more ago.
+    /// Files which can't be read or decoded are taken as missing.
+    pub fn get(&mut self, uuid: &str) -> Option<IPPResponse> {
+        if !self.entries.contains_key(uuid) {
+            let path = self.path(uuid)?;
+            let fetched = std::fs::metadata(&path).and_then(|e| e.modified()).ok()?;
+            let data = std::fs::read(&path).ok()?;
+            self.entries.insert(uuid.to_string(), (fetched, data));
+        }
+
+        let (fetched, data) = self.entries.get(uuid)?;
+        // A clock set back makes the entry stale rather than fresh forever.
+        let age = SystemTime::now().duration_since(*fetched).ok()?;
+        if age >= self.ttl {
+            return None;
+        }
+        IPPResponse::read_from_stream(&mut data.as_slice()).ok()
+    }
+
+    /// Keeps the response fetched now, replacing the one of the printer.
+    pub fn insert(&mut self, uuid: &str, response: &IPPResponse) -> Result<(), IppPrintError> {
+        let mut data = vec![];
+        response.write_to_stream(&mut data)?;
+        if let (Some(dir), Some(path)) = (&self.dir, self.path(uuid)) {
+            std::fs::create_dir_all(dir)?;
+            std::fs::write(path, &data)?;
+        }
+        self.entries
+            .insert(uuid.to_string(), (SystemTime::now(), data));
+        Ok(())
+    }
+
+    /// Drops the response of the printer, e.g. after changing its settings.
+    pub fn invalidate(&mut self, uuid: &str) -> Result<(), IppPrintError> {
+        self.entries.remove(uuid);
+        if let Some(path) = self.path(uuid) {
+            remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Drops the responses of all printers.
+    pub fn clear(&mut self) -> Result<(), IppPrintError> {
+        self.entries.clear();
+        let entries = match self.dir.as_ref().map(std::fs::read_dir) {
+            Some(Ok(entries)) => entries,
+            Some(Err(err)) if err.kind() != io::ErrorKind::NotFound => return Err(err.into()),
+            _ => return Ok(()),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "ipp") {
+                remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Removes the file, which may already be gone.
+fn remove_file(path: PathBuf) -> Result<(), IppPrintError> {
+    match std::fs::remove_file(path) {
+        Err(err) if err.kind() != io::ErrorKind::NotFound => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipp::{AttributeValue, DelimiterOrValueTag, StatusCode};
+
+    fn response(name: &str) -> IPPResponse {
+        IPPResponse {
+            version_major: 2,
+            version_minor: 0,
+            status_code: StatusCode::SuccessfulOk,
+            request_id: 1,
+            attrs: vec![(
+                DelimiterOrValueTag::PrinterAttributesTag,
+                vec![(
+                    "printer-name".to_string(),
+                    AttributeValue::NameWithoutLanguage(name.to_string()),
+                )],
+            )],
+            data: vec![],
+        }
+    }
+
+    #[test]
+    fn cache_in_files() {
+        let dir = std::env::temp_dir().join(format!("ipp-print-cache-{}", std::process::id()));
+        let uuid = "urn:uuid:e3248000-80ce-11db-8000-30055c773bcf";
+
+        let mut cache = AttributeCache::with_dir(Duration::from_secs(60), dir.clone());
+        assert_eq!(None, cache.get(uuid));
+        cache.insert(uuid, &response("office")).unwrap();
+        assert!(dir
+            .join("e3248000-80ce-11db-8000-30055c773bcf.ipp")
+            .exists());
+        // UUIDs which aren't file names are only kept in memory.
+        cache.insert("../x", &response("other")).unwrap();
+        assert_eq!(Some(response("other")), cache.get("../x"));
+
+        let mut cache = AttributeCache::with_dir(Duration::from_secs(60), dir.clone());
+        assert_eq!(Some(response("office")), cache.get(uuid));
+        assert_eq!(None, cache.get("../x"));
+        // Responses are stale after ttl.
+        assert_eq!(
+            None,
+            AttributeCache::with_dir(Duration::ZERO, dir.clone()).get(uuid)
+        );
+
+        cache.invalidate(uuid).unwrap();
+        assert_eq!(None, cache.get(uuid));
+        cache.insert(uuid, &response("office")).unwrap();
+        cache.clear().unwrap();
+        assert_eq!(
+            None,
+            AttributeCache::with_dir(Duration::from_secs(60), dir.clone()).get(uuid)
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
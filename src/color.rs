@@ -0,0 +1,343 @@
+#[cfg(feature = "icc")]
+use crate::error::IppPrintError;
+#[cfg(feature = "icc")]
+use crate::pwgraster::PageHeader;
+use crate::pwgraster::{Pixel, SrgbColor};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct CmykColor {
+    pub c: u8,
+    pub m: u8,
+    pub y: u8,
+    pub k: u8,
+}
+
+impl CmykColor {
+    pub fn new(c: u8, m: u8, y: u8, k: u8) -> Self {
+        Self { c, m, y, k }
+    }
+
+    /// Sum of all colorants in percent (0..=400).
+    pub fn total_coverage(&self) -> u32 {
+        (self.c as u32 + self.m as u32 + self.y as u32 + self.k as u32) * 100 / 255
+    }
+}
+
+/// cmyk_8 pixels (color space 6) are 0 for no ink and 255 for full coverage.
+impl Pixel for CmykColor {
+    const BITS_PER_PIXEL: u32 = 32;
+    const WHITE: Self = CmykColor {
+        c: 0,
+        m: 0,
+        y: 0,
+        k: 0,
+    };
+    const BLACK: Self = CmykColor {
+        c: 0,
+        m: 0,
+        y: 0,
+        k: 255,
+    };
+
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&[self.c, self.m, self.y, self.k]);
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CmykConverter {
+    /// Maximum total area coverage in percent.
+    /// Production devices typically want something around 240..=320.
+    /// 400 disables the limit.
+    pub total_ink_limit: u32,
+    /// How much of the gray component (min of C, M, Y) is generated as K,
+    /// in percent. 0 prints neutrals with CMY only.
+    pub black_generation: u32,
+    /// How much of the generated K is removed from C, M, and Y, in percent.
+    /// 100 gives pure-K neutrals (crisp text on lasers),
+    /// 0 keeps full CMY under the K (rich black).
+    pub under_color_removal: u32,
+}
+
+impl Default for CmykConverter {
+    fn default() -> Self {
+        Self {
+            total_ink_limit: 400,
+            black_generation: 100,
+            under_color_removal: 100,
+        }
+    }
+}
+
+impl CmykConverter {
+    pub fn convert(&self, color: &SrgbColor) -> CmykColor {
+        let c = 255 - color.r as u32;
+        let m = 255 - color.g as u32;
+        let y = 255 - color.b as u32;
+        let gray = c.min(m).min(y);
+        let k = gray * self.black_generation.min(100) / 100;
+        let ucr = k * self.under_color_removal.min(100) / 100;
+
+        let (c, m, y, k) = self.limit_coverage(c - ucr, m - ucr, y - ucr, k);
+
+        CmykColor::new(c as u8, m as u8, y as u8, k as u8)
+    }
+
+    /// Scales down CMY (keeping K, which matters most for sharp text)
+    /// until the sum of all channels fits in total_ink_limit.
+    fn limit_coverage(&self, c: u32, m: u32, y: u32, k: u32) -> (u32, u32, u32, u32) {
+        let limit = self.total_ink_limit.min(400) * 255 / 100;
+        if c + m + y + k <= limit {
+            return (c, m, y, k);
+        }
+        if k >= limit {
+            return (0, 0, 0, limit);
+        }
+
+        let cmy = c + m + y;
+        let avail = limit - k;
+        (c * avail / cmy, m * avail / cmy, y * avail / cmy, k)
+    }
+}
+
+/// How colors out of the destination gamut are mapped, as in ICC profiles.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub enum RenderingIntent {
+    /// Compresses the whole gamut, keeping relations between colors (photos).
+    #[default]
+    Perceptual,
+    /// Keeps colors in gamut as they are and clips the others.
+    RelativeColorimetric,
+    /// Keeps colors vivid (charts and graphics).
+    Saturation,
+    /// Like RelativeColorimetric, but simulating the paper white too.
+    AbsoluteColorimetric,
+}
+
+impl RenderingIntent {
+    /// Value of the RenderingIntent header field.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Perceptual => "Perceptual",
+            Self::RelativeColorimetric => "RelativeColorimetric",
+            Self::Saturation => "Saturation",
+            Self::AbsoluteColorimetric => "AbsoluteColorimetric",
+        }
+    }
+}
+
+/// Converts sRGB pixels to the color space of a printer or to Adobe RGB before
+/// encoding them. Converted pixels are still SrgbColor, as they are 8-bit RGB.
+#[cfg(feature = "icc")]
+pub struct ColorTransform {
+    transform: qcms::Transform,
+    intent: RenderingIntent,
+}
+
+#[cfg(feature = "icc")]
+impl ColorTransform {
+    /// Conversion to an RGB ICC profile, e.g. one of printer-icc-profiles.
+    pub fn from_icc(profile: &[u8], intent: RenderingIntent) -> Result<Self, IppPrintError> {
+        let mut output = qcms::Profile::new_from_slice(profile, false).ok_or_else(|| {
+            IppPrintError::Unsupported("ICC profile which can't be read".to_string())
+        })?;
+        output.precache_output_transform();
+        ColorTransform::new(&output, intent)
+    }
+
+    /// Conversion to Adobe RGB (1998) for adobe-rgb_8 raster.
+    pub fn to_adobe_rgb(intent: RenderingIntent) -> Result<Self, IppPrintError> {
+        let xy = |x, y| qcms::CIE_xyY { x, y, Y: 1.0 };
+        let primaries = qcms::CIE_xyYTRIPLE {
+            red: xy(0.64, 0.33),
+            green: xy(0.21, 0.71),
+            blue: xy(0.15, 0.06),
+        };
+        let gamma = 563.0 / 256.0;
+        let output = qcms::Profile::new_rgb_with_gamma_set(
+            xy(0.3127, 0.3290),
+            primaries,
+            gamma,
+            gamma,
+            gamma,
+        )
+        .ok_or_else(|| {
+            IppPrintError::NotAvailable("Adobe RGB profile can't be created".to_string())
+        })?;
+        ColorTransform::new(&output, intent)
+    }
+
+    fn new(output: &qcms::Profile, intent: RenderingIntent) -> Result<Self, IppPrintError> {
+        let qcms_intent = match intent {
+            RenderingIntent::Perceptual => qcms::Intent::Perceptual,
+            RenderingIntent::RelativeColorimetric => qcms::Intent::RelativeColorimetric,
+            RenderingIntent::Saturation => qcms::Intent::Saturation,
+            RenderingIntent::AbsoluteColorimetric => qcms::Intent::AbsoluteColorimetric,
+        };
+        let transform = qcms::Transform::new(
+            &qcms::Profile::new_sRGB(),
+            output,
+            qcms::DataType::RGB8,
+            qcms_intent,
+        )
+        .ok_or_else(|| {
+            IppPrintError::NotAvailable(
+                "conversion from sRGB to the ICC profile can't be created".to_string(),
+            )
+        })?;
+
+        Ok(Self { transform, intent })
+    }
+
+    /// Sets the rendering intent of the header to the one of the conversion.
+    pub fn apply(&self, header: &mut PageHeader) -> Result<(), IppPrintError> {
+        header.set_rendering_intent(self.intent.name())
+    }
+
+    pub fn convert(&self, pixels: &[SrgbColor]) -> Vec<SrgbColor> {
+        let mut bytes = Vec::with_capacity(pixels.len() * 3);
+        SrgbColor::write_row(pixels, &mut bytes);
+        self.transform.apply(&mut bytes);
+        bytes
+            .chunks_exact(3)
+            .map(|e| SrgbColor::new(e[0], e[1], e[2]))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_without_limit() {
+        let conv = CmykConverter::default();
+        assert_eq!(
+            CmykColor::new(0, 0, 0, 0),
+            conv.convert(&SrgbColor::new(255, 255, 255))
+        );
+        assert_eq!(
+            CmykColor::new(0, 0, 0, 255),
+            conv.convert(&SrgbColor::new(0, 0, 0))
+        );
+        assert_eq!(
+            CmykColor::new(0, 255, 255, 0),
+            conv.convert(&SrgbColor::new(255, 0, 0))
+        );
+        assert_eq!(
+            CmykColor::new(0, 128, 128, 127),
+            conv.convert(&SrgbColor::new(128, 0, 0))
+        );
+    }
+
+    #[test]
+    fn convert_with_total_ink_limit() {
+        let conv = CmykConverter {
+            total_ink_limit: 100,
+            ..Default::default()
+        };
+
+        let color = conv.convert(&SrgbColor::new(128, 0, 0));
+        assert!(color.total_coverage() <= 100);
+        assert_eq!(127, color.k);
+        assert_eq!(color.m, color.y);
+
+        // Below the limit, nothing changes.
+        assert_eq!(
+            CmykColor::new(0, 255, 0, 0),
+            conv.convert(&SrgbColor::new(255, 0, 255))
+        );
+    }
+
+    #[test]
+    fn limit_below_black() {
+        let conv = CmykConverter {
+            total_ink_limit: 80,
+            ..Default::default()
+        };
+        assert_eq!(
+            CmykColor::new(0, 0, 0, 204),
+            conv.convert(&SrgbColor::new(0, 0, 0))
+        );
+    }
+
+    #[test]
+    fn black_generation() {
+        let conv = CmykConverter {
+            black_generation: 0,
+            ..Default::default()
+        };
+        assert_eq!(
+            CmykColor::new(255, 255, 255, 0),
+            conv.convert(&SrgbColor::new(0, 0, 0))
+        );
+
+        let conv = CmykConverter {
+            black_generation: 50,
+            ..Default::default()
+        };
+        assert_eq!(
+            CmykColor::new(128, 128, 128, 127),
+            conv.convert(&SrgbColor::new(0, 0, 0))
+        );
+    }
+
+    #[test]
+    fn cmyk_pixels() {
+        let mut buf = vec![];
+        CmykColor::write_row(
+            &[CmykColor::new(1, 2, 3, 4), CmykColor::new(5, 6, 7, 8)],
+            &mut buf,
+        );
+        assert_eq!(vec![1, 2, 3, 4, 5, 6, 7, 8], buf);
+    }
+
+    #[test]
+    fn rich_black() {
+        let conv = CmykConverter {
+            under_color_removal: 0,
+            ..Default::default()
+        };
+        assert_eq!(
+            CmykColor::new(255, 255, 255, 255),
+            conv.convert(&SrgbColor::new(0, 0, 0))
+        );
+
+        // Rich black still obeys the ink limit.
+        let conv = CmykConverter {
+            under_color_removal: 0,
+            total_ink_limit: 250,
+            ..Default::default()
+        };
+        let color = conv.convert(&SrgbColor::new(0, 0, 0));
+        assert_eq!(255, color.k);
+        assert!(color.total_coverage() <= 250);
+    }
+
+    #[cfg(feature = "icc")]
+    #[test]
+    fn convert_to_adobe_rgb() {
+        let transform =
+            ColorTransform::to_adobe_rgb(RenderingIntent::RelativeColorimetric).unwrap();
+        let converted = transform.convert(&[
+            SrgbColor::new(255, 255, 255),
+            SrgbColor::new(0, 0, 0),
+            SrgbColor::new(0, 255, 0),
+        ]);
+        assert_eq!(SrgbColor::new(255, 255, 255), converted[0]);
+        assert_eq!(SrgbColor::new(0, 0, 0), converted[1]);
+        // sRGB green is inside the wider Adobe RGB gamut, so it's no longer pure green.
+        let green = &converted[2];
+        assert!(
+            (140..150).contains(&green.r) && green.g == 255 && (55..65).contains(&green.b),
+            "{:?}",
+            green
+        );
+
+        let mut header = PageHeader::default();
+        transform.apply(&mut header).unwrap();
+        assert_eq!("RelativeColorimetric", header.rendering_intent());
+
+        assert!(ColorTransform::from_icc(b"not a profile", RenderingIntent::Perceptual).is_err());
+    }
+}
@@ -0,0 +1,187 @@
+//! Configuration file of the ipp-print command with named printers.
+//!
+//! ```toml
+//! default-printer = "office"
+//!
+//! [printers.office]
+//! uri = "ipps://192.0.2.1/ipp/print"
+//! token = "secret"
+//! media = "iso_a4_210x297mm"
+//! color-mode = "monochrome"
+//! resolution = 600
+//! cache-ttl = 3600
+//! ```
+
+use crate::error::IppPrintError;
+use crate::ipp::PrintColorMode;
+use crate::transport::PrinterUri;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Printers by name and which one to use by default.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Config {
+    pub default_printer: Option<String>,
+    pub printers: BTreeMap<String, PrinterProfile>,
+}
+
+/// A printer and the defaults for jobs sent to it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PrinterProfile {
+    pub uri: PrinterUri,
+    /// Bearer token sent to the printer
+    pub token: Option<String>,
+    /// Media name like "iso_a4_210x297mm"
+    pub media: Option<String>,
+    pub color_mode: Option<PrintColorMode>,
+    /// Resolution in dpi, same in both directions
+    pub resolution: Option<u32>,
+    /// How long printer attributes are cached, or not cached if None
+    pub cache_ttl: Option<Duration>,
+}
+
+/// Config as written in the file, before values are parsed.
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct ConfigFile {
+    default_printer: Option<String>,
+    #[serde(default)]
+    printers: BTreeMap<String, ProfileFile>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+struct ProfileFile {
+    uri: String,
+    token: Option<String>,
+    media: Option<String>,
+    color_mode: Option<String>,
+    resolution: Option<u32>,
+    /// Seconds
+    cache_ttl: Option<u64>,
+}
+
+impl Config {
+    /// $XDG_CONFIG_HOME/ipp-print/config.toml, or ~/.config/ipp-print/config.toml.
+    pub fn default_path() -> Option<PathBuf> {
+        let config_home = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(config_home.join("ipp-print").join("config.toml"))
+    }
+
+    /// Reads the file at path. A missing file is an empty config.
+    pub fn load(path: &Path) -> Result<Self, IppPrintError> {
+        match std::fs::read_to_string(path) {
+            Ok(content) => Self::parse(&content),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn parse(content: &str) -> Result<Self, IppPrintError> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+        let file = toml::from_str::<ConfigFile>(content).map_err(|e| invalid(e.to_string()))?;
+        let mut printers = BTreeMap::new();
+        for (name, profile) in file.printers {
+            let color_mode = match profile.color_mode {
+                Some(keyword) => Some(PrintColorMode::from_keyword(&keyword).ok_or_else(|| {
+                    invalid(format!("unknown color-mode of {}: {}", name, keyword))
+                })?),
+                None => None,
+            };
+            let profile = PrinterProfile {
+                uri: PrinterUri::parse(&profile.uri)?,
+                token: profile.token,
+                media: profile.media,
+                color_mode,
+                resolution: profile.resolution,
+                cache_ttl: profile.cache_ttl.map(Duration::from_secs),
+            };
+            printers.insert(name, profile);
+        }
+
+        if let Some(name) = &file.default_printer {
+            if !printers.contains_key(name) {
+                return Err(invalid(format!("default-printer {} is not in printers", name)).into());
+            }
+        }
+
+        Ok(Self {
+            default_printer: file.default_printer,
+            printers,
+        })
+    }
+
+    /// The printer named name, or the default printer if name is None.
+    pub fn printer(&self, name: Option<&str>) -> Result<Option<&PrinterProfile>, IppPrintError> {
+        match name.or(self.default_printer.as_deref()) {
+            Some(name) => match self.printers.get(name) {
+                Some(profile) => Ok(Some(profile)),
+                None => Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("no printer named {} in the config", name),
+                )
+                .into()),
+            },
+            None => Ok(None),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_config() {
+        let config = Config::parse(
+            r#"
+            default-printer = "office"
+
+            [printers.office]
+            uri = "ipps://192.0.2.1/ipp/print"
+            token = "secret"
+            color-mode = "monochrome"
+            resolution = 600
+            cache-ttl = 3600
+
+            [printers.label]
+            uri = "192.0.2.2"
+            media = "oe_4x6-label_4x6in"
+            "#,
+        )
+        .unwrap();
+
+        let office = config.printer(None).unwrap().unwrap();
+        assert_eq!("ipps://192.0.2.1:631/ipp/print", office.uri.to_string());
+        assert_eq!(Some("secret"), office.token.as_deref());
+        assert_eq!(Some(PrintColorMode::Monochrome), office.color_mode);
+        assert_eq!(Some(600), office.resolution);
+        assert_eq!(Some(Duration::from_secs(3600)), office.cache_ttl);
+
+        let label = config.printer(Some("label")).unwrap().unwrap();
+        assert_eq!(Some("oe_4x6-label_4x6in"), label.media.as_deref());
+        assert_eq!(None, label.color_mode);
+        assert!(config.printer(Some("home")).is_err());
+
+        assert_eq!(None, Config::default().printer(None).unwrap());
+    }
+
+    #[test]
+    fn invalid_config() {
+        for content in [
+            "[printers.a]\nuri = \"http://192.0.2.1\"",
+            "[printers.a]\nuri = \"192.0.2.1\"\ncolor-mode = \"sepia\"",
+            "[printers.a]\nurl = \"192.0.2.1\"",
+            "default-printer = \"b\"\n[printers.a]\nuri = \"192.0.2.1\"",
+        ] {
+            assert!(Config::parse(content).is_err(), "{}", content);
+        }
+    }
+}
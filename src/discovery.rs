@@ -0,0 +1,175 @@
+//! Printer discovery with mDNS/DNS-SD (RFC 6762, RFC 6763).
+//!
+//! IPP printers advertise _ipp._tcp and _ipps._tcp services, with TXT records
+//! describing them as in the Bonjour Printing Specification and PWG5100.14.
+
+use crate::error::IppPrintError;
+use crate::transport::PrinterUri;
+use mdns_sd::{ServiceDaemon, ServiceEvent, ServiceInfo};
+use std::collections::BTreeMap;
+use std::io;
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+/// Service types to browse and whether they use TLS.
+const SERVICE_TYPES: &[(&str, bool)] = &[("_ipps._tcp.local.", true), ("_ipp._tcp.local.", false)];
+
+/// A printer found on the local network.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct DiscoveredPrinter {
+    /// Service instance name like "Office Printer"
+    pub name: String,
+    pub uri: PrinterUri,
+    /// Make and model from the ty key
+    pub make_and_model: Option<String>,
+    /// Document formats from the pdl key, like "image/pwg-raster"
+    pub document_formats: Vec<String>,
+    /// UUID from the UUID key, which is the same for every service of the printer
+    pub uuid: Option<String>,
+    /// Whether the printer can print in color, from the Color key
+    pub color: Option<bool>,
+    /// Whether the printer can print on both sides, from the Duplex key
+    pub duplex: Option<bool>,
+}
+
+impl DiscoveredPrinter {
+    /// txt returns the value of a TXT key, ignoring case of the key.
+    fn from_service<F>(instance_name: &str, secure: bool, host: String, port: u16, txt: F) -> Self
+    where
+        F: Fn(&str) -> Option<String>,
+    {
+        let boolean = |key| txt(key).map(|e| e.eq_ignore_ascii_case("T"));
+        let path = txt("rp").unwrap_or_else(|| "ipp/print".to_string());
+
+        Self {
+            name: instance_name.to_string(),
+            uri: PrinterUri {
+                secure,
+                host,
+                port,
+                path: format!("/{}", path.trim_start_matches('/')),
+            },
+            make_and_model: txt("ty"),
+            document_formats: txt("pdl")
+                .map(|e| e.split(',').map(|e| e.trim().to_string()).collect())
+                .unwrap_or_default(),
+            uuid: txt("UUID"),
+            color: boolean("Color"),
+            duplex: boolean("Duplex"),
+        }
+    }
+
+    fn from_info(info: &ServiceInfo, service_type: &str, secure: bool) -> Self {
+        let instance_name = info
+            .get_fullname()
+            .strip_suffix(service_type)
+            .map_or(info.get_fullname(), |e| e.trim_end_matches('.'));
+        let host = choose_host(info.get_addresses().iter(), info.get_hostname());
+        Self::from_service(instance_name, secure, host, info.get_port(), |key| {
+            info.get_property_val_str(key).map(str::to_string)
+        })
+    }
+}
+
+/// An IPv4 address if any, since link-local IPv6 addresses need a scope, or
+/// the host name.
+fn choose_host<'a, I>(addresses: I, hostname: &str) -> String
+where
+    I: Iterator<Item = &'a IpAddr>,
+{
+    let mut addresses = addresses.collect::<Vec<_>>();
+    addresses.sort_by_key(|e| (!e.is_ipv4(), *e));
+    match addresses.first() {
+        Some(IpAddr::V4(addr)) => addr.to_string(),
+        Some(IpAddr::V6(addr)) if (addr.segments()[0] & 0xffc0) != 0xfe80 => {
+            format!("[{}]", addr)
+        }
+        _ => hostname.trim_end_matches('.').to_string(),
+    }
+}
+
+/// Browses the network for timeout and returns printers sorted by name.
+/// A printer advertising both ipp and ipps is returned once with ipps.
+pub fn discover(timeout: Duration) -> Result<Vec<DiscoveredPrinter>, IppPrintError> {
+    let mdns_error = |e: mdns_sd::Error| io::Error::other(format!("mdns: {}", e));
+
+    let daemon = ServiceDaemon::new().map_err(mdns_error)?;
+    let receivers = SERVICE_TYPES
+        .iter()
+        .map(|(service_type, secure)| {
+            Ok((
+                daemon.browse(service_type).map_err(mdns_error)?,
+                *service_type,
+                *secure,
+            ))
+        })
+        .collect::<Result<Vec<_>, IppPrintError>>()?;
+
+    let mut printers = BTreeMap::<String, DiscoveredPrinter>::new();
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+        for (receiver, service_type, secure) in &receivers {
+            while let Ok(event) = receiver.try_recv() {
+                if let ServiceEvent::ServiceResolved(info) = event {
+                    let printer = DiscoveredPrinter::from_info(&info, service_type, *secure);
+                    if !printers.get(&printer.name).is_some_and(|e| e.uri.secure) {
+                        printers.insert(printer.name.clone(), printer);
+                    }
+                }
+            }
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+    // Shutting down only stops the daemon thread, so an error is of no interest.
+    let _ = daemon.shutdown();
+
+    Ok(printers.into_values().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    #[test]
+    fn parse_service() {
+        let txt = HashMap::from([
+            ("rp", "ipp/print"),
+            ("ty", "Example Printer"),
+            ("pdl", "image/urf,image/pwg-raster"),
+            ("uuid", "e3248000-80ce-11db-8000-30055c773bcf"),
+            ("color", "F"),
+            ("duplex", "T"),
+        ]);
+        let printer =
+            DiscoveredPrinter::from_service("Office", true, "192.0.2.1".to_string(), 631, |key| {
+                txt.get(key.to_lowercase().as_str()).map(|e| e.to_string())
+            });
+        assert_eq!("ipps://192.0.2.1:631/ipp/print", printer.uri.to_string());
+        assert_eq!(Some("Example Printer"), printer.make_and_model.as_deref());
+        assert_eq!(
+            vec!["image/urf", "image/pwg-raster"],
+            printer.document_formats
+        );
+        assert!(printer.uuid.is_some());
+        assert_eq!((Some(false), Some(true)), (printer.color, printer.duplex));
+
+        let printer =
+            DiscoveredPrinter::from_service("Old", false, "printer.local".to_string(), 631, |_| {
+                None
+            });
+        assert_eq!("ipp://printer.local:631/ipp/print", printer.uri.to_string());
+        assert_eq!(None, printer.color);
+    }
+
+    #[test]
+    fn choose_address() {
+        let v4: IpAddr = "192.0.2.1".parse().unwrap();
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+        let link_local: IpAddr = "fe80::1".parse().unwrap();
+        assert_eq!("192.0.2.1", choose_host([v6, v4].iter(), "p.local."));
+        assert_eq!("[2001:db8::1]", choose_host([v6].iter(), "p.local."));
+        assert_eq!("p.local", choose_host([link_local].iter(), "p.local."));
+        assert_eq!("p.local", choose_host([].iter(), "p.local."));
+    }
+}
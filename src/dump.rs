@@ -0,0 +1,374 @@
+//! Decoding captured IPP messages into a readable listing, like ipptool or Wireshark.
+//!
+//! Works with files written by [`crate::ipp::PrintClient::set_capture_dir`] as well as
+//! HTTP bodies exported from packet captures.
+
+use crate::ipp::{
+    AttributeGroup, AttributeValue, DelimiterOrValueTag, IPPError, IPPResponse, PrinterOperation,
+    StatusCode,
+};
+use std::fmt;
+
+/// Whether a message is a request or a response.
+/// Both have the same layout and only differ in the meaning of the operation-id/status-code.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MessageKind {
+    Request,
+    Response,
+}
+
+/// IPP message of either kind.
+#[derive(Debug, PartialEq, Eq)]
+pub struct IppMessage {
+    pub kind: MessageKind,
+    pub version_major: i8,
+    pub version_minor: i8,
+    /// operation-id of requests or status-code of responses.
+    pub code: u16,
+    pub request_id: i32,
+    pub attrs: Vec<AttributeGroup>,
+    pub data: Vec<u8>,
+}
+
+/// Decodes a message. If kind is None, it is guessed from the operation attributes,
+/// since only requests have a target like printer-uri.
+/// HTTP headers before the message, as in a followed TCP stream, are skipped.
+pub fn decode(buf: &[u8], kind: Option<MessageKind>) -> Result<IppMessage, IPPError> {
+    let body = strip_http(buf)?;
+    let (header, attrs, data) = IPPResponse::read_message(&mut body.as_slice())?;
+
+    let kind = kind.unwrap_or_else(|| {
+        let has_target = attrs
+            .iter()
+            .filter(|(tag, _)| *tag == DelimiterOrValueTag::OperationAttributesTag)
+            .flat_map(|(_, attrs)| attrs.iter())
+            .any(|(name, _)| {
+                matches!(
+                    name.as_str(),
+                    "printer-uri" | "job-uri" | "system-uri" | "document-uri"
+                )
+            });
+        if has_target {
+            MessageKind::Request
+        } else {
+            MessageKind::Response
+        }
+    });
+
+    Ok(IppMessage {
+        kind,
+        version_major: header[0] as i8,
+        version_minor: header[1] as i8,
+        code: u16::from_be_bytes([header[2], header[3]]),
+        request_id: i32::from_be_bytes([header[4], header[5], header[6], header[7]]),
+        attrs,
+        data,
+    })
+}
+
+impl From<&IPPResponse> for IppMessage {
+    fn from(resp: &IPPResponse) -> Self {
+        Self {
+            kind: MessageKind::Response,
+            version_major: resp.version_major,
+            version_minor: resp.version_minor,
+            code: u16::from(resp.status_code),
+            request_id: resp.request_id,
+            attrs: resp.attrs.clone(),
+            data: resp.data.clone(),
+        }
+    }
+}
+
+/// Returns the HTTP body if buf starts with an HTTP request or response, or buf itself.
+fn strip_http(buf: &[u8]) -> Result<Vec<u8>, IPPError> {
+    if !buf.starts_with(b"POST ") && !buf.starts_with(b"HTTP/") {
+        return Ok(buf.to_vec());
+    }
+
+    let Some(end) = buf.windows(4).position(|e| e == b"\r\n\r\n") else {
+        return Err(IPPError::ProtocolError);
+    };
+    let head = String::from_utf8_lossy(&buf[..end]).to_ascii_lowercase();
+    let body = &buf[end + 4..];
+    if !head.contains("transfer-encoding: chunked") {
+        return Ok(body.to_vec());
+    }
+
+    let mut result = vec![];
+    let mut rest = body;
+    loop {
+        let Some(line_end) = rest.windows(2).position(|e| e == b"\r\n") else {
+            return Err(IPPError::ProtocolError);
+        };
+        let size = std::str::from_utf8(&rest[..line_end])
+            .ok()
+            .and_then(|e| usize::from_str_radix(e.split(';').next().unwrap().trim(), 16).ok())
+            .ok_or(IPPError::ProtocolError)?;
+        rest = &rest[line_end + 2..];
+        if size == 0 {
+            return Ok(result);
+        }
+        // The size is untrusted, and may be close to usize::MAX.
+        let end = size.checked_add(2).ok_or(IPPError::ProtocolError)?;
+        if rest.len() < end {
+            return Err(IPPError::ProtocolError);
+        }
+        result.extend_from_slice(&rest[..size]);
+        rest = &rest[end..];
+    }
+}
+
+fn group_name(tag: DelimiterOrValueTag) -> &'static str {
+    match tag {
+        DelimiterOrValueTag::OperationAttributesTag => "operation-attributes-tag",
+        DelimiterOrValueTag::JobAttributesTag => "job-attributes-tag",
+        DelimiterOrValueTag::PrinterAttributesTag => "printer-attributes-tag",
+        DelimiterOrValueTag::UnsupportedAttributesTag => "unsupported-attributes-tag",
+        DelimiterOrValueTag::SubscriptionAttributesTag => "subscription-attributes-tag",
+        DelimiterOrValueTag::EventNotificationAttributesTag => "event-notification-attributes-tag",
+        DelimiterOrValueTag::ResourceAttributesTag => "resource-attributes-tag",
+        DelimiterOrValueTag::DocumentAttributesTag => "document-attributes-tag",
+        DelimiterOrValueTag::SystemAttributesTag => "system-attributes-tag",
+        _ => "unknown-tag",
+    }
+}
+
+/// Syntax name as in RFC 8011, e.g. "keyword" or "1setOf integer".
+fn type_name(value: &AttributeValue) -> String {
+    let name = match value {
+        AttributeValue::VectorAttribute(values) => {
+            return match values.first() {
+                Some(first) => format!("1setOf {}", type_name(first)),
+                None => "1setOf".to_string(),
+            }
+        }
+        AttributeValue::Unsupported(_) => "unsupported",
+        AttributeValue::Unknown(_) => "unknown",
+        AttributeValue::NoValue => "no-value",
+        AttributeValue::NotSettable => "not-settable",
+        AttributeValue::DeleteAttribute => "delete-attribute",
+        AttributeValue::AdminDefine => "admin-define",
+        AttributeValue::Integer(_) => "integer",
+        AttributeValue::Boolean(_) => "boolean",
+        AttributeValue::Enum(_) => "enum",
+        AttributeValue::OctetStringUnspecified(_) => "octetString",
+        AttributeValue::DateTime(_) => "dateTime",
+        AttributeValue::Resolution(_) => "resolution",
+        AttributeValue::RangeOfInteger(_) => "rangeOfInteger",
+        AttributeValue::BegCollection
+        | AttributeValue::EndCollection
+        | AttributeValue::CollectionAttribute(_) => "collection",
+        AttributeValue::TextWithLanguage(_) => "textWithLanguage",
+        AttributeValue::NameWithLanguage(_) => "nameWithLanguage",
+        AttributeValue::TextWithoutLanguage(_) => "textWithoutLanguage",
+        AttributeValue::NameWithoutLanguage(_) => "nameWithoutLanguage",
+        AttributeValue::Keyword(_) => "keyword",
+        AttributeValue::Uri(_) => "uri",
+        AttributeValue::UriScheme(_) => "uriScheme",
+        AttributeValue::Charset(_) => "charset",
+        AttributeValue::NaturalLanguage(_) => "naturalLanguage",
+        AttributeValue::MimeMediaType(_) => "mimeMediaType",
+        AttributeValue::MemberAttrName(_) => "memberAttrName",
+    };
+    name.to_string()
+}
+
+fn format_value(value: &AttributeValue) -> String {
+    match value {
+        AttributeValue::VectorAttribute(values) => values
+            .iter()
+            .map(format_value)
+            .collect::<Vec<_>>()
+            .join(","),
+        AttributeValue::CollectionAttribute(members) => {
+            // Sorted, because the order is lost in the map.
+            let mut members = members.iter().collect::<Vec<_>>();
+            members.sort_by_key(|(name, _)| *name);
+            let members = members
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, format_value(value)))
+                .collect::<Vec<_>>();
+            format!("{{{}}}", members.join(" "))
+        }
+        AttributeValue::Unsupported(buf) | AttributeValue::Unknown(buf) => {
+            buf.iter().map(|e| format!("{:02x}", e)).collect()
+        }
+        AttributeValue::NoValue
+        | AttributeValue::NotSettable
+        | AttributeValue::DeleteAttribute
+        | AttributeValue::AdminDefine
+        | AttributeValue::BegCollection
+        | AttributeValue::EndCollection => String::new(),
+        AttributeValue::Integer(val) | AttributeValue::Enum(val) => val.to_string(),
+        AttributeValue::Boolean(val) => val.to_string(),
+        AttributeValue::DateTime(val) => format!(
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{}{}{:02}{:02}",
+            val.year,
+            val.month,
+            val.day,
+            val.hour,
+            val.minutes,
+            val.seconds,
+            val.deci_seconds,
+            val.direction_from_utc,
+            val.hours_from_utc,
+            val.minutes_from_utc
+        ),
+        AttributeValue::Resolution(val) => format!(
+            "{}x{}{}",
+            val.resolution_cross_feed,
+            val.resolution_feed,
+            if val.units == 4 { "dpcm" } else { "dpi" }
+        ),
+        AttributeValue::RangeOfInteger(range) => format!("{}-{}", range.start(), range.end()),
+        AttributeValue::TextWithLanguage(val) | AttributeValue::NameWithLanguage(val) => {
+            format!("{} ({})", val.string, val.lang)
+        }
+        AttributeValue::OctetStringUnspecified(val)
+        | AttributeValue::TextWithoutLanguage(val)
+        | AttributeValue::NameWithoutLanguage(val)
+        | AttributeValue::Keyword(val)
+        | AttributeValue::Uri(val)
+        | AttributeValue::UriScheme(val)
+        | AttributeValue::Charset(val)
+        | AttributeValue::NaturalLanguage(val)
+        | AttributeValue::MimeMediaType(val)
+        | AttributeValue::MemberAttrName(val) => val.clone(),
+    }
+}
+
+impl fmt::Display for IppMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "version: {}.{}", self.version_major, self.version_minor)?;
+        match self.kind {
+            MessageKind::Request => {
+                writeln!(f, "operation: {}", PrinterOperation::from(self.code))?;
+            }
+            MessageKind::Response => {
+                writeln!(f, "status: {}", StatusCode::from(self.code))?;
+            }
+        }
+        writeln!(f, "request-id: {}", self.request_id)?;
+
+        for (tag, attrs) in &self.attrs {
+            writeln!(f, "{}", group_name(*tag))?;
+            for (name, value) in attrs {
+                writeln!(
+                    f,
+                    "    {} ({}) = {}",
+                    name,
+                    type_name(value),
+                    format_value(value)
+                )?;
+            }
+        }
+        writeln!(f, "end-of-attributes-tag")?;
+
+        if !self.data.is_empty() {
+            writeln!(f, "data: {} bytes", self.data.len())?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipp::IPPRequest;
+
+    fn request() -> Vec<u8> {
+        let req = IPPRequest {
+            version_major: 2,
+            version_minor: 0,
+            operation_id: PrinterOperation::GetPrinterAttributes,
+            request_id: 3,
+            attrs: vec![(
+                DelimiterOrValueTag::OperationAttributesTag,
+                vec![
+                    (
+                        "attributes-charset".to_string(),
+                        AttributeValue::Charset("utf-8".to_string()),
+                    ),
+                    (
+                        "printer-uri".to_string(),
+                        AttributeValue::Uri("ipp://localhost/ipp/print".to_string()),
+                    ),
+                    (
+                        "requested-attributes".to_string(),
+                        AttributeValue::VectorAttribute(vec![
+                            AttributeValue::Keyword("media-supported".to_string()),
+                            AttributeValue::Keyword("sides-supported".to_string()),
+                        ]),
+                    ),
+                ],
+            )],
+            data: vec![],
+        };
+        let mut buf = vec![];
+        req.write_to_stream(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn decode_request() {
+        let message = decode(&request(), None).unwrap();
+        assert_eq!(MessageKind::Request, message.kind);
+        assert_eq!(
+            "version: 2.0\n\
+             operation: Get-Printer-Attributes\n\
+             request-id: 3\n\
+             operation-attributes-tag\n    \
+             attributes-charset (charset) = utf-8\n    \
+             printer-uri (uri) = ipp://localhost/ipp/print\n    \
+             requested-attributes (1setOf keyword) = media-supported,sides-supported\n\
+             end-of-attributes-tag\n",
+            message.to_string()
+        );
+    }
+
+    #[test]
+    fn decode_http_capture() {
+        let body = request();
+        let mut chunked = b"POST /ipp/print HTTP/1.1\r\nContent-Type: application/ipp\r\n\
+                            Transfer-Encoding: chunked\r\n\r\n"
+            .to_vec();
+        for chunk in body.chunks(10) {
+            chunked.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+            chunked.extend_from_slice(chunk);
+            chunked.extend_from_slice(b"\r\n");
+        }
+        chunked.extend_from_slice(b"0\r\n\r\n");
+
+        assert_eq!(
+            decode(&body, None).unwrap(),
+            decode(&chunked, None).unwrap()
+        );
+
+        let message = decode(&body, Some(MessageKind::Response)).unwrap();
+        assert!(message.to_string().contains("status: 0x000b\n"));
+
+        let huge = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nffffffffffffffff\r\n";
+        assert!(decode(huge, None).is_err());
+    }
+
+    #[test]
+    fn format_response() {
+        let resp = IPPResponse {
+            version_major: 1,
+            version_minor: 1,
+            status_code: StatusCode::ClientErrorNotFound,
+            request_id: 5,
+            attrs: vec![(DelimiterOrValueTag::OperationAttributesTag, vec![])],
+            data: vec![],
+        };
+        assert_eq!(
+            "version: 1.1\n\
+             status: client-error-not-found (0x0406)\n\
+             request-id: 5\n\
+             operation-attributes-tag\n\
+             end-of-attributes-tag\n",
+            IppMessage::from(&resp).to_string()
+        );
+    }
+}
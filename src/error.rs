@@ -0,0 +1,117 @@
+use crate::ipp::{IPPError, JobStatus, StatusCode};
+use std::error::Error;
+use std::fmt;
+use std::io;
+
+#[derive(Debug)]
+pub enum IppPrintError {
+    IOError(io::Error),
+    HTTPError(reqwest::Error),
+    /// Malformed IPP message.
+    ProtocolError(IPPError),
+    /// The printer answered with a status code other than successful-ok-*,
+    /// with status-message and detailed-status-message if the response has them.
+    StatusError(StatusCode, Vec<String>),
+    /// An attribute required to continue was not in the response.
+    MissingAttribute(String),
+    /// Malformed raster data, or data which doesn't match the page header.
+    RasterError(String),
+    /// A raster stream started with none of the known synchronization words.
+    InvalidSyncWord([u8; 4]),
+    /// A printer URI or address which can't be parsed.
+    InvalidUri(String),
+    /// The job was canceled or aborted.
+    JobError(JobStatus),
+    /// The printer doesn't support what is needed to print the document.
+    Unsupported(String),
+    /// Something this build or machine can't do, e.g. a disabled feature.
+    NotAvailable(String),
+}
+
+impl fmt::Display for IppPrintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::IOError(err) => err.fmt(f),
+            Self::HTTPError(err) => err.fmt(f),
+            Self::ProtocolError(err) => err.fmt(f),
+            Self::StatusError(status, messages) if messages.is_empty() => {
+                write!(f, "printer returned error status: {}", status)
+            }
+            Self::StatusError(status, messages) => {
+                write!(
+                    f,
+                    "printer returned error status: {}: {}",
+                    status,
+                    messages.join("; ")
+                )
+            }
+            Self::MissingAttribute(name) => {
+                write!(f, "{} was not found in the response", name)
+            }
+            Self::RasterError(msg) => {
+                write!(f, "raster error: {}", msg)
+            }
+            Self::InvalidSyncWord(bytes) => {
+                write!(
+                    f,
+                    "invalid raster synchronization word: {:?}",
+                    String::from_utf8_lossy(bytes)
+                )
+            }
+            Self::InvalidUri(uri) => {
+                write!(f, "invalid printer URI: {}", uri)
+            }
+            Self::JobError(status) => {
+                write!(
+                    f,
+                    "job was {:?} ({})",
+                    status.state,
+                    status.reasons.join(", ")
+                )
+            }
+            Self::Unsupported(msg) => {
+                write!(f, "unsupported by the printer: {}", msg)
+            }
+            Self::NotAvailable(msg) => msg.fmt(f),
+        }
+    }
+}
+
+impl Error for IppPrintError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::IOError(err) => Some(err),
+            Self::HTTPError(err) => Some(err),
+            Self::ProtocolError(err) => Some(err),
+            Self::StatusError(..) => None,
+            Self::MissingAttribute(_) => None,
+            Self::RasterError(_) => None,
+            Self::InvalidSyncWord(_) => None,
+            Self::InvalidUri(_) => None,
+            Self::JobError(_) => None,
+            Self::Unsupported(_) => None,
+            Self::NotAvailable(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for IppPrintError {
+    fn from(err: io::Error) -> Self {
+        Self::IOError(err)
+    }
+}
+
+impl From<reqwest::Error> for IppPrintError {
+    fn from(err: reqwest::Error) -> Self {
+        Self::HTTPError(err)
+    }
+}
+
+impl From<IPPError> for IppPrintError {
+    fn from(err: IPPError) -> Self {
+        match err {
+            IPPError::IOError(err) => Self::IOError(err),
+            err => Self::ProtocolError(err),
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use crate::pwgraster::{BlackColor, GrayColor, SrgbColor};
+use std::f64::consts::PI;
+
+/// Clustered-dot (AM) screen for bi-level output.
+/// Dots grow from the center of each screen cell, which keeps tones stable on
+/// laser engines that cannot reliably reproduce isolated pixels.
+#[derive(Debug, Clone)]
+pub struct AmScreen {
+    /// Screen frequency in lines per inch.
+    pub lpi: f64,
+    /// Screen angle in degrees.
+    pub angle: f64,
+    /// Device resolution in dots per inch.
+    pub dpi: u32,
+}
+
+impl Default for AmScreen {
+    fn default() -> Self {
+        Self {
+            lpi: 85.0,
+            angle: 45.0,
+            dpi: 300,
+        }
+    }
+}
+
+impl AmScreen {
+    /// Threshold (0..=255) for the device pixel at (x, y).
+    fn threshold(&self, x: u32, y: u32) -> f64 {
+        let (sin, cos) = (self.angle * PI / 180.0).sin_cos();
+        let scale = self.lpi / self.dpi as f64;
+        let (x, y) = (x as f64 + 0.5, y as f64 + 0.5);
+
+        let u = (x * cos + y * sin) * scale;
+        let v = (y * cos - x * sin) * scale;
+
+        // Round dot spot function: -1 at the cell center, 1 at the corners.
+        let spot = ((2.0 * PI * u).cos() + (2.0 * PI * v).cos()) / 2.0;
+
+        (spot + 1.0) / 2.0 * 255.0
+    }
+
+    /// Returns true if the pixel should be marked (printed black).
+    /// gray is 0 for black and 255 for white.
+    pub fn is_marked(&self, x: u32, y: u32, gray: u8) -> bool {
+        let darkness = (255 - gray) as f64;
+        darkness > self.threshold(x, y)
+    }
+
+    pub fn screen_row(&self, y: u32, row: &[u8]) -> Vec<bool> {
+        row.iter()
+            .enumerate()
+            .map(|(x, gray)| self.is_marked(x as u32, y, *gray))
+            .collect()
+    }
+}
+
+/// 8x8 Bayer matrix for ordered dithering.
+const BAYER_8X8: [[u8; 8]; 8] = [
+    [0, 32, 8, 40, 2, 34, 10, 42],
+    [48, 16, 56, 24, 50, 18, 58, 26],
+    [12, 44, 4, 36, 14, 46, 6, 38],
+    [60, 28, 52, 20, 62, 30, 54, 22],
+    [3, 35, 11, 43, 1, 33, 9, 41],
+    [51, 19, 59, 27, 49, 17, 57, 25],
+    [15, 47, 7, 39, 13, 45, 5, 37],
+    [63, 31, 55, 23, 61, 29, 53, 21],
+];
+
+/// Dispersed-dot methods for bi-level output, for engines which can print
+/// isolated pixels, e.g. inkjets and thermal printers.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Dither {
+    /// Marks pixels darker than the level. Only good for text and line art.
+    Threshold(u8),
+    /// Ordered dithering with an 8x8 Bayer matrix.
+    Ordered,
+    /// Floyd-Steinberg error diffusion, alternating direction on every row.
+    FloydSteinberg,
+}
+
+impl Dither {
+    /// Returns true for pixels to be marked (printed black) in a page of gray
+    /// values with width pixels per row. gray is 0 for black and 255 for white.
+    pub fn apply(&self, gray: &[u8], width: usize) -> Vec<bool> {
+        let width = width.max(1);
+        match self {
+            Self::Threshold(level) => gray.iter().map(|e| e < level).collect(),
+            Self::Ordered => gray
+                .chunks(width)
+                .enumerate()
+                .flat_map(|(y, row)| {
+                    row.iter().enumerate().map(move |(x, e)| {
+                        // Thresholds are spread over 2..=254 so that 0 and 255 stay solid.
+                        let threshold = BAYER_8X8[y % 8][x % 8] as u32 * 4 + 2;
+                        (*e as u32) < threshold
+                    })
+                })
+                .collect(),
+            Self::FloydSteinberg => floyd_steinberg(gray, width),
+        }
+    }
+}
+
+fn floyd_steinberg(gray: &[u8], width: usize) -> Vec<bool> {
+    let mut result = Vec::with_capacity(gray.len());
+    // Errors carried to the current and the next row, with a pixel of padding on each side.
+    let mut errors = vec![0i32; width + 2];
+    let mut next_errors = vec![0i32; width + 2];
+
+    for (y, row) in gray.chunks(width).enumerate() {
+        let mut marked = vec![false; row.len()];
+        let reverse = y % 2 == 1;
+        for i in 0..row.len() {
+            let x = if reverse { row.len() - 1 - i } else { i };
+            let value = row[x] as i32 + errors[x + 1] / 16;
+            let output = if value < 128 { 0 } else { 255 };
+            marked[x] = output == 0;
+
+            let error = value - output;
+            let (ahead, behind) = if reverse { (x, x + 2) } else { (x + 2, x) };
+            errors[ahead] += error * 7;
+            next_errors[behind] += error * 3;
+            next_errors[x + 1] += error * 5;
+            next_errors[ahead] += error;
+        }
+        result.extend(marked);
+        std::mem::swap(&mut errors, &mut next_errors);
+        next_errors.fill(0);
+    }
+    result
+}
+
+/// Converts sRGB pixels to black_1 pixels by luminance, with width pixels per row.
+pub fn to_black(pixels: &[SrgbColor], width: usize, dither: Dither) -> Vec<BlackColor> {
+    let gray = pixels
+        .iter()
+        .map(|e| GrayColor::from(e).0)
+        .collect::<Vec<_>>();
+    dither
+        .apply(&gray, width)
+        .into_iter()
+        .map(BlackColor)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn coverage(screen: &AmScreen, gray: u8) -> f64 {
+        let mut marked = 0;
+        for y in 0..200 {
+            marked += screen
+                .screen_row(y, &[gray; 200])
+                .into_iter()
+                .filter(|e| *e)
+                .count();
+        }
+        marked as f64 / (200.0 * 200.0)
+    }
+
+    #[test]
+    fn solid_tones() {
+        let screen = AmScreen::default();
+        assert_eq!(0.0, coverage(&screen, 255));
+        assert_eq!(1.0, coverage(&screen, 0));
+    }
+
+    #[test]
+    fn mid_tone_coverage() {
+        for angle in [0.0, 15.0, 45.0, 75.0] {
+            let screen = AmScreen {
+                angle,
+                ..Default::default()
+            };
+            let c = coverage(&screen, 128);
+            assert!((0.4..0.6).contains(&c), "angle={} coverage={}", angle, c);
+        }
+    }
+
+    #[test]
+    fn dots_are_clustered() {
+        // With 0 degrees and 30 lpi at 300 dpi, a cell is 10x10 pixels, so
+        // a light tone should only mark pixels near the cell centers.
+        let screen = AmScreen {
+            lpi: 30.0,
+            angle: 0.0,
+            dpi: 300,
+        };
+        let row = screen.screen_row(5, &[230; 10]);
+        assert_eq!(
+            vec![false, false, false, false, true, true, false, false, false, false],
+            row
+        );
+    }
+
+    #[test]
+    fn dither_tones() {
+        for dither in [
+            Dither::Threshold(128),
+            Dither::Ordered,
+            Dither::FloydSteinberg,
+        ] {
+            let coverage = |gray: u8| {
+                let marked = dither.apply(&[gray; 64 * 64], 64);
+                marked.iter().filter(|e| **e).count() as f64 / marked.len() as f64
+            };
+            assert_eq!(0.0, coverage(255), "{:?}", dither);
+            assert_eq!(1.0, coverage(0), "{:?}", dither);
+            if dither != Dither::Threshold(128) {
+                for gray in [64, 128, 192] {
+                    let expected = 1.0 - gray as f64 / 255.0;
+                    let c = coverage(gray);
+                    assert!((c - expected).abs() < 0.02, "{:?} {} {}", dither, gray, c);
+                }
+            }
+        }
+
+        assert_eq!(
+            vec![true, false, true, false],
+            Dither::Threshold(100).apply(&[99, 100, 0, 255], 2)
+        );
+    }
+
+    #[test]
+    fn convert_to_black() {
+        let pixels = [
+            SrgbColor::new(0, 0, 0),
+            SrgbColor::new(255, 255, 255),
+            SrgbColor::new(0, 0, 255),
+        ];
+        assert_eq!(
+            vec![BlackColor(true), BlackColor(false), BlackColor(true)],
+            to_black(&pixels, 3, Dither::FloydSteinberg)
+        );
+    }
+}
@@ -1,30 +1,509 @@
+use crate::cache::AttributeCache;
+use crate::error::IppPrintError;
+use crate::media::MediaCol;
+use crate::transport::{HttpTransport, Transport};
 use num::FromPrimitive;
-use std::collections::{HashMap, LinkedList};
+use std::collections::{HashMap, LinkedList, VecDeque};
 use std::error::Error;
 use std::fmt;
 use std::io;
 use std::io::prelude::*;
-use std::ops::Range;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
 
-#[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, Eq, Clone, Copy)]
-#[allow(unused)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum PrinterOperation {
-    PrintJob = 0x0002,
-    PrintURI = 0x0003,
-    ValidateJob = 0x0004,
-    CreateJob = 0x0005,
-    SendDocument = 0x0006,
-    SendURI = 0x0007,
-    CancelJob = 0x0008,
-    GetJobAttributes = 0x0009,
-    GetJobs = 0x000a,
-    GetPrinterAttributes = 0x000b,
-    HoldJob = 0x000c,
-    ReleaseJob = 0x000d,
-    RestartJob = 0x000e,
-    PausePrinter = 0x0010,
-    ResumePrinter = 0x0011,
-    PurgeJobs = 0x0012,
+    PrintJob,
+    PrintURI,
+    ValidateJob,
+    CreateJob,
+    SendDocument,
+    SendURI,
+    CancelJob,
+    GetJobAttributes,
+    GetJobs,
+    GetPrinterAttributes,
+    HoldJob,
+    ReleaseJob,
+    RestartJob,
+    PausePrinter,
+    ResumePrinter,
+    PurgeJobs,
+    SetPrinterAttributes,
+    SetJobAttributes,
+    GetPrinterSupportedValues,
+    CreatePrinterSubscriptions,
+    CreateJobSubscriptions,
+    GetSubscriptionAttributes,
+    GetSubscriptions,
+    RenewSubscription,
+    CancelSubscription,
+    GetNotifications,
+    EnablePrinter,
+    DisablePrinter,
+    PausePrinterAfterCurrentJob,
+    HoldNewJobs,
+    ReleaseHeldNewJobs,
+    DeactivatePrinter,
+    ActivatePrinter,
+    RestartPrinter,
+    ShutdownPrinter,
+    StartupPrinter,
+    ReprocessJob,
+    CancelCurrentJob,
+    SuspendCurrentJob,
+    ResumeJob,
+    PromoteJob,
+    ScheduleJobAfter,
+    CancelDocument,
+    GetDocumentAttributes,
+    GetDocuments,
+    DeleteDocument,
+    SetDocumentAttributes,
+    CancelJobs,
+    CancelMyJobs,
+    ResubmitJob,
+    CloseJob,
+    IdentifyPrinter,
+    ValidateDocument,
+    AddDocumentImages,
+    AcknowledgeDocument,
+    AcknowledgeIdentifyPrinter,
+    AcknowledgeJob,
+    FetchDocument,
+    FetchJob,
+    GetOutputDeviceAttributes,
+    UpdateActiveJobs,
+    DeregisterOutputDevice,
+    UpdateDocumentStatus,
+    UpdateJobStatus,
+    UpdateOutputDeviceAttributes,
+    GetNextDocumentData,
+    AllocatePrinterResources,
+    CreatePrinter,
+    DeallocatePrinterResources,
+    DeletePrinter,
+    GetPrinters,
+    ShutdownOnePrinter,
+    StartupOnePrinter,
+    CancelResource,
+    CreateResource,
+    InstallResource,
+    SendResourceData,
+    SetResourceAttributes,
+    CreateResourceSubscriptions,
+    CreateSystemSubscriptions,
+    DisableAllPrinters,
+    EnableAllPrinters,
+    GetSystemAttributes,
+    GetSystemSupportedValues,
+    PauseAllPrinters,
+    PauseAllPrintersAfterCurrentJob,
+    RegisterOutputDevice,
+    RestartSystem,
+    ResumeAllPrinters,
+    SetSystemAttributes,
+    ShutdownAllPrinters,
+    StartupAllPrinters,
+    GetPrinterResources,
+    GetUserPrinterAttributes,
+    RestartOnePrinter,
+    /// Operation not registered to IANA (e.g. vendor extensions 0x4000..=0x7FFF).
+    Unknown(u16),
+}
+
+/// operation-id and registered name of each operation.
+const OPERATIONS: &[(u16, PrinterOperation, &str)] = &[
+    (0x0002, PrinterOperation::PrintJob, "Print-Job"),
+    (0x0003, PrinterOperation::PrintURI, "Print-URI"),
+    (0x0004, PrinterOperation::ValidateJob, "Validate-Job"),
+    (0x0005, PrinterOperation::CreateJob, "Create-Job"),
+    (0x0006, PrinterOperation::SendDocument, "Send-Document"),
+    (0x0007, PrinterOperation::SendURI, "Send-URI"),
+    (0x0008, PrinterOperation::CancelJob, "Cancel-Job"),
+    (
+        0x0009,
+        PrinterOperation::GetJobAttributes,
+        "Get-Job-Attributes",
+    ),
+    (0x000a, PrinterOperation::GetJobs, "Get-Jobs"),
+    (
+        0x000b,
+        PrinterOperation::GetPrinterAttributes,
+        "Get-Printer-Attributes",
+    ),
+    (0x000c, PrinterOperation::HoldJob, "Hold-Job"),
+    (0x000d, PrinterOperation::ReleaseJob, "Release-Job"),
+    (0x000e, PrinterOperation::RestartJob, "Restart-Job"),
+    (0x0010, PrinterOperation::PausePrinter, "Pause-Printer"),
+    (0x0011, PrinterOperation::ResumePrinter, "Resume-Printer"),
+    (0x0012, PrinterOperation::PurgeJobs, "Purge-Jobs"),
+    (
+        0x0013,
+        PrinterOperation::SetPrinterAttributes,
+        "Set-Printer-Attributes",
+    ),
+    (
+        0x0014,
+        PrinterOperation::SetJobAttributes,
+        "Set-Job-Attributes",
+    ),
+    (
+        0x0015,
+        PrinterOperation::GetPrinterSupportedValues,
+        "Get-Printer-Supported-Values",
+    ),
+    (
+        0x0016,
+        PrinterOperation::CreatePrinterSubscriptions,
+        "Create-Printer-Subscriptions",
+    ),
+    (
+        0x0017,
+        PrinterOperation::CreateJobSubscriptions,
+        "Create-Job-Subscriptions",
+    ),
+    (
+        0x0018,
+        PrinterOperation::GetSubscriptionAttributes,
+        "Get-Subscription-Attributes",
+    ),
+    (
+        0x0019,
+        PrinterOperation::GetSubscriptions,
+        "Get-Subscriptions",
+    ),
+    (
+        0x001a,
+        PrinterOperation::RenewSubscription,
+        "Renew-Subscription",
+    ),
+    (
+        0x001b,
+        PrinterOperation::CancelSubscription,
+        "Cancel-Subscription",
+    ),
+    (
+        0x001c,
+        PrinterOperation::GetNotifications,
+        "Get-Notifications",
+    ),
+    (0x0022, PrinterOperation::EnablePrinter, "Enable-Printer"),
+    (0x0023, PrinterOperation::DisablePrinter, "Disable-Printer"),
+    (
+        0x0024,
+        PrinterOperation::PausePrinterAfterCurrentJob,
+        "Pause-Printer-After-Current-Job",
+    ),
+    (0x0025, PrinterOperation::HoldNewJobs, "Hold-New-Jobs"),
+    (
+        0x0026,
+        PrinterOperation::ReleaseHeldNewJobs,
+        "Release-Held-New-Jobs",
+    ),
+    (
+        0x0027,
+        PrinterOperation::DeactivatePrinter,
+        "Deactivate-Printer",
+    ),
+    (
+        0x0028,
+        PrinterOperation::ActivatePrinter,
+        "Activate-Printer",
+    ),
+    (0x0029, PrinterOperation::RestartPrinter, "Restart-Printer"),
+    (
+        0x002a,
+        PrinterOperation::ShutdownPrinter,
+        "Shutdown-Printer",
+    ),
+    (0x002b, PrinterOperation::StartupPrinter, "Startup-Printer"),
+    (0x002c, PrinterOperation::ReprocessJob, "Reprocess-Job"),
+    (
+        0x002d,
+        PrinterOperation::CancelCurrentJob,
+        "Cancel-Current-Job",
+    ),
+    (
+        0x002e,
+        PrinterOperation::SuspendCurrentJob,
+        "Suspend-Current-Job",
+    ),
+    (0x002f, PrinterOperation::ResumeJob, "Resume-Job"),
+    (0x0030, PrinterOperation::PromoteJob, "Promote-Job"),
+    (
+        0x0031,
+        PrinterOperation::ScheduleJobAfter,
+        "Schedule-Job-After",
+    ),
+    (0x0033, PrinterOperation::CancelDocument, "Cancel-Document"),
+    (
+        0x0034,
+        PrinterOperation::GetDocumentAttributes,
+        "Get-Document-Attributes",
+    ),
+    (0x0035, PrinterOperation::GetDocuments, "Get-Documents"),
+    (0x0036, PrinterOperation::DeleteDocument, "Delete-Document"),
+    (
+        0x0037,
+        PrinterOperation::SetDocumentAttributes,
+        "Set-Document-Attributes",
+    ),
+    (0x0038, PrinterOperation::CancelJobs, "Cancel-Jobs"),
+    (0x0039, PrinterOperation::CancelMyJobs, "Cancel-My-Jobs"),
+    (0x003a, PrinterOperation::ResubmitJob, "Resubmit-Job"),
+    (0x003b, PrinterOperation::CloseJob, "Close-Job"),
+    (
+        0x003c,
+        PrinterOperation::IdentifyPrinter,
+        "Identify-Printer",
+    ),
+    (
+        0x003d,
+        PrinterOperation::ValidateDocument,
+        "Validate-Document",
+    ),
+    (
+        0x003e,
+        PrinterOperation::AddDocumentImages,
+        "Add-Document-Images",
+    ),
+    (
+        0x003f,
+        PrinterOperation::AcknowledgeDocument,
+        "Acknowledge-Document",
+    ),
+    (
+        0x0040,
+        PrinterOperation::AcknowledgeIdentifyPrinter,
+        "Acknowledge-Identify-Printer",
+    ),
+    (0x0041, PrinterOperation::AcknowledgeJob, "Acknowledge-Job"),
+    (0x0042, PrinterOperation::FetchDocument, "Fetch-Document"),
+    (0x0043, PrinterOperation::FetchJob, "Fetch-Job"),
+    (
+        0x0044,
+        PrinterOperation::GetOutputDeviceAttributes,
+        "Get-Output-Device-Attributes",
+    ),
+    (
+        0x0045,
+        PrinterOperation::UpdateActiveJobs,
+        "Update-Active-Jobs",
+    ),
+    (
+        0x0046,
+        PrinterOperation::DeregisterOutputDevice,
+        "Deregister-Output-Device",
+    ),
+    (
+        0x0047,
+        PrinterOperation::UpdateDocumentStatus,
+        "Update-Document-Status",
+    ),
+    (
+        0x0048,
+        PrinterOperation::UpdateJobStatus,
+        "Update-Job-Status",
+    ),
+    (
+        0x0049,
+        PrinterOperation::UpdateOutputDeviceAttributes,
+        "Update-Output-Device-Attributes",
+    ),
+    (
+        0x004a,
+        PrinterOperation::GetNextDocumentData,
+        "Get-Next-Document-Data",
+    ),
+    (
+        0x004b,
+        PrinterOperation::AllocatePrinterResources,
+        "Allocate-Printer-Resources",
+    ),
+    (0x004c, PrinterOperation::CreatePrinter, "Create-Printer"),
+    (
+        0x004d,
+        PrinterOperation::DeallocatePrinterResources,
+        "Deallocate-Printer-Resources",
+    ),
+    (0x004e, PrinterOperation::DeletePrinter, "Delete-Printer"),
+    (0x004f, PrinterOperation::GetPrinters, "Get-Printers"),
+    (
+        0x0050,
+        PrinterOperation::ShutdownOnePrinter,
+        "Shutdown-One-Printer",
+    ),
+    (
+        0x0051,
+        PrinterOperation::StartupOnePrinter,
+        "Startup-One-Printer",
+    ),
+    (0x0052, PrinterOperation::CancelResource, "Cancel-Resource"),
+    (0x0053, PrinterOperation::CreateResource, "Create-Resource"),
+    (
+        0x0054,
+        PrinterOperation::InstallResource,
+        "Install-Resource",
+    ),
+    (
+        0x0055,
+        PrinterOperation::SendResourceData,
+        "Send-Resource-Data",
+    ),
+    (
+        0x0056,
+        PrinterOperation::SetResourceAttributes,
+        "Set-Resource-Attributes",
+    ),
+    (
+        0x0057,
+        PrinterOperation::CreateResourceSubscriptions,
+        "Create-Resource-Subscriptions",
+    ),
+    (
+        0x0058,
+        PrinterOperation::CreateSystemSubscriptions,
+        "Create-System-Subscriptions",
+    ),
+    (
+        0x0059,
+        PrinterOperation::DisableAllPrinters,
+        "Disable-All-Printers",
+    ),
+    (
+        0x005a,
+        PrinterOperation::EnableAllPrinters,
+        "Enable-All-Printers",
+    ),
+    (
+        0x005b,
+        PrinterOperation::GetSystemAttributes,
+        "Get-System-Attributes",
+    ),
+    (
+        0x005c,
+        PrinterOperation::GetSystemSupportedValues,
+        "Get-System-Supported-Values",
+    ),
+    (
+        0x005d,
+        PrinterOperation::PauseAllPrinters,
+        "Pause-All-Printers",
+    ),
+    (
+        0x005e,
+        PrinterOperation::PauseAllPrintersAfterCurrentJob,
+        "Pause-All-Printers-After-Current-Job",
+    ),
+    (
+        0x005f,
+        PrinterOperation::RegisterOutputDevice,
+        "Register-Output-Device",
+    ),
+    (0x0060, PrinterOperation::RestartSystem, "Restart-System"),
+    (
+        0x0061,
+        PrinterOperation::ResumeAllPrinters,
+        "Resume-All-Printers",
+    ),
+    (
+        0x0062,
+        PrinterOperation::SetSystemAttributes,
+        "Set-System-Attributes",
+    ),
+    (
+        0x0063,
+        PrinterOperation::ShutdownAllPrinters,
+        "Shutdown-All-Printers",
+    ),
+    (
+        0x0064,
+        PrinterOperation::StartupAllPrinters,
+        "Startup-All-Printers",
+    ),
+    (
+        0x0065,
+        PrinterOperation::GetPrinterResources,
+        "Get-Printer-Resources",
+    ),
+    (
+        0x0066,
+        PrinterOperation::GetUserPrinterAttributes,
+        "Get-User-Printer-Attributes",
+    ),
+    (
+        0x0067,
+        PrinterOperation::RestartOnePrinter,
+        "Restart-One-Printer",
+    ),
+];
+
+impl PrinterOperation {
+    /// Registered name of the operation, e.g. "Get-Printer-Attributes".
+    pub fn name(&self) -> Option<&'static str> {
+        OPERATIONS
+            .iter()
+            .find(|(_, op, _)| op == self)
+            .map(|(_, _, name)| *name)
+    }
+
+    /// Whether doing the operation twice has the same effect as doing it once,
+    /// so that it can be sent again when the response was lost.
+    pub fn is_idempotent(&self) -> bool {
+        matches!(
+            self,
+            PrinterOperation::GetJobAttributes
+                | PrinterOperation::GetJobs
+                | PrinterOperation::GetPrinterAttributes
+                | PrinterOperation::GetPrinterSupportedValues
+                | PrinterOperation::GetSubscriptionAttributes
+                | PrinterOperation::GetSubscriptions
+                | PrinterOperation::GetNotifications
+                | PrinterOperation::GetDocumentAttributes
+                | PrinterOperation::GetDocuments
+                | PrinterOperation::GetOutputDeviceAttributes
+                | PrinterOperation::ValidateJob
+                | PrinterOperation::ValidateDocument
+                | PrinterOperation::IdentifyPrinter
+                | PrinterOperation::CancelJob
+                | PrinterOperation::CancelJobs
+                | PrinterOperation::CancelMyJobs
+                | PrinterOperation::CancelDocument
+        )
+    }
+}
+
+impl From<u16> for PrinterOperation {
+    fn from(value: u16) -> Self {
+        match OPERATIONS.iter().find(|(id, _, _)| *id == value) {
+            Some((_, op, _)) => *op,
+            None => PrinterOperation::Unknown(value),
+        }
+    }
+}
+
+impl From<PrinterOperation> for u16 {
+    fn from(value: PrinterOperation) -> Self {
+        match value {
+            PrinterOperation::Unknown(id) => id,
+            op => OPERATIONS
+                .iter()
+                .find(|(_, o, _)| *o == op)
+                .map(|(id, _, _)| *id)
+                .unwrap(),
+        }
+    }
+}
+
+impl fmt::Display for PrinterOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "0x{:04x}", u16::from(*self)),
+        }
+    }
 }
 
 #[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, Eq, Clone, Copy)]
@@ -36,11 +515,20 @@ pub enum DelimiterOrValueTag {
     EndOfAttributesTag = 0x03,
     PrinterAttributesTag = 0x04,
     UnsupportedAttributesTag = 0x05,
+    SubscriptionAttributesTag = 0x06,
+    EventNotificationAttributesTag = 0x07,
+    ResourceAttributesTag = 0x08,
+    DocumentAttributesTag = 0x09,
+    SystemAttributesTag = 0x0a,
 
     // value-tag
+    // out-of-band
     Unsupported = 0x10,
     Unknown = 0x12,
     NoValue = 0x13,
+    NotSettable = 0x15,
+    DeleteAttribute = 0x16,
+    AdminDefine = 0x17,
 
     Integer = 0x21,
     Boolean = 0x22,
@@ -66,41 +554,366 @@ pub enum DelimiterOrValueTag {
     MemberAttrName = 0x4a,
 }
 
-#[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, Eq)]
-#[allow(unused)]
+impl DelimiterOrValueTag {
+    /// Whether the tag starts an attribute group.
+    pub fn is_group(&self) -> bool {
+        (*self as u8) < 0x10 && *self != Self::EndOfAttributesTag
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
 pub enum StatusCode {
-    SuccessfulOk = 0x0000,
-    SuccessfulOkIgnoredOrSubstitutedAttributes = 0x0001,
-    SuccessfulOkConflictingAttributes = 0x0002,
-    ClientErrorBadRequest = 0x0400,
-    ClientErrorForbidden = 0x0401,
-    ClientErrorNotAuthenticated = 0x0402,
-    ClientErrorNotAuthorized = 0x0403,
-    ClientErrorNotPossible = 0x0404,
-    ClientErrorTimeout = 0x045,
-    ClientErrorNotFound = 0x0406,
-    ClientErrorGone = 0x0407,
-    ClientErrorRequestEntityTooLarge = 0x0408,
-    ClientErrorRequestValueTooLong = 0x0409,
-    ClientErrorDocumentFormatNotSupported = 0x040a,
-    ClientErrorAttributesOrValuesNotSupported = 0x040b,
-    ClientErrorUriSchemeNotSupported = 0x040c,
-    ClientErrorCharsetNotSupported = 0x040d,
-    ClientErrorConflictingAttributes = 0x040e,
-    ClientErrorCompressionNotSupported = 0x040f,
-    ClientErrorCompressionError = 0x0410,
-    ClientErrorDocumentFormatError = 0x0411,
-    ClientErrorDocumentAccessError = 0x04012,
-    ServerErrorInternalError = 0x0500,
-    ServerErrorOperationNotSupported = 0x0501,
-    ServerErrorServiceUnavailable = 0x0502,
-    ServerErrorVersionNotSupported = 0x0503,
-    ServerErrorDeviceError = 0x0504,
-    ServerErrorTemporaryError = 0x0505,
-    ServerErrorNotAcceptingJobs = 0x0506,
-    ServerErrorBusy = 0x0507,
-    ServerErrorJobCanceled = 0x0508,
-    ServerErrorMultipleDocumentJobsNotSupported = 0x0509,
+    SuccessfulOk,
+    SuccessfulOkIgnoredOrSubstitutedAttributes,
+    SuccessfulOkConflictingAttributes,
+    SuccessfulOkIgnoredSubscriptions,
+    SuccessfulOkTooManyEvents,
+    SuccessfulOkEventsComplete,
+    ClientErrorBadRequest,
+    ClientErrorForbidden,
+    ClientErrorNotAuthenticated,
+    ClientErrorNotAuthorized,
+    ClientErrorNotPossible,
+    ClientErrorTimeout,
+    ClientErrorNotFound,
+    ClientErrorGone,
+    ClientErrorRequestEntityTooLarge,
+    ClientErrorRequestValueTooLong,
+    ClientErrorDocumentFormatNotSupported,
+    ClientErrorAttributesOrValuesNotSupported,
+    ClientErrorUriSchemeNotSupported,
+    ClientErrorCharsetNotSupported,
+    ClientErrorConflictingAttributes,
+    ClientErrorCompressionNotSupported,
+    ClientErrorCompressionError,
+    ClientErrorDocumentFormatError,
+    ClientErrorDocumentAccessError,
+    ClientErrorAttributesNotSettable,
+    ClientErrorIgnoredAllSubscriptions,
+    ClientErrorTooManySubscriptions,
+    ClientErrorDocumentPasswordError,
+    ClientErrorDocumentPermissionError,
+    ClientErrorDocumentSecurityError,
+    ClientErrorDocumentUnprintableError,
+    ClientErrorAccountInfoNeeded,
+    ClientErrorAccountClosed,
+    ClientErrorAccountLimitReached,
+    ClientErrorAccountAuthorizationFailed,
+    ClientErrorNotFetchable,
+    ServerErrorInternalError,
+    ServerErrorOperationNotSupported,
+    ServerErrorServiceUnavailable,
+    ServerErrorVersionNotSupported,
+    ServerErrorDeviceError,
+    ServerErrorTemporaryError,
+    ServerErrorNotAcceptingJobs,
+    ServerErrorBusy,
+    ServerErrorJobCanceled,
+    ServerErrorMultipleDocumentJobsNotSupported,
+    ServerErrorPrinterIsDeactivated,
+    ServerErrorTooManyJobs,
+    ServerErrorTooManyDocuments,
+    /// Status code not known to this crate, like vendor extensions or codes
+    /// registered after RFC 8011.
+    Unknown(u16),
+}
+
+/// status-code and registered keyword of each status.
+const STATUS_CODES: &[(u16, StatusCode, &str)] = &[
+    (0x0000, StatusCode::SuccessfulOk, "successful-ok"),
+    (
+        0x0001,
+        StatusCode::SuccessfulOkIgnoredOrSubstitutedAttributes,
+        "successful-ok-ignored-or-substituted-attributes",
+    ),
+    (
+        0x0002,
+        StatusCode::SuccessfulOkConflictingAttributes,
+        "successful-ok-conflicting-attributes",
+    ),
+    (
+        0x0003,
+        StatusCode::SuccessfulOkIgnoredSubscriptions,
+        "successful-ok-ignored-subscriptions",
+    ),
+    (
+        0x0005,
+        StatusCode::SuccessfulOkTooManyEvents,
+        "successful-ok-too-many-events",
+    ),
+    (
+        0x0007,
+        StatusCode::SuccessfulOkEventsComplete,
+        "successful-ok-events-complete",
+    ),
+    (
+        0x0400,
+        StatusCode::ClientErrorBadRequest,
+        "client-error-bad-request",
+    ),
+    (
+        0x0401,
+        StatusCode::ClientErrorForbidden,
+        "client-error-forbidden",
+    ),
+    (
+        0x0402,
+        StatusCode::ClientErrorNotAuthenticated,
+        "client-error-not-authenticated",
+    ),
+    (
+        0x0403,
+        StatusCode::ClientErrorNotAuthorized,
+        "client-error-not-authorized",
+    ),
+    (
+        0x0404,
+        StatusCode::ClientErrorNotPossible,
+        "client-error-not-possible",
+    ),
+    (
+        0x0405,
+        StatusCode::ClientErrorTimeout,
+        "client-error-timeout",
+    ),
+    (
+        0x0406,
+        StatusCode::ClientErrorNotFound,
+        "client-error-not-found",
+    ),
+    (0x0407, StatusCode::ClientErrorGone, "client-error-gone"),
+    (
+        0x0408,
+        StatusCode::ClientErrorRequestEntityTooLarge,
+        "client-error-request-entity-too-large",
+    ),
+    (
+        0x0409,
+        StatusCode::ClientErrorRequestValueTooLong,
+        "client-error-request-value-too-long",
+    ),
+    (
+        0x040a,
+        StatusCode::ClientErrorDocumentFormatNotSupported,
+        "client-error-document-format-not-supported",
+    ),
+    (
+        0x040b,
+        StatusCode::ClientErrorAttributesOrValuesNotSupported,
+        "client-error-attributes-or-values-not-supported",
+    ),
+    (
+        0x040c,
+        StatusCode::ClientErrorUriSchemeNotSupported,
+        "client-error-uri-scheme-not-supported",
+    ),
+    (
+        0x040d,
+        StatusCode::ClientErrorCharsetNotSupported,
+        "client-error-charset-not-supported",
+    ),
+    (
+        0x040e,
+        StatusCode::ClientErrorConflictingAttributes,
+        "client-error-conflicting-attributes",
+    ),
+    (
+        0x040f,
+        StatusCode::ClientErrorCompressionNotSupported,
+        "client-error-compression-not-supported",
+    ),
+    (
+        0x0410,
+        StatusCode::ClientErrorCompressionError,
+        "client-error-compression-error",
+    ),
+    (
+        0x0411,
+        StatusCode::ClientErrorDocumentFormatError,
+        "client-error-document-format-error",
+    ),
+    (
+        0x0412,
+        StatusCode::ClientErrorDocumentAccessError,
+        "client-error-document-access-error",
+    ),
+    (
+        0x0413,
+        StatusCode::ClientErrorAttributesNotSettable,
+        "client-error-attributes-not-settable",
+    ),
+    (
+        0x0414,
+        StatusCode::ClientErrorIgnoredAllSubscriptions,
+        "client-error-ignored-all-subscriptions",
+    ),
+    (
+        0x0415,
+        StatusCode::ClientErrorTooManySubscriptions,
+        "client-error-too-many-subscriptions",
+    ),
+    (
+        0x0418,
+        StatusCode::ClientErrorDocumentPasswordError,
+        "client-error-document-password-error",
+    ),
+    (
+        0x0419,
+        StatusCode::ClientErrorDocumentPermissionError,
+        "client-error-document-permission-error",
+    ),
+    (
+        0x041a,
+        StatusCode::ClientErrorDocumentSecurityError,
+        "client-error-document-security-error",
+    ),
+    (
+        0x041b,
+        StatusCode::ClientErrorDocumentUnprintableError,
+        "client-error-document-unprintable-error",
+    ),
+    (
+        0x041c,
+        StatusCode::ClientErrorAccountInfoNeeded,
+        "client-error-account-info-needed",
+    ),
+    (
+        0x041d,
+        StatusCode::ClientErrorAccountClosed,
+        "client-error-account-closed",
+    ),
+    (
+        0x041e,
+        StatusCode::ClientErrorAccountLimitReached,
+        "client-error-account-limit-reached",
+    ),
+    (
+        0x041f,
+        StatusCode::ClientErrorAccountAuthorizationFailed,
+        "client-error-account-authorization-failed",
+    ),
+    (
+        0x0420,
+        StatusCode::ClientErrorNotFetchable,
+        "client-error-not-fetchable",
+    ),
+    (
+        0x0500,
+        StatusCode::ServerErrorInternalError,
+        "server-error-internal-error",
+    ),
+    (
+        0x0501,
+        StatusCode::ServerErrorOperationNotSupported,
+        "server-error-operation-not-supported",
+    ),
+    (
+        0x0502,
+        StatusCode::ServerErrorServiceUnavailable,
+        "server-error-service-unavailable",
+    ),
+    (
+        0x0503,
+        StatusCode::ServerErrorVersionNotSupported,
+        "server-error-version-not-supported",
+    ),
+    (
+        0x0504,
+        StatusCode::ServerErrorDeviceError,
+        "server-error-device-error",
+    ),
+    (
+        0x0505,
+        StatusCode::ServerErrorTemporaryError,
+        "server-error-temporary-error",
+    ),
+    (
+        0x0506,
+        StatusCode::ServerErrorNotAcceptingJobs,
+        "server-error-not-accepting-jobs",
+    ),
+    (0x0507, StatusCode::ServerErrorBusy, "server-error-busy"),
+    (
+        0x0508,
+        StatusCode::ServerErrorJobCanceled,
+        "server-error-job-canceled",
+    ),
+    (
+        0x0509,
+        StatusCode::ServerErrorMultipleDocumentJobsNotSupported,
+        "server-error-multiple-document-jobs-not-supported",
+    ),
+    (
+        0x050a,
+        StatusCode::ServerErrorPrinterIsDeactivated,
+        "server-error-printer-is-deactivated",
+    ),
+    (
+        0x050b,
+        StatusCode::ServerErrorTooManyJobs,
+        "server-error-too-many-jobs",
+    ),
+    (
+        0x050c,
+        StatusCode::ServerErrorTooManyDocuments,
+        "server-error-too-many-documents",
+    ),
+];
+
+impl StatusCode {
+    /// successful-ok-* (0x0000..=0x00FF)
+    pub fn is_success(&self) -> bool {
+        u16::from(*self) <= 0x00FF
+    }
+
+    /// client-error-* (0x0400..=0x04FF)
+    pub fn is_client_error(&self) -> bool {
+        (0x0400..=0x04FF).contains(&u16::from(*self))
+    }
+
+    /// server-error-* (0x0500..=0x05FF)
+    pub fn is_server_error(&self) -> bool {
+        (0x0500..=0x05FF).contains(&u16::from(*self))
+    }
+
+    /// Registered keyword of the status code, e.g. "successful-ok".
+    pub fn name(&self) -> Option<&'static str> {
+        STATUS_CODES
+            .iter()
+            .find(|(_, status, _)| status == self)
+            .map(|(_, _, name)| *name)
+    }
+}
+
+impl From<u16> for StatusCode {
+    fn from(value: u16) -> Self {
+        match STATUS_CODES.iter().find(|(code, _, _)| *code == value) {
+            Some((_, status, _)) => *status,
+            None => StatusCode::Unknown(value),
+        }
+    }
+}
+
+impl From<StatusCode> for u16 {
+    fn from(value: StatusCode) -> Self {
+        match value {
+            StatusCode::Unknown(code) => code,
+            status => STATUS_CODES
+                .iter()
+                .find(|(_, s, _)| *s == status)
+                .map(|(code, _, _)| *code)
+                .unwrap(),
+        }
+    }
+}
+
+impl fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.name() {
+            Some(name) => write!(f, "{} (0x{:04x})", name, u16::from(*self)),
+            None => write!(f, "0x{:04x}", u16::from(*self)),
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -108,6 +921,11 @@ pub enum IPPError {
     IOError(io::Error),
     ProtocolError,
     InvalidValue,
+    /// The message is corrupt at the byte offset from the beginning of the message.
+    Malformed {
+        offset: u64,
+        reason: String,
+    },
 }
 
 impl fmt::Display for IPPError {
@@ -120,6 +938,9 @@ impl fmt::Display for IPPError {
             Self::InvalidValue => {
                 write!(f, "value format error")
             }
+            Self::Malformed { offset, reason } => {
+                write!(f, "malformed message at byte {}: {}", offset, reason)
+            }
         }
     }
 }
@@ -130,10 +951,20 @@ impl Error for IPPError {
             Self::IOError(err) => Some(err),
             Self::ProtocolError => None,
             Self::InvalidValue => None,
+            Self::Malformed { .. } => None,
         }
     }
 }
 
+/// Writes all of buf, which Write::write doesn't guarantee, and returns its length.
+fn write_bytes<W>(writer: &mut W, buf: &[u8]) -> Result<usize, IPPError>
+where
+    W: Write,
+{
+    writer.write_all(buf).map_err(IPPError::IOError)?;
+    Ok(buf.len())
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 #[allow(unused)]
 pub struct StringWithLanguage {
@@ -147,26 +978,31 @@ impl StringWithLanguage {
         if len < 2 {
             return Err(IPPError::InvalidValue);
         }
-        let lang_len = i16::from_be_bytes([buf[0], buf[1]]) as usize;
+        let lang_len = u16::from_be_bytes([buf[0], buf[1]]) as usize;
         if len < 2 + lang_len + 2 {
             return Err(IPPError::InvalidValue);
         }
-        let str_len = i16::from_be_bytes([buf[2 + lang_len], buf[2 + lang_len + 1]]) as usize;
+        let str_len = u16::from_be_bytes([buf[2 + lang_len], buf[2 + lang_len + 1]]) as usize;
         if len < 2 + lang_len + 2 + str_len {
             return Err(IPPError::InvalidValue);
         }
 
-        Ok(Self {
-            lang: String::from_utf8(buf[2..(2 + lang_len)].to_vec()).unwrap(),
-            string: String::from_utf8(
-                buf[(2 + lang_len + 2)..(2 + lang_len + 2 + str_len)].to_vec(),
-            )
-            .unwrap(),
-        })
+        let lang = match String::from_utf8(buf[2..(2 + lang_len)].to_vec()) {
+            Ok(lang) => lang,
+            Err(_) => return Err(IPPError::InvalidValue),
+        };
+        let string =
+            match String::from_utf8(buf[(2 + lang_len + 2)..(2 + lang_len + 2 + str_len)].to_vec())
+            {
+                Ok(string) => string,
+                Err(_) => return Err(IPPError::InvalidValue),
+            };
+
+        Ok(Self { lang, string })
     }
 
     fn byte_len(&self) -> u16 {
-        (2 + self.lang.as_bytes().len() + 2 + self.string.as_bytes().len()) as u16
+        (2 + self.lang.len() + 2 + self.string.len()) as u16
     }
 
     fn write_to_stream<W>(&self, writer: &mut W) -> Result<usize, IPPError>
@@ -176,24 +1012,12 @@ impl StringWithLanguage {
         let mut written = 0;
 
         let lang_bytes = self.lang.as_bytes();
-        written += match writer.write(&(lang_bytes.len() as u16).to_be_bytes()) {
-            Ok(written) => written,
-            Err(err) => return Err(IPPError::IOError(err)),
-        };
-        written += match writer.write(lang_bytes) {
-            Ok(written) => written,
-            Err(err) => return Err(IPPError::IOError(err)),
-        };
+        written += write_bytes(writer, &(lang_bytes.len() as u16).to_be_bytes())?;
+        written += write_bytes(writer, lang_bytes)?;
 
         let str_bytes = self.string.as_bytes();
-        written += match writer.write(&(str_bytes.len() as u16).to_be_bytes()) {
-            Ok(written) => written,
-            Err(err) => return Err(IPPError::IOError(err)),
-        };
-        written += match writer.write(str_bytes) {
-            Ok(written) => written,
-            Err(err) => return Err(IPPError::IOError(err)),
-        };
+        written += write_bytes(writer, &(str_bytes.len() as u16).to_be_bytes())?;
+        written += write_bytes(writer, str_bytes)?;
 
         Ok(written)
     }
@@ -208,6 +1032,21 @@ pub struct Resolution {
 }
 
 impl Resolution {
+    pub const DOTS_PER_INCH: i8 = 3;
+    pub const DOTS_PER_CENTIMETER: i8 = 4;
+
+    /// Resolution in dots per inch, converting from dots per centimeter if needed.
+    pub fn to_dpi(&self) -> (i32, i32) {
+        if self.units == Self::DOTS_PER_CENTIMETER {
+            (
+                (self.resolution_cross_feed as f64 * 2.54).round() as i32,
+                (self.resolution_feed as f64 * 2.54).round() as i32,
+            )
+        } else {
+            (self.resolution_cross_feed, self.resolution_feed)
+        }
+    }
+
     fn parse_buffer(buf: Vec<u8>) -> Result<Self, IPPError> {
         if buf.len() != 9 {
             return Err(IPPError::InvalidValue);
@@ -228,15 +1067,9 @@ impl Resolution {
     where
         W: Write,
     {
-        if let Err(err) = writer.write(&self.resolution_cross_feed.to_be_bytes()) {
-            return Err(IPPError::IOError(err));
-        }
-        if let Err(err) = writer.write(&self.resolution_feed.to_be_bytes()) {
-            return Err(IPPError::IOError(err));
-        }
-        if let Err(err) = writer.write(&[self.units as u8]) {
-            return Err(IPPError::IOError(err));
-        }
+        write_bytes(writer, &self.resolution_cross_feed.to_be_bytes())?;
+        write_bytes(writer, &self.resolution_feed.to_be_bytes())?;
+        write_bytes(writer, &[self.units as u8])?;
 
         Ok(9)
     }
@@ -284,22 +1117,21 @@ impl DateTime {
     where
         W: Write,
     {
-        if let Err(err) = writer.write(&self.year.to_be_bytes()) {
-            return Err(IPPError::IOError(err));
-        }
-        if let Err(err) = writer.write(&[
-            self.month,
-            self.day,
-            self.hour,
-            self.minutes,
-            self.seconds,
-            self.deci_seconds,
-            self.direction_from_utc as u8,
-            self.hours_from_utc,
-            self.minutes_from_utc,
-        ]) {
-            return Err(IPPError::IOError(err));
-        }
+        write_bytes(writer, &self.year.to_be_bytes())?;
+        write_bytes(
+            writer,
+            &[
+                self.month,
+                self.day,
+                self.hour,
+                self.minutes,
+                self.seconds,
+                self.deci_seconds,
+                self.direction_from_utc as u8,
+                self.hours_from_utc,
+                self.minutes_from_utc,
+            ],
+        )?;
 
         Ok(11)
     }
@@ -310,13 +1142,20 @@ pub enum AttributeValue {
     Unsupported(Vec<u8>),
     Unknown(Vec<u8>),
     NoValue,
+    /// Returned by Set-Printer-Attributes for read-only attributes (RFC 3380).
+    NotSettable,
+    /// Requests deleting the attribute in Set-Printer-Attributes (RFC 3380).
+    DeleteAttribute,
+    /// The value is defined by the administrator (RFC 3380).
+    AdminDefine,
     Integer(i32),
     Boolean(bool),
     Enum(i32),
     OctetStringUnspecified(String),
     DateTime(DateTime),
     Resolution(Resolution),
-    RangeOfInteger(Range<i32>),
+    /// Both lower and upper bounds are inclusive.
+    RangeOfInteger(RangeInclusive<i32>),
     BegCollection,
     TextWithLanguage(StringWithLanguage),
     NameWithLanguage(StringWithLanguage),
@@ -331,12 +1170,44 @@ pub enum AttributeValue {
     MimeMediaType(String),
     MemberAttrName(String),
     CollectionAttribute(HashMap<String, AttributeValue>),
+    /// 1setOf values. Written as additional values with empty names.
     VectorAttribute(Vec<AttributeValue>),
 }
 
+impl AttributeValue {
+    /// Values of 1setOf attribute.
+    /// A single value is returned as a set with one element, because printers
+    /// send a 1setOf attribute without additional values if it has only one value.
+    pub fn values(&self) -> &[AttributeValue] {
+        match self {
+            Self::VectorAttribute(vals) => vals.as_slice(),
+            val => std::slice::from_ref(val),
+        }
+    }
+
+    /// String of text, name, keyword, and other string-like values.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::OctetStringUnspecified(val)
+            | Self::TextWithoutLanguage(val)
+            | Self::NameWithoutLanguage(val)
+            | Self::Keyword(val)
+            | Self::Uri(val)
+            | Self::UriScheme(val)
+            | Self::Charset(val)
+            | Self::NaturalLanguage(val)
+            | Self::MimeMediaType(val) => Some(val),
+            Self::TextWithLanguage(val) | Self::NameWithLanguage(val) => Some(&val.string),
+            _ => None,
+        }
+    }
+}
+
+/// Delimiter tag of the group and attributes in it.
 pub type AttributeGroup = (DelimiterOrValueTag, Vec<(String, AttributeValue)>);
 
-#[derive(Debug, PartialEq, Eq)]
+/// An IPP request message (RFC 8010 3.1.1).
+#[derive(Debug, PartialEq, Eq, Clone)]
 pub struct IPPRequest {
     pub version_major: i8,
     pub version_minor: i8,
@@ -351,20 +1222,14 @@ impl IPPRequest {
     where
         W: Write,
     {
-        match writer.write(&[tag as u8]) {
-            Ok(written) => Ok(written),
-            Err(err) => Err(IPPError::IOError(err)),
-        }
+        write_bytes(writer, &[tag as u8])
     }
 
     fn write_u16<W>(writer: &mut W, val: u16) -> Result<usize, IPPError>
     where
         W: Write,
     {
-        match writer.write(&val.to_be_bytes()) {
-            Ok(written) => Ok(written),
-            Err(err) => Err(IPPError::IOError(err)),
-        }
+        write_bytes(writer, &val.to_be_bytes())
     }
 
     fn write_str_and_len<W>(writer: &mut W, val: &str) -> Result<usize, IPPError>
@@ -372,11 +1237,8 @@ impl IPPRequest {
         W: Write,
     {
         let mut written = 0;
-        written += IPPRequest::write_u16(writer, val.as_bytes().len() as u16)?;
-        written += match writer.write(val.as_bytes()) {
-            Ok(written) => written,
-            Err(err) => return Err(IPPError::IOError(err)),
-        };
+        written += IPPRequest::write_u16(writer, val.len() as u16)?;
+        written += write_bytes(writer, val.as_bytes())?;
         Ok(written)
     }
 
@@ -390,51 +1252,51 @@ impl IPPRequest {
                 written += IPPRequest::write_tag(writer, DelimiterOrValueTag::Unsupported)?;
                 written += IPPRequest::write_str_and_len(writer, name)?;
                 written += IPPRequest::write_u16(writer, val.len() as u16)?;
-                written += match writer.write(val.as_slice()) {
-                    Ok(written) => written,
-                    Err(err) => return Err(IPPError::IOError(err)),
-                };
+                written += write_bytes(writer, val.as_slice())?;
             }
             AttributeValue::Unknown(val) => {
                 written += IPPRequest::write_tag(writer, DelimiterOrValueTag::Unknown)?;
                 written += IPPRequest::write_str_and_len(writer, name)?;
                 written += IPPRequest::write_u16(writer, val.len() as u16)?;
-                written += match writer.write(val.as_slice()) {
-                    Ok(written) => written,
-                    Err(err) => return Err(IPPError::IOError(err)),
-                };
+                written += write_bytes(writer, val.as_slice())?;
             }
             AttributeValue::NoValue => {
                 written += IPPRequest::write_tag(writer, DelimiterOrValueTag::NoValue)?;
                 written += IPPRequest::write_str_and_len(writer, name)?;
                 written += IPPRequest::write_u16(writer, 0u16)?;
             }
+            AttributeValue::NotSettable => {
+                written += IPPRequest::write_tag(writer, DelimiterOrValueTag::NotSettable)?;
+                written += IPPRequest::write_str_and_len(writer, name)?;
+                written += IPPRequest::write_u16(writer, 0u16)?;
+            }
+            AttributeValue::DeleteAttribute => {
+                written += IPPRequest::write_tag(writer, DelimiterOrValueTag::DeleteAttribute)?;
+                written += IPPRequest::write_str_and_len(writer, name)?;
+                written += IPPRequest::write_u16(writer, 0u16)?;
+            }
+            AttributeValue::AdminDefine => {
+                written += IPPRequest::write_tag(writer, DelimiterOrValueTag::AdminDefine)?;
+                written += IPPRequest::write_str_and_len(writer, name)?;
+                written += IPPRequest::write_u16(writer, 0u16)?;
+            }
             AttributeValue::Integer(val) => {
                 written += IPPRequest::write_tag(writer, DelimiterOrValueTag::Integer)?;
                 written += IPPRequest::write_str_and_len(writer, name)?;
                 written += IPPRequest::write_u16(writer, 4u16)?;
-                written += match writer.write(&val.to_be_bytes()) {
-                    Ok(written) => written,
-                    Err(err) => return Err(IPPError::IOError(err)),
-                };
+                written += write_bytes(writer, &val.to_be_bytes())?;
             }
             AttributeValue::Boolean(val) => {
                 written += IPPRequest::write_tag(writer, DelimiterOrValueTag::Boolean)?;
                 written += IPPRequest::write_str_and_len(writer, name)?;
                 written += IPPRequest::write_u16(writer, 1u16)?;
-                written += match writer.write(&[if *val { 1u8 } else { 0u8 }]) {
-                    Ok(written) => written,
-                    Err(err) => return Err(IPPError::IOError(err)),
-                };
+                written += write_bytes(writer, &[if *val { 1u8 } else { 0u8 }])?;
             }
             AttributeValue::Enum(val) => {
                 written += IPPRequest::write_tag(writer, DelimiterOrValueTag::Enum)?;
                 written += IPPRequest::write_str_and_len(writer, name)?;
                 written += IPPRequest::write_u16(writer, 4u16)?;
-                written += match writer.write(&val.to_be_bytes()) {
-                    Ok(written) => written,
-                    Err(err) => return Err(IPPError::IOError(err)),
-                };
+                written += write_bytes(writer, &val.to_be_bytes())?;
             }
             AttributeValue::OctetStringUnspecified(val) => {
                 written +=
@@ -458,14 +1320,8 @@ impl IPPRequest {
                 written += IPPRequest::write_tag(writer, DelimiterOrValueTag::RangeOfInteger)?;
                 written += IPPRequest::write_str_and_len(writer, name)?;
                 written += IPPRequest::write_u16(writer, 8u16)?;
-                written += match writer.write(&val.start.to_be_bytes()) {
-                    Ok(written) => written,
-                    Err(err) => return Err(IPPError::IOError(err)),
-                };
-                written += match writer.write(&val.end.to_be_bytes()) {
-                    Ok(written) => written,
-                    Err(err) => return Err(IPPError::IOError(err)),
-                };
+                written += write_bytes(writer, &val.start().to_be_bytes())?;
+                written += write_bytes(writer, &val.end().to_be_bytes())?;
             }
             AttributeValue::BegCollection => {
                 written += IPPRequest::write_tag(writer, DelimiterOrValueTag::BegCollection)?;
@@ -539,7 +1395,11 @@ impl IPPRequest {
                 written += IPPRequest::write_str_and_len(writer, name)?;
                 written += IPPRequest::write_u16(writer, 0)?;
 
-                for (k, v) in val {
+                // Sort members so that the output doesn't depend on the order of HashMap.
+                let mut members = val.iter().collect::<Vec<_>>();
+                members.sort_by(|a, b| a.0.cmp(b.0));
+
+                for (k, v) in members {
                     written += IPPRequest::write_tag(writer, DelimiterOrValueTag::MemberAttrName)?;
                     written += IPPRequest::write_u16(writer, 0)?;
 
@@ -565,60 +1425,162 @@ impl IPPRequest {
         Ok(written)
     }
 
-    fn write_attr_group<W>(&self, writer: &mut W) -> Result<usize, IPPError>
+    fn write_attr_group<W>(writer: &mut W, attrs: &[AttributeGroup]) -> Result<usize, IPPError>
     where
         W: Write,
     {
         let mut written = 0;
 
-        for group in &self.attrs {
-            written += match writer.write(&[group.0 as u8]) {
-                Ok(written) => written,
-                Err(err) => return Err(IPPError::IOError(err)),
-            };
+        for group in attrs {
+            written += write_bytes(writer, &[group.0 as u8])?;
 
             for attr in &group.1 {
                 written += IPPRequest::write_attr(writer, attr.0.as_str(), &attr.1)?;
             }
         }
 
-        written += match writer.write(&[DelimiterOrValueTag::EndOfAttributesTag as u8]) {
-            Ok(written) => written,
-            Err(err) => return Err(IPPError::IOError(err)),
-        };
+        written += write_bytes(writer, &[DelimiterOrValueTag::EndOfAttributesTag as u8])?;
 
         Ok(written)
     }
 
-    pub fn write_to_stream<W>(&self, writer: &mut W) -> Result<usize, IPPError>
+    /// Encodes a message shared by requests and responses, which differ only in
+    /// whether `code` is an operation-id or a status-code.
+    fn write_message<W>(
+        writer: &mut W,
+        version: (i8, i8),
+        code: u16,
+        request_id: i32,
+        attrs: &[AttributeGroup],
+        data: &[u8],
+    ) -> Result<usize, IPPError>
     where
         W: Write,
     {
+        // Attributes are written a few bytes at a time.
+        let mut writer = io::BufWriter::new(writer);
         let mut written = 0;
-        written += match writer.write(&[self.version_major as u8, self.version_minor as u8]) {
-            Ok(written) => written,
-            Err(err) => return Err(IPPError::IOError(err)),
-        };
-        written += match writer.write(&i16::to_be_bytes(self.operation_id as i16)) {
-            Ok(written) => written,
-            Err(err) => return Err(IPPError::IOError(err)),
-        };
-        written += match writer.write(&i32::to_be_bytes(self.request_id)) {
-            Ok(written) => written,
-            Err(err) => return Err(IPPError::IOError(err)),
-        };
+        written += write_bytes(&mut writer, &[version.0 as u8, version.1 as u8])?;
+        written += write_bytes(&mut writer, &code.to_be_bytes())?;
+        written += write_bytes(&mut writer, &request_id.to_be_bytes())?;
 
-        written += self.write_attr_group(writer)?;
+        written += IPPRequest::write_attr_group(&mut writer, attrs)?;
 
-        written += match writer.write(&self.data) {
-            Ok(written) => written,
-            Err(err) => return Err(IPPError::IOError(err)),
-        };
+        written += write_bytes(&mut writer, data)?;
+        writer.flush().map_err(IPPError::IOError)?;
 
         Ok(written)
     }
+
+    /// Encodes the request including its data and returns the number of bytes written.
+    pub fn write_to_stream<W>(&self, writer: &mut W) -> Result<usize, IPPError>
+    where
+        W: Write,
+    {
+        IPPRequest::write_message(
+            writer,
+            (self.version_major, self.version_minor),
+            self.operation_id.into(),
+            self.request_id,
+            &self.attrs,
+            &self.data,
+        )
+    }
+
+    /// Decodes a request, e.g. to implement a printer.
+    /// Everything after end-of-attributes-tag is read into data.
+    pub fn read_from_stream<R>(reader: &mut R) -> Result<IPPRequest, IPPError>
+    where
+        R: Read,
+    {
+        let (header, attrs, data) = IPPResponse::read_message(reader)?;
+
+        Ok(IPPRequest {
+            version_major: header[0] as i8,
+            version_minor: header[1] as i8,
+            operation_id: PrinterOperation::from(u16::from_be_bytes([header[2], header[3]])),
+            request_id: i32::from_be_bytes([header[4], header[5], header[6], header[7]]),
+            attrs,
+            data,
+        })
+    }
+
+    /// Returns the first attribute named `name` in any group.
+    pub fn get_attribute(&self, name: &str) -> Option<&AttributeValue> {
+        self.attrs
+            .iter()
+            .flat_map(|(_, attrs)| attrs.iter())
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+    }
+}
+
+/// Limits on decoded messages, so that a corrupt length or a malicious printer
+/// makes decoding fail instead of exhausting memory or the stack.
+const MAX_ATTRIBUTES_LEN: u64 = 16 * 1024 * 1024;
+const MAX_VALUES: usize = 100_000;
+const MAX_COLLECTION_DEPTH: usize = 16;
+
+/// Header, attribute groups, and data of a message of either kind.
+pub(crate) type RawMessage = ([u8; 8], Vec<AttributeGroup>, Vec<u8>);
+
+/// Reader which tracks the position in a message for error messages and the limits.
+pub(crate) struct MessageReader<'a, R> {
+    inner: &'a mut R,
+    offset: u64,
+    values: usize,
+}
+
+impl<'a, R> MessageReader<'a, R>
+where
+    R: Read,
+{
+    fn new(inner: &'a mut R) -> Self {
+        Self {
+            inner,
+            offset: 0,
+            values: 0,
+        }
+    }
+
+    fn error(&self, reason: &str) -> IPPError {
+        self.error_at(self.offset, reason)
+    }
+
+    fn error_at<S>(&self, offset: u64, reason: S) -> IPPError
+    where
+        S: Into<String>,
+    {
+        IPPError::Malformed {
+            offset,
+            reason: reason.into(),
+        }
+    }
+
+    fn read_exact(&mut self, buf: &mut [u8]) -> Result<(), IPPError> {
+        if self.offset + buf.len() as u64 > MAX_ATTRIBUTES_LEN {
+            return Err(self.error("attributes are too large"));
+        }
+        match self.inner.read_exact(buf) {
+            Ok(()) => {
+                self.offset += buf.len() as u64;
+                Ok(())
+            }
+            Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => {
+                Err(self.error("unexpected end of message"))
+            }
+            Err(err) => Err(IPPError::IOError(err)),
+        }
+    }
+
+    fn read_u16(&mut self) -> Result<u16, IPPError> {
+        let mut buf = [0u8; 2];
+        self.read_exact(&mut buf)?;
+        Ok(u16::from_be_bytes(buf))
+    }
 }
 
+/// An IPP response message (RFC 8010 3.1.1).
 #[derive(Debug, PartialEq, Eq)]
 pub struct IPPResponse {
     pub version_major: i8,
@@ -637,13 +1599,12 @@ impl IPPResponse {
         match value_type {
             DelimiterOrValueTag::Unsupported => Ok(AttributeValue::Unsupported(buf)),
             DelimiterOrValueTag::Unknown => Ok(AttributeValue::Unknown(buf)),
-            DelimiterOrValueTag::NoValue => {
-                if buf.is_empty() {
-                    Ok(AttributeValue::NoValue)
-                } else {
-                    Err(IPPError::ProtocolError)
-                }
-            }
+            // Out-of-band values should be empty, but RFC 8010 3.8 requires
+            // the value to be ignored if there is one.
+            DelimiterOrValueTag::NoValue => Ok(AttributeValue::NoValue),
+            DelimiterOrValueTag::NotSettable => Ok(AttributeValue::NotSettable),
+            DelimiterOrValueTag::DeleteAttribute => Ok(AttributeValue::DeleteAttribute),
+            DelimiterOrValueTag::AdminDefine => Ok(AttributeValue::AdminDefine),
             DelimiterOrValueTag::Integer => {
                 if buf.len() == 4 {
                     Ok(AttributeValue::Integer(i32::from_be_bytes([
@@ -688,7 +1649,7 @@ impl IPPResponse {
                 let start = i32::from_be_bytes([buf[0], buf[1], buf[2], buf[3]]);
                 let end = i32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]);
 
-                Ok(AttributeValue::RangeOfInteger(start..end))
+                Ok(AttributeValue::RangeOfInteger(start..=end))
             }
             DelimiterOrValueTag::BegCollection => {
                 if !buf.is_empty() {
@@ -766,62 +1727,99 @@ impl IPPResponse {
     }
 
     fn parse_attribute<R>(
-        reader: &mut R,
+        reader: &mut MessageReader<R>,
         value_type: DelimiterOrValueTag,
     ) -> Result<(String, AttributeValue), IPPError>
     where
         R: Read,
     {
-        let mut buf = [0u8; 2];
-        if let Err(err) = reader.read_exact(&mut buf) {
-            return Err(IPPError::IOError(err));
-        };
-        let name_len = i16::from_be_bytes(buf);
+        // Errors point to the value tag read before.
+        let start = reader.offset - 1;
+        reader.values += 1;
+        if reader.values > MAX_VALUES {
+            return Err(reader.error_at(start, "too many values"));
+        }
+
+        let name_len = reader.read_u16()?;
         let mut name_buf = vec![0u8; name_len as usize];
-        if let Err(err) = reader.read_exact(name_buf.as_mut_slice()) {
-            return Err(IPPError::IOError(err));
-        };
+        reader.read_exact(&mut name_buf)?;
         let name = match String::from_utf8(name_buf) {
             Ok(name) => name,
-            Err(_) => return Err(IPPError::InvalidValue),
+            Err(_) => return Err(reader.error_at(start, "attribute name is not UTF-8")),
         };
 
-        if let Err(err) = reader.read_exact(&mut buf) {
-            return Err(IPPError::IOError(err));
-        };
-        let value_len = i16::from_be_bytes(buf);
+        let value_len = reader.read_u16()?;
         let mut value_buf = vec![0u8; value_len as usize];
-        if let Err(err) = reader.read_exact(value_buf.as_mut_slice()) {
-            return Err(IPPError::IOError(err));
-        };
+        reader.read_exact(&mut value_buf)?;
 
-        let value = IPPResponse::decode_attribute_value(value_type, value_buf)?;
+        let value = IPPResponse::decode_attribute_value(value_type, value_buf).map_err(|_| {
+            reader.error_at(
+                start,
+                format!("invalid {:?} value of \"{}\"", value_type, name),
+            )
+        })?;
 
         Ok((name, value))
     }
 
-    fn parse_tag<R>(reader: &mut R) -> Result<DelimiterOrValueTag, IPPError>
+    fn parse_tag<R>(reader: &mut MessageReader<R>) -> Result<DelimiterOrValueTag, IPPError>
     where
         R: Read,
     {
         let mut buf = [0u8; 1];
-        if let Err(err) = reader.read_exact(&mut buf) {
-            return Err(IPPError::IOError(err));
-        };
+        reader.read_exact(&mut buf)?;
 
         match FromPrimitive::from_u8(buf[0]) {
             Some(value) => Ok(value),
-            None => Err(IPPError::ProtocolError),
+            None => {
+                Err(reader.error_at(reader.offset - 1, format!("unknown tag 0x{:02x}", buf[0])))
+            }
+        }
+    }
+
+    fn parse_member_value<R>(
+        reader: &mut MessageReader<R>,
+        tag: DelimiterOrValueTag,
+        depth: usize,
+    ) -> Result<AttributeValue, IPPError>
+    where
+        R: Read,
+    {
+        if tag.is_group() {
+            return Err(reader.error_at(reader.offset - 1, "collection is not terminated"));
+        }
+        match tag {
+            DelimiterOrValueTag::MemberAttrName | DelimiterOrValueTag::EndCollection => {
+                Err(reader.error_at(reader.offset - 1, "member value is missing"))
+            }
+            DelimiterOrValueTag::BegCollection => {
+                IPPResponse::parse_attribute(reader, DelimiterOrValueTag::BegCollection)?;
+                IPPResponse::parse_collection(reader, depth + 1)
+            }
+            tag => {
+                let (_, attr) = IPPResponse::parse_attribute(reader, tag)?;
+                Ok(attr)
+            }
         }
     }
 
-    fn parse_collection<R>(reader: &mut R) -> Result<AttributeValue, IPPError>
+    /// Parses members of a collection (RFC 8010 3.1.6) after begCollection.
+    /// Members with additional values are converted to VectorAttribute.
+    fn parse_collection<R>(
+        reader: &mut MessageReader<R>,
+        depth: usize,
+    ) -> Result<AttributeValue, IPPError>
     where
         R: Read,
     {
+        if depth > MAX_COLLECTION_DEPTH {
+            return Err(reader.error("collections are nested too deeply"));
+        }
+
         let mut map = HashMap::<String, AttributeValue>::new();
+        let mut tag = IPPResponse::parse_tag(reader)?;
         loop {
-            let attr_name = match IPPResponse::parse_tag(reader)? {
+            let attr_name = match tag {
                 DelimiterOrValueTag::EndCollection => {
                     IPPResponse::parse_attribute(reader, DelimiterOrValueTag::EndCollection)?;
                     break;
@@ -832,41 +1830,55 @@ impl IPPResponse {
                     {
                         name
                     } else {
-                        return Err(IPPError::ProtocolError);
+                        return Err(reader.error("member name is not a name"));
                     }
                 }
-                _ => return Err(IPPError::ProtocolError),
+                _ => {
+                    return Err(
+                        reader.error_at(reader.offset - 1, "expected a member name in collection")
+                    )
+                }
             };
 
-            let value = match IPPResponse::parse_tag(reader)? {
-                DelimiterOrValueTag::OperationAttributesTag
-                | DelimiterOrValueTag::JobAttributesTag
-                | DelimiterOrValueTag::PrinterAttributesTag
-                | DelimiterOrValueTag::UnsupportedAttributesTag => {
-                    return Err(IPPError::ProtocolError)
-                }
-                DelimiterOrValueTag::BegCollection => {
-                    IPPResponse::parse_attribute(reader, DelimiterOrValueTag::BegCollection)?;
-                    IPPResponse::parse_collection(reader)?
-                }
-                tag => {
-                    let (_, attr) = IPPResponse::parse_attribute(reader, tag)?;
-                    attr
+            let value_tag = IPPResponse::parse_tag(reader)?;
+            let mut values = vec![IPPResponse::parse_member_value(reader, value_tag, depth)?];
+            loop {
+                tag = IPPResponse::parse_tag(reader)?;
+                match tag {
+                    DelimiterOrValueTag::MemberAttrName | DelimiterOrValueTag::EndCollection => {
+                        break
+                    }
+                    // Additional value of the same member.
+                    value_tag => {
+                        values.push(IPPResponse::parse_member_value(reader, value_tag, depth)?)
+                    }
                 }
-            };
+            }
 
-            map.insert(attr_name, value);
+            if values.len() == 1 {
+                map.insert(attr_name, values.pop().unwrap());
+            } else {
+                map.insert(attr_name, AttributeValue::VectorAttribute(values));
+            }
         }
 
         Ok(AttributeValue::CollectionAttribute(map))
     }
 
-    fn parse_attribute_group<R>(reader: &mut R) -> Result<Vec<AttributeGroup>, IPPError>
+    fn parse_attribute_group<R>(
+        reader: &mut MessageReader<R>,
+    ) -> Result<Vec<AttributeGroup>, IPPError>
     where
         R: Read,
     {
         let mut cur_attr_tag = IPPResponse::parse_tag(reader)?;
         let mut attr_groups = Vec::new();
+        if cur_attr_tag == DelimiterOrValueTag::EndOfAttributesTag {
+            return Ok(attr_groups);
+        }
+        if !cur_attr_tag.is_group() {
+            return Err(reader.error_at(reader.offset - 1, "expected a group tag"));
+        }
 
         loop {
             let mut attrs = LinkedList::<(String, AttributeValue)>::new();
@@ -875,28 +1887,32 @@ impl IPPResponse {
 
             loop {
                 let tag = IPPResponse::parse_tag(reader)?;
+                let start = reader.offset - 1;
 
-                match tag {
+                let attr = match tag {
                     DelimiterOrValueTag::EndOfAttributesTag => {
                         next_attr_tag = tag;
                         end = true;
                         break;
                     }
-                    DelimiterOrValueTag::OperationAttributesTag
-                    | DelimiterOrValueTag::JobAttributesTag
-                    | DelimiterOrValueTag::PrinterAttributesTag
-                    | DelimiterOrValueTag::UnsupportedAttributesTag => {
+                    tag if tag.is_group() => {
                         next_attr_tag = tag;
                         break;
                     }
+                    DelimiterOrValueTag::MemberAttrName | DelimiterOrValueTag::EndCollection => {
+                        return Err(reader.error_at(start, format!("unexpected {:?}", tag)));
+                    }
                     DelimiterOrValueTag::BegCollection => {
                         let (name, _) = IPPResponse::parse_attribute(reader, tag)?;
-                        attrs.push_back((name, IPPResponse::parse_collection(reader)?));
+                        (name, IPPResponse::parse_collection(reader, 1)?)
                     }
-                    _ => attrs.push_back(IPPResponse::parse_attribute(reader, tag)?),
+                    _ => IPPResponse::parse_attribute(reader, tag)?,
+                };
+                if attr.0.is_empty() && attrs.is_empty() {
+                    return Err(reader.error_at(start, "additional value without an attribute"));
                 }
+                attrs.push_back(attr);
             }
-
             let mut group = Vec::new();
 
             let mut attr_vec = vec![];
@@ -940,64 +1956,2089 @@ impl IPPResponse {
         Ok(attr_groups)
     }
 
-    pub fn read_from_stream<R>(reader: &mut R) -> Result<IPPResponse, IPPError>
+    /// Decodes the header and attributes of a message of either kind, and returns
+    /// them with the rest of the message.
+    pub(crate) fn read_message<R>(reader: &mut R) -> Result<RawMessage, IPPError>
     where
         R: Read,
     {
-        let mut buf = [0u8; 8];
-        if let Err(err) = reader.read_exact(&mut buf) {
-            return Err(IPPError::IOError(err));
-        };
-        let version_major = buf[0] as i8;
-        let version_minor = buf[1] as i8;
-        let status_code: StatusCode =
-            match FromPrimitive::from_i16(i16::from_be_bytes([buf[2], buf[3]])) {
-                Some(status_code) => status_code,
-                None => return Err(IPPError::ProtocolError),
-            };
-        let request_id = i32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]);
-
-        let attrs = IPPResponse::parse_attribute_group(reader)?;
+        let mut reader = MessageReader::new(reader);
+        let mut header = [0u8; 8];
+        reader.read_exact(&mut header)?;
+        let attrs = IPPResponse::parse_attribute_group(&mut reader)?;
 
         let mut data = Vec::<u8>::new();
-        if let Err(err) = reader.read_to_end(&mut data) {
+        if let Err(err) = reader.inner.read_to_end(&mut data) {
             return Err(IPPError::IOError(err));
         };
 
+        Ok((header, attrs, data))
+    }
+
+    /// Decodes a response. Everything after end-of-attributes-tag is read into data.
+    /// Corrupt messages are reported as [`IPPError::Malformed`].
+    pub fn read_from_stream<R>(reader: &mut R) -> Result<IPPResponse, IPPError>
+    where
+        R: Read,
+    {
+        let (header, attrs, data) = IPPResponse::read_message(reader)?;
+
+        let status_code = StatusCode::from(u16::from_be_bytes([header[2], header[3]]));
+
         Ok(IPPResponse {
-            version_major,
-            version_minor,
+            version_major: header[0] as i8,
+            version_minor: header[1] as i8,
             status_code,
-            request_id,
+            request_id: i32::from_be_bytes([header[4], header[5], header[6], header[7]]),
             attrs,
             data,
         })
     }
+
+    /// Returns the first attribute named `name` in any group.
+    pub fn get_attribute(&self, name: &str) -> Option<&AttributeValue> {
+        self.attrs
+            .iter()
+            .flat_map(|(_, attrs)| attrs.iter())
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Encodes the response, e.g. to implement a printer.
+    pub fn write_to_stream<W>(&self, writer: &mut W) -> Result<usize, IPPError>
+    where
+        W: Write,
+    {
+        IPPRequest::write_message(
+            writer,
+            (self.version_major, self.version_minor),
+            self.status_code.into(),
+            self.request_id,
+            &self.attrs,
+            &self.data,
+        )
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// job-state (RFC 8011 5.3.7)
+#[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, Eq, Clone, Copy)]
+pub enum JobState {
+    Pending = 3,
+    PendingHeld = 4,
+    Processing = 5,
+    ProcessingStopped = 6,
+    Canceled = 7,
+    Aborted = 8,
+    Completed = 9,
+}
 
-    macro_rules! write_int_be {
-        ($writer:ident,$var:path as $ty:ident) => {{
-            let data = $ty::to_be_bytes($var as $ty);
-            $writer.write(&data)
-        }};
+impl JobState {
+    /// Whether the job will never change its state again.
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Canceled | Self::Aborted | Self::Completed)
     }
+}
 
-    macro_rules! write_attr {
-        ($writer:ident,$type:ident,$name:expr,$value:expr) => {
-            if let Err(err) = $writer.write(&[DelimiterOrValueTag::$type as u8]) {
-                Err(err)
-            } else {
-                if let Err(err) = $writer.write(&i16::to_be_bytes($name.len() as i16)) {
-                    Err(err)
-                } else {
-                    if let Err(err) = $writer.write($name.as_bytes()) {
-                        Err(err)
-                    } else {
-                        if let Err(err) = $writer.write(&i16::to_be_bytes($value.len() as i16)) {
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct JobStatus {
+    pub state: JobState,
+    /// job-state-reasons keywords like "job-printing" or "aborted-by-system".
+    pub reasons: Vec<String>,
+}
+
+impl JobStatus {
+    fn from_response(resp: &IPPResponse) -> Result<Self, IppPrintError> {
+        let state = match resp.get_attribute("job-state") {
+            Some(AttributeValue::Enum(state)) => match FromPrimitive::from_i32(*state) {
+                Some(state) => state,
+                None => return Err(IppPrintError::ProtocolError(IPPError::InvalidValue)),
+            },
+            _ => return Err(IppPrintError::MissingAttribute("job-state".to_string())),
+        };
+        let reasons = match resp.get_attribute("job-state-reasons") {
+            Some(reasons) => reasons
+                .values()
+                .iter()
+                .filter_map(|e| match e {
+                    AttributeValue::Keyword(reason) => Some(reason.clone()),
+                    _ => None,
+                })
+                .collect(),
+            None => vec![],
+        };
+
+        Ok(Self { state, reasons })
+    }
+}
+
+/// Job attributes requested for [`JobInfo`].
+pub const JOB_INFO_ATTRIBUTES: &[&str] = &[
+    "job-id",
+    "job-name",
+    "job-originating-user-name",
+    "job-state",
+    "job-state-reasons",
+    "job-k-octets",
+    "job-impressions",
+    "job-impressions-completed",
+    "job-media-sheets-completed",
+    "time-at-creation",
+    "time-at-processing",
+    "time-at-completed",
+];
+
+/// A job as returned by Get-Jobs or Get-Job-Attributes. Attributes the
+/// printer didn't return are None or empty.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct JobInfo {
+    pub id: i32,
+    pub name: Option<String>,
+    /// job-originating-user-name
+    pub user: Option<String>,
+    pub state: Option<JobState>,
+    /// job-state-reasons keywords like "job-printing".
+    pub state_reasons: Vec<String>,
+    /// Size of the documents in kilobytes, rounded up.
+    pub k_octets: Option<i32>,
+    /// Pages to print, which printers know only for some document formats.
+    pub impressions: Option<i32>,
+    pub impressions_completed: Option<i32>,
+    pub media_sheets_completed: Option<i32>,
+    /// time-at-* in seconds since the printer started (printer-up-time).
+    /// time-at-processing and time-at-completed are None until then, and
+    /// some printers report them as 0 or negative instead.
+    pub time_at_creation: Option<i32>,
+    pub time_at_processing: Option<i32>,
+    pub time_at_completed: Option<i32>,
+}
+
+impl JobInfo {
+    /// Parses a job attributes group, or returns None if it has no job-id.
+    pub fn from_attributes(attrs: &[(String, AttributeValue)]) -> Option<Self> {
+        let get = |name: &str| attrs.iter().find(|(key, _)| key == name).map(|(_, e)| e);
+        let integer = |name: &str| match get(name) {
+            Some(AttributeValue::Integer(value)) => Some(*value),
+            _ => None,
+        };
+        let string = |name: &str| get(name).and_then(|e| e.as_str()).map(str::to_string);
+
+        Some(Self {
+            id: integer("job-id")?,
+            name: string("job-name"),
+            user: string("job-originating-user-name"),
+            state: match get("job-state") {
+                Some(AttributeValue::Enum(state)) => FromPrimitive::from_i32(*state),
+                _ => None,
+            },
+            state_reasons: get("job-state-reasons").map_or(vec![], strings_of),
+            k_octets: integer("job-k-octets"),
+            impressions: integer("job-impressions"),
+            impressions_completed: integer("job-impressions-completed"),
+            media_sheets_completed: integer("job-media-sheets-completed"),
+            time_at_creation: integer("time-at-creation"),
+            time_at_processing: integer("time-at-processing"),
+            time_at_completed: integer("time-at-completed"),
+        })
+    }
+
+    /// Jobs in the job attributes groups of the response.
+    pub fn from_response(resp: &IPPResponse) -> Vec<Self> {
+        resp.attrs
+            .iter()
+            .filter(|(tag, _)| *tag == DelimiterOrValueTag::JobAttributesTag)
+            .filter_map(|(_, attrs)| JobInfo::from_attributes(attrs))
+            .collect()
+    }
+}
+
+/// printer-state (RFC 8011 5.4.11)
+#[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, Eq, Clone, Copy)]
+pub enum PrinterState {
+    Idle = 3,
+    Processing = 4,
+    Stopped = 5,
+}
+
+fn strings_of(value: &AttributeValue) -> Vec<String> {
+    value
+        .values()
+        .iter()
+        .filter_map(|e| e.as_str().map(|e| e.to_string()))
+        .collect()
+}
+
+fn resolutions_of(value: &AttributeValue) -> Vec<Resolution> {
+    value
+        .values()
+        .iter()
+        .filter_map(|e| match e {
+            AttributeValue::Resolution(res) => Some(res.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Printer attributes returned by Get-Printer-Attributes.
+/// Attributes without a dedicated field are kept in other.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct PrinterAttributes {
+    pub printer_name: Option<String>,
+    pub printer_make_and_model: Option<String>,
+    pub printer_state: Option<PrinterState>,
+    pub printer_state_reasons: Vec<String>,
+    pub ipp_versions_supported: Vec<String>,
+    pub operations_supported: Vec<PrinterOperation>,
+    pub document_format_supported: Vec<String>,
+    pub media_default: Option<String>,
+    pub media_supported: Vec<String>,
+    pub media_ready: Vec<String>,
+    pub sides_supported: Vec<String>,
+    /// None if the printer doesn't report color-supported.
+    pub color_supported: Option<bool>,
+    pub printer_resolution_supported: Vec<Resolution>,
+    pub pwg_raster_document_resolution_supported: Vec<Resolution>,
+    /// Color space and bit depth like "srgb_8" or "sgray_8".
+    pub pwg_raster_document_type_supported: Vec<String>,
+    /// How back sides of duplex sheets should be transformed, like "flipped".
+    pub pwg_raster_document_sheet_back: Option<String>,
+    /// AirPrint capabilities for image/urf like "SRGB24" or "RS300".
+    pub urf_supported: Vec<String>,
+    pub pclm_source_resolution_supported: Vec<Resolution>,
+    /// Rows per strip the printer prefers in application/PCLm.
+    pub pclm_strip_height_preferred: Option<i32>,
+    /// Strip compression in order of preference, like "flate" or "jpeg".
+    pub pclm_compression_method_preferred: Vec<String>,
+    /// How back sides of duplex sheets should be transformed in application/PCLm.
+    pub pclm_raster_back_side: Option<String>,
+    /// None if the printer doesn't report multiple-document-jobs-supported.
+    pub multiple_document_jobs_supported: Option<bool>,
+    /// URI schemes like "http" the printer fetches documents of Print-URI and
+    /// Send-URI from.
+    pub reference_uri_schemes_supported: Vec<String>,
+    /// compression keywords like "gzip", including "none".
+    pub compression_supported: Vec<String>,
+    pub other: HashMap<String, AttributeValue>,
+}
+
+impl PrinterAttributes {
+    pub fn from_response(resp: &IPPResponse) -> Self {
+        let mut result = Self::default();
+
+        for (name, value) in resp
+            .attrs
+            .iter()
+            .filter(|(tag, _)| *tag == DelimiterOrValueTag::PrinterAttributesTag)
+            .flat_map(|(_, attrs)| attrs.iter())
+        {
+            match name.as_str() {
+                "printer-name" => result.printer_name = value.as_str().map(|e| e.to_string()),
+                "printer-make-and-model" => {
+                    result.printer_make_and_model = value.as_str().map(|e| e.to_string())
+                }
+                "printer-state" => {
+                    result.printer_state = match value {
+                        AttributeValue::Enum(state) => FromPrimitive::from_i32(*state),
+                        _ => None,
+                    }
+                }
+                "printer-state-reasons" => result.printer_state_reasons = strings_of(value),
+                "ipp-versions-supported" => result.ipp_versions_supported = strings_of(value),
+                "operations-supported" => {
+                    result.operations_supported = value
+                        .values()
+                        .iter()
+                        .filter_map(|e| match e {
+                            AttributeValue::Enum(op) => Some(PrinterOperation::from(*op as u16)),
+                            _ => None,
+                        })
+                        .collect()
+                }
+                "document-format-supported" => result.document_format_supported = strings_of(value),
+                "media-default" => result.media_default = value.as_str().map(|e| e.to_string()),
+                "media-supported" => result.media_supported = strings_of(value),
+                "media-ready" => result.media_ready = strings_of(value),
+                "sides-supported" => result.sides_supported = strings_of(value),
+                "color-supported" => {
+                    result.color_supported = match value {
+                        AttributeValue::Boolean(supported) => Some(*supported),
+                        _ => None,
+                    }
+                }
+                "printer-resolution-supported" => {
+                    result.printer_resolution_supported = resolutions_of(value)
+                }
+                "pwg-raster-document-resolution-supported" => {
+                    result.pwg_raster_document_resolution_supported = resolutions_of(value)
+                }
+                "pwg-raster-document-type-supported" => {
+                    result.pwg_raster_document_type_supported = strings_of(value)
+                }
+                "pwg-raster-document-sheet-back" => {
+                    result.pwg_raster_document_sheet_back = value.as_str().map(|e| e.to_string())
+                }
+                "urf-supported" => result.urf_supported = strings_of(value),
+                "pclm-source-resolution-supported" => {
+                    result.pclm_source_resolution_supported = resolutions_of(value)
+                }
+                "pclm-strip-height-preferred" => {
+                    result.pclm_strip_height_preferred = match value {
+                        AttributeValue::Integer(height) => Some(*height),
+                        _ => None,
+                    }
+                }
+                "pclm-compression-method-preferred" => {
+                    result.pclm_compression_method_preferred = strings_of(value)
+                }
+                "pclm-raster-back-side" => {
+                    result.pclm_raster_back_side = value.as_str().map(|e| e.to_string())
+                }
+                "compression-supported" => result.compression_supported = strings_of(value),
+                "reference-uri-schemes-supported" => {
+                    result.reference_uri_schemes_supported = strings_of(value)
+                }
+                "multiple-document-jobs-supported" => {
+                    result.multiple_document_jobs_supported = match value {
+                        AttributeValue::Boolean(supported) => Some(*supported),
+                        _ => None,
+                    }
+                }
+                _ => {
+                    result.other.insert(name.clone(), value.clone());
+                }
+            }
+        }
+
+        result
+    }
+
+    /// Checks that the printer can fetch document_uri for Print-URI or
+    /// Send-URI, by its scheme compared ignoring case.
+    pub fn check_reference_uri(&self, document_uri: &str) -> Result<(), IppPrintError> {
+        let scheme = document_uri
+            .split_once(':')
+            .map(|(scheme, _)| scheme)
+            .filter(|e| !e.is_empty() && !e.contains('/'))
+            .ok_or_else(|| IppPrintError::InvalidUri(document_uri.to_string()))?;
+        if self
+            .reference_uri_schemes_supported
+            .iter()
+            .any(|e| e.eq_ignore_ascii_case(scheme))
+        {
+            Ok(())
+        } else {
+            Err(IppPrintError::Unsupported(format!(
+                "reference-uri-scheme {}",
+                scheme
+            )))
+        }
+    }
+}
+
+/// which-jobs of Get-Jobs
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum WhichJobs {
+    Completed,
+    NotCompleted,
+}
+
+/// Operation attributes to filter jobs returned by Get-Jobs.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct GetJobsOptions {
+    pub which_jobs: WhichJobs,
+    /// Only return jobs submitted by the requesting user.
+    pub my_jobs: bool,
+    /// Maximum number of jobs to return.
+    pub limit: Option<i32>,
+    /// Job attributes to return. Printers return only job-uri and job-id if empty.
+    pub requested_attributes: Vec<String>,
+}
+
+impl Default for GetJobsOptions {
+    fn default() -> Self {
+        Self {
+            which_jobs: WhichJobs::NotCompleted,
+            my_jobs: false,
+            limit: None,
+            requested_attributes: vec![],
+        }
+    }
+}
+
+impl GetJobsOptions {
+    fn to_attrs(&self) -> Vec<(String, AttributeValue)> {
+        let which_jobs = match self.which_jobs {
+            WhichJobs::Completed => "completed",
+            WhichJobs::NotCompleted => "not-completed",
+        };
+        let mut attrs = vec![
+            (
+                "which-jobs".to_string(),
+                AttributeValue::Keyword(which_jobs.to_string()),
+            ),
+            ("my-jobs".to_string(), AttributeValue::Boolean(self.my_jobs)),
+        ];
+        if let Some(limit) = self.limit {
+            attrs.push(("limit".to_string(), AttributeValue::Integer(limit)));
+        }
+        if !self.requested_attributes.is_empty() {
+            attrs.push((
+                "requested-attributes".to_string(),
+                AttributeValue::VectorAttribute(
+                    self.requested_attributes
+                        .iter()
+                        .map(|e| AttributeValue::Keyword(e.clone()))
+                        .collect(),
+                ),
+            ));
+        }
+        attrs
+    }
+}
+
+/// sides (RFC 8011 5.2.8)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Sides {
+    OneSided,
+    /// Duplex bound on the long edge, like a book.
+    TwoSidedLongEdge,
+    /// Duplex bound on the short edge, like a calendar.
+    TwoSidedShortEdge,
+}
+
+impl Sides {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Self::OneSided => "one-sided",
+            Self::TwoSidedLongEdge => "two-sided-long-edge",
+            Self::TwoSidedShortEdge => "two-sided-short-edge",
+        }
+    }
+}
+
+/// print-quality (RFC 8011 5.2.13)
+#[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, Eq, Clone, Copy)]
+pub enum PrintQuality {
+    Draft = 3,
+    Normal = 4,
+    High = 5,
+}
+
+/// print-color-mode (PWG 5100.13)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum PrintColorMode {
+    Auto,
+    BiLevel,
+    Color,
+    Monochrome,
+}
+
+impl PrintColorMode {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::BiLevel => "bi-level",
+            Self::Color => "color",
+            Self::Monochrome => "monochrome",
+        }
+    }
+
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "auto" => Some(Self::Auto),
+            "bi-level" => Some(Self::BiLevel),
+            "color" => Some(Self::Color),
+            "monochrome" => Some(Self::Monochrome),
+            _ => None,
+        }
+    }
+}
+
+/// compression of document data (RFC 8011 4.4.32)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Compression {
+    /// RFC 1952
+    Gzip,
+    /// RFC 1951
+    Deflate,
+}
+
+impl Compression {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Self::Gzip => "gzip",
+            Self::Deflate => "deflate",
+        }
+    }
+
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "gzip" => Some(Self::Gzip),
+            "deflate" => Some(Self::Deflate),
+            _ => None,
+        }
+    }
+
+    /// gzip if the printer supports it, since it is more widely implemented
+    /// than deflate, or None if it supports neither.
+    pub fn choose(compression_supported: &[String]) -> Option<Self> {
+        [Self::Gzip, Self::Deflate]
+            .into_iter()
+            .find(|e| compression_supported.iter().any(|k| k == e.keyword()))
+    }
+
+    /// Compresses data as it is read from reader.
+    pub fn encoder<R>(&self, reader: R) -> Box<dyn Read + Send>
+    where
+        R: Read + Send + 'static,
+    {
+        let level = flate2::Compression::default();
+        match self {
+            Self::Gzip => Box::new(flate2::read::GzEncoder::new(reader, level)),
+            Self::Deflate => Box::new(flate2::read::DeflateEncoder::new(reader, level)),
+        }
+    }
+}
+
+/// orientation-requested (RFC 8011 5.2.10)
+#[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, Eq, Clone, Copy)]
+pub enum OrientationRequested {
+    Portrait = 3,
+    Landscape = 4,
+    ReverseLandscape = 5,
+    ReversePortrait = 6,
+}
+
+/// Job template attributes for Create-Job and Print-Job.
+/// Attributes left None are chosen by the printer.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct JobTemplate {
+    /// job-name shown on the printer panel. Sent as an operation attribute,
+    /// so it is not in to_attrs.
+    pub job_name: Option<String>,
+    pub copies: Option<i32>,
+    pub sides: Option<Sides>,
+    /// Media name like "iso_a4_210x297mm".
+    pub media: Option<String>,
+    /// Media with type, source, or margins. Use either this or media.
+    pub media_col: Option<MediaCol>,
+    pub print_quality: Option<PrintQuality>,
+    pub print_color_mode: Option<PrintColorMode>,
+    pub orientation_requested: Option<OrientationRequested>,
+    /// finishings enum values (RFC 8011 5.2.6), e.g. 4 for staple.
+    pub finishings: Vec<i32>,
+    /// page-ranges (RFC 8011 5.2.7) counted from 1, for formats the printer
+    /// interprets. Empty prints all pages.
+    pub page_ranges: Vec<RangeInclusive<i32>>,
+}
+
+impl JobTemplate {
+    /// Attributes for the job attributes group.
+    pub fn to_attrs(&self) -> Vec<(String, AttributeValue)> {
+        let mut attrs = vec![];
+        if let Some(copies) = self.copies {
+            attrs.push(("copies".to_string(), AttributeValue::Integer(copies)));
+        }
+        if let Some(sides) = self.sides {
+            attrs.push((
+                "sides".to_string(),
+                AttributeValue::Keyword(sides.keyword().to_string()),
+            ));
+        }
+        if let Some(media) = &self.media {
+            attrs.push(("media".to_string(), AttributeValue::Keyword(media.clone())));
+        }
+        if let Some(media_col) = &self.media_col {
+            attrs.push(("media-col".to_string(), media_col.to_attribute()));
+        }
+        if let Some(quality) = self.print_quality {
+            attrs.push((
+                "print-quality".to_string(),
+                AttributeValue::Enum(quality as i32),
+            ));
+        }
+        if let Some(mode) = self.print_color_mode {
+            attrs.push((
+                "print-color-mode".to_string(),
+                AttributeValue::Keyword(mode.keyword().to_string()),
+            ));
+        }
+        if let Some(orientation) = self.orientation_requested {
+            attrs.push((
+                "orientation-requested".to_string(),
+                AttributeValue::Enum(orientation as i32),
+            ));
+        }
+        if !self.page_ranges.is_empty() {
+            attrs.push((
+                "page-ranges".to_string(),
+                AttributeValue::VectorAttribute(
+                    self.page_ranges
+                        .iter()
+                        .map(|e| AttributeValue::RangeOfInteger(e.clone()))
+                        .collect(),
+                ),
+            ));
+        }
+        if !self.finishings.is_empty() {
+            attrs.push((
+                "finishings".to_string(),
+                AttributeValue::VectorAttribute(
+                    self.finishings
+                        .iter()
+                        .map(|e| AttributeValue::Enum(*e))
+                        .collect(),
+                ),
+            ));
+        }
+        attrs
+    }
+}
+
+/// Parses page ranges like "1-3,7,9-", where an open end means the last page.
+/// Returns None if a range is malformed or doesn't start from 1 or later.
+pub fn parse_page_ranges(ranges: &str) -> Option<Vec<RangeInclusive<i32>>> {
+    ranges
+        .split(',')
+        .map(|range| {
+            let range = range.trim();
+            let (start, end) = match range.split_once('-') {
+                Some((start, "")) => (start.trim().parse().ok()?, i32::MAX),
+                Some((start, end)) => (start.trim().parse().ok()?, end.trim().parse().ok()?),
+                None => (range.parse().ok()?, range.parse().ok()?),
+            };
+            (1 <= start && start <= end).then_some(start..=end)
+        })
+        .collect()
+}
+
+/// identify-actions of Identify-Printer (PWG 5100.13)
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum IdentifyAction {
+    /// Shows the message on the control panel.
+    Display,
+    /// Flashes lights on the printer.
+    Flash,
+    /// Makes a sound.
+    Sound,
+    /// Speaks the message.
+    Speak,
+}
+
+impl IdentifyAction {
+    pub fn keyword(&self) -> &'static str {
+        match self {
+            Self::Display => "display",
+            Self::Flash => "flash",
+            Self::Sound => "sound",
+            Self::Speak => "speak",
+        }
+    }
+
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "display" => Some(Self::Display),
+            "flash" => Some(Self::Flash),
+            "sound" => Some(Self::Sound),
+            "speak" => Some(Self::Speak),
+            _ => None,
+        }
+    }
+}
+
+fn integer_attr(attrs: &[(String, AttributeValue)], name: &str) -> Option<i32> {
+    attrs.iter().find_map(|(key, value)| match value {
+        AttributeValue::Integer(value) if key == name => Some(*value),
+        _ => None,
+    })
+}
+
+/// An event notification returned by Get-Notifications (RFC 3996).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Event {
+    pub subscription_id: i32,
+    pub sequence_number: i32,
+    /// notify-subscribed-event keyword like "job-completed".
+    pub event: String,
+    pub job_id: Option<i32>,
+    pub job_state: Option<JobState>,
+    /// All attributes in the event notification group.
+    pub attrs: Vec<(String, AttributeValue)>,
+}
+
+impl Event {
+    fn from_attrs(attrs: &[(String, AttributeValue)]) -> Result<Self, IppPrintError> {
+        let subscription_id = integer_attr(attrs, "notify-subscription-id")
+            .ok_or_else(|| IppPrintError::MissingAttribute("notify-subscription-id".to_string()))?;
+        let sequence_number = integer_attr(attrs, "notify-sequence-number")
+            .ok_or_else(|| IppPrintError::MissingAttribute("notify-sequence-number".to_string()))?;
+        let event = attrs
+            .iter()
+            .find_map(|(key, value)| match value {
+                AttributeValue::Keyword(event) if key == "notify-subscribed-event" => {
+                    Some(event.clone())
+                }
+                _ => None,
+            })
+            .ok_or_else(|| {
+                IppPrintError::MissingAttribute("notify-subscribed-event".to_string())
+            })?;
+        let job_state = attrs.iter().find_map(|(key, value)| match value {
+            AttributeValue::Enum(state) if key == "job-state" => FromPrimitive::from_i32(*state),
+            _ => None,
+        });
+
+        Ok(Self {
+            subscription_id,
+            sequence_number,
+            event,
+            job_id: integer_attr(attrs, "notify-job-id"),
+            job_state,
+            attrs: attrs.to_vec(),
+        })
+    }
+}
+
+/// Response of Get-Notifications.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Notifications {
+    pub events: Vec<Event>,
+    /// How long the printer wants the client to wait before the next Get-Notifications.
+    pub get_interval: Option<Duration>,
+}
+
+impl Notifications {
+    fn from_response(resp: &IPPResponse) -> Result<Self, IppPrintError> {
+        let events = resp
+            .attrs
+            .iter()
+            .filter(|(tag, _)| *tag == DelimiterOrValueTag::EventNotificationAttributesTag)
+            .map(|(_, attrs)| Event::from_attrs(attrs))
+            .collect::<Result<Vec<_>, _>>()?;
+        let get_interval = match resp.get_attribute("notify-get-interval") {
+            Some(AttributeValue::Integer(secs)) if *secs >= 0 => {
+                Some(Duration::from_secs(*secs as u64))
+            }
+            _ => None,
+        };
+
+        Ok(Self {
+            events,
+            get_interval,
+        })
+    }
+}
+
+/// A document sent with [`Job::send_document`].
+pub struct Document {
+    pub document_format: String,
+    /// document-name shown on the printer, like the file name
+    pub name: Option<String>,
+    data: Box<dyn Read + Send>,
+}
+
+impl Document {
+    /// A document which is streamed from data when it is sent.
+    pub fn new<R>(document_format: &str, data: R) -> Self
+    where
+        R: Read + Send + 'static,
+    {
+        Self {
+            document_format: document_format.to_string(),
+            name: None,
+            data: Box::new(data),
+        }
+    }
+
+    pub fn with_name(self, name: &str) -> Self {
+        Self {
+            name: Some(name.to_string()),
+            ..self
+        }
+    }
+}
+
+/// A job created with Create-Job, which documents are sent to one by one.
+/// Documents can be of different formats, and are printed in order as a
+/// single job if the printer supports multiple-document-jobs.
+pub struct Job<'a> {
+    client: &'a mut PrintClient,
+    id: i32,
+}
+
+impl Job<'_> {
+    pub fn id(&self) -> i32 {
+        self.id
+    }
+
+    /// Sends the document with Send-Document. last must be true for the last
+    /// document, after which the printer starts processing the job and
+    /// accepts no more documents.
+    pub fn send_document(
+        &mut self,
+        document: Document,
+        last: bool,
+    ) -> Result<IPPResponse, IppPrintError> {
+        let mut attrs = self
+            .client
+            .send_document_attrs(self.id, &document.document_format, last);
+        if let Some(name) = &document.name {
+            attrs.push((
+                "document-name".to_string(),
+                AttributeValue::NameWithoutLanguage(name.clone()),
+            ));
+        }
+        let req = self
+            .client
+            .build_request(PrinterOperation::SendDocument, attrs, vec![]);
+        self.client.send_document_request(req, document.data)
+    }
+
+    /// Adds the document the printer fetches from document_uri with Send-URI.
+    pub fn send_uri(
+        &mut self,
+        document_uri: &str,
+        document_format: Option<&str>,
+        last: bool,
+    ) -> Result<IPPResponse, IppPrintError> {
+        self.client
+            .send_uri(self.id, document_uri, document_format, last)
+    }
+}
+
+/// Iterator over events of a subscription, which polls with Get-Notifications.
+/// Iteration ends after the first error, e.g. when the subscription has expired.
+pub struct EventStream<'a> {
+    client: &'a mut PrintClient,
+    subscription_id: i32,
+    /// The lowest sequence number not yet returned.
+    sequence_number: i32,
+    events: VecDeque<Event>,
+    poll_interval: Duration,
+    polled: bool,
+    done: bool,
+}
+
+impl<'a> Iterator for EventStream<'a> {
+    type Item = Result<Event, IppPrintError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        while self.events.is_empty() {
+            if self.polled {
+                std::thread::sleep(self.poll_interval);
+            }
+            self.polled = true;
+
+            let notifications = match self
+                .client
+                .get_notifications(&[(self.subscription_id, self.sequence_number)])
+            {
+                Ok(notifications) => notifications,
+                Err(err) => {
+                    self.done = true;
+                    return Some(Err(err));
+                }
+            };
+            if let Some(interval) = notifications.get_interval {
+                self.poll_interval = interval;
+            }
+            for event in notifications.events {
+                if event.sequence_number >= self.sequence_number {
+                    self.sequence_number = event.sequence_number + 1;
+                    self.events.push_back(event);
+                }
+            }
+        }
+
+        self.events.pop_front().map(Ok)
+    }
+}
+
+/// Version of IPP used in requests.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy)]
+pub enum IppVersion {
+    V1_0,
+    V1_1,
+    V2_0,
+    V2_1,
+    V2_2,
+}
+
+impl IppVersion {
+    pub fn major(&self) -> i8 {
+        match self {
+            Self::V1_0 | Self::V1_1 => 1,
+            Self::V2_0 | Self::V2_1 | Self::V2_2 => 2,
+        }
+    }
+
+    pub fn minor(&self) -> i8 {
+        match self {
+            Self::V1_0 | Self::V2_0 => 0,
+            Self::V1_1 | Self::V2_1 => 1,
+            Self::V2_2 => 2,
+        }
+    }
+
+    /// Parses a keyword of ipp-versions-supported like "2.0".
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "1.0" => Some(Self::V1_0),
+            "1.1" => Some(Self::V1_1),
+            "2.0" => Some(Self::V2_0),
+            "2.1" => Some(Self::V2_1),
+            "2.2" => Some(Self::V2_2),
+            _ => None,
+        }
+    }
+
+    /// The next version to try if the printer doesn't support this.
+    pub fn lower(&self) -> Option<Self> {
+        match self {
+            Self::V1_0 => None,
+            Self::V1_1 => Some(Self::V1_0),
+            Self::V2_0 => Some(Self::V1_1),
+            Self::V2_1 => Some(Self::V2_0),
+            Self::V2_2 => Some(Self::V2_1),
+        }
+    }
+}
+
+impl fmt::Display for IppVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}", self.major(), self.minor())
+    }
+}
+
+/// When and how often PrintClient retries an operation which failed
+/// because the printer was busy or the connection was dropped.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total number of attempts including the first one. 1 disables retrying.
+    pub max_attempts: u32,
+    /// Wait before the first retry. This doubles on every retry up to max_backoff.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// IPP status codes worth retrying.
+    pub retry_status_codes: Vec<StatusCode>,
+    /// Whether to retry when the connection failed or was dropped.
+    pub retry_connection_errors: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 4,
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(16),
+            retry_status_codes: vec![
+                StatusCode::ServerErrorServiceUnavailable,
+                StatusCode::ServerErrorTemporaryError,
+                StatusCode::ServerErrorBusy,
+            ],
+            retry_connection_errors: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether the operation which failed with err can be sent again.
+    /// Errors after the request may have reached the printer, such as timeouts
+    /// and dropped connections, are only retried for idempotent operations,
+    /// so that a slow printer doesn't get the same job twice.
+    pub fn is_retryable(&self, operation: PrinterOperation, err: &IppPrintError) -> bool {
+        match err {
+            IppPrintError::StatusError(status, _) => self.retry_status_codes.contains(status),
+            IppPrintError::IOError(_) => self.retry_connection_errors && operation.is_idempotent(),
+            IppPrintError::HTTPError(err) => {
+                self.retry_connection_errors
+                    && (err.is_connect()
+                        || err.status() == Some(reqwest::StatusCode::SERVICE_UNAVAILABLE)
+                        || (operation.is_idempotent() && (err.is_timeout() || err.is_request())))
+            }
+            _ => false,
+        }
+    }
+
+    /// How long to wait before the retry-th retry (starting from 1).
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let factor = 2u32.saturating_pow(retry.saturating_sub(1));
+        self.initial_backoff
+            .saturating_mul(factor)
+            .min(self.max_backoff)
+    }
+}
+
+/// Reader copying data to a file as it is read, to capture streamed documents.
+struct CaptureReader<R> {
+    inner: R,
+    file: std::fs::File,
+}
+
+impl<R> Read for CaptureReader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.file.write_all(&buf[..len])?;
+        Ok(len)
+    }
+}
+
+/// Called before retrying with the attempt number which failed, its error, and the wait.
+pub type RetryHook = Box<dyn FnMut(u32, &IppPrintError, Duration) + Send>;
+
+/// Called while waiting for a job which is stopped, e.g. by a paper jam, with
+/// its status and printer-state-reasons like "media-jam-error".
+pub type StoppedHook = Box<dyn FnMut(&JobStatus, &[String]) + Send>;
+
+/// Client to print documents on a printer.
+/// This takes care of request ids and attributes required for every operation.
+pub struct PrintClient {
+    transport: Box<dyn Transport>,
+    printer_uri: String,
+    natural_language: String,
+    user_name: String,
+    request_id: i32,
+    /// operations-supported of the printer, fetched on first use.
+    operations_supported: Option<Vec<PrinterOperation>>,
+    retry_policy: RetryPolicy,
+    retry_hook: Option<RetryHook>,
+    stopped_hook: Option<StoppedHook>,
+    /// Version used for requests. Lowered if the printer doesn't support it.
+    version: IppVersion,
+    /// Directory to save raw requests and responses into.
+    capture_dir: Option<PathBuf>,
+    attribute_cache: Option<AttributeCache>,
+    /// Compression of documents sent with Print-Job and Send-Document.
+    compression: Option<Compression>,
+}
+
+impl PrintClient {
+    /// Creates a client which talks to the printer over HTTP.
+    /// printer_addr is an address like "192.0.2.1:631", or a URI with ipp or ipps scheme
+    /// like "ipps://192.0.2.1:631/ipp/print". TLS is used for ipps.
+    /// Fails with InvalidUri if printer_addr can't be parsed.
+    pub fn new(printer_addr: &str) -> Result<Self, IppPrintError> {
+        Ok(PrintClient::with_transport(Box::new(HttpTransport::new(
+            printer_addr,
+        )?)))
+    }
+
+    pub fn with_transport(transport: Box<dyn Transport>) -> Self {
+        Self {
+            printer_uri: transport.printer_uri(),
+            transport,
+            natural_language: "ja-jp".to_string(),
+            user_name: std::env::var("USER").unwrap_or_else(|_| "anonymous".to_string()),
+            request_id: 0,
+            operations_supported: None,
+            retry_policy: RetryPolicy::default(),
+            retry_hook: None,
+            stopped_hook: None,
+            version: IppVersion::V1_1,
+            capture_dir: None,
+            attribute_cache: None,
+            compression: None,
+        }
+    }
+
+    /// Saves every request and response as files like "0001-Get-Printer-Attributes-request.ipp"
+    /// in dir. Document data is included in requests.
+    pub fn set_capture_dir<P>(&mut self, dir: P)
+    where
+        P: Into<PathBuf>,
+    {
+        self.capture_dir = Some(dir.into());
+    }
+
+    /// Keeps the responses of get_attributes in cache, which is looked up by
+    /// printer-uuid. Cached responses are used only while
+    /// printer-config-change-time stays the same, so printers which report it
+    /// don't need a short ttl.
+    pub fn set_attribute_cache(&mut self, cache: AttributeCache) {
+        self.attribute_cache = Some(cache);
+    }
+
+    /// The cache set with set_attribute_cache, e.g. to invalidate it after
+    /// changing settings of the printer.
+    pub fn attribute_cache_mut(&mut self) -> Option<&mut AttributeCache> {
+        self.attribute_cache.as_mut()
+    }
+
+    /// Compresses documents and sends them with the compression operation
+    /// attribute. The printer must list it in compression-supported.
+    pub fn set_compression(&mut self, compression: Option<Compression>) {
+        self.compression = compression;
+    }
+
+    /// The compression operation attribute, if documents are compressed.
+    fn compression_attr(&self) -> Vec<(String, AttributeValue)> {
+        match self.compression {
+            Some(compression) => vec![(
+                "compression".to_string(),
+                AttributeValue::Keyword(compression.keyword().to_string()),
+            )],
+            None => vec![],
+        }
+    }
+
+    fn compress(&self, document: Vec<u8>) -> Result<Vec<u8>, IppPrintError> {
+        match self.compression {
+            Some(compression) => {
+                let mut data = vec![];
+                compression
+                    .encoder(io::Cursor::new(document))
+                    .read_to_end(&mut data)?;
+                Ok(data)
+            }
+            None => Ok(document),
+        }
+    }
+
+    /// Sets the version of requests. If the printer doesn't support it,
+    /// requests are sent again with lower versions.
+    pub fn set_ipp_version(&mut self, version: IppVersion) {
+        self.version = version;
+    }
+
+    pub fn ipp_version(&self) -> IppVersion {
+        self.version
+    }
+
+    /// Uses the highest version in ipp-versions-supported of the printer.
+    pub fn negotiate_ipp_version(&mut self) -> Result<IppVersion, IppPrintError> {
+        let resp = self.get_printer_attributes(&["ipp-versions-supported"])?;
+        let attrs = PrinterAttributes::from_response(&resp);
+        if let Some(version) = attrs
+            .ipp_versions_supported
+            .iter()
+            .filter_map(|e| IppVersion::from_keyword(e))
+            .max()
+        {
+            self.version = version;
+        }
+        Ok(self.version)
+    }
+
+    /// Sets requesting-user-name of requests, which is $USER by default.
+    pub fn set_user_name(&mut self, user_name: &str) {
+        self.user_name = user_name.to_string();
+    }
+
+    pub fn user_name(&self) -> &str {
+        &self.user_name
+    }
+
+    pub fn set_retry_policy(&mut self, policy: RetryPolicy) {
+        self.retry_policy = policy;
+    }
+
+    /// Sets a callback to observe retries, e.g. for logging.
+    pub fn set_retry_hook(&mut self, hook: RetryHook) {
+        self.retry_hook = Some(hook);
+    }
+
+    /// Sets a callback to report why a job wait_for_completion waits for is
+    /// stopped. It is called again only when the reasons change.
+    pub fn set_stopped_hook(&mut self, hook: StoppedHook) {
+        self.stopped_hook = Some(hook);
+    }
+
+    /// Attributes which should be the first of every operation attribute group.
+    fn operation_attrs(&self) -> Vec<(String, AttributeValue)> {
+        vec![
+            (
+                "attributes-charset".to_string(),
+                AttributeValue::Charset("utf-8".to_string()),
+            ),
+            (
+                "attributes-natural-language".to_string(),
+                AttributeValue::NaturalLanguage(self.natural_language.clone()),
+            ),
+            (
+                "printer-uri".to_string(),
+                AttributeValue::Uri(self.printer_uri.clone()),
+            ),
+        ]
+    }
+
+    fn user_name_attr(&self) -> (String, AttributeValue) {
+        (
+            "requesting-user-name".to_string(),
+            AttributeValue::NameWithoutLanguage(self.user_name.clone()),
+        )
+    }
+
+    fn job_id_of(resp: &IPPResponse) -> Result<i32, IppPrintError> {
+        match resp.get_attribute("job-id") {
+            Some(AttributeValue::Integer(job_id)) => Ok(*job_id),
+            _ => Err(IppPrintError::MissingAttribute("job-id".to_string())),
+        }
+    }
+
+    fn build_request(
+        &mut self,
+        operation_id: PrinterOperation,
+        extra_attrs: Vec<(String, AttributeValue)>,
+        data: Vec<u8>,
+    ) -> IPPRequest {
+        self.request_id += 1;
+
+        let mut attrs = self.operation_attrs();
+        attrs.extend(extra_attrs);
+
+        IPPRequest {
+            version_major: self.version.major(),
+            version_minor: self.version.minor(),
+            operation_id,
+            request_id: self.request_id,
+            attrs: vec![(DelimiterOrValueTag::OperationAttributesTag, attrs)],
+            data,
+        }
+    }
+
+    /// Sends the request, downgrading the IPP version if the printer doesn't support it.
+    fn send(&mut self, mut request: IPPRequest) -> Result<IPPResponse, IppPrintError> {
+        loop {
+            request.version_major = self.version.major();
+            request.version_minor = self.version.minor();
+
+            match self.send_with_retry(&request) {
+                Err(IppPrintError::StatusError(StatusCode::ServerErrorVersionNotSupported, _))
+                    if self.version.lower().is_some() =>
+                {
+                    self.version = self.version.lower().unwrap();
+                }
+                result => return result,
+            }
+        }
+    }
+
+    fn send_with_retry(&mut self, request: &IPPRequest) -> Result<IPPResponse, IppPrintError> {
+        let _span = PrintClient::span(request).entered();
+        let mut buf = Vec::new();
+        request.write_to_stream(&mut buf)?;
+        tracing::debug!(version = %self.version, len = buf.len(), "sending request");
+        tracing::trace!(attrs = ?request.attrs, "request attributes");
+        self.capture(request, "request", &buf)?;
+
+        let mut attempt = 1;
+        loop {
+            let started = Instant::now();
+            let result = self
+                .transport
+                .send_ipp(buf.clone())
+                .and_then(|reader| self.receive(request, reader));
+            PrintClient::log_result(&result, started);
+            match result {
+                Err(err)
+                    if attempt < self.retry_policy.max_attempts
+                        && self.retry_policy.is_retryable(request.operation_id, &err) =>
+                {
+                    let wait = self.retry_policy.backoff(attempt);
+                    if let Some(hook) = self.retry_hook.as_mut() {
+                        hook(attempt, &err, wait);
+                    }
+                    std::thread::sleep(wait);
+                    attempt += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+
+    /// Sends the request followed by data, without reading data into memory.
+    /// This is not retried, since data can't be read again. A printer not
+    /// supporting the version makes later requests use a lower one.
+    fn send_stream(
+        &mut self,
+        mut request: IPPRequest,
+        data: Box<dyn Read + Send>,
+    ) -> Result<IPPResponse, IppPrintError> {
+        request.version_major = self.version.major();
+        request.version_minor = self.version.minor();
+
+        let _span = PrintClient::span(&request).entered();
+        let mut buf = Vec::new();
+        request.write_to_stream(&mut buf)?;
+        tracing::debug!(version = %self.version, "sending request with streamed data");
+        tracing::trace!(attrs = ?request.attrs, "request attributes");
+        // The request is captured with data as it is sent.
+        let data: Box<dyn Read + Send> = match self.capture_path(&request, "request") {
+            Some(path) => {
+                let mut file = std::fs::File::create(path)?;
+                file.write_all(&buf)?;
+                Box::new(CaptureReader { inner: data, file })
+            }
+            None => data,
+        };
+
+        let started = Instant::now();
+        let result = self
+            .transport
+            .send_ipp_stream(buf, data)
+            .and_then(|reader| self.receive(&request, reader));
+        PrintClient::log_result(&result, started);
+        if let Err(IppPrintError::StatusError(StatusCode::ServerErrorVersionNotSupported, _)) =
+            &result
+        {
+            if let Some(lower) = self.version.lower() {
+                self.version = lower;
+            }
+        }
+        result
+    }
+
+    /// Span of an operation, which events of its requests and responses are in.
+    fn span(request: &IPPRequest) -> tracing::Span {
+        tracing::info_span!(
+            "ipp",
+            operation = %request.operation_id,
+            request_id = request.request_id
+        )
+    }
+
+    fn log_result(result: &Result<IPPResponse, IppPrintError>, started: Instant) {
+        let duration = started.elapsed();
+        match result {
+            Ok(response) => {
+                tracing::debug!(
+                    status = %response.status_code,
+                    ?duration,
+                    "received response"
+                );
+                tracing::trace!(attrs = ?response.attrs, "response attributes");
+            }
+            Err(err) => tracing::debug!(error = %err, ?duration, "request failed"),
+        }
+    }
+
+    /// Decodes the response, capturing it if enabled.
+    fn receive(
+        &self,
+        request: &IPPRequest,
+        mut reader: Box<dyn Read>,
+    ) -> Result<IPPResponse, IppPrintError> {
+        if self.capture_dir.is_some() {
+            let mut buf = vec![];
+            reader.read_to_end(&mut buf)?;
+            self.capture(request, "response", &buf)?;
+            PrintClient::read_response(&mut buf.as_slice())
+        } else {
+            PrintClient::read_response(&mut reader)
+        }
+    }
+
+    /// Writes raw bytes of a message to the capture directory, if enabled.
+    fn capture(&self, request: &IPPRequest, kind: &str, buf: &[u8]) -> Result<(), IppPrintError> {
+        if let Some(path) = self.capture_path(request, kind) {
+            std::fs::write(path, buf)?;
+        }
+        Ok(())
+    }
+
+    /// File to capture a message of kind "request" or "response" into, if enabled.
+    fn capture_path(&self, request: &IPPRequest, kind: &str) -> Option<PathBuf> {
+        let name = format!(
+            "{:04}-{}-{}.ipp",
+            request.request_id, request.operation_id, kind
+        );
+        Some(self.capture_dir.as_ref()?.join(name))
+    }
+
+    fn read_response<R>(reader: &mut R) -> Result<IPPResponse, IppPrintError>
+    where
+        R: Read,
+    {
+        let response = IPPResponse::read_from_stream(reader)?;
+
+        if !response.status_code.is_success() {
+            let messages = ["status-message", "detailed-status-message"]
+                .iter()
+                .filter_map(|name| response.get_attribute(name)?.as_str())
+                .map(str::to_string)
+                .collect();
+            return Err(IppPrintError::StatusError(response.status_code, messages));
+        }
+
+        Ok(response)
+    }
+
+    /// Fetches all printer attributes, or takes them from the attribute cache
+    /// if one is set.
+    pub fn get_attributes(&mut self) -> Result<IPPResponse, IppPrintError> {
+        if self.attribute_cache.is_none() {
+            return self.get_printer_attributes(&[]);
+        }
+
+        let resp = self.get_printer_attributes(&["printer-uuid", "printer-config-change-time"])?;
+        let uuid = match resp.get_attribute("printer-uuid").and_then(|e| e.as_str()) {
+            Some(uuid) => uuid.to_string(),
+            None => return self.get_printer_attributes(&[]),
+        };
+        let change_time = resp.get_attribute("printer-config-change-time");
+        let cache = self.attribute_cache.as_mut().unwrap();
+        if let Some(cached) = cache.get(&uuid) {
+            if cached.get_attribute("printer-config-change-time") == change_time {
+                tracing::debug!(uuid, "printer attributes from the cache");
+                return Ok(cached);
+            }
+        }
+
+        let resp = self.get_printer_attributes(&[])?;
+        // The printer has answered, so a cache which can't be written is only warned about.
+        if let Err(err) = self.attribute_cache.as_mut().unwrap().insert(&uuid, &resp) {
+            tracing::warn!(uuid, %err, "failed to cache printer attributes");
+        }
+        Ok(resp)
+    }
+
+    /// Fetches only requested_attributes, which can be attribute names or group keywords
+    /// like "printer-description" (RFC 8011 4.2.5.1). All attributes are returned if empty.
+    pub fn get_printer_attributes(
+        &mut self,
+        requested_attributes: &[&str],
+    ) -> Result<IPPResponse, IppPrintError> {
+        let mut attrs = vec![];
+        if !requested_attributes.is_empty() {
+            attrs.push(PrintClient::requested_attributes_attr(requested_attributes));
+        }
+        let req = self.build_request(PrinterOperation::GetPrinterAttributes, attrs, vec![]);
+        self.send(req)
+    }
+
+    pub fn printer_attributes(&mut self) -> Result<PrinterAttributes, IppPrintError> {
+        let resp = self.get_attributes()?;
+        Ok(PrinterAttributes::from_response(&resp))
+    }
+
+    fn requested_attributes_attr(names: &[&str]) -> (String, AttributeValue) {
+        (
+            "requested-attributes".to_string(),
+            AttributeValue::VectorAttribute(
+                names
+                    .iter()
+                    .map(|e| AttributeValue::Keyword(e.to_string()))
+                    .collect(),
+            ),
+        )
+    }
+
+    /// Checks if the printer would accept a job for document_format (4.2.1.1. Print-Job Request).
+    pub fn validate(&mut self, document_format: &str) -> Result<IPPResponse, IppPrintError> {
+        let attrs = vec![
+            self.user_name_attr(),
+            (
+                "document-format".to_string(),
+                AttributeValue::MimeMediaType(document_format.to_string()),
+            ),
+        ];
+        let req = self.build_request(PrinterOperation::ValidateJob, attrs, vec![]);
+        self.send(req)
+    }
+
+    /// Adds the job attributes group if the template has any attribute.
+    fn add_job_template(req: &mut IPPRequest, template: &JobTemplate) {
+        let job_attrs = template.to_attrs();
+        if !job_attrs.is_empty() {
+            req.attrs
+                .push((DelimiterOrValueTag::JobAttributesTag, job_attrs));
+        }
+    }
+
+    /// Creates a job and returns its job-id.
+    pub fn create_job(&mut self, template: &JobTemplate) -> Result<i32, IppPrintError> {
+        let mut attrs = vec![self.user_name_attr()];
+        if let Some(job_name) = &template.job_name {
+            attrs.push((
+                "job-name".to_string(),
+                AttributeValue::NameWithoutLanguage(job_name.clone()),
+            ));
+        }
+        let mut req = self.build_request(PrinterOperation::CreateJob, attrs, vec![]);
+        PrintClient::add_job_template(&mut req, template);
+        let resp = self.send(req)?;
+
+        PrintClient::job_id_of(&resp)
+    }
+
+    fn send_document_attrs(
+        &self,
+        job_id: i32,
+        document_format: &str,
+        last: bool,
+    ) -> Vec<(String, AttributeValue)> {
+        vec![
+            ("job-id".to_string(), AttributeValue::Integer(job_id)),
+            self.user_name_attr(),
+            (
+                "document-format".to_string(),
+                AttributeValue::MimeMediaType(document_format.to_string()),
+            ),
+            ("last-document".to_string(), AttributeValue::Boolean(last)),
+        ]
+        .into_iter()
+        .chain(self.compression_attr())
+        .collect()
+    }
+
+    /// Sends the only document of the job.
+    pub fn send_document(
+        &mut self,
+        job_id: i32,
+        document_format: &str,
+        document: Vec<u8>,
+    ) -> Result<IPPResponse, IppPrintError> {
+        let attrs = self.send_document_attrs(job_id, document_format, true);
+        let document = self.compress(document)?;
+        let req = self.build_request(PrinterOperation::SendDocument, attrs, document);
+        self.send(req)
+    }
+
+    /// Sends the only document of the job, streaming it from document.
+    /// Use this for large documents like high resolution rasters, which need not
+    /// be built in memory. The document is sent with chunked transfer encoding over HTTP.
+    pub fn send_document_stream<R>(
+        &mut self,
+        job_id: i32,
+        document_format: &str,
+        document: R,
+    ) -> Result<IPPResponse, IppPrintError>
+    where
+        R: Read + Send + 'static,
+    {
+        let attrs = self.send_document_attrs(job_id, document_format, true);
+        let req = self.build_request(PrinterOperation::SendDocument, attrs, vec![]);
+        self.send_document_request(req, Box::new(document))
+    }
+
+    /// Streams the document of a Send-Document request, compressed if enabled.
+    fn send_document_request(
+        &mut self,
+        req: IPPRequest,
+        document: Box<dyn Read + Send>,
+    ) -> Result<IPPResponse, IppPrintError> {
+        match self.compression {
+            Some(compression) => self.send_stream(req, compression.encoder(document)),
+            None => self.send_stream(req, document),
+        }
+    }
+
+    /// Operation attributes of Print-URI and Send-URI. The printer detects the
+    /// format if document_format is None.
+    fn document_uri_attrs(
+        &self,
+        document_uri: &str,
+        document_format: Option<&str>,
+    ) -> Vec<(String, AttributeValue)> {
+        let mut attrs = vec![
+            self.user_name_attr(),
+            (
+                "document-uri".to_string(),
+                AttributeValue::Uri(document_uri.to_string()),
+            ),
+        ];
+        if let Some(document_format) = document_format {
+            attrs.push((
+                "document-format".to_string(),
+                AttributeValue::MimeMediaType(document_format.to_string()),
+            ));
+        }
+        attrs
+    }
+
+    /// Creates a job printing the document the printer fetches from
+    /// document_uri, and returns its job-id. See
+    /// [`PrinterAttributes::check_reference_uri`] for the schemes it accepts.
+    pub fn print_uri(
+        &mut self,
+        document_uri: &str,
+        document_format: Option<&str>,
+        template: &JobTemplate,
+    ) -> Result<i32, IppPrintError> {
+        let attrs = self.document_uri_attrs(document_uri, document_format);
+        let mut req = self.build_request(PrinterOperation::PrintURI, attrs, vec![]);
+        PrintClient::add_job_template(&mut req, template);
+        let resp = self.send(req)?;
+
+        PrintClient::job_id_of(&resp)
+    }
+
+    /// Adds the document the printer fetches from document_uri to the job.
+    /// last must be true for the last document.
+    pub fn send_uri(
+        &mut self,
+        job_id: i32,
+        document_uri: &str,
+        document_format: Option<&str>,
+        last: bool,
+    ) -> Result<IPPResponse, IppPrintError> {
+        let mut attrs = vec![("job-id".to_string(), AttributeValue::Integer(job_id))];
+        attrs.extend(self.document_uri_attrs(document_uri, document_format));
+        attrs.push(("last-document".to_string(), AttributeValue::Boolean(last)));
+        let req = self.build_request(PrinterOperation::SendURI, attrs, vec![]);
+        self.send(req)
+    }
+
+    /// Creates a job with Create-Job, to send one or more documents to it with
+    /// [`Job::send_document`].
+    pub fn start_job(&mut self, template: &JobTemplate) -> Result<Job<'_>, IppPrintError> {
+        let job_id = self.create_job(template)?;
+        Ok(self.job(job_id))
+    }
+
+    /// A job created before, e.g. with create_job.
+    pub fn job(&mut self, job_id: i32) -> Job<'_> {
+        Job {
+            client: self,
+            id: job_id,
+        }
+    }
+
+    pub fn get_jobs(&mut self, options: &GetJobsOptions) -> Result<IPPResponse, IppPrintError> {
+        let mut attrs = vec![self.user_name_attr()];
+        attrs.extend(options.to_attrs());
+        let req = self.build_request(PrinterOperation::GetJobs, attrs, vec![]);
+        self.send(req)
+    }
+
+    /// Jobs with the attributes of JobInfo, unless options request others.
+    pub fn list_jobs(&mut self, options: &GetJobsOptions) -> Result<Vec<JobInfo>, IppPrintError> {
+        let resp = if options.requested_attributes.is_empty() {
+            self.get_jobs(&GetJobsOptions {
+                requested_attributes: JOB_INFO_ATTRIBUTES.iter().map(|e| e.to_string()).collect(),
+                ..options.clone()
+            })?
+        } else {
+            self.get_jobs(options)?
+        };
+        Ok(JobInfo::from_response(&resp))
+    }
+
+    pub fn get_job_info(&mut self, job_id: i32) -> Result<JobInfo, IppPrintError> {
+        let attrs = vec![PrintClient::requested_attributes_attr(JOB_INFO_ATTRIBUTES)];
+        let resp = self.job_operation(PrinterOperation::GetJobAttributes, job_id, attrs)?;
+        JobInfo::from_response(&resp)
+            .pop()
+            .ok_or_else(|| IppPrintError::MissingAttribute("job-id".to_string()))
+    }
+
+    /// Sends an operation which targets a job.
+    fn job_operation(
+        &mut self,
+        operation: PrinterOperation,
+        job_id: i32,
+        extra_attrs: Vec<(String, AttributeValue)>,
+    ) -> Result<IPPResponse, IppPrintError> {
+        let mut attrs = vec![
+            ("job-id".to_string(), AttributeValue::Integer(job_id)),
+            self.user_name_attr(),
+        ];
+        attrs.extend(extra_attrs);
+        let req = self.build_request(operation, attrs, vec![]);
+        self.send(req)
+    }
+
+    fn job_hold_until_attr(hold_until: Option<&str>) -> Vec<(String, AttributeValue)> {
+        match hold_until {
+            Some(hold_until) => vec![(
+                "job-hold-until".to_string(),
+                AttributeValue::Keyword(hold_until.to_string()),
+            )],
+            None => vec![],
+        }
+    }
+
+    pub fn cancel_job(&mut self, job_id: i32) -> Result<IPPResponse, IppPrintError> {
+        self.job_operation(PrinterOperation::CancelJob, job_id, vec![])
+    }
+
+    /// Cancels all jobs and deletes them, including the history of completed
+    /// jobs. Printers usually allow this only for operators.
+    pub fn purge_jobs(&mut self) -> Result<IPPResponse, IppPrintError> {
+        let attrs = vec![self.user_name_attr()];
+        let req = self.build_request(PrinterOperation::PurgeJobs, attrs, vec![]);
+        self.send(req)
+    }
+
+    /// Cancels the jobs which are not completed one by one, only those
+    /// submitted by user if given, and returns their job-ids.
+    /// This works on printers without Purge-Jobs. Jobs which completed in the
+    /// meantime are skipped.
+    pub fn cancel_all_jobs(&mut self, user: Option<&str>) -> Result<Vec<i32>, IppPrintError> {
+        let job_ids = self
+            .list_jobs(&GetJobsOptions::default())?
+            .into_iter()
+            .filter(|e| !e.state.is_some_and(|e| e.is_terminal()))
+            .filter(|e| user.is_none() || e.user.as_deref() == user)
+            .map(|e| e.id)
+            .collect::<Vec<_>>();
+
+        let mut canceled = vec![];
+        for job_id in job_ids {
+            match self.cancel_job(job_id) {
+                Ok(_) => canceled.push(job_id),
+                Err(IppPrintError::StatusError(StatusCode::ClientErrorNotPossible, _)) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(canceled)
+    }
+
+    /// Holds the job so that it won't be scheduled until released.
+    /// hold_until is a job-hold-until keyword like "indefinite" or "night" (default: "indefinite").
+    pub fn hold_job(
+        &mut self,
+        job_id: i32,
+        hold_until: Option<&str>,
+    ) -> Result<IPPResponse, IppPrintError> {
+        let attrs = PrintClient::job_hold_until_attr(hold_until);
+        self.job_operation(PrinterOperation::HoldJob, job_id, attrs)
+    }
+
+    pub fn release_job(&mut self, job_id: i32) -> Result<IPPResponse, IppPrintError> {
+        self.job_operation(PrinterOperation::ReleaseJob, job_id, vec![])
+    }
+
+    /// Restarts a retained job. If hold_until is given, the restarted job is held.
+    pub fn restart_job(
+        &mut self,
+        job_id: i32,
+        hold_until: Option<&str>,
+    ) -> Result<IPPResponse, IppPrintError> {
+        let attrs = PrintClient::job_hold_until_attr(hold_until);
+        self.job_operation(PrinterOperation::RestartJob, job_id, attrs)
+    }
+
+    /// Makes the printer identify itself, e.g. by flashing or beeping.
+    /// If actions is empty, the printer uses its default identify-actions.
+    /// message is shown or spoken by the display and speak actions.
+    pub fn identify_printer(
+        &mut self,
+        actions: &[IdentifyAction],
+        message: Option<&str>,
+    ) -> Result<IPPResponse, IppPrintError> {
+        let mut attrs = vec![self.user_name_attr()];
+        if !actions.is_empty() {
+            attrs.push((
+                "identify-actions".to_string(),
+                AttributeValue::VectorAttribute(
+                    actions
+                        .iter()
+                        .map(|e| AttributeValue::Keyword(e.keyword().to_string()))
+                        .collect(),
+                ),
+            ));
+        }
+        if let Some(message) = message {
+            attrs.push((
+                "message".to_string(),
+                AttributeValue::TextWithoutLanguage(message.to_string()),
+            ));
+        }
+        let req = self.build_request(PrinterOperation::IdentifyPrinter, attrs, vec![]);
+        self.send(req)
+    }
+
+    /// Subscription attributes requesting events delivered with Get-Notifications.
+    fn subscription_attrs(events: &[&str]) -> Vec<(String, AttributeValue)> {
+        vec![
+            (
+                "notify-pull-method".to_string(),
+                AttributeValue::Keyword("ippget".to_string()),
+            ),
+            (
+                "notify-events".to_string(),
+                AttributeValue::VectorAttribute(
+                    events
+                        .iter()
+                        .map(|e| AttributeValue::Keyword(e.to_string()))
+                        .collect(),
+                ),
+            ),
+        ]
+    }
+
+    fn subscription_id_of(resp: &IPPResponse) -> Result<i32, IppPrintError> {
+        match resp.get_attribute("notify-subscription-id") {
+            Some(AttributeValue::Integer(id)) => Ok(*id),
+            _ => Err(IppPrintError::MissingAttribute(
+                "notify-subscription-id".to_string(),
+            )),
+        }
+    }
+
+    /// Subscribes to printer events like "printer-state-changed" or "job-completed"
+    /// and returns notify-subscription-id.
+    /// lease_duration is in seconds, and the printer chooses it if None.
+    pub fn create_printer_subscription(
+        &mut self,
+        events: &[&str],
+        lease_duration: Option<i32>,
+    ) -> Result<i32, IppPrintError> {
+        let attrs = vec![self.user_name_attr()];
+        let mut sub_attrs = PrintClient::subscription_attrs(events);
+        if let Some(lease_duration) = lease_duration {
+            sub_attrs.push((
+                "notify-lease-duration".to_string(),
+                AttributeValue::Integer(lease_duration),
+            ));
+        }
+        let mut req =
+            self.build_request(PrinterOperation::CreatePrinterSubscriptions, attrs, vec![]);
+        req.attrs
+            .push((DelimiterOrValueTag::SubscriptionAttributesTag, sub_attrs));
+        let resp = self.send(req)?;
+
+        PrintClient::subscription_id_of(&resp)
+    }
+
+    /// Subscribes to events of the job and returns notify-subscription-id.
+    /// The subscription ends when the job is completed.
+    pub fn create_job_subscription(
+        &mut self,
+        job_id: i32,
+        events: &[&str],
+    ) -> Result<i32, IppPrintError> {
+        let attrs = vec![self.user_name_attr()];
+        let mut sub_attrs = PrintClient::subscription_attrs(events);
+        sub_attrs.push(("notify-job-id".to_string(), AttributeValue::Integer(job_id)));
+        let mut req = self.build_request(PrinterOperation::CreateJobSubscriptions, attrs, vec![]);
+        req.attrs
+            .push((DelimiterOrValueTag::SubscriptionAttributesTag, sub_attrs));
+        let resp = self.send(req)?;
+
+        PrintClient::subscription_id_of(&resp)
+    }
+
+    /// Fetches events of the subscriptions.
+    /// Each subscription is given with the lowest sequence number to return, starting from 1.
+    pub fn get_notifications(
+        &mut self,
+        subscriptions: &[(i32, i32)],
+    ) -> Result<Notifications, IppPrintError> {
+        let attrs = vec![
+            self.user_name_attr(),
+            (
+                "notify-subscription-ids".to_string(),
+                AttributeValue::VectorAttribute(
+                    subscriptions
+                        .iter()
+                        .map(|(id, _)| AttributeValue::Integer(*id))
+                        .collect(),
+                ),
+            ),
+            (
+                "notify-sequence-numbers".to_string(),
+                AttributeValue::VectorAttribute(
+                    subscriptions
+                        .iter()
+                        .map(|(_, seq)| AttributeValue::Integer(*seq))
+                        .collect(),
+                ),
+            ),
+            ("notify-wait".to_string(), AttributeValue::Boolean(false)),
+        ];
+        let req = self.build_request(PrinterOperation::GetNotifications, attrs, vec![]);
+        let resp = self.send(req)?;
+
+        Notifications::from_response(&resp)
+    }
+
+    /// Returns an iterator which polls the subscription for new events.
+    pub fn events(&mut self, subscription_id: i32) -> EventStream<'_> {
+        EventStream {
+            client: self,
+            subscription_id,
+            sequence_number: 1,
+            events: VecDeque::new(),
+            poll_interval: Duration::from_secs(5),
+            polled: false,
+            done: false,
+        }
+    }
+
+    /// Extends the lease of a printer subscription by lease_duration seconds.
+    pub fn renew_subscription(
+        &mut self,
+        subscription_id: i32,
+        lease_duration: Option<i32>,
+    ) -> Result<IPPResponse, IppPrintError> {
+        let mut attrs = vec![
+            (
+                "notify-subscription-id".to_string(),
+                AttributeValue::Integer(subscription_id),
+            ),
+            self.user_name_attr(),
+        ];
+        if let Some(lease_duration) = lease_duration {
+            attrs.push((
+                "notify-lease-duration".to_string(),
+                AttributeValue::Integer(lease_duration),
+            ));
+        }
+        let req = self.build_request(PrinterOperation::RenewSubscription, attrs, vec![]);
+        self.send(req)
+    }
+
+    pub fn cancel_subscription(
+        &mut self,
+        subscription_id: i32,
+    ) -> Result<IPPResponse, IppPrintError> {
+        let attrs = vec![
+            (
+                "notify-subscription-id".to_string(),
+                AttributeValue::Integer(subscription_id),
+            ),
+            self.user_name_attr(),
+        ];
+        let req = self.build_request(PrinterOperation::CancelSubscription, attrs, vec![]);
+        self.send(req)
+    }
+
+    pub fn get_job_attributes(&mut self, job_id: i32) -> Result<IPPResponse, IppPrintError> {
+        self.job_operation(PrinterOperation::GetJobAttributes, job_id, vec![])
+    }
+
+    pub fn get_job_status(&mut self, job_id: i32) -> Result<JobStatus, IppPrintError> {
+        let attrs = vec![PrintClient::requested_attributes_attr(&[
+            "job-state",
+            "job-state-reasons",
+        ])];
+        let resp = self.job_operation(PrinterOperation::GetJobAttributes, job_id, attrs)?;
+        JobStatus::from_response(&resp)
+    }
+
+    /// Polls the job until it reaches a terminal state.
+    /// Returns an error if the job was canceled or aborted.
+    /// A job stopped with the printer, e.g. by a paper jam, is waited for until
+    /// it resumes, reporting it to the hook set with set_stopped_hook.
+    pub fn wait_for_completion(
+        &mut self,
+        job_id: i32,
+        poll_interval: Duration,
+    ) -> Result<JobStatus, IppPrintError> {
+        let mut reported = None;
+        loop {
+            let status = self.get_job_status(job_id)?;
+            if status.state.is_terminal() {
+                if status.state == JobState::Completed {
+                    return Ok(status);
+                }
+                return Err(IppPrintError::JobError(status));
+            }
+
+            if self.stopped_hook.is_some() {
+                // Printers may keep the job processing while they are stopped.
+                let resp =
+                    self.get_printer_attributes(&["printer-state", "printer-state-reasons"])?;
+                let printer = PrinterAttributes::from_response(&resp);
+                let stopped = status.state == JobState::ProcessingStopped
+                    || printer.printer_state == Some(PrinterState::Stopped);
+                let reasons =
+                    stopped.then(|| (status.reasons.clone(), printer.printer_state_reasons));
+                if let (Some((_, printer_reasons)), Some(hook)) =
+                    (reasons.as_ref(), self.stopped_hook.as_mut())
+                {
+                    if reasons != reported {
+                        hook(&status, printer_reasons);
+                    }
+                }
+                reported = reasons;
+            }
+
+            std::thread::sleep(poll_interval);
+        }
+    }
+
+    /// Creates a job with its only document in one request, and returns its job-id.
+    pub fn print_job(
+        &mut self,
+        document_format: &str,
+        document: Vec<u8>,
+        template: &JobTemplate,
+    ) -> Result<i32, IppPrintError> {
+        let mut attrs = vec![
+            self.user_name_attr(),
+            (
+                "document-format".to_string(),
+                AttributeValue::MimeMediaType(document_format.to_string()),
+            ),
+        ];
+        attrs.extend(self.compression_attr());
+        let document = self.compress(document)?;
+        let mut req = self.build_request(PrinterOperation::PrintJob, attrs, document);
+        PrintClient::add_job_template(&mut req, template);
+        let resp = self.send(req)?;
+
+        PrintClient::job_id_of(&resp)
+    }
+
+    pub fn supports_operation(
+        &mut self,
+        operation: PrinterOperation,
+    ) -> Result<bool, IppPrintError> {
+        if self.operations_supported.is_none() {
+            let resp = self.get_printer_attributes(&["operations-supported"])?;
+            let attrs = PrinterAttributes::from_response(&resp);
+            self.operations_supported = Some(attrs.operations_supported);
+        }
+
+        Ok(self
+            .operations_supported
+            .as_ref()
+            .unwrap()
+            .contains(&operation))
+    }
+
+    /// Prints a single document and returns job-id of the created job.
+    /// Print-Job is used if the printer supports it, and Create-Job + Send-Document otherwise.
+    pub fn print(
+        &mut self,
+        document_format: &str,
+        document: Vec<u8>,
+        template: &JobTemplate,
+    ) -> Result<i32, IppPrintError> {
+        self.validate(document_format)?;
+
+        if self.supports_operation(PrinterOperation::PrintJob)? {
+            return self.print_job(document_format, document, template);
+        }
+
+        let job_id = self.create_job(template)?;
+        self.send_document(job_id, document_format, document)?;
+        Ok(job_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    macro_rules! write_int_be {
+        ($writer:ident,$var:path as $ty:ident) => {{
+            let data = $ty::to_be_bytes($var as $ty);
+            $writer.write(&data)
+        }};
+    }
+
+    macro_rules! write_attr {
+        ($writer:ident,$type:ident,$name:expr,$value:expr) => {
+            if let Err(err) = $writer.write(&[DelimiterOrValueTag::$type as u8]) {
+                Err(err)
+            } else {
+                if let Err(err) = $writer.write(&i16::to_be_bytes($name.len() as i16)) {
+                    Err(err)
+                } else {
+                    if let Err(err) = $writer.write($name.as_bytes()) {
+                        Err(err)
+                    } else {
+                        if let Err(err) = $writer.write(&i16::to_be_bytes($value.len() as i16)) {
                             Err(err)
                         } else {
                             $writer.write($value.as_bytes())
@@ -1009,98 +4050,1188 @@ mod tests {
     }
 
     #[test]
-    fn parse_collection() {
+    fn parse_collection() {
+        let mut buf = Vec::<u8>::new();
+
+        // version-number
+        buf.write_all(&[1u8, 1u8]).unwrap();
+        // status-code
+        buf.write_all(&u16::from(StatusCode::SuccessfulOk).to_be_bytes())
+            .unwrap();
+        // request-id
+        let req_id = 1;
+        write_int_be!(buf, req_id as i32).unwrap();
+
+        // begin-attribute-group-tag
+        write_int_be!(buf, DelimiterOrValueTag::OperationAttributesTag as i8).unwrap();
+
+        write_attr!(buf, Charset, "attributes-charset", "utf-8").unwrap();
+        write_attr!(buf, NaturalLanguage, "attributes-natural-language", "ja-jp").unwrap();
+        write_attr!(buf, Uri, "array", "ipp://192.0.2.1:631").unwrap();
+        write_attr!(buf, Uri, "", "ipp://192.0.2.2:631").unwrap();
+        write_attr!(buf, Uri, "", "ipp://192.0.2.3:631").unwrap();
+
+        write_attr!(buf, BegCollection, "collection", "").unwrap();
+
+        write_attr!(buf, MemberAttrName, "", "key1").unwrap();
+        write_attr!(buf, Keyword, "", "value1").unwrap();
+
+        write_attr!(buf, MemberAttrName, "", "key2").unwrap();
+        write_attr!(buf, BegCollection, "", "").unwrap();
+        write_attr!(buf, MemberAttrName, "", "key2-1").unwrap();
+        write_attr!(buf, Keyword, "", "value2-1").unwrap();
+        write_attr!(buf, EndCollection, "", "").unwrap();
+
+        write_attr!(buf, EndCollection, "", "").unwrap();
+
+        // end-of-attributes
+        write_int_be!(buf, DelimiterOrValueTag::EndOfAttributesTag as i8).unwrap();
+
+        let mut reader = &buf[..];
+        let response = IPPResponse::read_from_stream(&mut reader).unwrap();
+
+        let expected_resp = IPPResponse {
+            version_major: 1,
+            version_minor: 1,
+            status_code: StatusCode::SuccessfulOk,
+            request_id: req_id,
+            attrs: vec![(
+                DelimiterOrValueTag::OperationAttributesTag,
+                vec![
+                    (
+                        "attributes-charset".to_string(),
+                        AttributeValue::Charset("utf-8".to_string()),
+                    ),
+                    (
+                        "attributes-natural-language".to_string(),
+                        AttributeValue::NaturalLanguage("ja-jp".to_string()),
+                    ),
+                    (
+                        "array".to_string(),
+                        AttributeValue::VectorAttribute(vec![
+                            AttributeValue::Uri("ipp://192.0.2.1:631".to_string()),
+                            AttributeValue::Uri("ipp://192.0.2.2:631".to_string()),
+                            AttributeValue::Uri("ipp://192.0.2.3:631".to_string()),
+                        ]),
+                    ),
+                    (
+                        "collection".to_string(),
+                        AttributeValue::CollectionAttribute(
+                            [
+                                (
+                                    "key1".to_string(),
+                                    AttributeValue::Keyword("value1".to_string()),
+                                ),
+                                (
+                                    "key2".to_string(),
+                                    AttributeValue::CollectionAttribute(
+                                        [(
+                                            "key2-1".to_string(),
+                                            AttributeValue::Keyword("value2-1".to_string()),
+                                        )]
+                                        .into_iter()
+                                        .collect::<HashMap<_, _>>(),
+                                    ),
+                                ),
+                            ]
+                            .into_iter()
+                            .collect::<HashMap<_, _>>(),
+                        ),
+                    ),
+                ],
+            )],
+            data: vec![],
+        };
+
+        assert_eq!(expected_resp, response);
+    }
+
+    #[test]
+    fn build_request() {
+        let mut client = PrintClient::new("192.0.2.1:631").unwrap();
+        client.user_name = "user".to_string();
+
+        let req = client.build_request(
+            PrinterOperation::CreateJob,
+            vec![client.user_name_attr()],
+            vec![],
+        );
+        assert_eq!(1, req.request_id);
+        assert_eq!(
+            vec![(
+                DelimiterOrValueTag::OperationAttributesTag,
+                vec![
+                    (
+                        "attributes-charset".to_string(),
+                        AttributeValue::Charset("utf-8".to_string()),
+                    ),
+                    (
+                        "attributes-natural-language".to_string(),
+                        AttributeValue::NaturalLanguage("ja-jp".to_string()),
+                    ),
+                    (
+                        "printer-uri".to_string(),
+                        AttributeValue::Uri("ipp://192.0.2.1:631/ipp/print".to_string()),
+                    ),
+                    (
+                        "requesting-user-name".to_string(),
+                        AttributeValue::NameWithoutLanguage("user".to_string()),
+                    ),
+                ],
+            )],
+            req.attrs
+        );
+
+        let req = client.build_request(PrinterOperation::GetJobs, vec![], vec![]);
+        assert_eq!(2, req.request_id);
+    }
+
+    #[test]
+    fn parse_collection_with_additional_values() {
+        let mut buf = Vec::<u8>::new();
+
+        buf.write_all(&[1u8, 1u8]).unwrap();
+        buf.write_all(&u16::from(StatusCode::SuccessfulOk).to_be_bytes())
+            .unwrap();
+        let req_id = 1;
+        write_int_be!(buf, req_id as i32).unwrap();
+        write_int_be!(buf, DelimiterOrValueTag::PrinterAttributesTag as i8).unwrap();
+
+        write_attr!(buf, BegCollection, "media-col-ready", "").unwrap();
+        write_attr!(buf, MemberAttrName, "", "media-source-properties").unwrap();
+        write_attr!(buf, Keyword, "", "a").unwrap();
+        write_attr!(buf, Keyword, "", "b").unwrap();
+        write_attr!(buf, Keyword, "", "c").unwrap();
+        write_attr!(buf, MemberAttrName, "", "media-type").unwrap();
+        write_attr!(buf, Keyword, "", "stationery").unwrap();
+        write_attr!(buf, EndCollection, "", "").unwrap();
+
+        write_attr!(buf, BegCollection, "", "").unwrap();
+        write_attr!(buf, MemberAttrName, "", "media-type").unwrap();
+        write_attr!(buf, Keyword, "", "photographic").unwrap();
+        write_attr!(buf, EndCollection, "", "").unwrap();
+
+        write_int_be!(buf, DelimiterOrValueTag::EndOfAttributesTag as i8).unwrap();
+
+        let response = IPPResponse::read_from_stream(&mut &buf[..]).unwrap();
+
+        assert_eq!(
+            Some(&AttributeValue::VectorAttribute(vec![
+                AttributeValue::CollectionAttribute(
+                    [
+                        (
+                            "media-source-properties".to_string(),
+                            AttributeValue::VectorAttribute(vec![
+                                AttributeValue::Keyword("a".to_string()),
+                                AttributeValue::Keyword("b".to_string()),
+                                AttributeValue::Keyword("c".to_string()),
+                            ]),
+                        ),
+                        (
+                            "media-type".to_string(),
+                            AttributeValue::Keyword("stationery".to_string()),
+                        ),
+                    ]
+                    .into_iter()
+                    .collect::<HashMap<_, _>>(),
+                ),
+                AttributeValue::CollectionAttribute(
+                    [(
+                        "media-type".to_string(),
+                        AttributeValue::Keyword("photographic".to_string()),
+                    )]
+                    .into_iter()
+                    .collect::<HashMap<_, _>>(),
+                ),
+            ])),
+            response.get_attribute("media-col-ready")
+        );
+    }
+
+    #[test]
+    fn write_collection() {
+        let media_col = AttributeValue::CollectionAttribute(
+            [
+                (
+                    "media-size".to_string(),
+                    AttributeValue::CollectionAttribute(
+                        [
+                            ("x-dimension".to_string(), AttributeValue::Integer(21000)),
+                            ("y-dimension".to_string(), AttributeValue::Integer(29700)),
+                        ]
+                        .into_iter()
+                        .collect::<HashMap<_, _>>(),
+                    ),
+                ),
+                (
+                    "media-type".to_string(),
+                    AttributeValue::Keyword("stationery".to_string()),
+                ),
+            ]
+            .into_iter()
+            .collect::<HashMap<_, _>>(),
+        );
+
+        let mut out = Vec::new();
+        IPPRequest::write_attr(&mut out, "media-col", &media_col).unwrap();
+
+        let mut expected = Vec::<u8>::new();
+        write_attr!(expected, BegCollection, "media-col", "").unwrap();
+        write_attr!(expected, MemberAttrName, "", "media-size").unwrap();
+        write_attr!(expected, BegCollection, "", "").unwrap();
+        write_attr!(expected, MemberAttrName, "", "x-dimension").unwrap();
+        expected
+            .write_all(&[DelimiterOrValueTag::Integer as u8, 0, 0, 0, 4])
+            .unwrap();
+        expected.write_all(&21000i32.to_be_bytes()).unwrap();
+        write_attr!(expected, MemberAttrName, "", "y-dimension").unwrap();
+        expected
+            .write_all(&[DelimiterOrValueTag::Integer as u8, 0, 0, 0, 4])
+            .unwrap();
+        expected.write_all(&29700i32.to_be_bytes()).unwrap();
+        write_attr!(expected, EndCollection, "", "").unwrap();
+        write_attr!(expected, MemberAttrName, "", "media-type").unwrap();
+        write_attr!(expected, Keyword, "", "stationery").unwrap();
+        write_attr!(expected, EndCollection, "", "").unwrap();
+
+        assert_eq!(expected, out);
+    }
+
+    #[test]
+    fn write_set() {
+        let set = AttributeValue::VectorAttribute(vec![
+            AttributeValue::Keyword("one-sided".to_string()),
+            AttributeValue::Keyword("two-sided-long-edge".to_string()),
+        ]);
+
+        let mut out = Vec::new();
+        IPPRequest::write_attr(&mut out, "sides-supported", &set).unwrap();
+
+        let mut expected = Vec::<u8>::new();
+        write_attr!(expected, Keyword, "sides-supported", "one-sided").unwrap();
+        write_attr!(expected, Keyword, "", "two-sided-long-edge").unwrap();
+        assert_eq!(expected, out);
+    }
+
+    #[test]
+    fn set_values() {
         let mut buf = Vec::<u8>::new();
 
-        // version-number
-        buf.write(&[1u8, 1u8]).unwrap();
-        // status-code
-        write_int_be!(buf, StatusCode::SuccessfulOk as i16).unwrap();
-        // request-id
+        buf.write_all(&[1u8, 1u8]).unwrap();
+        buf.write_all(&u16::from(StatusCode::SuccessfulOk).to_be_bytes())
+            .unwrap();
         let req_id = 1;
         write_int_be!(buf, req_id as i32).unwrap();
+        write_int_be!(buf, DelimiterOrValueTag::PrinterAttributesTag as i8).unwrap();
+        write_attr!(
+            buf,
+            MimeMediaType,
+            "document-format-supported",
+            "image/pwg-raster"
+        )
+        .unwrap();
+        write_attr!(buf, MimeMediaType, "", "image/urf").unwrap();
+        write_attr!(buf, Keyword, "media-supported", "iso_a4_210x297mm").unwrap();
+        write_int_be!(buf, DelimiterOrValueTag::EndOfAttributesTag as i8).unwrap();
 
-        // begin-attribute-group-tag
-        write_int_be!(buf, DelimiterOrValueTag::OperationAttributesTag as i8).unwrap();
+        let response = IPPResponse::read_from_stream(&mut &buf[..]).unwrap();
 
-        write_attr!(buf, Charset, "attributes-charset", "utf-8").unwrap();
-        write_attr!(buf, NaturalLanguage, "attributes-natural-language", "ja-jp").unwrap();
-        write_attr!(buf, Uri, "array", "ipp://192.0.2.1:631").unwrap();
-        write_attr!(buf, Uri, "", "ipp://192.0.2.2:631").unwrap();
-        write_attr!(buf, Uri, "", "ipp://192.0.2.3:631").unwrap();
+        assert_eq!(
+            &[
+                AttributeValue::MimeMediaType("image/pwg-raster".to_string()),
+                AttributeValue::MimeMediaType("image/urf".to_string()),
+            ],
+            response
+                .get_attribute("document-format-supported")
+                .unwrap()
+                .values()
+        );
+        assert_eq!(
+            &[AttributeValue::Keyword("iso_a4_210x297mm".to_string())],
+            response.get_attribute("media-supported").unwrap().values()
+        );
+    }
 
-        write_attr!(buf, BegCollection, "collection", "").unwrap();
+    #[test]
+    fn range_resolution_and_date_time() {
+        let attrs = vec![
+            ("copies-supported", AttributeValue::RangeOfInteger(1..=99)),
+            (
+                "printer-resolution-default",
+                AttributeValue::Resolution(Resolution {
+                    resolution_cross_feed: 600,
+                    resolution_feed: 300,
+                    units: Resolution::DOTS_PER_INCH,
+                }),
+            ),
+            (
+                "printer-current-time",
+                AttributeValue::DateTime(DateTime {
+                    year: 2023,
+                    month: 12,
+                    day: 10,
+                    hour: 21,
+                    minutes: 5,
+                    seconds: 30,
+                    deci_seconds: 7,
+                    direction_from_utc: '+',
+                    hours_from_utc: 9,
+                    minutes_from_utc: 0,
+                }),
+            ),
+        ];
 
-        write_attr!(buf, MemberAttrName, "", "key1").unwrap();
-        write_attr!(buf, Keyword, "", "value1").unwrap();
+        let mut buf = Vec::<u8>::new();
+        buf.write_all(&[1u8, 1u8]).unwrap();
+        buf.write_all(&u16::from(StatusCode::SuccessfulOk).to_be_bytes())
+            .unwrap();
+        let req_id = 1;
+        write_int_be!(buf, req_id as i32).unwrap();
+        write_int_be!(buf, DelimiterOrValueTag::PrinterAttributesTag as i8).unwrap();
+        for (name, value) in &attrs {
+            IPPRequest::write_attr(&mut buf, name, value).unwrap();
+        }
+        write_int_be!(buf, DelimiterOrValueTag::EndOfAttributesTag as i8).unwrap();
 
-        write_attr!(buf, MemberAttrName, "", "key2").unwrap();
-        write_attr!(buf, BegCollection, "", "").unwrap();
-        write_attr!(buf, MemberAttrName, "", "key2-1").unwrap();
-        write_attr!(buf, Keyword, "", "value2-1").unwrap();
-        write_attr!(buf, EndCollection, "", "").unwrap();
+        // Check the binary layout of values.
+        let expected_range = [0x33, 0, 16, b'c', b'o', b'p', b'i', b'e', b's', b'-'];
+        assert_eq!(&expected_range, &buf[9..19]);
+        assert!(buf
+            .windows(11)
+            .any(|w| w == [0, 0, 0, 1, 0, 0, 0, 99, 0x32, 0, 26]));
+        assert!(buf
+            .windows(9)
+            .any(|w| w == [0, 0, 2, 0x58, 0, 0, 1, 0x2C, 3]));
+        assert!(buf
+            .windows(11)
+            .any(|w| w == [0x07, 0xE7, 12, 10, 21, 5, 30, 7, b'+', 9, 0]));
 
-        write_attr!(buf, EndCollection, "", "").unwrap();
+        let response = IPPResponse::read_from_stream(&mut &buf[..]).unwrap();
+        for (name, value) in &attrs {
+            assert_eq!(Some(value), response.get_attribute(name));
+        }
+    }
 
-        // end-of-attributes
+    #[test]
+    fn resolution_to_dpi() {
+        let res = Resolution {
+            resolution_cross_feed: 118,
+            resolution_feed: 236,
+            units: Resolution::DOTS_PER_CENTIMETER,
+        };
+        assert_eq!((300, 599), res.to_dpi());
+    }
+
+    #[test]
+    fn out_of_band_values() {
+        let mut buf = Vec::<u8>::new();
+
+        buf.write_all(&[1u8, 1u8]).unwrap();
+        buf.write_all(
+            &u16::from(StatusCode::SuccessfulOkIgnoredOrSubstitutedAttributes).to_be_bytes(),
+        )
+        .unwrap();
+        let req_id = 1;
+        write_int_be!(buf, req_id as i32).unwrap();
+        write_int_be!(buf, DelimiterOrValueTag::OperationAttributesTag as i8).unwrap();
+        write_attr!(buf, Charset, "attributes-charset", "utf-8").unwrap();
+        write_int_be!(buf, DelimiterOrValueTag::UnsupportedAttributesTag as i8).unwrap();
+        write_attr!(buf, Unsupported, "print-quality", "").unwrap();
+        write_int_be!(buf, DelimiterOrValueTag::PrinterAttributesTag as i8).unwrap();
+        write_attr!(buf, NoValue, "printer-info", "").unwrap();
+        // Value of out-of-band tags must be ignored.
+        write_attr!(buf, NoValue, "printer-location", "ignored").unwrap();
+        write_attr!(buf, Unknown, "printer-geo-location", "").unwrap();
+        write_attr!(buf, NotSettable, "printer-name", "").unwrap();
+        write_attr!(buf, AdminDefine, "printer-organization", "").unwrap();
         write_int_be!(buf, DelimiterOrValueTag::EndOfAttributesTag as i8).unwrap();
 
-        let mut reader = &buf[..];
-        let response = IPPResponse::read_from_stream(&mut reader).unwrap();
+        let response = IPPResponse::read_from_stream(&mut &buf[..]).unwrap();
 
-        let expected_resp = IPPResponse {
-            version_major: 1,
-            version_minor: 1,
-            status_code: StatusCode::SuccessfulOk,
-            request_id: req_id,
-            attrs: vec![(
-                DelimiterOrValueTag::OperationAttributesTag,
+        assert_eq!(
+            (
+                DelimiterOrValueTag::UnsupportedAttributesTag,
+                vec![(
+                    "print-quality".to_string(),
+                    AttributeValue::Unsupported(vec![])
+                )]
+            ),
+            response.attrs[1]
+        );
+        assert_eq!(
+            (
+                DelimiterOrValueTag::PrinterAttributesTag,
                 vec![
+                    ("printer-info".to_string(), AttributeValue::NoValue),
+                    ("printer-location".to_string(), AttributeValue::NoValue),
                     (
-                        "attributes-charset".to_string(),
-                        AttributeValue::Charset("utf-8".to_string()),
+                        "printer-geo-location".to_string(),
+                        AttributeValue::Unknown(vec![])
                     ),
+                    ("printer-name".to_string(), AttributeValue::NotSettable),
                     (
-                        "attributes-natural-language".to_string(),
-                        AttributeValue::NaturalLanguage("ja-jp".to_string()),
+                        "printer-organization".to_string(),
+                        AttributeValue::AdminDefine
                     ),
+                ]
+            ),
+            response.attrs[2]
+        );
+
+        let mut out = Vec::new();
+        IPPRequest::write_attr(&mut out, "printer-info", &AttributeValue::DeleteAttribute).unwrap();
+        let mut expected = Vec::<u8>::new();
+        write_attr!(expected, DeleteAttribute, "printer-info", "").unwrap();
+        assert_eq!(expected, out);
+    }
+
+    #[test]
+    fn status_code() {
+        assert!(StatusCode::SuccessfulOkConflictingAttributes.is_success());
+        assert!(!StatusCode::SuccessfulOk.is_client_error());
+        assert!(StatusCode::ClientErrorDocumentAccessError.is_client_error());
+        assert!(StatusCode::ServerErrorBusy.is_server_error());
+        assert!(!StatusCode::ServerErrorBusy.is_success());
+
+        assert_eq!(StatusCode::ClientErrorTimeout, 0x0405.into());
+        assert_eq!(
+            "server-error-busy (0x0507)",
+            StatusCode::ServerErrorBusy.to_string()
+        );
+
+        // Codes unknown to the crate are classified by their range.
+        assert_eq!(StatusCode::Unknown(0x0042), 0x0042.into());
+        assert!(StatusCode::Unknown(0x0042).is_success());
+        assert!(StatusCode::Unknown(0x04ff).is_client_error());
+        assert!(StatusCode::Unknown(0x0580).is_server_error());
+        assert!(!StatusCode::Unknown(0x0600).is_success());
+        assert_eq!("0x0580", StatusCode::Unknown(0x0580).to_string());
+
+        for (code, status, _) in STATUS_CODES {
+            assert_eq!(*status, StatusCode::from(*code));
+            assert_eq!(*code, u16::from(*status));
+        }
+    }
+
+    #[test]
+    fn printer_operation() {
+        assert_eq!(PrinterOperation::GetPrinterAttributes, 0x000b.into());
+        assert_eq!(PrinterOperation::CloseJob, 0x003b.into());
+        assert_eq!(PrinterOperation::Unknown(0x4001), 0x4001.into());
+        assert_eq!(0x003c, u16::from(PrinterOperation::IdentifyPrinter));
+        assert_eq!(0x4001, u16::from(PrinterOperation::Unknown(0x4001)));
+
+        assert_eq!("Print-URI", PrinterOperation::PrintURI.to_string());
+        assert_eq!("0x4001", PrinterOperation::Unknown(0x4001).to_string());
+
+        // Every registered operation must round-trip.
+        for (id, op, _) in OPERATIONS {
+            assert_eq!(*op, PrinterOperation::from(*id));
+            assert_eq!(*id, u16::from(*op));
+        }
+    }
+
+    #[test]
+    fn job_info() {
+        let job = |attrs: Vec<(&str, AttributeValue)>| {
+            (
+                DelimiterOrValueTag::JobAttributesTag,
+                attrs
+                    .into_iter()
+                    .map(|(name, value)| (name.to_string(), value))
+                    .collect(),
+            )
+        };
+        let resp = IPPResponse {
+            version_major: 2,
+            version_minor: 0,
+            status_code: StatusCode::SuccessfulOk,
+            request_id: 1,
+            attrs: vec![
+                (DelimiterOrValueTag::OperationAttributesTag, vec![]),
+                job(vec![
+                    ("job-id", AttributeValue::Integer(12)),
                     (
-                        "array".to_string(),
-                        AttributeValue::VectorAttribute(vec![
-                            AttributeValue::Uri("ipp://192.0.2.1:631".to_string()),
-                            AttributeValue::Uri("ipp://192.0.2.2:631".to_string()),
-                            AttributeValue::Uri("ipp://192.0.2.3:631".to_string()),
-                        ]),
+                        "job-name",
+                        AttributeValue::NameWithoutLanguage("report.pdf".to_string()),
                     ),
                     (
-                        "collection".to_string(),
-                        AttributeValue::CollectionAttribute(
-                            [
-                                (
-                                    "key1".to_string(),
-                                    AttributeValue::Keyword("value1".to_string()),
-                                ),
-                                (
-                                    "key2".to_string(),
-                                    AttributeValue::CollectionAttribute(
-                                        [(
-                                            "key2-1".to_string(),
-                                            AttributeValue::Keyword("value2-1".to_string()),
-                                        )]
-                                        .into_iter()
-                                        .collect::<HashMap<_, _>>(),
-                                    ),
-                                ),
-                            ]
-                            .into_iter()
-                            .collect::<HashMap<_, _>>(),
-                        ),
+                        "job-originating-user-name",
+                        AttributeValue::NameWithoutLanguage("alice".to_string()),
                     ),
-                ],
+                    ("job-state", AttributeValue::Enum(5)),
+                    (
+                        "job-state-reasons",
+                        AttributeValue::Keyword("job-printing".to_string()),
+                    ),
+                    ("job-k-octets", AttributeValue::Integer(240)),
+                    ("job-impressions", AttributeValue::Integer(10)),
+                    ("job-impressions-completed", AttributeValue::Integer(4)),
+                    ("job-media-sheets-completed", AttributeValue::Integer(2)),
+                    ("time-at-creation", AttributeValue::Integer(3600)),
+                    ("time-at-processing", AttributeValue::Integer(3602)),
+                    ("time-at-completed", AttributeValue::NoValue),
+                ]),
+                // Only job-uri and job-id are returned without requested-attributes.
+                job(vec![
+                    (
+                        "job-uri",
+                        AttributeValue::Uri("ipp://192.0.2.1/ipp/print/13".to_string()),
+                    ),
+                    ("job-id", AttributeValue::Integer(13)),
+                ]),
+                job(vec![(
+                    "job-name",
+                    AttributeValue::NameWithoutLanguage("x".to_string()),
+                )]),
+            ],
+            data: vec![],
+        };
+
+        let jobs = JobInfo::from_response(&resp);
+        assert_eq!(2, jobs.len());
+        assert_eq!(
+            JobInfo {
+                id: 12,
+                name: Some("report.pdf".to_string()),
+                user: Some("alice".to_string()),
+                state: Some(JobState::Processing),
+                state_reasons: vec!["job-printing".to_string()],
+                k_octets: Some(240),
+                impressions: Some(10),
+                impressions_completed: Some(4),
+                media_sheets_completed: Some(2),
+                time_at_creation: Some(3600),
+                time_at_processing: Some(3602),
+                time_at_completed: None,
+            },
+            jobs[0]
+        );
+        assert_eq!(
+            (13, None, vec![]),
+            (jobs[1].id, jobs[1].state, jobs[1].state_reasons.clone())
+        );
+    }
+
+    #[test]
+    fn job_status() {
+        let resp = IPPResponse {
+            version_major: 1,
+            version_minor: 1,
+            status_code: StatusCode::SuccessfulOk,
+            request_id: 1,
+            attrs: vec![
+                (DelimiterOrValueTag::OperationAttributesTag, vec![]),
+                (
+                    DelimiterOrValueTag::JobAttributesTag,
+                    vec![
+                        ("job-state".to_string(), AttributeValue::Enum(6)),
+                        (
+                            "job-state-reasons".to_string(),
+                            AttributeValue::VectorAttribute(vec![
+                                AttributeValue::Keyword("job-printing".to_string()),
+                                AttributeValue::Keyword("printer-stopped".to_string()),
+                            ]),
+                        ),
+                    ],
+                ),
+            ],
+            data: vec![],
+        };
+
+        let status = JobStatus::from_response(&resp).unwrap();
+        assert_eq!(JobState::ProcessingStopped, status.state);
+        assert!(!status.state.is_terminal());
+        assert_eq!(
+            vec!["job-printing".to_string(), "printer-stopped".to_string()],
+            status.reasons
+        );
+    }
+
+    #[test]
+    fn get_jobs_options() {
+        assert_eq!(
+            vec![
+                (
+                    "which-jobs".to_string(),
+                    AttributeValue::Keyword("not-completed".to_string())
+                ),
+                ("my-jobs".to_string(), AttributeValue::Boolean(false)),
+            ],
+            GetJobsOptions::default().to_attrs()
+        );
+
+        let options = GetJobsOptions {
+            which_jobs: WhichJobs::Completed,
+            my_jobs: true,
+            limit: Some(10),
+            requested_attributes: vec!["job-name".to_string(), "job-state".to_string()],
+        };
+        assert_eq!(
+            vec![
+                (
+                    "which-jobs".to_string(),
+                    AttributeValue::Keyword("completed".to_string())
+                ),
+                ("my-jobs".to_string(), AttributeValue::Boolean(true)),
+                ("limit".to_string(), AttributeValue::Integer(10)),
+                (
+                    "requested-attributes".to_string(),
+                    AttributeValue::VectorAttribute(vec![
+                        AttributeValue::Keyword("job-name".to_string()),
+                        AttributeValue::Keyword("job-state".to_string()),
+                    ])
+                ),
+            ],
+            options.to_attrs()
+        );
+    }
+
+    #[test]
+    fn identify_action() {
+        for action in [
+            IdentifyAction::Display,
+            IdentifyAction::Flash,
+            IdentifyAction::Sound,
+            IdentifyAction::Speak,
+        ] {
+            assert_eq!(Some(action), IdentifyAction::from_keyword(action.keyword()));
+        }
+        assert_eq!(None, IdentifyAction::from_keyword("dance"));
+    }
+
+    #[test]
+    fn notifications() {
+        let event = |seq: i32, event: &str| {
+            vec![
+                (
+                    "notify-subscription-id".to_string(),
+                    AttributeValue::Integer(3),
+                ),
+                (
+                    "notify-sequence-number".to_string(),
+                    AttributeValue::Integer(seq),
+                ),
+                (
+                    "notify-subscribed-event".to_string(),
+                    AttributeValue::Keyword(event.to_string()),
+                ),
+                ("notify-job-id".to_string(), AttributeValue::Integer(12)),
+                ("job-state".to_string(), AttributeValue::Enum(9)),
+            ]
+        };
+        let resp = IPPResponse {
+            version_major: 1,
+            version_minor: 1,
+            status_code: StatusCode::SuccessfulOk,
+            request_id: 1,
+            attrs: vec![
+                (
+                    DelimiterOrValueTag::OperationAttributesTag,
+                    vec![(
+                        "notify-get-interval".to_string(),
+                        AttributeValue::Integer(30),
+                    )],
+                ),
+                (
+                    DelimiterOrValueTag::EventNotificationAttributesTag,
+                    event(1, "job-state-changed"),
+                ),
+                (
+                    DelimiterOrValueTag::EventNotificationAttributesTag,
+                    event(2, "job-completed"),
+                ),
+            ],
+            data: vec![],
+        };
+
+        let notifications = Notifications::from_response(&resp).unwrap();
+        assert_eq!(Some(Duration::from_secs(30)), notifications.get_interval);
+        assert_eq!(2, notifications.events.len());
+        let completed = &notifications.events[1];
+        assert_eq!(3, completed.subscription_id);
+        assert_eq!(2, completed.sequence_number);
+        assert_eq!("job-completed", completed.event);
+        assert_eq!(Some(12), completed.job_id);
+        assert_eq!(Some(JobState::Completed), completed.job_state);
+    }
+
+    #[test]
+    fn parse_event_notification_groups() {
+        let mut buf = vec![0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01];
+        buf.push(DelimiterOrValueTag::OperationAttributesTag as u8);
+        buf.push(DelimiterOrValueTag::EventNotificationAttributesTag as u8);
+        buf.push(DelimiterOrValueTag::Integer as u8);
+        buf.extend(22u16.to_be_bytes());
+        buf.extend(b"notify-subscription-id");
+        buf.extend(4u16.to_be_bytes());
+        buf.extend(5i32.to_be_bytes());
+        buf.push(DelimiterOrValueTag::EndOfAttributesTag as u8);
+
+        let resp = IPPResponse::read_from_stream(&mut buf.as_slice()).unwrap();
+        assert_eq!(
+            vec![
+                (DelimiterOrValueTag::OperationAttributesTag, vec![]),
+                (
+                    DelimiterOrValueTag::EventNotificationAttributesTag,
+                    vec![(
+                        "notify-subscription-id".to_string(),
+                        AttributeValue::Integer(5)
+                    )]
+                ),
+            ],
+            resp.attrs
+        );
+    }
+
+    #[test]
+    fn unknown_status_code() {
+        let buf = [0x02, 0x00, 0x04, 0x80, 0x00, 0x00, 0x00, 0x01, 0x03];
+        let resp = IPPResponse::read_from_stream(&mut buf.as_slice()).unwrap();
+        assert_eq!(StatusCode::Unknown(0x0480), resp.status_code);
+        assert!(resp.status_code.is_client_error());
+
+        let mut out = vec![];
+        resp.write_to_stream(&mut out).unwrap();
+        assert_eq!(&buf[..], &out[..]);
+    }
+
+    #[test]
+    fn reject_malformed_responses() {
+        let header = vec![0x01, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x01];
+        let parse = |body: &[u8]| {
+            let mut buf = header.clone();
+            buf.extend_from_slice(body);
+            match IPPResponse::read_from_stream(&mut buf.as_slice()) {
+                Err(IPPError::Malformed { offset, reason }) => (offset, reason),
+                result => panic!("must be malformed: {:?}", result),
+            }
+        };
+
+        assert_eq!((8, "unexpected end of message".to_string()), parse(&[]));
+        assert_eq!((8, "unknown tag 0x0f".to_string()), parse(&[0x0f]));
+        assert_eq!(
+            (8, "expected a group tag".to_string()),
+            parse(&[DelimiterOrValueTag::Keyword as u8])
+        );
+        // A length of 0xffff must not be read as negative.
+        assert_eq!(
+            (15, "unexpected end of message".to_string()),
+            parse(&[0x01, 0x44, 0x00, 0x01, b'a', 0xff, 0xff, b'b'])
+        );
+        assert_eq!(
+            (9, "additional value without an attribute".to_string()),
+            parse(&[0x01, 0x44, 0x00, 0x00, 0x00, 0x01, b'a', 0x03])
+        );
+        assert_eq!(
+            (9, "invalid Integer value of \"a\"".to_string()),
+            parse(&[0x01, 0x21, 0x00, 0x01, b'a', 0x00, 0x01, 0x00, 0x03])
+        );
+
+        let mut nested = vec![0x01];
+        for _ in 0..100 {
+            nested.extend([0x34, 0x00, 0x01, b'a', 0x00, 0x00]);
+            nested.extend([0x4a, 0x00, 0x00, 0x00, 0x01, b'a']);
+        }
+        assert_eq!("collections are nested too deeply", parse(&nested).1);
+    }
+
+    #[test]
+    fn printer_attributes() {
+        let resp = IPPResponse {
+            version_major: 1,
+            version_minor: 1,
+            status_code: StatusCode::SuccessfulOk,
+            request_id: 1,
+            attrs: vec![
+                (
+                    DelimiterOrValueTag::OperationAttributesTag,
+                    vec![(
+                        "attributes-charset".to_string(),
+                        AttributeValue::Charset("utf-8".to_string()),
+                    )],
+                ),
+                (
+                    DelimiterOrValueTag::PrinterAttributesTag,
+                    vec![
+                        (
+                            "printer-name".to_string(),
+                            AttributeValue::NameWithLanguage(StringWithLanguage {
+                                lang: "en".to_string(),
+                                string: "Office".to_string(),
+                            }),
+                        ),
+                        ("printer-state".to_string(), AttributeValue::Enum(4)),
+                        (
+                            "printer-state-reasons".to_string(),
+                            AttributeValue::Keyword("none".to_string()),
+                        ),
+                        (
+                            "operations-supported".to_string(),
+                            AttributeValue::VectorAttribute(vec![
+                                AttributeValue::Enum(0x02),
+                                AttributeValue::Enum(0x0b),
+                            ]),
+                        ),
+                        (
+                            "document-format-supported".to_string(),
+                            AttributeValue::VectorAttribute(vec![
+                                AttributeValue::MimeMediaType("image/pwg-raster".to_string()),
+                                AttributeValue::MimeMediaType("image/jpeg".to_string()),
+                            ]),
+                        ),
+                        ("color-supported".to_string(), AttributeValue::Boolean(true)),
+                        (
+                            "printer-resolution-supported".to_string(),
+                            AttributeValue::Resolution(Resolution {
+                                resolution_cross_feed: 600,
+                                resolution_feed: 600,
+                                units: Resolution::DOTS_PER_INCH,
+                            }),
+                        ),
+                        ("queued-job-count".to_string(), AttributeValue::Integer(2)),
+                    ],
+                ),
+            ],
+            data: vec![],
+        };
+
+        let attrs = PrinterAttributes::from_response(&resp);
+        assert_eq!(Some("Office".to_string()), attrs.printer_name);
+        assert_eq!(Some(PrinterState::Processing), attrs.printer_state);
+        assert_eq!(vec!["none".to_string()], attrs.printer_state_reasons);
+        assert_eq!(
+            vec![
+                PrinterOperation::PrintJob,
+                PrinterOperation::GetPrinterAttributes
+            ],
+            attrs.operations_supported
+        );
+        assert_eq!(
+            vec!["image/pwg-raster".to_string(), "image/jpeg".to_string()],
+            attrs.document_format_supported
+        );
+        assert_eq!(Some(true), attrs.color_supported);
+        assert_eq!((600, 600), attrs.printer_resolution_supported[0].to_dpi());
+        assert_eq!(None, attrs.media_default);
+        assert_eq!(1, attrs.other.len());
+        assert_eq!(
+            Some(&AttributeValue::Integer(2)),
+            attrs.other.get("queued-job-count")
+        );
+    }
+
+    #[test]
+    fn job_template() {
+        assert!(JobTemplate::default().to_attrs().is_empty());
+
+        let template = JobTemplate {
+            job_name: Some("report".to_string()),
+            copies: Some(2),
+            sides: Some(Sides::TwoSidedLongEdge),
+            media: Some("iso_a4_210x297mm".to_string()),
+            media_col: None,
+            print_quality: Some(PrintQuality::High),
+            print_color_mode: Some(PrintColorMode::Monochrome),
+            orientation_requested: Some(OrientationRequested::Landscape),
+            finishings: vec![4],
+            page_ranges: vec![1..=3],
+        };
+        assert_eq!(
+            vec![
+                ("copies".to_string(), AttributeValue::Integer(2)),
+                (
+                    "sides".to_string(),
+                    AttributeValue::Keyword("two-sided-long-edge".to_string())
+                ),
+                (
+                    "media".to_string(),
+                    AttributeValue::Keyword("iso_a4_210x297mm".to_string())
+                ),
+                ("print-quality".to_string(), AttributeValue::Enum(5)),
+                (
+                    "print-color-mode".to_string(),
+                    AttributeValue::Keyword("monochrome".to_string())
+                ),
+                ("orientation-requested".to_string(), AttributeValue::Enum(4)),
+                (
+                    "page-ranges".to_string(),
+                    AttributeValue::VectorAttribute(vec![AttributeValue::RangeOfInteger(1..=3)])
+                ),
+                (
+                    "finishings".to_string(),
+                    AttributeValue::VectorAttribute(vec![AttributeValue::Enum(4)])
+                ),
+            ],
+            template.to_attrs()
+        );
+    }
+
+    #[test]
+    fn page_ranges() {
+        assert_eq!(
+            Some(vec![1..=3, 7..=7, 9..=i32::MAX]),
+            parse_page_ranges("1-3,7, 9-")
+        );
+        for ranges in ["", "0", "3-1", "1,,2", "-3", "a-b", "1-2-3"] {
+            assert_eq!(None, parse_page_ranges(ranges), "{}", ranges);
+        }
+    }
+
+    /// Answers requests with responses in order, repeating the last one.
+    struct CannedTransport {
+        responses: Vec<Vec<u8>>,
+        requests: Vec<Vec<u8>>,
+    }
+
+    impl Transport for CannedTransport {
+        fn printer_uri(&self) -> String {
+            "ipp://localhost/ipp/print".to_string()
+        }
+
+        fn send_ipp(&mut self, request: Vec<u8>) -> Result<Box<dyn Read>, IppPrintError> {
+            let response =
+                self.responses[self.requests.len().min(self.responses.len() - 1)].clone();
+            self.requests.push(request);
+            Ok(Box::new(io::Cursor::new(response)))
+        }
+    }
+
+    /// Encoded response with job-id in the job attributes group.
+    fn job_id_response(status_code: StatusCode, job_id: i32) -> Vec<u8> {
+        let mut response = vec![0x01, 0x01];
+        response.extend(u16::from(status_code).to_be_bytes());
+        response.extend(1i32.to_be_bytes());
+        response.push(DelimiterOrValueTag::OperationAttributesTag as u8);
+        response.push(DelimiterOrValueTag::JobAttributesTag as u8);
+        response.push(DelimiterOrValueTag::Integer as u8);
+        response.extend(6u16.to_be_bytes());
+        response.extend(b"job-id");
+        response.extend(4u16.to_be_bytes());
+        response.extend(job_id.to_be_bytes());
+        response.push(DelimiterOrValueTag::EndOfAttributesTag as u8);
+        response
+    }
+
+    #[test]
+    fn client_with_transport() {
+        let mut client = PrintClient::with_transport(Box::new(CannedTransport {
+            responses: vec![job_id_response(StatusCode::SuccessfulOk, 42)],
+            requests: vec![],
+        }));
+        assert_eq!(42, client.create_job(&JobTemplate::default()).unwrap());
+    }
+
+    #[test]
+    fn status_messages() {
+        let mut response = vec![];
+        IPPResponse {
+            version_major: 1,
+            version_minor: 1,
+            status_code: StatusCode::ClientErrorDocumentFormatNotSupported,
+            request_id: 1,
+            attrs: vec![(
+                DelimiterOrValueTag::OperationAttributesTag,
+                vec![(
+                    "status-message".to_string(),
+                    AttributeValue::TextWithoutLanguage("Bad format".to_string()),
+                )],
             )],
             data: vec![],
+        }
+        .write_to_stream(&mut response)
+        .unwrap();
+        let mut client = PrintClient::with_transport(Box::new(CannedTransport {
+            responses: vec![response],
+            requests: vec![],
+        }));
+
+        let err = client.create_job(&JobTemplate::default()).unwrap_err();
+        assert!(matches!(
+            &err,
+            IppPrintError::StatusError(StatusCode::ClientErrorDocumentFormatNotSupported, messages)
+                if messages == &["Bad format"]
+        ));
+        assert_eq!(
+            "printer returned error status: client-error-document-format-not-supported (0x040a): Bad format",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn retry_busy_printer() {
+        let busy = job_id_response(StatusCode::ServerErrorBusy, 0);
+        let transport = CannedTransport {
+            responses: vec![
+                busy.clone(),
+                busy,
+                job_id_response(StatusCode::SuccessfulOk, 7),
+            ],
+            requests: vec![],
         };
 
-        assert_eq!(expected_resp, response);
+        let mut client = PrintClient::with_transport(Box::new(transport));
+        client.set_retry_policy(RetryPolicy {
+            initial_backoff: Duration::ZERO,
+            ..Default::default()
+        });
+        let retries = std::sync::Arc::new(std::sync::Mutex::new(vec![]));
+        let hook_retries = retries.clone();
+        client.set_retry_hook(Box::new(move |attempt, err, _| {
+            hook_retries
+                .lock()
+                .unwrap()
+                .push((attempt, err.to_string()));
+        }));
+        assert_eq!(7, client.create_job(&JobTemplate::default()).unwrap());
+        assert_eq!(2, retries.lock().unwrap().len());
+        assert_eq!(1, retries.lock().unwrap()[0].0);
+
+        // Errors which won't go away are returned immediately.
+        let transport = CannedTransport {
+            responses: vec![job_id_response(StatusCode::ClientErrorNotFound, 0)],
+            requests: vec![],
+        };
+        let mut client = PrintClient::with_transport(Box::new(transport));
+        assert!(matches!(
+            client.create_job(&JobTemplate::default()),
+            Err(IppPrintError::StatusError(
+                StatusCode::ClientErrorNotFound,
+                _
+            ))
+        ));
+    }
+
+    #[test]
+    fn retry_backoff() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            ..Default::default()
+        };
+        assert_eq!(Duration::from_secs(1), policy.backoff(1));
+        assert_eq!(Duration::from_secs(2), policy.backoff(2));
+        assert_eq!(Duration::from_secs(4), policy.backoff(3));
+        assert_eq!(Duration::from_secs(5), policy.backoff(4));
+        assert_eq!(Duration::from_secs(5), policy.backoff(100));
+    }
+
+    #[test]
+    fn retry_only_idempotent_operations() {
+        let policy = RetryPolicy::default();
+        let dropped = || IppPrintError::IOError(io::ErrorKind::ConnectionReset.into());
+        assert!(policy.is_retryable(PrinterOperation::GetJobs, &dropped()));
+        assert!(policy.is_retryable(PrinterOperation::CancelJob, &dropped()));
+        // The printer may have accepted the job before the connection dropped.
+        assert!(!policy.is_retryable(PrinterOperation::PrintJob, &dropped()));
+        assert!(!policy.is_retryable(PrinterOperation::SendDocument, &dropped()));
+
+        // A busy printer didn't accept the job.
+        let busy = IppPrintError::StatusError(StatusCode::ServerErrorBusy, vec![]);
+        assert!(policy.is_retryable(PrinterOperation::CreateJob, &busy));
+    }
+
+    #[test]
+    fn downgrade_ipp_version() {
+        let transport = CannedTransport {
+            responses: vec![
+                job_id_response(StatusCode::ServerErrorVersionNotSupported, 0),
+                job_id_response(StatusCode::SuccessfulOk, 3),
+            ],
+            requests: vec![],
+        };
+        let mut client = PrintClient::with_transport(Box::new(transport));
+        client.set_ipp_version(IppVersion::V2_0);
+
+        assert_eq!(3, client.create_job(&JobTemplate::default()).unwrap());
+        assert_eq!(IppVersion::V1_1, client.ipp_version());
+
+        let req = client.build_request(PrinterOperation::GetJobs, vec![], vec![]);
+        assert_eq!((1, 1), (req.version_major, req.version_minor));
+        assert_eq!("2.1", IppVersion::V2_1.to_string());
+        assert_eq!(Some(IppVersion::V2_0), IppVersion::from_keyword("2.0"));
+    }
+
+    #[test]
+    fn capture_messages() {
+        let dir = std::env::temp_dir().join(format!("ipp-print-capture-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let response = job_id_response(StatusCode::SuccessfulOk, 5);
+        let transport = CannedTransport {
+            responses: vec![response.clone()],
+            requests: vec![],
+        };
+        let mut client = PrintClient::with_transport(Box::new(transport));
+        client.set_capture_dir(&dir);
+        client.set_user_name("alice");
+        let template = JobTemplate {
+            job_name: Some("report.pdf".to_string()),
+            ..Default::default()
+        };
+        client.create_job(&template).unwrap();
+
+        let request = std::fs::read(dir.join("0001-Create-Job-request.ipp")).unwrap();
+        assert_eq!([0x01, 0x01, 0x00, 0x05], request[..4]);
+        let decoded = IPPRequest::read_from_stream(&mut request.as_slice()).unwrap();
+        assert_eq!(
+            Some(&AttributeValue::NameWithoutLanguage("alice".to_string())),
+            decoded.get_attribute("requesting-user-name")
+        );
+        assert_eq!(
+            Some(&AttributeValue::NameWithoutLanguage(
+                "report.pdf".to_string()
+            )),
+            decoded.get_attribute("job-name")
+        );
+        assert_eq!(
+            response,
+            std::fs::read(dir.join("0001-Create-Job-response.ipp")).unwrap()
+        );
+
+        // Streamed documents are captured as they are sent.
+        client
+            .send_document_stream(5, "image/pwg-raster", io::Cursor::new(b"RaS2".to_vec()))
+            .unwrap();
+        let request = std::fs::read(dir.join("0002-Send-Document-request.ipp")).unwrap();
+        let decoded = IPPRequest::read_from_stream(&mut request.as_slice()).unwrap();
+        assert_eq!(b"RaS2", &decoded.data[..]);
+        assert!(dir.join("0002-Send-Document-response.ipp").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Accepts a single byte per call, which Write::write is allowed to do.
+    struct ShortWriter(Vec<u8>);
+
+    impl Write for ShortWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write(&buf[..buf.len().min(1)])
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_with_short_writes() {
+        let mut client = PrintClient::new("192.0.2.1:631").unwrap();
+        let req = client.build_request(
+            PrinterOperation::GetJobs,
+            vec![(
+                "requested-attributes".to_string(),
+                AttributeValue::Keyword("job-id".to_string()),
+            )],
+            vec![1, 2, 3],
+        );
+
+        let mut expected = vec![];
+        let len = req.write_to_stream(&mut expected).unwrap();
+        assert_eq!(expected.len(), len);
+
+        let mut writer = ShortWriter(vec![]);
+        assert_eq!(len, req.write_to_stream(&mut writer).unwrap());
+        assert_eq!(expected, writer.0);
     }
 }
@@ -0,0 +1,522 @@
+//! Placing source images on pages, including N-up and booklet imposition.
+
+use crate::error::IppPrintError;
+use crate::ipp::Sides;
+#[cfg(feature = "image")]
+use crate::pwgraster::SrgbColor;
+use crate::pwgraster::{Orientation, PageHeader, Pixel};
+
+/// An image as rows of pixels from the top.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Image<P> {
+    width: usize,
+    height: usize,
+    pixels: Vec<P>,
+}
+
+impl<P> Image<P>
+where
+    P: Pixel,
+{
+    /// Fails unless pixels has width * height pixels.
+    pub fn new(width: usize, height: usize, pixels: Vec<P>) -> Result<Self, IppPrintError> {
+        if width.checked_mul(height) != Some(pixels.len()) {
+            return Err(IppPrintError::RasterError(format!(
+                "image has {} pixels but the size is {}x{}",
+                pixels.len(),
+                width,
+                height
+            )));
+        }
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[P] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [P] {
+        &mut self.pixels
+    }
+
+    pub fn into_pixels(self) -> Vec<P> {
+        self.pixels
+    }
+
+    pub fn is_landscape(&self) -> bool {
+        self.width > self.height
+    }
+
+    /// An image of a single color.
+    pub fn filled(width: usize, height: usize, pixel: P) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![pixel; width * height],
+        }
+    }
+
+    /// Scales to the largest size within max_width x max_height keeping the aspect
+    /// ratio, with nearest-neighbor sampling.
+    pub fn scale_to_fit(&self, max_width: usize, max_height: usize) -> Self {
+        if self.width == 0 || self.height == 0 {
+            return self.clone();
+        }
+        // Whichever dimension is tighter decides the scale.
+        let (width, height) = if max_width * self.height <= max_height * self.width {
+            (max_width, self.height * max_width / self.width)
+        } else {
+            (self.width * max_height / self.height, max_height)
+        };
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height {
+            let src_y = y * self.height / height;
+            for x in 0..width {
+                pixels.push(self.pixels[src_y * self.width + x * self.width / width].clone());
+            }
+        }
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Copies image with its top-left corner at (x, y), clipping what goes outside.
+    pub fn draw(&mut self, image: &Image<P>, x: usize, y: usize) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let width = image.width.min(self.width.saturating_sub(x));
+        for (src_y, row) in image.pixels.chunks(image.width.max(1)).enumerate() {
+            if y + src_y >= self.height {
+                break;
+            }
+            let start = (y + src_y) * self.width + x;
+            self.pixels[start..start + width].clone_from_slice(&row[..width]);
+        }
+    }
+
+    /// Draws a 1-pixel rectangle outline, clipping what goes outside.
+    pub fn draw_rect(&mut self, x: usize, y: usize, width: usize, height: usize, pixel: P) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        let (right, bottom) = (x + width - 1, y + height - 1);
+        let mut plot = |px: usize, py: usize| {
+            if px < self.width && py < self.height {
+                self.pixels[py * self.width + px] = pixel.clone();
+            }
+        };
+        for px in x..=right {
+            plot(px, y);
+            plot(px, bottom);
+        }
+        for py in y..=bottom {
+            plot(x, py);
+            plot(right, py);
+        }
+    }
+
+    /// Rotates 90 degrees counter-clockwise, so that the top comes to the left.
+    pub fn rotate_counterclockwise(&self) -> Self {
+        let mut pixels = Vec::with_capacity(self.pixels.len());
+        for x in (0..self.width).rev() {
+            for y in 0..self.height {
+                pixels.push(self.pixels[y * self.width + x].clone());
+            }
+        }
+        Self {
+            width: self.height,
+            height: self.width,
+            pixels,
+        }
+    }
+}
+
+#[cfg(feature = "image")]
+impl Image<SrgbColor> {
+    pub fn from_rgb_image(image: &image::RgbImage) -> Self {
+        Self {
+            width: image.width() as usize,
+            height: image.height() as usize,
+            pixels: image
+                .pixels()
+                .map(|e| SrgbColor::new(e[0], e[1], e[2]))
+                .collect(),
+        }
+    }
+
+    pub fn to_rgb_image(&self) -> image::RgbImage {
+        let buf = self.pixels.iter().flat_map(|e| [e.r, e.g, e.b]).collect();
+        image::RgbImage::from_raw(self.width as u32, self.height as u32, buf)
+            .expect("buffer of the image size")
+    }
+
+    /// Saves the image as a PNG file.
+    pub fn to_png<Q>(&self, path: Q) -> Result<(), IppPrintError>
+    where
+        Q: AsRef<std::path::Path>,
+    {
+        self.to_rgb_image()
+            .save_with_format(path, image::ImageFormat::Png)
+            .map_err(|err| match err {
+                image::ImageError::IoError(err) => IppPrintError::IOError(err),
+                err => IppPrintError::RasterError(err.to_string()),
+            })
+    }
+}
+
+/// How images are placed on pages.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Layout {
+    /// Rotate images whose orientation differs from the page.
+    pub auto_rotate: bool,
+}
+
+impl Default for Layout {
+    fn default() -> Self {
+        Self { auto_rotate: true }
+    }
+}
+
+impl Layout {
+    /// Rotates a landscape image on a portrait page, or the other way around,
+    /// counter-clockwise and sets the orientation of the header to landscape.
+    /// Square images and pages are left as they are.
+    pub fn orient<P>(&self, image: Image<P>, header: &mut PageHeader) -> Image<P>
+    where
+        P: Pixel,
+    {
+        let (width, height) = (header.width(), header.height());
+        let page_landscape = width > height;
+        if !self.auto_rotate
+            || image.width == image.height
+            || width == height
+            || image.is_landscape() == page_landscape
+        {
+            header.set_orientation(Orientation::Portrait);
+            return image;
+        }
+
+        header.set_orientation(Orientation::Landscape);
+        image.rotate_counterclockwise()
+    }
+}
+
+/// N-up imposition: several pages on a sheet in a grid, ordered from left to
+/// right and then from top to bottom.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct NUp {
+    pub columns: usize,
+    pub rows: usize,
+    /// Space between cells and around the grid in pixels
+    pub gutter: usize,
+    /// Draw a black border around each page
+    pub border: bool,
+}
+
+impl NUp {
+    pub fn new(columns: usize, rows: usize) -> Self {
+        Self {
+            columns,
+            rows,
+            gutter: 0,
+            border: false,
+        }
+    }
+
+    pub fn pages_per_sheet(&self) -> usize {
+        self.columns * self.rows
+    }
+
+    /// Composes sheets of width x height from pages, each scaled to fit its cell
+    /// and centered in it. The last sheet may have empty cells.
+    pub fn impose<P>(
+        &self,
+        pages: &[Image<P>],
+        width: usize,
+        height: usize,
+    ) -> Result<Vec<Image<P>>, IppPrintError>
+    where
+        P: Pixel,
+    {
+        let pages = pages.iter().map(Some).collect::<Vec<_>>();
+        pages
+            .chunks(self.pages_per_sheet().max(1))
+            .map(|e| self.compose(e, width, height))
+            .collect()
+    }
+
+    /// Composes a sheet with cells in order, where None leaves a cell blank.
+    fn compose<P>(
+        &self,
+        cells: &[Option<&Image<P>>],
+        width: usize,
+        height: usize,
+    ) -> Result<Image<P>, IppPrintError>
+    where
+        P: Pixel,
+    {
+        let gutters = |cells: usize| (cells + 1) * self.gutter;
+        if self.columns == 0
+            || self.rows == 0
+            || width <= gutters(self.columns)
+            || height <= gutters(self.rows)
+        {
+            return Err(IppPrintError::RasterError(format!(
+                "{}x{} cells with gutter {} don't fit in {}x{}",
+                self.columns, self.rows, self.gutter, width, height
+            )));
+        }
+        let cell_width = (width - gutters(self.columns)) / self.columns;
+        let cell_height = (height - gutters(self.rows)) / self.rows;
+
+        let mut sheet = Image::filled(width, height, P::WHITE);
+        for (i, page) in cells.iter().enumerate() {
+            let Some(page) = page else {
+                continue;
+            };
+            let page = page.scale_to_fit(cell_width, cell_height);
+            let (column, row) = (i % self.columns, i / self.columns);
+            let x =
+                self.gutter + column * (cell_width + self.gutter) + (cell_width - page.width) / 2;
+            let y =
+                self.gutter + row * (cell_height + self.gutter) + (cell_height - page.height) / 2;
+            sheet.draw(&page, x, y);
+            if self.border {
+                sheet.draw_rect(x, y, page.width, page.height, P::BLACK);
+            }
+        }
+        Ok(sheet)
+    }
+}
+
+/// Page indices for the left and right halves of each side of a saddle-stitched
+/// booklet, front and back of each sheet alternating. None is a blank page
+/// padding page_count to a multiple of 4.
+pub fn booklet_order(page_count: usize) -> Vec<[Option<usize>; 2]> {
+    let n = page_count.div_ceil(4) * 4;
+    let page = |i: usize| (i < page_count).then_some(i);
+    (0..n / 2)
+        .map(|side| {
+            let (outer, inner) = (page(n - 1 - side), page(side));
+            // The outer page is on the left of fronts and the right of backs.
+            if side.is_multiple_of(2) {
+                [outer, inner]
+            } else {
+                [inner, outer]
+            }
+        })
+        .collect()
+}
+
+/// Booklet imposition: two pages side by side on each side of duplex sheets,
+/// which are folded in the middle to read in order.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct Booklet {
+    /// Space between the pages and around them in pixels
+    pub gutter: usize,
+    /// Draw a black border around each page
+    pub border: bool,
+}
+
+impl Booklet {
+    /// Composes sides of sheets in the size of header, front and back alternating,
+    /// and sets the header to short-edge duplex. On portrait sheets, pages are
+    /// placed on the rotated sheet so that the fold is along the short edge.
+    pub fn impose<P>(
+        &self,
+        pages: &[Image<P>],
+        header: &mut PageHeader,
+    ) -> Result<Vec<Image<P>>, IppPrintError>
+    where
+        P: Pixel,
+    {
+        let n_up = NUp {
+            gutter: self.gutter,
+            border: self.border,
+            ..NUp::new(2, 1)
+        };
+        let (width, height) = (header.width() as usize, header.height() as usize);
+        let portrait = width < height;
+        let (sheet_width, sheet_height) = if portrait {
+            (height, width)
+        } else {
+            (width, height)
+        };
+
+        let mut sides = vec![];
+        for cells in booklet_order(pages.len()) {
+            let cells = cells.map(|e| e.map(|i| &pages[i]));
+            let side = n_up.compose(&cells, sheet_width, sheet_height)?;
+            sides.push(if portrait {
+                side.rotate_counterclockwise()
+            } else {
+                side
+            });
+        }
+        header.set_sides(Sides::TwoSidedShortEdge);
+        Ok(sides)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pwgraster::{GrayColor, PageHeaderBuilder};
+
+    fn image(width: usize, height: usize) -> Image<GrayColor> {
+        let pixels = (0..width * height).map(|e| GrayColor(e as u8)).collect();
+        Image::new(width, height, pixels).unwrap()
+    }
+
+    #[test]
+    fn rotate_image() {
+        // 0 1 2      2 5
+        // 3 4 5  ->  1 4
+        //            0 3
+        let rotated = image(3, 2).rotate_counterclockwise();
+        assert_eq!((2, 3), (rotated.width(), rotated.height()));
+        assert_eq!([2, 5, 1, 4, 0, 3].map(GrayColor), rotated.pixels());
+
+        assert!(Image::new(3, 2, vec![GrayColor(0); 5]).is_err());
+    }
+
+    #[test]
+    fn orient_landscape_image() {
+        let mut header = PageHeaderBuilder::new(20, 30).build().unwrap();
+        let oriented = Layout::default().orient(image(3, 2), &mut header);
+        assert_eq!(image(3, 2).rotate_counterclockwise(), oriented);
+        assert_eq!(Some(Orientation::Landscape), header.orientation());
+
+        let oriented = Layout::default().orient(image(2, 3), &mut header);
+        assert_eq!(image(2, 3), oriented);
+        assert_eq!(Some(Orientation::Portrait), header.orientation());
+
+        let layout = Layout { auto_rotate: false };
+        assert_eq!(image(3, 2), layout.orient(image(3, 2), &mut header));
+    }
+
+    #[test]
+    fn scale_and_draw() {
+        let scaled = image(4, 2).scale_to_fit(2, 2);
+        assert_eq!((2, 1), (scaled.width(), scaled.height()));
+        assert_eq!([0, 2].map(GrayColor), scaled.pixels());
+        let scaled = image(2, 2).scale_to_fit(4, 5);
+        assert_eq!([0, 0, 1, 1].map(GrayColor), scaled.pixels()[..4]);
+        assert_eq!((4, 4), (scaled.width(), scaled.height()));
+
+        let mut canvas = Image::filled(3, 3, GrayColor::WHITE);
+        canvas.draw(&image(2, 2), 2, 1);
+        assert_eq!(
+            [255, 255, 255, 255, 255, 0, 255, 255, 2].map(GrayColor),
+            canvas.pixels()
+        );
+        canvas.draw_rect(0, 0, 2, 2, GrayColor::BLACK);
+        assert_eq!([0, 0, 255, 0, 0].map(GrayColor), canvas.pixels()[..5]);
+    }
+
+    #[test]
+    fn draw_off_canvas() {
+        let mut canvas = Image::filled(3, 3, GrayColor::WHITE);
+        canvas.draw(&image(2, 2), 4, 2);
+        canvas.draw(&image(2, 2), 3, 0);
+        canvas.draw(&image(2, 2), 0, 3);
+        assert_eq!(Image::filled(3, 3, GrayColor::WHITE), canvas);
+    }
+
+    #[test]
+    fn impose_two_up() {
+        let pages = vec![
+            Image::filled(4, 4, GrayColor(10)),
+            Image::filled(4, 4, GrayColor(20)),
+            Image::filled(4, 4, GrayColor(30)),
+        ];
+        let n_up = NUp {
+            gutter: 1,
+            border: true,
+            ..NUp::new(2, 1)
+        };
+        let sheets = n_up.impose(&pages, 9, 6).unwrap();
+        assert_eq!(2, sheets.len());
+
+        // Cells are 3x4 and pages are scaled to 3x3, starting at the top of the cell
+        // since the remaining pixel is split.
+        let row = |sheet: &Image<GrayColor>, y: usize| sheet.pixels()[y * 9..(y + 1) * 9].to_vec();
+        assert_eq!([255; 9].map(GrayColor), row(&sheets[0], 0)[..]);
+        assert_eq!(
+            [255, 0, 0, 0, 255, 0, 0, 0, 255].map(GrayColor),
+            row(&sheets[0], 1)[..]
+        );
+        assert_eq!(
+            [255, 0, 10, 0, 255, 0, 20, 0, 255].map(GrayColor),
+            row(&sheets[0], 2)[..]
+        );
+        assert_eq!(
+            [255, 0, 30, 0, 255, 255, 255, 255, 255].map(GrayColor),
+            row(&sheets[1], 2)[..]
+        );
+
+        assert!(NUp::new(0, 1).impose(&pages, 9, 6).is_err());
+        assert!(n_up.impose(&pages, 3, 6).is_err());
+    }
+
+    #[test]
+    fn booklet_page_order() {
+        assert_eq!(
+            vec![
+                [Some(7), Some(0)],
+                [Some(1), Some(6)],
+                [Some(5), Some(2)],
+                [Some(3), Some(4)]
+            ],
+            booklet_order(8)
+        );
+        assert_eq!(
+            vec![
+                [None, Some(0)],
+                [Some(1), None],
+                [None, Some(2)],
+                [Some(3), Some(4)]
+            ],
+            booklet_order(5)
+        );
+        assert!(booklet_order(0).is_empty());
+    }
+
+    #[test]
+    fn impose_booklet() {
+        let mut header = PageHeaderBuilder::new(4, 6).build().unwrap();
+        let pages = (1..=3)
+            .map(|e| Image::filled(3, 2, GrayColor(e)))
+            .collect::<Vec<_>>();
+        let sides = Booklet::default().impose(&pages, &mut header).unwrap();
+        assert_eq!(Sides::TwoSidedShortEdge, header.sides());
+        assert_eq!(2, sides.len());
+
+        // Pages are laid out on the 6x4 sheet and rotated onto the 4x6 page, so
+        // the left page is at the bottom.
+        let front = &sides[0];
+        assert_eq!((4, 6), (front.width(), front.height()));
+        assert_eq!([255, 255, 255, 255].map(GrayColor), front.pixels()[20..]);
+        assert_eq!([255, 1, 1, 255].map(GrayColor), front.pixels()[..4]);
+        assert_eq!(
+            [255, 2, 2, 255, 255, 2, 2, 255, 255, 2, 2, 255].map(GrayColor),
+            sides[1].pixels()[12..]
+        );
+    }
+}
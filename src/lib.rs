@@ -0,0 +1,69 @@
+//! Driverless printing over IPP.
+//!
+//! - [`ipp`]: Encoding and decoding of IPP requests and responses (RFC 8010).
+//! - [`dump`]: Pretty-printing captured IPP messages for debugging.
+//! - [`pwgraster`]: PWG Raster (PWG5102.4) page header and encoder/decoder, also for CUPS raster v2/v3.
+//! - [`urf`]: Apple Raster (URF) encoder for AirPrint printers without PWG Raster.
+//! - [`pclm`]: PCLm generator for printers preferring it over PWG Raster.
+//! - [`ppm`]: Reading and writing pages as PPM images.
+//! - [`cache`]: Printer attributes kept for a while, in memory or in files.
+//! - [`transport`]: How IPP messages reach the printer (HTTP by default).
+//! - `usb`: IPP-over-USB transport (requires the `usb` feature).
+//! - `testing`: Mock printer for tests (requires the `testing` feature).
+//! - `discovery`: Finding printers on the local network with mDNS (requires the `discovery` feature).
+//! - [`auth`]: Credentials sent to printers which require authentication.
+//! - [`tls`]: Certificate verification for ipps:// printers.
+//! - [`media`]: PWG5101.1 media sizes and media-col.
+//! - [`color`]: Color conversion used when generating raster data.
+//! - [`halftone`]: Screening and dithering for bi-level output.
+//! - [`layout`]: Placing source images on pages, including N-up and booklet imposition.
+//! - `pdf`: PDF rendering with pdfium (requires the `pdf` feature).
+//! - `svg`: SVG rendering with resvg (requires the `svg` feature).
+//! - `text`: Plain text rendering with a font (requires the `text` feature).
+//! - [`negotiation`]: Choosing the document format and raster settings a printer accepts.
+//! - `config`: Configuration file of the ipp-print command (requires the `cli` feature).
+//! - [`report`]: Human-readable summary of printer capabilities.
+//! - [`supplies`]: Ink and toner levels from the marker-* printer attributes.
+//! - [`testpage`]: Built-in page with color bars and alignment marks for checking printers.
+//! - [`error`]: Error type returned by the high-level APIs.
+//!
+//! A page is sent to a printer by encoding it with [`pwgraster::ImageEncoder`]
+//! after a [`pwgraster::PageHeader`], and then posting it as the data of a
+//! Send-Document [`ipp::IPPRequest`] to the printer over HTTP.
+
+#[macro_use]
+extern crate num_derive;
+
+pub mod auth;
+pub mod cache;
+pub mod color;
+#[cfg(feature = "cli")]
+pub mod config;
+#[cfg(feature = "discovery")]
+pub mod discovery;
+pub mod dump;
+pub mod error;
+pub mod halftone;
+pub mod ipp;
+pub mod layout;
+pub mod media;
+pub mod negotiation;
+pub mod pclm;
+#[cfg(feature = "pdf")]
+pub mod pdf;
+pub mod ppm;
+pub mod pwgraster;
+pub mod report;
+pub mod supplies;
+#[cfg(feature = "svg")]
+pub mod svg;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
+pub mod testpage;
+#[cfg(feature = "text")]
+pub mod text;
+pub mod tls;
+pub mod transport;
+pub mod urf;
+#[cfg(feature = "usb")]
+pub mod usb;
@@ -1,294 +1,1889 @@
-use reqwest::blocking::Client;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use indicatif::{ProgressBar, ProgressStyle};
+use ipp_print::auth::BearerToken;
+use ipp_print::cache::AttributeCache;
+use ipp_print::color::CmykConverter;
+#[cfg(feature = "icc")]
+use ipp_print::color::{ColorTransform, RenderingIntent};
+use ipp_print::config::{Config, PrinterProfile};
+#[cfg(feature = "discovery")]
+use ipp_print::discovery;
+use ipp_print::dump::{self, IppMessage, MessageKind};
+use ipp_print::error::IppPrintError;
+use ipp_print::halftone::{AmScreen, Dither};
+use ipp_print::ipp::*;
+use ipp_print::layout::Image;
+use ipp_print::media::{MediaCol, MediaSize};
+use ipp_print::negotiation::*;
+use ipp_print::pclm::PclmDocumentWriter;
+use ipp_print::ppm;
+use ipp_print::pwgraster::*;
+use ipp_print::report::CapabilityReport;
+use ipp_print::supplies::{MarkerLevel, Supplies, SUPPLY_ATTRIBUTES};
+use ipp_print::testpage;
+use ipp_print::transport::{HttpTransport, PrinterUri, ProgressReader};
+use ipp_print::urf::UrfDocumentWriter;
 use std::error::Error;
-use std::io::prelude::*;
-
-#[macro_use]
-extern crate num_derive;
-
-mod ipp;
-use crate::ipp::*;
-
-mod pwgraster;
-use crate::pwgraster::*;
-
-fn print_page(raster_data: Vec<u8>) -> Result<(), Box<dyn Error>> {
-    let printer_addr = std::env::var("PRINTER_ADDR")
-        .expect("PRINTER_ADDR is not set (should be a value like \"192.0.2.1:631\")");
-
-    let client = Client::new();
-    let mut buf = Vec::new();
-
-    // Get-Printer-Attributes
-    IPPRequest {
-        version_major: 1,
-        version_minor: 1,
-        operation_id: PrinterOperation::GetPrinterAttributes,
-        request_id: 1,
-        attrs: vec![(
-            DelimiterOrValueTag::OperationAttributesTag,
-            vec![
-                (
-                    "attributes-charset".to_string(),
-                    AttributeValue::Charset("utf-8".to_string()),
-                ),
-                (
-                    "attributes-natural-language".to_string(),
-                    AttributeValue::NaturalLanguage("ja-jp".to_string()),
-                ),
-                (
-                    "printer-uri".to_string(),
-                    AttributeValue::Uri(format!("ipp://{}", printer_addr)),
-                ),
-            ],
-        )],
-        data: vec![],
+use std::io::{self, IsTerminal, Read, Write};
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::prelude::*;
+
+#[derive(Parser)]
+#[command(version, about = "Driverless printing over IPP")]
+struct Cli {
+    /// Printer URI like ipp://192.0.2.1/ipp/print (ipps:// for TLS), or an address
+    /// like 192.0.2.1:631
+    #[arg(short, long, global = true, env = "PRINTER_ADDR")]
+    printer: Option<PrinterUri>,
+    /// Printer named in ~/.config/ipp-print/config.toml, instead of --printer
+    #[arg(short = 'P', long, global = true)]
+    printer_name: Option<String>,
+    /// requesting-user-name of requests, instead of $USER
+    #[arg(long, global = true)]
+    user: Option<String>,
+    /// Log more to stderr: -v for jobs, -vv for IPP operations, -vvv for their attributes
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Log only errors and hide the progress bar
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+    /// Fetch printer attributes again instead of using the cache
+    #[arg(long, global = true)]
+    refresh: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print text, PDF, SVG, JPEG, or PNG files as one job
+    Print {
+        /// Files to print in order, or - for stdin
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+        #[command(flatten)]
+        job: JobArgs,
+        /// Render and encode the file without contacting the printer
+        #[arg(long, requires = "output")]
+        dry_run: bool,
+        /// File to write the document data to with --dry-run
+        #[arg(short, long, requires = "dry_run")]
+        output: Option<PathBuf>,
+    },
+    /// Print a document the printer fetches from a URI like http://192.0.2.10/a.pdf
+    PrintUri {
+        uri: String,
+        #[command(flatten)]
+        job: JobArgs,
+    },
+    /// Show the printer state
+    Status,
+    /// List jobs which are not completed
+    Jobs {
+        /// List completed jobs instead
+        #[arg(long)]
+        completed: bool,
+    },
+    /// Show the printer state and jobs, updated until interrupted
+    Watch {
+        /// Seconds between updates
+        #[arg(long, default_value_t = 2, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+    },
+    /// Show what the printer supports, like media, resolutions, and duplex
+    Capabilities,
+    /// Show the levels of ink, toner, and other supplies
+    Supplies {
+        /// Print JSON for monitoring systems
+        #[arg(long)]
+        json: bool,
+    },
+    /// Show all printer attributes
+    Attrs,
+    /// Cancel a job
+    Cancel { job_id: i32 },
+    /// Cancel all jobs, or only the jobs of --user
+    Purge {
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// Make the printer flash or beep to locate it
+    Identify {
+        /// Actions like flash or sound
+        #[arg(value_delimiter = ',')]
+        actions: Vec<String>,
+    },
+    /// Print a page with color bars, alignment crosses, and resolution wedges
+    TestPage {
+        #[command(flatten)]
+        job: JobArgs,
+    },
+    /// Convert a file to PWG Raster (.pwg), URF (.urf), PNG (.png), or PPM (.ppm)
+    /// by the extension of the output, rendering documents which aren't images
+    Convert {
+        /// PWG Raster, CUPS raster, PNG, or PPM image, or a document to render,
+        /// or - for stdin
+        input: PathBuf,
+        output: PathBuf,
+    },
+    /// List printers on the local network found with mDNS
+    #[cfg(feature = "discovery")]
+    Discover {
+        /// Seconds to wait for printers to respond
+        #[arg(long, default_value_t = 3)]
+        timeout: u64,
+    },
+    /// Render a file to PNG as it would be printed, with the printer's raster
+    /// settings if a printer is given
+    #[cfg(feature = "image")]
+    Preview {
+        file: PathBuf,
+        #[command(flatten)]
+        job: JobArgs,
+        /// PNG file, numbered like preview-2.png if there are several pages
+        #[arg(long)]
+        out: PathBuf,
+    },
+    /// Show the pages of a PWG Raster or CUPS raster file
+    RasterInfo { file: PathBuf },
+    /// Pretty-print a captured IPP message
+    Decode {
+        file: PathBuf,
+        #[arg(value_enum)]
+        kind: Option<Kind>,
+    },
+    /// Print a completion script for the shell
+    Completions {
+        #[arg(value_enum)]
+        shell: Shell,
+    },
+    /// Write man pages for ipp-print and its subcommands to a directory
+    Man { dir: PathBuf },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Kind {
+    Request,
+    Response,
+}
+
+/// Options of a job, overriding the printer profile.
+#[derive(clap::Args)]
+struct JobArgs {
+    /// Job name shown on the printer, instead of the file name
+    #[arg(long)]
+    job_name: Option<String>,
+    /// Number of copies
+    #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+    copies: Option<u32>,
+    /// Print on both sides, bound on the long or short edge
+    #[arg(long, value_enum)]
+    duplex: Option<Duplex>,
+    /// Media name like iso_a4_210x297mm
+    #[arg(long)]
+    media: Option<String>,
+    /// Tray to feed media from, like main, manual, or tray-2
+    #[arg(long)]
+    source: Option<String>,
+    #[arg(long, value_enum)]
+    quality: Option<Quality>,
+    /// Pages to print like 1-3,7,9- (to the last page)
+    #[arg(long, value_parser = page_ranges)]
+    pages: Option<PageRanges>,
+    /// Print in color, or in gray if the printer can't
+    #[arg(long, conflicts_with_all = ["grayscale", "bilevel"])]
+    color: bool,
+    /// Print in shades of gray
+    #[arg(long, conflicts_with = "bilevel")]
+    grayscale: bool,
+    /// Print in black and white only, dithering shades of gray
+    #[arg(long)]
+    bilevel: bool,
+    /// How shades of gray are dithered for black and white printers: a
+    /// clustered-dot screen for lasers (default), or dispersed dots for inkjets
+    /// and thermal printers
+    #[arg(long, value_enum)]
+    dither: Option<DitherMethod>,
+    /// Type of the file, detected from its content if not given
+    #[arg(long, value_enum)]
+    format: Option<InputFormat>,
+    /// ICC profile of the printer to convert colors into before encoding
+    #[cfg(feature = "icc")]
+    #[arg(long)]
+    icc_profile: Option<PathBuf>,
+    /// How colors the printer can't print are mapped with --icc-profile
+    #[cfg(feature = "icc")]
+    #[arg(long, value_enum, requires = "icc_profile")]
+    rendering_intent: Option<Intent>,
+}
+
+impl JobArgs {
+    fn color_mode(&self) -> Option<PrintColorMode> {
+        match (self.color, self.grayscale, self.bilevel) {
+            (true, _, _) => Some(PrintColorMode::Color),
+            (_, true, _) => Some(PrintColorMode::Monochrome),
+            (_, _, true) => Some(PrintColorMode::BiLevel),
+            _ => None,
+        }
     }
-    .write_to_stream(&mut buf)?;
+}
 
-    println!(
-        "{:?}",
-        IPPResponse::read_from_stream(
-            &mut client
-                .post(format!("http://{}", printer_addr))
-                .header("Content-Type", "application/ipp")
-                .body(buf)
-                .send()?
-        )?
-    );
+#[derive(Clone)]
+struct PageRanges(Vec<RangeInclusive<i32>>);
 
-    buf = Vec::new();
-
-    // Validate-Job (like 4.2.1.1. Print-Job Request)
-    IPPRequest {
-        version_major: 1,
-        version_minor: 1,
-        operation_id: PrinterOperation::ValidateJob,
-        request_id: 2,
-        attrs: vec![(
-            DelimiterOrValueTag::OperationAttributesTag,
-            vec![
-                (
-                    "attributes-charset".to_string(),
-                    AttributeValue::Charset("utf-8".to_string()),
-                ),
-                (
-                    "attributes-natural-language".to_string(),
-                    AttributeValue::NaturalLanguage("ja-jp".to_string()),
-                ),
-                (
-                    "printer-uri".to_string(),
-                    AttributeValue::Uri(format!("ipp://{}", printer_addr)),
-                ),
-                (
-                    "requesting-user-name".to_string(),
-                    AttributeValue::NameWithoutLanguage(std::env::var("USER")?),
-                ),
-                (
-                    "document-format".to_string(),
-                    AttributeValue::MimeMediaType("image/pwg-raster".to_string()),
-                ),
-            ],
-        )],
-        data: vec![],
+fn page_ranges(ranges: &str) -> Result<PageRanges, String> {
+    parse_page_ranges(ranges)
+        .map(PageRanges)
+        .ok_or_else(|| format!("invalid page ranges: {}", ranges))
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Duplex {
+    Long,
+    Short,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Quality {
+    Draft,
+    Normal,
+    High,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum DitherMethod {
+    Screen,
+    Ordered,
+    FloydSteinberg,
+    Threshold,
+}
+
+#[cfg(feature = "icc")]
+#[derive(Clone, Copy, ValueEnum)]
+enum Intent {
+    Perceptual,
+    Relative,
+    Saturation,
+    Absolute,
+}
+
+/// Type of an input file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum InputFormat {
+    Text,
+    Pdf,
+    Svg,
+    Jpeg,
+    Png,
+}
+
+impl InputFormat {
+    /// Text unless the data starts like one of the other formats.
+    fn detect(data: &[u8]) -> Self {
+        if data.starts_with(b"%PDF-") {
+            Self::Pdf
+        } else if data.starts_with(b"\xff\xd8\xff") {
+            Self::Jpeg
+        } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Self::Png
+        } else if is_svg(data) {
+            Self::Svg
+        } else {
+            Self::Text
+        }
     }
-    .write_to_stream(&mut buf)?;
 
+    /// MIME type for printers fetching the document themselves.
+    fn mime_type(self) -> &'static str {
+        match self {
+            Self::Text => "text/plain",
+            Self::Pdf => "application/pdf",
+            Self::Svg => "image/svg+xml",
+            Self::Jpeg => "image/jpeg",
+            Self::Png => "image/png",
+        }
+    }
+
+    /// document-format of formats which can be sent as they are.
+    fn document_format(self) -> Option<&'static str> {
+        match self {
+            Self::Jpeg => Some("image/jpeg"),
+            Self::Png => Some("image/png"),
+            _ => None,
+        }
+    }
+}
+
+/// Settings of a job, from the printer profile and the command line.
+#[derive(Default)]
+struct JobOptions {
+    media: Option<String>,
+    color_mode: Option<PrintColorMode>,
+    resolution: Option<u32>,
+    copies: Option<u32>,
+    sides: Option<Sides>,
+    /// media-source keyword
+    source: Option<String>,
+    quality: Option<PrintQuality>,
+    /// Pages to print counted from 1, or all pages if empty
+    page_ranges: Vec<RangeInclusive<i32>>,
+    job_name: Option<String>,
+    /// Type of the input, detected from its content if None
+    format: Option<InputFormat>,
+    /// Dithering of black_1 output, or the clustered-dot screen if None
+    dither: Option<Dither>,
+    /// ICC profile to convert RGB output into
+    #[cfg(feature = "icc")]
+    icc_profile: Option<PathBuf>,
+    #[cfg(feature = "icc")]
+    rendering_intent: RenderingIntent,
+}
+
+impl JobOptions {
+    fn from_profile(profile: &PrinterProfile) -> Self {
+        Self {
+            media: profile.media.clone(),
+            color_mode: profile.color_mode,
+            resolution: profile.resolution,
+            ..Default::default()
+        }
+    }
+
+    fn with_args(self, args: &JobArgs) -> Self {
+        Self {
+            job_name: args.job_name.clone().or(self.job_name),
+            media: args.media.clone().or(self.media),
+            color_mode: args.color_mode().or(self.color_mode),
+            copies: args.copies.or(self.copies),
+            sides: args
+                .duplex
+                .map(|e| match e {
+                    Duplex::Long => Sides::TwoSidedLongEdge,
+                    Duplex::Short => Sides::TwoSidedShortEdge,
+                })
+                .or(self.sides),
+            source: args.source.clone().or(self.source),
+            quality: args
+                .quality
+                .map(|e| match e {
+                    Quality::Draft => PrintQuality::Draft,
+                    Quality::Normal => PrintQuality::Normal,
+                    Quality::High => PrintQuality::High,
+                })
+                .or(self.quality),
+            page_ranges: args
+                .pages
+                .as_ref()
+                .map_or(self.page_ranges, |e| e.0.clone()),
+            format: args.format.or(self.format),
+            dither: match args.dither {
+                Some(DitherMethod::Screen) => None,
+                Some(DitherMethod::Ordered) => Some(Dither::Ordered),
+                Some(DitherMethod::FloydSteinberg) => Some(Dither::FloydSteinberg),
+                Some(DitherMethod::Threshold) => Some(Dither::Threshold(128)),
+                None => self.dither,
+            },
+            #[cfg(feature = "icc")]
+            icc_profile: args.icc_profile.clone().or(self.icc_profile),
+            #[cfg(feature = "icc")]
+            rendering_intent: args
+                .rendering_intent
+                .map_or(self.rendering_intent, |e| match e {
+                    Intent::Perceptual => RenderingIntent::Perceptual,
+                    Intent::Relative => RenderingIntent::RelativeColorimetric,
+                    Intent::Saturation => RenderingIntent::Saturation,
+                    Intent::Absolute => RenderingIntent::AbsoluteColorimetric,
+                }),
+            ..self
+        }
+    }
+
+    /// Media with a source is requested with media-col, which needs the size.
+    /// Color is only requested if the printer supports it.
+    fn template(&self, attrs: &PrinterAttributes) -> Result<JobTemplate, Box<dyn Error>> {
+        let (media, media_col) = match (&self.media, &self.source) {
+            (Some(media), Some(source)) => {
+                let size =
+                    MediaSize::from_name(media).ok_or(format!("unknown media: {}", media))?;
+                let mut media_col = MediaCol::new(size);
+                media_col.media_source = Some(source.clone());
+                (None, Some(media_col))
+            }
+            (None, Some(_)) => return Err("--source needs --media or media in the config".into()),
+            (media, None) => (media.clone(), None),
+        };
+        Ok(JobTemplate {
+            job_name: self.job_name.clone(),
+            copies: self.copies.map(|e| e as i32),
+            sides: self.sides,
+            media,
+            media_col,
+            print_quality: self.quality,
+            print_color_mode: self.color_mode.map(|e| supported_color_mode(e, attrs)),
+            ..Default::default()
+        })
+    }
+
+    /// Sets the header fields matching the job template.
+    fn apply(&self, hdr: &mut PageHeader) -> Result<(), Box<dyn Error>> {
+        if let Some(media) = &self.media {
+            let size = MediaSize::from_name(media).ok_or(format!("unknown media: {}", media))?;
+            hdr.set_media_size(&size);
+        }
+        if let Some(copies) = self.copies {
+            hdr.set_num_copies(copies);
+        }
+        if let Some(sides) = self.sides {
+            hdr.set_sides(sides);
+        }
+        // Trays PWG Raster can't express are only in media-col.
+        if let Some(position) = self.source.as_deref().and_then(MediaPosition::from_keyword) {
+            hdr.set_media_position(position)?;
+        }
+        hdr.set_print_quality(self.quality);
+        Ok(())
+    }
+}
+
+/// The printer named with --printer-name, given with --printer, or the default
+/// printer of the config, in this order. A name not in the config is looked up
+/// on the local network with the discovery feature.
+fn printer_profile(
+    printer: Option<&PrinterUri>,
+    printer_name: Option<&str>,
+) -> Result<PrinterProfile, Box<dyn Error>> {
+    find_printer_profile(printer, printer_name)?.ok_or_else(|| {
+        "no printer given (use --printer, PRINTER_ADDR, or default-printer in the config)".into()
+    })
+}
+
+/// Same as printer_profile, but None if no printer is given.
+fn find_printer_profile(
+    printer: Option<&PrinterUri>,
+    printer_name: Option<&str>,
+) -> Result<Option<PrinterProfile>, Box<dyn Error>> {
+    if let (Some(uri), None) = (printer, printer_name) {
+        return Ok(Some(uri_profile(uri.clone())));
+    }
+
+    let config = match Config::default_path() {
+        Some(path) => Config::load(&path)?,
+        None => Config::default(),
+    };
+    #[cfg(feature = "discovery")]
+    if let Some(name) = printer_name.filter(|e| !config.printers.contains_key(*e)) {
+        return discovered_printer(name).map(Some);
+    }
+    Ok(config.printer(printer_name)?.cloned())
+}
+
+/// A profile without defaults for jobs.
+fn uri_profile(uri: PrinterUri) -> PrinterProfile {
+    PrinterProfile {
+        uri,
+        token: None,
+        media: None,
+        color_mode: None,
+        resolution: None,
+        cache_ttl: None,
+    }
+}
+
+/// The printer whose service name is name, ignoring case.
+#[cfg(feature = "discovery")]
+fn discovered_printer(name: &str) -> Result<PrinterProfile, Box<dyn Error>> {
+    let printer = discovery::discover(Duration::from_secs(3))?
+        .into_iter()
+        .find(|e| e.name.eq_ignore_ascii_case(name))
+        .ok_or_else(|| format!("no printer named {} in the config or on the network", name))?;
+    Ok(uri_profile(printer.uri))
+}
+
+/// PRINTER_TOKEN takes precedence over the token in the profile.
+/// Printer attributes are cached in files if the profile has cache-ttl; with
+/// refresh, cached ones are not used but replaced.
+fn new_client(profile: &PrinterProfile, user: Option<&str>, refresh: bool) -> PrintClient {
+    let mut transport = HttpTransport::with_uri(profile.uri.clone());
+    if let Some(token) = std::env::var("PRINTER_TOKEN")
+        .ok()
+        .or_else(|| profile.token.clone())
+    {
+        transport.set_auth(Box::new(BearerToken(token)));
+    }
+    let mut client = PrintClient::with_transport(Box::new(transport));
+    if let Some(user) = user {
+        client.set_user_name(user);
+    }
+    client.set_stopped_hook(Box::new(|status, printer_reasons| {
+        tracing::warn!(
+            job_reasons = ?status.reasons,
+            printer_reasons = ?printer_reasons,
+            "the job is stopped; waiting for it to resume"
+        );
+    }));
+    if let (Some(ttl), Some(dir)) = (profile.cache_ttl, AttributeCache::default_dir()) {
+        let ttl = if refresh { Duration::ZERO } else { ttl };
+        client.set_attribute_cache(AttributeCache::with_dir(ttl, dir));
+    }
+    client
+}
+
+fn status(client: &mut PrintClient) -> Result<(), Box<dyn Error>> {
+    let attrs = client.printer_attributes()?;
     println!(
-        "{:?}",
-        IPPResponse::read_from_stream(
-            &mut client
-                .post(format!("http://{}", printer_addr))
-                .header("Content-Type", "application/ipp")
-                .body(buf)
-                .send()?
-        )?
+        "{} ({})",
+        attrs.printer_name.as_deref().unwrap_or("unknown"),
+        attrs.printer_make_and_model.as_deref().unwrap_or("unknown")
+    );
+    match attrs.printer_state {
+        Some(state) => println!("printer-state: {:?}", state),
+        None => println!("printer-state: unknown"),
+    }
+    println!(
+        "printer-state-reasons: {}",
+        attrs.printer_state_reasons.join(", ")
+    );
+    println!(
+        "document-format-supported: {}",
+        attrs.document_format_supported.join(", ")
     );
 
-    buf = Vec::new();
-
-    // Create-Job
-    IPPRequest {
-        version_major: 1,
-        version_minor: 1,
-        operation_id: PrinterOperation::CreateJob,
-        request_id: 2,
-        attrs: vec![(
-            DelimiterOrValueTag::OperationAttributesTag,
-            vec![
-                (
-                    "attributes-charset".to_string(),
-                    AttributeValue::Charset("utf-8".to_string()),
-                ),
-                (
-                    "attributes-natural-language".to_string(),
-                    AttributeValue::NaturalLanguage("ja-jp".to_string()),
-                ),
-                (
-                    "printer-uri".to_string(),
-                    AttributeValue::Uri(format!("ipp://{}", printer_addr)),
-                ),
-                (
-                    "requesting-user-name".to_string(),
-                    AttributeValue::NameWithoutLanguage(std::env::var("USER")?),
-                ),
-            ],
-        )],
-        data: vec![],
+    Ok(())
+}
+
+fn jobs(client: &mut PrintClient, completed: bool) -> Result<(), Box<dyn Error>> {
+    let options = GetJobsOptions {
+        which_jobs: if completed {
+            WhichJobs::Completed
+        } else {
+            WhichJobs::NotCompleted
+        },
+        requested_attributes: ["job-id", "job-state", "job-name"]
+            .map(String::from)
+            .to_vec(),
+        ..Default::default()
+    };
+
+    for job in client.list_jobs(&options)? {
+        println!(
+            "{}\t{}\t{}",
+            job.id,
+            job.state.map_or(String::new(), |e| format!("{:?}", e)),
+            job.name.unwrap_or_default()
+        );
+    }
+
+    Ok(())
+}
+
+/// Asks on the terminal, taking anything other than y as no.
+fn confirm(question: &str) -> Result<bool, Box<dyn Error>> {
+    if !io::stdin().is_terminal() {
+        return Err("not on a terminal to confirm (use --yes)".into());
     }
-    .write_to_stream(&mut buf)?;
+    eprint!("{} [y/N] ", question);
+    io::stderr().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(answer.trim().eq_ignore_ascii_case("y"))
+}
+
+/// Purges the queue with Purge-Jobs, or cancels jobs one by one if the
+/// printer doesn't support it or only the jobs of user are canceled.
+fn purge(
+    client: &mut PrintClient,
+    uri: &PrinterUri,
+    user: Option<&str>,
+    yes: bool,
+) -> Result<(), Box<dyn Error>> {
+    let question = match user {
+        Some(user) => format!("Cancel the jobs of {} on {}?", user, uri),
+        None => format!("Cancel all jobs on {}?", uri),
+    };
+    if !yes && !confirm(&question)? {
+        return Err("nothing canceled".into());
+    }
+
+    if user.is_none() && client.supports_operation(PrinterOperation::PurgeJobs)? {
+        match client.purge_jobs() {
+            Ok(_) => {
+                println!("Purged all jobs");
+                return Ok(());
+            }
+            Err(IppPrintError::StatusError(StatusCode::ServerErrorOperationNotSupported, _)) => {
+                tracing::info!("Purge-Jobs is not supported; canceling jobs one by one");
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    let canceled = client.cancel_all_jobs(user)?;
+    if canceled.is_empty() {
+        println!("No jobs to cancel");
+    }
+    for job_id in canceled {
+        println!("Canceled job {}", job_id);
+    }
+    Ok(())
+}
 
-    let create_job_resp = IPPResponse::read_from_stream(
-        &mut client
-            .post(format!("http://{}", printer_addr))
-            .header("Content-Type", "application/ipp")
-            .body(buf)
-            .send()?,
-    )?;
-    println!("{:?}", create_job_resp);
+fn supplies(client: &mut PrintClient, json: bool) -> Result<(), Box<dyn Error>> {
+    let resp = client.get_printer_attributes(SUPPLY_ATTRIBUTES)?;
+    let supplies = Supplies::from_attributes(&PrinterAttributes::from_response(&resp));
+    if json {
+        println!("{}", serde_json::to_string_pretty(&supplies)?);
+        return Ok(());
+    }
 
-    if create_job_resp.attrs.len() < 2 {
-        panic!("Create-Job response doesn't contain required attribute group");
+    if supplies.markers.is_empty() {
+        println!("The printer doesn't report supplies");
     }
-    let job_id = *match create_job_resp.attrs[1]
-        .1
+    let width = supplies
+        .markers
         .iter()
-        .find(|(key, _)| key == "job-id")
+        .map(|e| e.name.chars().count())
+        .max()
+        .unwrap_or(0);
+    for marker in &supplies.markers {
+        let bar = match marker.level {
+            MarkerLevel::Percent(level) => format!("[{:<20}]", "#".repeat(level as usize / 5)),
+            _ => String::new(),
+        };
+        let low = if marker.is_low() { " low" } else { "" };
+        println!(
+            "{:width$}  {:>14}  {}{}",
+            marker.name,
+            marker.level.to_string(),
+            bar,
+            low,
+            width = width
+        );
+    }
+    Ok(())
+}
+
+/// Printer state and jobs which are not completed, with their progress.
+fn watch_view(client: &mut PrintClient) -> Result<String, Box<dyn Error>> {
+    let resp = client.get_printer_attributes(&[
+        "printer-name",
+        "printer-state",
+        "printer-state-reasons",
+        "printer-state-message",
+    ])?;
+    let attrs = PrinterAttributes::from_response(&resp);
+    let mut view = format!(
+        "{}: {}",
+        attrs.printer_name.as_deref().unwrap_or("printer"),
+        attrs
+            .printer_state
+            .map_or("unknown".to_string(), |e| format!("{:?}", e))
+    );
+    if !attrs.printer_state_reasons.is_empty() {
+        view += &format!(" ({})", attrs.printer_state_reasons.join(", "));
+    }
+    if let Some(message) = resp
+        .get_attribute("printer-state-message")
+        .and_then(|e| e.as_str())
+        .filter(|e| !e.is_empty())
     {
-        Some((_, AttributeValue::Integer(val))) => val,
-        _ => panic!("job-id was not found"),
+        view += &format!("\n{}", message);
+    }
+    view += "\n\nJOB\tSTATE\tPAGES\tUSER\tNAME\n";
+
+    for job in client.list_jobs(&GetJobsOptions::default())? {
+        // job-impressions is only known for some document formats.
+        let pages = match (job.impressions_completed, job.impressions) {
+            (Some(completed), Some(total)) => format!("{}/{}", completed, total),
+            (Some(completed), None) => completed.to_string(),
+            _ => String::new(),
+        };
+        view += &[
+            job.id.to_string(),
+            job.state.map_or(String::new(), |e| format!("{:?}", e)),
+            pages,
+            job.user.unwrap_or_default(),
+            job.name.unwrap_or_default(),
+        ]
+        .join("\t");
+        view.push('\n');
+    }
+    Ok(view)
+}
+
+/// Redraws the view in place on terminals, and prints it only when it changes
+/// otherwise, so that the output can be logged.
+fn watch(client: &mut PrintClient, interval: Duration) -> Result<(), Box<dyn Error>> {
+    let terminal = io::stdout().is_terminal();
+    let mut last = String::new();
+    loop {
+        let view = watch_view(client)?;
+        if terminal {
+            // Move the cursor home and clear the screen.
+            print!("\x1b[H\x1b[2J{}", view);
+            io::stdout().flush()?;
+        } else if view != last {
+            println!("{}", view);
+        }
+        last = view;
+        std::thread::sleep(interval);
+    }
+}
+
+fn identify(client: &mut PrintClient, actions: &[String]) -> Result<(), Box<dyn Error>> {
+    let actions = actions
+        .iter()
+        .map(|e| IdentifyAction::from_keyword(e).ok_or(format!("unknown identify action: {}", e)))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    println!("{:?}", client.identify_printer(&actions, None)?);
+
+    Ok(())
+}
+
+fn decode(path: &Path, kind: Option<Kind>) -> Result<(), Box<dyn Error>> {
+    let kind = kind.map(|e| match e {
+        Kind::Request => MessageKind::Request,
+        Kind::Response => MessageKind::Response,
+    });
+
+    let buf = std::fs::read(path)?;
+    print!("{}", dump::decode(&buf, kind)?);
+
+    Ok(())
+}
+
+fn print_documents(
+    client: &mut PrintClient,
+    documents: Vec<PreparedDocument>,
+    template: &JobTemplate,
+    show_progress: bool,
+) -> Result<(), Box<dyn Error>> {
+    let _span = tracing::info_span!("print", documents = documents.len()).entered();
+    for document in &documents {
+        client.validate(&document.document_format)?;
+    }
+
+    let mut job = client.start_job(template)?;
+    let job_id = job.id();
+    tracing::info!(job_id, "created job");
+
+    // Drawn on stderr only if it is a terminal.
+    let total = documents.iter().map(|e| e.data.len() as u64).sum();
+    let progress = if show_progress {
+        ProgressBar::new(total)
+    } else {
+        ProgressBar::hidden()
     };
+    progress.set_style(ProgressStyle::with_template(
+        "{bar:40} {bytes}/{total_bytes} {bytes_per_sec}",
+    )?);
+    let count = documents.len();
+    let mut offset = 0;
+    for (i, document) in documents.into_iter().enumerate() {
+        let len = document.data.len() as u64;
+        let reader = ProgressReader::new(io::Cursor::new(document.data), {
+            let progress = progress.clone();
+            move |sent| progress.set_position(offset + sent)
+        });
+        let mut ipp_document = Document::new(&document.document_format, reader);
+        if let Some(name) = &document.name {
+            ipp_document = ipp_document.with_name(name);
+        }
+        let response = job.send_document(ipp_document, i + 1 == count);
+        if response.is_err() {
+            progress.finish_and_clear();
+        }
+        response?;
+        tracing::info!(
+            job_id,
+            document_format = document.document_format,
+            bytes = len,
+            "sent document"
+        );
+        offset += len;
+    }
+    progress.finish_and_clear();
 
-    buf = Vec::new();
-
-    // Send-Document
-    IPPRequest {
-        version_major: 1,
-        version_minor: 1,
-        operation_id: PrinterOperation::SendDocument,
-        request_id: 2,
-        attrs: vec![(
-            DelimiterOrValueTag::OperationAttributesTag,
-            vec![
-                (
-                    "attributes-charset".to_string(),
-                    AttributeValue::Charset("utf-8".to_string()),
-                ),
-                (
-                    "attributes-natural-language".to_string(),
-                    AttributeValue::NaturalLanguage("ja-jp".to_string()),
-                ),
-                (
-                    "printer-uri".to_string(),
-                    AttributeValue::Uri(format!("ipp://{}", printer_addr)),
-                ),
-                ("job-id".to_string(), AttributeValue::Integer(job_id)),
-                (
-                    "requesting-user-name".to_string(),
-                    AttributeValue::NameWithoutLanguage(std::env::var("USER")?),
-                ),
-                (
-                    "document-format".to_string(),
-                    AttributeValue::MimeMediaType("image/pwg-raster".to_string()),
-                ),
-                ("last-document".to_string(), AttributeValue::Boolean(true)),
-            ],
-        )],
-        data: raster_data,
+    let status = client.wait_for_completion(job_id, Duration::from_secs(2))?;
+    tracing::info!(job_id, state = ?status.state, reasons = ?status.reasons, "job finished");
+
+    Ok(())
+}
+
+/// Converts the sRGB bitmap to the negotiated raster type and writes it.
+fn write_srgb_page<W>(
+    writer: &mut W,
+    settings: &RasterSettings,
+    bitmap: &[SrgbColor],
+    width: usize,
+    dither: Option<Dither>,
+) -> Result<(), Box<dyn Error>>
+where
+    W: PageWriter,
+{
+    if settings.raster_type.starts_with("sgray") {
+        let bitmap = bitmap.iter().map(GrayColor::from).collect::<Vec<_>>();
+        writer.write_page(&bitmap)?;
+    } else if settings.raster_type == "srgb_16" {
+        let bitmap = bitmap.iter().map(Rgb16Color::from).collect::<Vec<_>>();
+        writer.write_page(&bitmap)?;
+    } else if settings.raster_type == "cmyk_8" {
+        let converter = CmykConverter::default();
+        let bitmap = bitmap
+            .iter()
+            .map(|e| converter.convert(e))
+            .collect::<Vec<_>>();
+        writer.write_page(&bitmap)?;
+    } else if settings.raster_type == "black_1" {
+        let marked = match dither {
+            Some(dither) => {
+                let gray = bitmap
+                    .iter()
+                    .map(|e| GrayColor::from(e).0)
+                    .collect::<Vec<_>>();
+                dither.apply(&gray, width)
+            }
+            None => {
+                let screen = AmScreen {
+                    dpi: settings.resolution[0],
+                    ..Default::default()
+                };
+                bitmap
+                    .chunks(width)
+                    .enumerate()
+                    .flat_map(|(y, row)| {
+                        let gray = row.iter().map(|e| GrayColor::from(e).0).collect::<Vec<_>>();
+                        screen.screen_row(y as u32, &gray)
+                    })
+                    .collect()
+            }
+        };
+        let bitmap = marked.into_iter().map(BlackColor).collect::<Vec<_>>();
+        writer.write_page(&bitmap)?;
+    } else {
+        writer.write_page(bitmap)?;
     }
-    .write_to_stream(&mut buf)?;
 
+    Ok(())
+}
+
+fn printer_attributes(client: &mut PrintClient) -> Result<PrinterAttributes, Box<dyn Error>> {
+    let attrs = client.printer_attributes()?;
+    tracing::info!(
+        printer_state = ?attrs.printer_state,
+        document_format_supported = ?attrs.document_format_supported,
+        "got printer attributes"
+    );
+    Ok(attrs)
+}
+
+/// Negotiates raster settings with the printer and a page header for them.
+fn negotiate(
+    attrs: &PrinterAttributes,
+    options: &JobOptions,
+) -> Result<(RasterSettings, PageHeader), Box<dyn Error>> {
+    let color_mode = options.color_mode.unwrap_or(PrintColorMode::Auto);
+    let mut settings = RasterSettings::negotiate_color(attrs, color_mode)?;
+    if let Some(resolution) = options.resolution {
+        settings.resolution = [resolution, resolution];
+    }
+    tracing::info!(?settings, "negotiated raster settings");
+
+    let mut hdr = PageHeader::default();
+    settings.apply(&mut hdr)?;
+    options.apply(&mut hdr)?;
+    Ok((settings, hdr))
+}
+
+/// Converts RGB pages to the ICC profile of options, setting the rendering
+/// intent of the header. Returns None if there is nothing to convert.
+#[cfg(feature = "icc")]
+fn convert_colors(
+    settings: &RasterSettings,
+    hdr: &mut PageHeader,
+    pages: &[Vec<SrgbColor>],
+    options: &JobOptions,
+) -> Result<Option<Vec<Vec<SrgbColor>>>, Box<dyn Error>> {
+    let Some(path) = &options.icc_profile else {
+        return Ok(None);
+    };
+    if !settings.raster_type.starts_with("srgb") {
+        return Ok(None);
+    }
+    let transform = ColorTransform::from_icc(&std::fs::read(path)?, options.rendering_intent)?;
+    transform.apply(hdr)?;
+    Ok(Some(pages.iter().map(|e| transform.convert(e)).collect()))
+}
+
+/// Encodes sRGB pages in the negotiated document format.
+fn encode_pages(
+    attrs: &PrinterAttributes,
+    settings: &RasterSettings,
+    hdr: PageHeader,
+    pages: &[Vec<SrgbColor>],
+    options: &JobOptions,
+) -> Result<Vec<u8>, Box<dyn Error>> {
+    #[cfg(feature = "icc")]
+    let mut hdr = hdr;
+    #[cfg(feature = "icc")]
+    let converted = convert_colors(settings, &mut hdr, pages, options)?;
+    #[cfg(feature = "icc")]
+    let pages = converted.as_deref().unwrap_or(pages);
+
+    let width = hdr.width() as usize;
+    Ok(if settings.document_format == "image/urf" {
+        let mut writer =
+            UrfDocumentWriter::new(Vec::new(), hdr, settings.sheet_back, pages.len() as u32)?;
+        for page in pages {
+            write_srgb_page(&mut writer, settings, page, width, options.dither)?;
+        }
+        writer.into_inner()
+    } else if settings.document_format == "application/PCLm" {
+        let mut writer = PclmDocumentWriter::new(Vec::new(), hdr, settings.sheet_back)?;
+        if let Some(strip_height) = attrs.pclm_strip_height_preferred {
+            writer.set_strip_height(strip_height as u32);
+        }
+        for page in pages {
+            write_srgb_page(&mut writer, settings, page, width, options.dither)?;
+        }
+        writer.finish()?
+    } else {
+        let mut writer = RasterDocumentWriter::new(Vec::new(), hdr, settings.sheet_back)?;
+        for page in pages {
+            write_srgb_page(&mut writer, settings, page, width, options.dither)?;
+        }
+        writer.into_inner()
+    })
+}
+
+/// Fonts tried when PRINTER_FONT is not set. CJK fonts first since they also
+/// have Latin glyphs.
+#[cfg(feature = "text")]
+const FONT_PATHS: &[&str] = &[
+    "/usr/share/fonts/opentype/noto/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/noto-cjk/NotoSansCJK-Regular.ttc",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSansMono.ttf",
+    "/usr/share/fonts/TTF/DejaVuSansMono.ttf",
+    "/System/Library/Fonts/Menlo.ttc",
+    "C:\\Windows\\Fonts\\consola.ttf",
+];
+
+#[cfg(feature = "text")]
+fn render_text(text: &str, hdr: &PageHeader) -> Result<Vec<Vec<SrgbColor>>, Box<dyn Error>> {
+    use ipp_print::text::TextRenderer;
+
+    let font = match std::env::var("PRINTER_FONT") {
+        Ok(font) => std::fs::read(font)?,
+        Err(_) => FONT_PATHS
+            .iter()
+            .find_map(|e| std::fs::read(e).ok())
+            .ok_or("no font found; set PRINTER_FONT to the path of a TrueType font")?,
+    };
+    let renderer = TextRenderer::new(&font)?;
+    Ok(renderer
+        .render(text, hdr)?
+        .into_iter()
+        .map(|page| {
+            page.pixels()
+                .iter()
+                .map(|e| SrgbColor::new(e.0, e.0, e.0))
+                .collect()
+        })
+        .collect())
+}
+
+#[cfg(feature = "pdf")]
+fn render_pdf(
+    pdf: &[u8],
+    hdr: &PageHeader,
+    page_ranges: &[RangeInclusive<i32>],
+) -> Result<Vec<Vec<SrgbColor>>, Box<dyn Error>> {
+    use ipp_print::pdf::PdfRenderer;
+
+    let library = std::env::var_os("PDFIUM_LIBRARY").map(std::path::PathBuf::from);
+    let renderer = PdfRenderer::new(library.as_deref())?;
+    let page_ranges = page_ranges
+        .iter()
+        .map(|e| *e.start() as u32..=*e.end() as u32)
+        .collect::<Vec<_>>();
+    Ok(renderer
+        .render(pdf, hdr, &page_ranges)?
+        .into_iter()
+        .map(|e| e.into_pixels())
+        .collect())
+}
+
+#[cfg(feature = "svg")]
+fn render_svg(svg: &[u8], hdr: &PageHeader) -> Result<Vec<Vec<SrgbColor>>, Box<dyn Error>> {
+    use ipp_print::svg::SvgRenderer;
+
+    Ok(vec![SvgRenderer::new().render(svg, hdr)?.into_pixels()])
+}
+
+/// Whether data looks like SVG: an svg element near the start, or gzip for SVGZ.
+fn is_svg(data: &[u8]) -> bool {
+    data.starts_with(&[0x1f, 0x8b])
+        || data[..data.len().min(1024)]
+            .windows(4)
+            .any(|e| e == b"<svg")
+}
+
+/// Renders the pages in page_ranges (all if empty) of a PDF file, an SVG file,
+/// or a text file. The format is detected from data if None.
+#[cfg_attr(
+    not(any(feature = "text", feature = "pdf", feature = "svg")),
+    allow(unused_variables)
+)]
+fn render_file(
+    data: &[u8],
+    format: Option<InputFormat>,
+    hdr: &PageHeader,
+    page_ranges: &[RangeInclusive<i32>],
+) -> Result<Vec<Vec<SrgbColor>>, Box<dyn Error>> {
+    match format.unwrap_or_else(|| InputFormat::detect(data)) {
+        // Pages out of the ranges aren't rendered at all.
+        #[cfg(feature = "pdf")]
+        InputFormat::Pdf => render_pdf(data, hdr, page_ranges),
+        #[cfg(feature = "svg")]
+        InputFormat::Svg => Ok(select_pages(render_svg(data, hdr)?, page_ranges)),
+        #[cfg(feature = "text")]
+        InputFormat::Text => Ok(select_pages(
+            render_text(std::str::from_utf8(data)?, hdr)?,
+            page_ranges,
+        )),
+        format @ (InputFormat::Jpeg | InputFormat::Png) => {
+            Err(IppPrintError::NotAvailable(format!(
+                "{} is not accepted by the printer and can't be rendered locally",
+                format.document_format().unwrap()
+            ))
+            .into())
+        }
+        #[allow(unreachable_patterns)]
+        format => Err(format!(
+            "{} files can't be rendered (see the text, pdf, and svg features)",
+            format.to_possible_value().unwrap().get_name()
+        )
+        .into()),
+    }
+}
+
+#[cfg(any(feature = "text", feature = "svg"))]
+fn select_pages<T>(pages: Vec<T>, page_ranges: &[RangeInclusive<i32>]) -> Vec<T> {
+    pages
+        .into_iter()
+        .zip(1..)
+        .filter(|(_, number)| {
+            page_ranges.is_empty() || page_ranges.iter().any(|e| e.contains(number))
+        })
+        .map(|(page, _)| page)
+        .collect()
+}
+
+/// document-format to send a file as it is with, if the printer supports its
+/// format and it isn't declared as another format.
+fn passthrough(
+    data: &[u8],
+    attrs: &PrinterAttributes,
+    options: &JobOptions,
+) -> Option<&'static str> {
+    passthrough_format(data, attrs).filter(|e| {
+        options
+            .format
+            .is_none_or(|format| format.document_format() == Some(e))
+    })
+}
+
+/// A document ready to send, rendered or as read from a file.
+struct PreparedDocument {
+    /// File name shown on the printer
+    name: Option<String>,
+    document_format: String,
+    data: Vec<u8>,
+    /// Whether the file was rendered rather than sent as is
+    rendered: bool,
+}
+
+/// Document format and data to send for a file. The file is sent as it is if
+/// the printer supports its format, and rendered otherwise.
+fn prepare_document(
+    data: Vec<u8>,
+    attrs: &PrinterAttributes,
+    options: &JobOptions,
+) -> Result<(String, Vec<u8>), Box<dyn Error>> {
+    if let Some(document_format) = passthrough(&data, attrs, options) {
+        return Ok((document_format.to_string(), data));
+    }
+
+    let (settings, hdr) = negotiate(attrs, options)?;
+    let pages = render_file(&data, options.format, &hdr, &options.page_ranges)?;
+
+    let data = encode_pages(attrs, &settings, hdr, &pages, options)?;
+    Ok((settings.document_format, data))
+}
+
+fn is_stdin(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Reads the file, or stdin if the path is "-".
+fn read_input(path: &Path) -> io::Result<Vec<u8>> {
+    if is_stdin(path) {
+        let mut data = vec![];
+        io::stdin().read_to_end(&mut data)?;
+        Ok(data)
+    } else {
+        std::fs::read(path)
+    }
+}
+
+fn print_files(
+    client: &mut PrintClient,
+    paths: &[PathBuf],
+    options: &JobOptions,
+    show_progress: bool,
+) -> Result<(), Box<dyn Error>> {
+    let attrs = printer_attributes(client)?;
+    let template = options.template(&attrs)?;
+
+    let mut documents = vec![];
+    for path in paths {
+        let data = read_input(path)?;
+        let rendered = passthrough(&data, &attrs, options).is_none();
+        let (document_format, data) = prepare_document(data, &attrs, options)?;
+        documents.push(PreparedDocument {
+            name: path
+                .file_name()
+                .filter(|_| !is_stdin(path))
+                .map(|e| e.to_string_lossy().into_owned()),
+            document_format,
+            data,
+            rendered,
+        });
+    }
+
+    if documents.len() > 1 && attrs.multiple_document_jobs_supported != Some(true) {
+        tracing::warn!("the printer doesn't support multiple documents in a job; printing them as separate jobs");
+        for document in documents {
+            print_file_documents(
+                client,
+                vec![document],
+                &template,
+                &attrs,
+                options,
+                show_progress,
+            )?;
+        }
+        return Ok(());
+    }
+    print_file_documents(client, documents, &template, &attrs, options, show_progress)
+}
+
+/// Prints documents of files in a job named after the first of them.
+fn print_file_documents(
+    client: &mut PrintClient,
+    documents: Vec<PreparedDocument>,
+    template: &JobTemplate,
+    attrs: &PrinterAttributes,
+    options: &JobOptions,
+    show_progress: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut template = template.clone();
+    if template.job_name.is_none() {
+        template.job_name = documents.first().and_then(|e| e.name.clone());
+    }
+
+    // Rendered documents only have the selected pages, while the printer
+    // selects them from the others. Images are already compressed, but
+    // rasters get much smaller.
+    let rendered = documents.iter().filter(|e| e.rendered).count();
+    if rendered == 0 {
+        template.page_ranges = options.page_ranges.clone();
+    } else if rendered < documents.len() && !options.page_ranges.is_empty() {
+        return Err("--pages can't be used for files sent as is and files rendered in a job; print them separately".into());
+    }
+    client.set_compression(if rendered > 0 {
+        Compression::choose(&attrs.compression_supported)
+    } else {
+        None
+    });
+
+    print_documents(client, documents, &template, show_progress)
+}
+
+/// Makes the printer fetch and print the document with Print-URI, or with
+/// Create-Job and Send-URI.
+fn print_uri(
+    client: &mut PrintClient,
+    uri: &str,
+    options: &JobOptions,
+) -> Result<(), Box<dyn Error>> {
+    let attrs = printer_attributes(client)?;
+    attrs.check_reference_uri(uri)?;
+    let mut template = options.template(&attrs)?;
+    if template.job_name.is_none() {
+        template.job_name = uri
+            .rsplit('/')
+            .next()
+            .filter(|e| !e.is_empty())
+            .map(str::to_string);
+    }
+    let document_format = options.format.map(InputFormat::mime_type);
+
+    let operations = &attrs.operations_supported;
+    let job_id = if operations.contains(&PrinterOperation::PrintURI) {
+        client.print_uri(uri, document_format, &template)?
+    } else if operations.contains(&PrinterOperation::SendURI) {
+        let mut job = client.start_job(&template)?;
+        job.send_uri(uri, document_format, true)?;
+        job.id()
+    } else {
+        return Err(IppPrintError::Unsupported("operation Print-URI".to_string()).into());
+    };
+    tracing::info!(job_id, uri, "created job");
+
+    let status = client.wait_for_completion(job_id, Duration::from_secs(2))?;
+    tracing::info!(job_id, state = ?status.state, reasons = ?status.reasons, "job finished");
+    Ok(())
+}
+
+/// Prints the built-in test page in the size and raster settings of the job.
+fn print_test_page(
+    client: &mut PrintClient,
+    options: &JobOptions,
+    show_progress: bool,
+) -> Result<(), Box<dyn Error>> {
+    let attrs = printer_attributes(client)?;
+    let mut template = options.template(&attrs)?;
+    template
+        .job_name
+        .get_or_insert_with(|| "Test page".to_string());
+
+    client.set_compression(Compression::choose(&attrs.compression_supported));
+    let (settings, hdr) = negotiate(&attrs, options)?;
+    let page = testpage::render(&hdr).into_pixels();
+    let data = encode_pages(&attrs, &settings, hdr, &[page], options)?;
+    let document = PreparedDocument {
+        name: None,
+        document_format: settings.document_format,
+        data,
+        rendered: true,
+    };
+    print_documents(client, vec![document], &template, show_progress)
+}
+
+/// Renders input with the settings for a printer accepting anything, and
+/// writes what would be sent with Send-Document to output.
+fn convert(input: &Path, output: &Path, options: &JobOptions) -> Result<(), Box<dyn Error>> {
+    let data = read_input(input)?;
+
+    let (document_format, data) = prepare_document(data, &PrinterAttributes::default(), options)?;
+    std::fs::write(output, &data)?;
     println!(
-        "{:?}",
-        IPPResponse::read_from_stream(
-            &mut client
-                .post(format!("http://{}", printer_addr))
-                .header("Content-Type", "application/ipp")
-                .body(buf)
-                .send()?,
-        )?
+        "{}: {} bytes of {}",
+        output.display(),
+        data.len(),
+        document_format
     );
+    Ok(())
+}
 
-    buf = Vec::new();
-
-    // Get-Jobs
-    IPPRequest {
-        version_major: 1,
-        version_minor: 1,
-        operation_id: PrinterOperation::GetJobs,
-        request_id: 2,
-        attrs: vec![(
-            DelimiterOrValueTag::OperationAttributesTag,
-            vec![
-                (
-                    "attributes-charset".to_string(),
-                    AttributeValue::Charset("utf-8".to_string()),
-                ),
-                (
-                    "attributes-natural-language".to_string(),
-                    AttributeValue::NaturalLanguage("ja-jp".to_string()),
-                ),
-                (
-                    "printer-uri".to_string(),
-                    AttributeValue::Uri(format!("ipp://{}", printer_addr)),
-                ),
-                (
-                    "requesting-user-name".to_string(),
-                    AttributeValue::NameWithoutLanguage(std::env::var("USER")?),
-                ),
-            ],
-        )],
-        data: vec![],
+/// Pages of a raster or an image with the header to encode them.
+type Decoded = (PageHeader, Vec<Image<SrgbColor>>);
+
+/// Decodes a raster document or an image, or returns None for other files.
+/// Images are taken to be in options.resolution, or 300 dpi.
+fn decode_image(data: &[u8], options: &JobOptions) -> Result<Option<Decoded>, Box<dyn Error>> {
+    let sync_word = data
+        .get(..4)
+        .and_then(|e| e.try_into().ok())
+        .and_then(SyncWord::from_bytes);
+    if sync_word.is_some() {
+        let doc = PwgDocument::read_from_stream(&mut &data[..])?;
+        let header = doc
+            .pages
+            .first()
+            .ok_or("the raster has no pages")?
+            .header
+            .clone();
+        // Encoders use a header for every page.
+        if doc
+            .pages
+            .iter()
+            .any(|e| (e.header.width(), e.header.height()) != (header.width(), header.height()))
+        {
+            return Err("pages of different sizes can't be converted".into());
+        }
+        let pages = doc
+            .pages
+            .iter()
+            .map(PwgPage::to_srgb)
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(Some((header, pages)));
+    }
+
+    let image = if data.starts_with(b"P6") {
+        ppm::read_ppm(data)?
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        #[cfg(feature = "image")]
+        {
+            let image = image::load_from_memory_with_format(data, image::ImageFormat::Png)?;
+            Image::from_rgb_image(&image.to_rgb8())
+        }
+        #[cfg(not(feature = "image"))]
+        return Err("PNG files are read with the image feature".into());
+    } else {
+        return Ok(None);
+    };
+    let dpi = options.resolution.unwrap_or(300);
+    let header = PageHeaderBuilder::new(image.width() as u32, image.height() as u32)
+        .resolution(dpi, dpi)
+        .build()?;
+    Ok(Some((header, vec![image])))
+}
+
+/// Path of the index-th page, numbered like out-2.png if there are several pages.
+fn page_path(output: &Path, index: usize, count: usize) -> PathBuf {
+    if count == 1 {
+        return output.to_path_buf();
+    }
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match output.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, index + 1, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, index + 1),
+    };
+    output.with_file_name(name)
+}
+
+/// Writes the pages in the format of the extension of output: PNG, PPM, URF,
+/// or PWG Raster otherwise. Rasters are written in srgb_8.
+fn write_pages(
+    output: &Path,
+    mut header: PageHeader,
+    pages: &[Image<SrgbColor>],
+) -> Result<(), Box<dyn Error>> {
+    let extension = output
+        .extension()
+        .map(|e| e.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("png") | Some("ppm") => {
+            for (i, page) in pages.iter().enumerate() {
+                let path = page_path(output, i, pages.len());
+                if extension.as_deref() == Some("ppm") {
+                    let mut writer = io::BufWriter::new(std::fs::File::create(&path)?);
+                    ppm::write_ppm(&mut writer, page)?;
+                    writer.flush()?;
+                } else {
+                    #[cfg(feature = "image")]
+                    page.to_png(&path)?;
+                    #[cfg(not(feature = "image"))]
+                    return Err("PNG files are written with the image feature".into());
+                }
+                println!("{}", path.display());
+            }
+        }
+        _ => {
+            header.set_raster_type("srgb_8")?;
+            let (document_format, data) = if extension.as_deref() == Some("urf") {
+                let mut writer = UrfDocumentWriter::new(
+                    Vec::new(),
+                    header,
+                    SheetBack::Normal,
+                    pages.len() as u32,
+                )?;
+                for page in pages {
+                    writer.write_page(page.pixels())?;
+                }
+                ("image/urf", writer.into_inner())
+            } else {
+                let mut writer = RasterDocumentWriter::new(Vec::new(), header, SheetBack::Normal)?;
+                for page in pages {
+                    writer.write_page(page.pixels())?;
+                }
+                ("image/pwg-raster", writer.into_inner())
+            };
+            std::fs::write(output, &data)?;
+            println!(
+                "{}: {} bytes of {}",
+                output.display(),
+                data.len(),
+                document_format
+            );
+        }
     }
-    .write_to_stream(&mut buf)?;
+    Ok(())
+}
 
+/// Converts between rasters and images, or renders a document with the
+/// settings for a printer accepting anything, and writes it in the format of
+/// the extension of output.
+fn convert_file(input: &Path, output: &Path, options: &JobOptions) -> Result<(), Box<dyn Error>> {
+    let data = read_input(input)?;
+
+    let (header, pages) = match decode_image(&data, options)? {
+        Some(decoded) => decoded,
+        None => {
+            let (_, hdr) = negotiate(&PrinterAttributes::default(), options)?;
+            let (width, height) = (hdr.width() as usize, hdr.height() as usize);
+            let pages = render_file(&data, options.format, &hdr, &options.page_ranges)?
+                .into_iter()
+                .map(|e| Image::new(width, height, e))
+                .collect::<Result<Vec<_>, _>>()?;
+            (hdr, pages)
+        }
+    };
+    write_pages(output, header, &pages)
+}
+
+/// Encodes the pages as PWG Raster in the raster type for the printer, so that
+/// dithering for bi-level printers shows up, and saves them as PNG.
+#[cfg(feature = "image")]
+fn preview(
+    input: &Path,
+    output: &Path,
+    attrs: &PrinterAttributes,
+    options: &JobOptions,
+) -> Result<(), Box<dyn Error>> {
+    let data = read_input(input)?;
+
+    let (settings, hdr) = negotiate(attrs, options)?;
+    let pages = render_file(&data, options.format, &hdr, &options.page_ranges)?;
+    let settings = RasterSettings {
+        document_format: "image/pwg-raster".to_string(),
+        sheet_back: SheetBack::Normal,
+        ..settings
+    };
+    let raster = encode_pages(attrs, &settings, hdr, &pages, options)?;
+
+    let doc = PwgDocument::read_from_stream(&mut raster.as_slice())?;
+    for (i, page) in doc.pages.iter().enumerate() {
+        let path = page_path(output, i, doc.pages.len());
+        page.to_png(&path)?;
+        println!("{}", path.display());
+    }
+    Ok(())
+}
+
+/// Prints the header fields of each page and how well it is compressed.
+fn raster_info(path: &Path) -> Result<(), Box<dyn Error>> {
+    let data = std::fs::read(path)?;
+    let mut reader = RasterReader::new(data.as_slice())?;
+    let position = |reader: &RasterReader<&[u8]>| data.len() - reader.get_ref().len();
     println!(
-        "{:?}",
-        IPPResponse::read_from_stream(
-            &mut client
-                .post(format!("http://{}", printer_addr))
-                .header("Content-Type", "application/ipp")
-                .body(buf)
-                .send()?,
-        )?
+        "SyncWord={}",
+        String::from_utf8_lossy(reader.sync_word().bytes())
     );
 
+    let (mut pages, mut total_compressed, mut total_uncompressed) = (0, 0, 0);
+    while let Some(header) = reader.next_page()? {
+        let header = header.clone();
+        let start = position(&reader);
+        while reader.next_page_row()?.is_some() {}
+        let compressed = position(&reader) - start;
+        let uncompressed = header.bytes_per_line() as usize * header.height() as usize;
+
+        pages += 1;
+        total_compressed += compressed;
+        total_uncompressed += uncompressed;
+        let [x_dpi, y_dpi] = header.resolution();
+        let color_space = match header.color_space() {
+            Some(color_space) => format!("{:?}", color_space),
+            None => "unknown color space".to_string(),
+        };
+        println!(
+            "page {}: {}x{} {} {}bpp {}x{}dpi {}, {} bytes ({:.2}%)",
+            pages,
+            header.width(),
+            header.height(),
+            color_space,
+            header.bits_per_pixel(),
+            x_dpi,
+            y_dpi,
+            header.page_size_name(),
+            compressed,
+            compressed as f64 * 100.0 / uncompressed.max(1) as f64
+        );
+    }
+    println!(
+        "pages: {}, {} bytes of {} uncompressed ({:.2}%)",
+        pages,
+        total_compressed,
+        total_uncompressed,
+        total_compressed as f64 * 100.0 / total_uncompressed.max(1) as f64
+    );
+    Ok(())
+}
+
+#[cfg(feature = "discovery")]
+fn discover(timeout: Duration) -> Result<(), Box<dyn Error>> {
+    for printer in discovery::discover(timeout)? {
+        println!(
+            "{}\t{}\t{}",
+            printer.name,
+            printer.uri,
+            printer.make_and_model.as_deref().unwrap_or("")
+        );
+    }
     Ok(())
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    let mut bitmap = vec![SrgbColor::new(255, 255, 255); 2480 * 3507];
+/// Exit codes for scripts; 1 is for other errors and 2 for usage errors.
+const EXIT_CLIENT_ERROR: u8 = 3;
+const EXIT_SERVER_ERROR: u8 = 4;
+const EXIT_UNSUPPORTED_FORMAT: u8 = 5;
+const EXIT_AUTHENTICATION_REQUIRED: u8 = 6;
+const EXIT_PRINTER_UNREACHABLE: u8 = 7;
 
-    for y in 100..125 {
-        for x in 100..300 {
-            bitmap[y * 2480 + x] = SrgbColor::new(255, 0, 0);
+fn exit_code(err: &(dyn Error + 'static)) -> u8 {
+    let Some(err) = err.downcast_ref::<IppPrintError>() else {
+        return 1;
+    };
+    match err {
+        IppPrintError::StatusError(StatusCode::ClientErrorDocumentFormatNotSupported, _)
+        | IppPrintError::Unsupported(_) => EXIT_UNSUPPORTED_FORMAT,
+        IppPrintError::StatusError(StatusCode::ClientErrorNotAuthenticated, _) => {
+            EXIT_AUTHENTICATION_REQUIRED
+        }
+        IppPrintError::StatusError(status, _) if status.is_client_error() => EXIT_CLIENT_ERROR,
+        IppPrintError::StatusError(status, _) if status.is_server_error() => EXIT_SERVER_ERROR,
+        IppPrintError::HTTPError(err)
+            if err.status() == Some(reqwest::StatusCode::UNAUTHORIZED) =>
+        {
+            EXIT_AUTHENTICATION_REQUIRED
+        }
+        IppPrintError::HTTPError(err) if err.is_connect() || err.is_timeout() => {
+            EXIT_PRINTER_UNREACHABLE
+        }
+        IppPrintError::IOError(err)
+            if matches!(
+                err.kind(),
+                io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::TimedOut
+                    | io::ErrorKind::HostUnreachable
+                    | io::ErrorKind::NetworkUnreachable
+            ) =>
+        {
+            EXIT_PRINTER_UNREACHABLE
         }
+        _ => 1,
     }
+}
 
-    for y in 150..175 {
-        for x in 100..300 {
-            bitmap[y * 2480 + x] = SrgbColor::new(0, 255, 0);
+/// Logs to stderr; warnings only by default, so printing is silent on success.
+fn init_logging(verbose: u8, quiet: bool) {
+    let level = match (quiet, verbose) {
+        (true, _) => LevelFilter::ERROR,
+        (false, 0) => LevelFilter::WARN,
+        (false, 1) => LevelFilter::INFO,
+        (false, 2) => LevelFilter::DEBUG,
+        (false, _) => LevelFilter::TRACE,
+    };
+    // Only warnings of dependencies like hyper, which are noisy at lower levels.
+    let targets = Targets::new()
+        .with_target("ipp_print", level)
+        .with_default(LevelFilter::WARN.min(level));
+    tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_writer(io::stderr)
+        .finish()
+        .with(targets)
+        .init();
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    init_logging(cli.verbose, cli.quiet);
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("ipp-print: {}", err);
+            ExitCode::from(exit_code(err.as_ref()))
         }
     }
+}
 
-    for y in 200..225 {
-        for x in 100..300 {
-            bitmap[y * 2480 + x] = SrgbColor::new(0, 0, 255);
+fn run(cli: Cli) -> Result<(), Box<dyn Error>> {
+    let profile = || printer_profile(cli.printer.as_ref(), cli.printer_name.as_deref());
+    let client = |profile: &PrinterProfile| new_client(profile, cli.user.as_deref(), cli.refresh);
+
+    match &cli.command {
+        Command::Print {
+            files,
+            job,
+            dry_run: true,
+            output,
+        } => {
+            let [file] = files.as_slice() else {
+                return Err("--dry-run takes a single file".into());
+            };
+            // Defaults for the job are still taken from the printer profile.
+            let options = find_printer_profile(cli.printer.as_ref(), cli.printer_name.as_deref())?
+                .map(|e| JobOptions::from_profile(&e))
+                .unwrap_or_default()
+                .with_args(job);
+            convert(
+                file,
+                output.as_deref().ok_or("--dry-run needs --output")?,
+                &options,
+            )
+        }
+        Command::Print { files, job, .. } => {
+            let profile = profile()?;
+            print_files(
+                &mut client(&profile),
+                files,
+                &JobOptions::from_profile(&profile).with_args(job),
+                !cli.quiet,
+            )
+        }
+        Command::PrintUri { uri, job } => {
+            let profile = profile()?;
+            print_uri(
+                &mut client(&profile),
+                uri,
+                &JobOptions::from_profile(&profile).with_args(job),
+            )
+        }
+        Command::TestPage { job } => {
+            let profile = profile()?;
+            print_test_page(
+                &mut client(&profile),
+                &JobOptions::from_profile(&profile).with_args(job),
+                !cli.quiet,
+            )
+        }
+        Command::Status => status(&mut client(&profile()?)),
+        Command::Jobs { completed } => jobs(&mut client(&profile()?), *completed),
+        Command::Supplies { json } => supplies(&mut client(&profile()?), *json),
+        Command::Watch { interval } => {
+            watch(&mut client(&profile()?), Duration::from_secs(*interval))
         }
+        Command::Capabilities => {
+            let attrs = client(&profile()?).printer_attributes()?;
+            print!("{}", CapabilityReport(&attrs));
+            Ok(())
+        }
+        Command::Attrs => {
+            let resp = client(&profile()?).get_attributes()?;
+            print!("{}", IppMessage::from(&resp));
+            Ok(())
+        }
+        Command::Cancel { job_id } => {
+            println!("{:?}", client(&profile()?).cancel_job(*job_id)?);
+            Ok(())
+        }
+        Command::Purge { yes } => {
+            let profile = profile()?;
+            purge(
+                &mut client(&profile),
+                &profile.uri,
+                cli.user.as_deref(),
+                *yes,
+            )
+        }
+        Command::Identify { actions } => identify(&mut client(&profile()?), actions),
+        Command::Convert { input, output } => convert_file(input, output, &JobOptions::default()),
+        #[cfg(feature = "discovery")]
+        Command::Discover { timeout } => discover(Duration::from_secs(*timeout)),
+        #[cfg(feature = "image")]
+        Command::Preview { file, job, out } => {
+            let profile = find_printer_profile(cli.printer.as_ref(), cli.printer_name.as_deref())?;
+            let (attrs, options) = match profile {
+                Some(profile) => (
+                    client(&profile).printer_attributes()?,
+                    JobOptions::from_profile(&profile),
+                ),
+                None => (PrinterAttributes::default(), JobOptions::default()),
+            };
+            preview(file, out, &attrs, &options.with_args(job))
+        }
+        Command::RasterInfo { file } => raster_info(file),
+        Command::Decode { file, kind } => decode(file, *kind),
+        Command::Completions { shell } => {
+            // generate panics on write errors like a closed pipe.
+            let mut script = Vec::new();
+            clap_complete::generate(*shell, &mut Cli::command(), "ipp-print", &mut script);
+            io::stdout().write_all(&script)?;
+            Ok(())
+        }
+        Command::Man { dir } => {
+            std::fs::create_dir_all(dir)?;
+            clap_mangen::generate_to(Cli::command(), dir)?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Job options of a print command line with args.
+    fn print_options(profile: &PrinterProfile, args: &[&str]) -> JobOptions {
+        let cli = Cli::try_parse_from(["ipp-print", "print", "a.txt"].iter().chain(args)).unwrap();
+        let Command::Print { job, .. } = cli.command else {
+            unreachable!()
+        };
+        JobOptions::from_profile(profile).with_args(&job)
     }
 
-    let hdr = PageHeader::default();
-    let mut encoder = ImageEncoder::new(2480, 3507);
+    #[test]
+    fn exit_codes() {
+        let code = |err: IppPrintError| exit_code(&err);
+        assert_eq!(
+            EXIT_UNSUPPORTED_FORMAT,
+            code(IppPrintError::StatusError(
+                StatusCode::ClientErrorDocumentFormatNotSupported,
+                vec![]
+            ))
+        );
+        assert_eq!(
+            EXIT_UNSUPPORTED_FORMAT,
+            code(IppPrintError::Unsupported(
+                "operation Print-URI".to_string()
+            ))
+        );
+        assert_eq!(
+            EXIT_AUTHENTICATION_REQUIRED,
+            code(IppPrintError::StatusError(
+                StatusCode::ClientErrorNotAuthenticated,
+                vec![]
+            ))
+        );
+        assert_eq!(
+            EXIT_CLIENT_ERROR,
+            code(IppPrintError::StatusError(
+                StatusCode::ClientErrorNotFound,
+                vec![]
+            ))
+        );
+        assert_eq!(
+            EXIT_SERVER_ERROR,
+            code(IppPrintError::StatusError(
+                StatusCode::ServerErrorBusy,
+                vec![]
+            ))
+        );
+        assert_eq!(
+            EXIT_PRINTER_UNREACHABLE,
+            code(IppPrintError::IOError(
+                io::ErrorKind::ConnectionRefused.into()
+            ))
+        );
+        let refused = reqwest::blocking::get("http://127.0.0.1:1/").unwrap_err();
+        assert_eq!(
+            EXIT_PRINTER_UNREACHABLE,
+            code(IppPrintError::HTTPError(refused))
+        );
+        assert_eq!(
+            1,
+            code(IppPrintError::IOError(io::ErrorKind::NotFound.into()))
+        );
+        assert_eq!(
+            1,
+            code(IppPrintError::NotAvailable(
+                "no IPP-USB printer is connected".to_string()
+            ))
+        );
 
-    let mut data = Vec::<u8>::new();
-    data.write(b"RaS2")?;
-    hdr.write_to_stream(&mut data)?;
-    let row = vec![SrgbColor::new(255, 255, 255); 2480];
-    for _ in 0..3507 {
-        encoder.write_row(&mut data, row.clone())?;
+        // Errors reach main boxed, along with ones of the command itself.
+        let err: Box<dyn Error> =
+            IppPrintError::StatusError(StatusCode::ServerErrorBusy, vec![]).into();
+        assert_eq!(EXIT_SERVER_ERROR, exit_code(err.as_ref()));
+        let err: Box<dyn Error> = "unknown media: a5".into();
+        assert_eq!(1, exit_code(err.as_ref()));
     }
 
-    let mut f = std::fs::File::open("/tmp/doc.pwg")?;
-    read_raster(&mut f)
+    #[test]
+    fn detect_input_format() {
+        assert_eq!(InputFormat::Pdf, InputFormat::detect(b"%PDF-1.7\n"));
+        assert_eq!(InputFormat::Jpeg, InputFormat::detect(b"\xff\xd8\xff\xe0"));
+        assert_eq!(InputFormat::Png, InputFormat::detect(b"\x89PNG\r\n\x1a\n"));
+        assert_eq!(
+            InputFormat::Svg,
+            InputFormat::detect(b"<?xml version=\"1.0\"?>\n<svg xmlns=\"\">")
+        );
+        assert_eq!(InputFormat::Svg, InputFormat::detect(b"\x1f\x8b\x08"));
+        assert_eq!(
+            InputFormat::Text,
+            InputFormat::detect("こんにちは".as_bytes())
+        );
+        assert_eq!(InputFormat::Text, InputFormat::detect(b""));
+    }
+
+    #[test]
+    fn merge_job_options() {
+        let mut profile = uri_profile(PrinterUri::parse("192.0.2.1").unwrap());
+        profile.media = Some("iso_a4_210x297mm".to_string());
+        profile.color_mode = Some(PrintColorMode::Monochrome);
+        profile.resolution = Some(600);
+
+        let options = print_options(&profile, &[]);
+        assert_eq!(Some("iso_a4_210x297mm"), options.media.as_deref());
+        assert_eq!(Some(PrintColorMode::Monochrome), options.color_mode);
+        assert_eq!(Some(600), options.resolution);
+        assert!(options.page_ranges.is_empty());
 
-    // print_page(data)
+        // The command line takes precedence over the profile.
+        let options = print_options(
+            &profile,
+            &[
+                "--media",
+                "na_letter_8.5x11in",
+                "--color",
+                "--duplex",
+                "short",
+                "--pages",
+                "1-3,7,9-",
+                "--dither",
+                "ordered",
+            ],
+        );
+        assert_eq!(Some("na_letter_8.5x11in"), options.media.as_deref());
+        assert_eq!(Some(PrintColorMode::Color), options.color_mode);
+        assert_eq!(Some(600), options.resolution);
+        assert_eq!(Some(Sides::TwoSidedShortEdge), options.sides);
+        assert_eq!(vec![1..=3, 7..=7, 9..=i32::MAX], options.page_ranges);
+        assert_eq!(Some(Dither::Ordered), options.dither);
+
+        for pages in ["3-1", "0", "1,,2", "a-"] {
+            assert!(
+                Cli::try_parse_from(["ipp-print", "print", "a.txt", "--pages", pages]).is_err()
+            );
+        }
+    }
+
+    #[test]
+    fn passthrough_supported_images() {
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+        let attrs = PrinterAttributes {
+            document_format_supported: vec![
+                "image/pwg-raster".to_string(),
+                "image/png".to_string(),
+            ],
+            ..Default::default()
+        };
+        let options = JobOptions::default();
+        assert_eq!(Some("image/png"), passthrough(png, &attrs, &options));
+        assert_eq!(None, passthrough(b"%PDF-1.7\n", &attrs, &options));
+
+        // A format given on the command line has to match.
+        let options = JobOptions {
+            format: Some(InputFormat::Jpeg),
+            ..Default::default()
+        };
+        assert_eq!(None, passthrough(png, &attrs, &options));
+
+        assert_eq!(
+            None,
+            passthrough(png, &PrinterAttributes::default(), &JobOptions::default())
+        );
+    }
 }
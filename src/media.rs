@@ -0,0 +1,265 @@
+use crate::ipp::AttributeValue;
+use std::collections::HashMap;
+
+/// Media sizes from PWG5101.1: (self-describing name, legacy name, width, height).
+/// Dimensions are in hundredths of millimeters.
+const MEDIA_SIZES: &[(&str, &str, u32, u32)] = &[
+    ("iso_a3_297x420mm", "iso-a3", 29700, 42000),
+    ("iso_a4_210x297mm", "iso-a4", 21000, 29700),
+    ("iso_a5_148x210mm", "iso-a5", 14800, 21000),
+    ("iso_a6_105x148mm", "iso-a6", 10500, 14800),
+    ("iso_b5_176x250mm", "iso-b5", 17600, 25000),
+    ("iso_c5_162x229mm", "iso-c5", 16200, 22900),
+    ("iso_dl_110x220mm", "iso-designated", 11000, 22000),
+    ("jis_b4_257x364mm", "jis-b4", 25700, 36400),
+    ("jis_b5_182x257mm", "jis-b5", 18200, 25700),
+    ("jpn_hagaki_100x148mm", "jpn-hagaki", 10000, 14800),
+    ("jpn_oufuku_148x200mm", "jpn-oufuku", 14800, 20000),
+    ("jpn_chou3_120x235mm", "jpn-chou3", 12000, 23500),
+    ("jpn_chou4_90x205mm", "jpn-chou4", 9000, 20500),
+    ("na_letter_8.5x11in", "na-letter", 21590, 27940),
+    ("na_legal_8.5x14in", "na-legal", 21590, 35560),
+    ("na_executive_7.25x10.5in", "executive", 18415, 26670),
+    ("na_ledger_11x17in", "ledger", 27940, 43180),
+    ("na_index-4x6_4x6in", "na-index-4x6", 10160, 15240),
+    ("na_5x7_5x7in", "na-5x7", 12700, 17780),
+    ("na_number-10_4.125x9.5in", "na-number-10", 10478, 24130),
+    ("oe_photo-l_3.5x5in", "oe-photo-l", 8890, 12700),
+];
+
+/// Media size identified by its self-describing name (PWG5101.1).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MediaSize {
+    /// Self-describing name like "iso_a4_210x297mm".
+    pub name: String,
+    /// Width in hundredths of millimeters
+    pub width: u32,
+    /// Height in hundredths of millimeters
+    pub height: u32,
+}
+
+impl MediaSize {
+    /// Looks up a self-describing or legacy media name.
+    /// Self-describing names not in the table are parsed, so "custom_100x100mm" works too.
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Some((name, _, width, height)) = MEDIA_SIZES
+            .iter()
+            .find(|(pwg_name, legacy_name, _, _)| *pwg_name == name || *legacy_name == name)
+        {
+            return Some(Self {
+                name: name.to_string(),
+                width: *width,
+                height: *height,
+            });
+        }
+
+        let (width, height) = MediaSize::parse_dimensions(name)?;
+        Some(Self {
+            name: name.to_string(),
+            width,
+            height,
+        })
+    }
+
+    /// Custom size in hundredths of millimeters, named like "custom_100x150.5mm".
+    pub fn custom(width: u32, height: u32) -> Self {
+        let mm = |hmm: u32| {
+            if hmm.is_multiple_of(100) {
+                (hmm / 100).to_string()
+            } else {
+                format!("{}", hmm as f64 / 100.0)
+            }
+        };
+        Self {
+            name: format!("custom_{}x{}mm", mm(width), mm(height)),
+            width,
+            height,
+        }
+    }
+
+    /// Parses dimensions part of a self-describing name like "8.5x11in".
+    fn parse_dimensions(name: &str) -> Option<(u32, u32)> {
+        let (_, size) = name.rsplit_once('_')?;
+        let (size, hmm_per_unit) = if let Some(size) = size.strip_suffix("mm") {
+            (size, 100.0)
+        } else if let Some(size) = size.strip_suffix("in") {
+            (size, 2540.0)
+        } else {
+            return None;
+        };
+        let (width, height) = size.split_once('x')?;
+        let width = width.parse::<f64>().ok()?;
+        let height = height.parse::<f64>().ok()?;
+        if width <= 0.0 || height <= 0.0 {
+            return None;
+        }
+
+        Some((
+            (width * hmm_per_unit).round() as u32,
+            (height * hmm_per_unit).round() as u32,
+        ))
+    }
+
+    /// [0]: Width in points
+    /// [1]: Height in points
+    pub fn points(&self) -> [u32; 2] {
+        let to_points = |hmm: u32| ((hmm as u64 * 72 + 1270) / 2540) as u32;
+        [to_points(self.width), to_points(self.height)]
+    }
+
+    /// [0]: Width in pixels at resolution[0] dpi
+    /// [1]: Height in pixels at resolution[1] dpi
+    pub fn pixels(&self, resolution: [u32; 2]) -> [u32; 2] {
+        let to_pixels = |hmm: u32, dpi: u32| (hmm as u64 * dpi as u64 / 2540) as u32;
+        [
+            to_pixels(self.width, resolution[0]),
+            to_pixels(self.height, resolution[1]),
+        ]
+    }
+
+    /// Uncompressed bytes per line at x_dpi with pixels of bits_per_pixel.
+    pub fn bytes_per_line(&self, x_dpi: u32, bits_per_pixel: u32) -> u32 {
+        let [width, _] = self.pixels([x_dpi, x_dpi]);
+        (width as u64 * bits_per_pixel as u64).div_ceil(8) as u32
+    }
+}
+
+/// media-col collection (PWG5100.7) to request media with more than its name.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MediaCol {
+    pub size: MediaSize,
+    /// media-type keyword like "stationery" or "photographic-glossy".
+    pub media_type: Option<String>,
+    /// media-source keyword like "main" or "manual".
+    pub media_source: Option<String>,
+    /// Margins in hundredths of millimeters: [bottom, left, right, top]
+    pub margins: Option<[i32; 4]>,
+}
+
+impl MediaCol {
+    pub fn new(size: MediaSize) -> Self {
+        Self {
+            size,
+            media_type: None,
+            media_source: None,
+            margins: None,
+        }
+    }
+
+    pub fn to_attribute(&self) -> AttributeValue {
+        let mut media_size = HashMap::new();
+        media_size.insert(
+            "x-dimension".to_string(),
+            AttributeValue::Integer(self.size.width as i32),
+        );
+        media_size.insert(
+            "y-dimension".to_string(),
+            AttributeValue::Integer(self.size.height as i32),
+        );
+
+        let mut col = HashMap::new();
+        col.insert(
+            "media-size".to_string(),
+            AttributeValue::CollectionAttribute(media_size),
+        );
+        col.insert(
+            "media-size-name".to_string(),
+            AttributeValue::Keyword(self.size.name.clone()),
+        );
+        if let Some(media_type) = &self.media_type {
+            col.insert(
+                "media-type".to_string(),
+                AttributeValue::Keyword(media_type.clone()),
+            );
+        }
+        if let Some(media_source) = &self.media_source {
+            col.insert(
+                "media-source".to_string(),
+                AttributeValue::Keyword(media_source.clone()),
+            );
+        }
+        if let Some([bottom, left, right, top]) = self.margins {
+            for (name, margin) in [
+                ("media-bottom-margin", bottom),
+                ("media-left-margin", left),
+                ("media-right-margin", right),
+                ("media-top-margin", top),
+            ] {
+                col.insert(name.to_string(), AttributeValue::Integer(margin));
+            }
+        }
+
+        AttributeValue::CollectionAttribute(col)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup_media_size() {
+        let a4 = MediaSize::from_name("iso_a4_210x297mm").unwrap();
+        assert_eq!([21000, 29700], [a4.width, a4.height]);
+        assert_eq!([595, 842], a4.points());
+        assert_eq!(Some(a4), MediaSize::from_name("iso-a4"));
+
+        let letter = MediaSize::from_name("na-letter").unwrap();
+        assert_eq!("na_letter_8.5x11in", letter.name);
+        assert_eq!([612, 792], letter.points());
+
+        let custom = MediaSize::from_name("custom_max_4.125x100mm").unwrap();
+        assert_eq!([413, 10000], [custom.width, custom.height]);
+
+        let custom = MediaSize::custom(10000, 15050);
+        assert_eq!("custom_100x150.5mm", custom.name);
+        assert_eq!(Some(custom), MediaSize::from_name("custom_100x150.5mm"));
+
+        assert_eq!(None, MediaSize::from_name("iso_a4"));
+        assert_eq!(None, MediaSize::from_name("custom_0x100mm"));
+    }
+
+    #[test]
+    fn media_geometry() {
+        let a4 = MediaSize::from_name("iso_a4_210x297mm").unwrap();
+        assert_eq!([2480, 3507], a4.pixels([300, 300]));
+        assert_eq!([4960, 3507], a4.pixels([600, 300]));
+        assert_eq!(7440, a4.bytes_per_line(300, 24));
+        assert_eq!(310, a4.bytes_per_line(300, 1));
+    }
+
+    #[test]
+    fn table_matches_names() {
+        for (name, _, width, height) in MEDIA_SIZES {
+            assert_eq!(
+                Some((*width, *height)),
+                MediaSize::parse_dimensions(name),
+                "{}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn media_col() {
+        let mut media_col = MediaCol::new(MediaSize::from_name("jpn_hagaki_100x148mm").unwrap());
+        media_col.media_source = Some("manual".to_string());
+        media_col.margins = Some([0, 0, 0, 0]);
+
+        let AttributeValue::CollectionAttribute(col) = media_col.to_attribute() else {
+            panic!("media-col must be a collection");
+        };
+        assert_eq!(7, col.len());
+        assert_eq!(
+            Some(&AttributeValue::Keyword("manual".to_string())),
+            col.get("media-source")
+        );
+        let Some(AttributeValue::CollectionAttribute(size)) = col.get("media-size") else {
+            panic!("media-size must be a collection");
+        };
+        assert_eq!(
+            Some(&AttributeValue::Integer(14800)),
+            size.get("y-dimension")
+        );
+    }
+}
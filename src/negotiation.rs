@@ -0,0 +1,439 @@
+use crate::error::IppPrintError;
+use crate::ipp::{PrintColorMode, PrinterAttributes};
+use crate::pwgraster::{PageHeader, SheetBack};
+use crate::urf::UrfSupported;
+
+/// Raster types ImageEncoder can produce, in order of preference.
+const ENCODABLE_RASTER_TYPES: &[&str] = &["srgb_8", "srgb_16", "cmyk_8", "sgray_8", "black_1"];
+
+/// Raster types for monochrome and bi-level output, in order of preference.
+const MONOCHROME_RASTER_TYPES: &[&str] = &["sgray_8", "black_1"];
+const BI_LEVEL_RASTER_TYPES: &[&str] = &["black_1", "sgray_8"];
+
+/// Formats sent to printers as they are if supported, with their magic numbers.
+const PASSTHROUGH_FORMATS: &[(&str, &[u8])] = &[
+    ("image/jpeg", b"\xff\xd8\xff"),
+    ("image/png", b"\x89PNG\r\n\x1a\n"),
+];
+
+/// Resolution used when the printer supports it, since it keeps page data small.
+const PREFERRED_DPI: u32 = 300;
+
+/// How a document should be generated for a printer.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct RasterSettings {
+    /// document-format for Print-Job and Send-Document.
+    pub document_format: String,
+    /// [0]: Horizontal dpi
+    /// [1]: Vertical dpi
+    pub resolution: [u32; 2],
+    /// pwg-raster-document-type keyword like "srgb_8".
+    pub raster_type: String,
+    /// How back sides of duplex sheets are transformed.
+    pub sheet_back: SheetBack,
+}
+
+impl RasterSettings {
+    /// Chooses settings from pwg-raster-document-*-supported and document-format-supported.
+    /// Printers which don't report an attribute are assumed to accept our defaults.
+    /// Printers without PWG Raster get URF settings from urf-supported, or
+    /// PCLm settings from pclm-*.
+    pub fn negotiate(attrs: &PrinterAttributes) -> Result<Self, IppPrintError> {
+        let formats = &attrs.document_format_supported;
+        if !formats.is_empty() && !formats.iter().any(|e| e == "image/pwg-raster") {
+            if formats.iter().any(|e| e == "image/urf") && !attrs.urf_supported.is_empty() {
+                return RasterSettings::negotiate_urf(attrs);
+            }
+            if formats.iter().any(|e| e == "application/PCLm") {
+                return RasterSettings::negotiate_pclm(attrs);
+            }
+            return Err(IppPrintError::Unsupported(
+                "document-format image/pwg-raster".to_string(),
+            ));
+        }
+
+        let resolutions = attrs
+            .pwg_raster_document_resolution_supported
+            .iter()
+            .map(|e| {
+                let (x, y) = e.to_dpi();
+                [x as u32, y as u32]
+            })
+            .collect::<Vec<_>>();
+
+        let sheet_back = attrs
+            .pwg_raster_document_sheet_back
+            .as_deref()
+            .and_then(SheetBack::from_keyword)
+            .unwrap_or(SheetBack::Normal);
+
+        Ok(Self {
+            document_format: "image/pwg-raster".to_string(),
+            resolution: choose_resolution(&resolutions),
+            raster_type: choose_raster_type(
+                &attrs.pwg_raster_document_type_supported,
+                ENCODABLE_RASTER_TYPES,
+            )?
+            .to_string(),
+            sheet_back,
+        })
+    }
+
+    fn negotiate_urf(attrs: &PrinterAttributes) -> Result<Self, IppPrintError> {
+        let supported = UrfSupported::parse(&attrs.urf_supported);
+        if supported.raster_types.is_empty() {
+            return Err(IppPrintError::Unsupported(format!(
+                "urf-supported {}",
+                attrs.urf_supported.join(", ")
+            )));
+        }
+
+        let resolutions = supported
+            .resolutions
+            .iter()
+            .map(|e| [*e, *e])
+            .collect::<Vec<_>>();
+
+        Ok(Self {
+            document_format: "image/urf".to_string(),
+            resolution: choose_resolution(&resolutions),
+            raster_type: choose_raster_type(&supported.raster_types, ENCODABLE_RASTER_TYPES)?
+                .to_string(),
+            sheet_back: supported.sheet_back.unwrap_or(SheetBack::Normal),
+        })
+    }
+
+    fn negotiate_pclm(attrs: &PrinterAttributes) -> Result<Self, IppPrintError> {
+        let methods = &attrs.pclm_compression_method_preferred;
+        if !methods.is_empty() && !methods.iter().any(|e| e == "flate") {
+            return Err(IppPrintError::Unsupported(format!(
+                "pclm-compression-method-preferred {}",
+                methods.join(", ")
+            )));
+        }
+
+        let resolutions = attrs
+            .pclm_source_resolution_supported
+            .iter()
+            .map(|e| {
+                let (x, y) = e.to_dpi();
+                [x as u32, y as u32]
+            })
+            .collect::<Vec<_>>();
+
+        let sheet_back = attrs
+            .pclm_raster_back_side
+            .as_deref()
+            .and_then(SheetBack::from_keyword)
+            .unwrap_or(SheetBack::Normal);
+
+        // PCLm is always 8-bit RGB or gray.
+        let raster_type = if attrs.color_supported == Some(true) {
+            "srgb_8"
+        } else {
+            "sgray_8"
+        };
+
+        Ok(Self {
+            document_format: "application/PCLm".to_string(),
+            resolution: choose_resolution(&resolutions),
+            raster_type: raster_type.to_string(),
+            sheet_back,
+        })
+    }
+
+    /// Same as negotiate, but with a raster type for color_mode. Monochrome is
+    /// sent in gray, and bi-level in black_1 if the printer supports it and in
+    /// gray otherwise. Color falls back to monochrome for printers reporting
+    /// color-supported=false.
+    pub fn negotiate_color(
+        attrs: &PrinterAttributes,
+        color_mode: PrintColorMode,
+    ) -> Result<Self, IppPrintError> {
+        let mut settings = Self::negotiate(attrs)?;
+        let candidates = match supported_color_mode(color_mode, attrs) {
+            PrintColorMode::Auto | PrintColorMode::Color => return Ok(settings),
+            PrintColorMode::Monochrome => MONOCHROME_RASTER_TYPES,
+            PrintColorMode::BiLevel => BI_LEVEL_RASTER_TYPES,
+        };
+
+        let supported = match settings.document_format.as_str() {
+            "image/urf" => UrfSupported::parse(&attrs.urf_supported).raster_types,
+            "application/PCLm" => vec!["sgray_8".to_string()],
+            _ => attrs.pwg_raster_document_type_supported.clone(),
+        };
+        settings.raster_type = choose_raster_type(&supported, candidates)?.to_string();
+        Ok(settings)
+    }
+
+    /// Configures resolution and color space of the header.
+    pub fn apply(&self, header: &mut PageHeader) -> Result<(), IppPrintError> {
+        header.set_resolution(self.resolution[0], self.resolution[1])?;
+        header.set_raster_type(&self.raster_type)
+    }
+}
+
+/// document-format of data if it is JPEG or PNG and the printer supports it,
+/// in which case data can be sent without rasterizing it.
+pub fn passthrough_format(data: &[u8], attrs: &PrinterAttributes) -> Option<&'static str> {
+    PASSTHROUGH_FORMATS
+        .iter()
+        .find(|(_, magic)| data.starts_with(magic))
+        .map(|(format, _)| *format)
+        .filter(|format| attrs.document_format_supported.iter().any(|e| e == format))
+}
+
+/// color_mode, or monochrome if it needs color and the printer reports that it
+/// can't print in color.
+pub fn supported_color_mode(
+    color_mode: PrintColorMode,
+    attrs: &PrinterAttributes,
+) -> PrintColorMode {
+    match color_mode {
+        PrintColorMode::Auto | PrintColorMode::Color if attrs.color_supported == Some(false) => {
+            PrintColorMode::Monochrome
+        }
+        mode => mode,
+    }
+}
+
+/// PREFERRED_DPI if supported, otherwise the lowest resolution.
+/// An empty list means any resolution is accepted.
+fn choose_resolution(resolutions: &[[u32; 2]]) -> [u32; 2] {
+    let preferred = [PREFERRED_DPI, PREFERRED_DPI];
+    if resolutions.is_empty() || resolutions.contains(&preferred) {
+        preferred
+    } else {
+        *resolutions.iter().min_by_key(|e| e[0] * e[1]).unwrap()
+    }
+}
+
+/// The first of candidates in supported.
+/// An empty list means any raster type is accepted.
+fn choose_raster_type(
+    supported: &[String],
+    candidates: &[&'static str],
+) -> Result<&'static str, IppPrintError> {
+    if supported.is_empty() {
+        return Ok(candidates[0]);
+    }
+    match candidates
+        .iter()
+        .find(|e| supported.iter().any(|supported| supported == *e))
+    {
+        Some(raster_type) => Ok(raster_type),
+        None => Err(IppPrintError::Unsupported(format!(
+            "pwg-raster-document-type {}",
+            supported.join(", ")
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipp::Resolution;
+    use crate::pwgraster::ColorSpace;
+
+    fn dpi(dpi: i32) -> Resolution {
+        Resolution {
+            resolution_cross_feed: dpi,
+            resolution_feed: dpi,
+            units: Resolution::DOTS_PER_INCH,
+        }
+    }
+
+    #[test]
+    fn negotiate_defaults() {
+        let settings = RasterSettings::negotiate(&PrinterAttributes::default()).unwrap();
+        assert_eq!("image/pwg-raster", settings.document_format);
+        assert_eq!([300, 300], settings.resolution);
+        assert_eq!("srgb_8", settings.raster_type);
+        assert_eq!(SheetBack::Normal, settings.sheet_back);
+    }
+
+    #[test]
+    fn negotiate_resolution() {
+        let attrs = PrinterAttributes {
+            pwg_raster_document_resolution_supported: vec![dpi(600), dpi(1200)],
+            pwg_raster_document_sheet_back: Some("flipped".to_string()),
+            ..Default::default()
+        };
+        let settings = RasterSettings::negotiate(&attrs).unwrap();
+        assert_eq!([600, 600], settings.resolution);
+        assert_eq!(SheetBack::Flipped, settings.sheet_back);
+
+        let mut header = PageHeader::default();
+        settings.apply(&mut header).unwrap();
+        assert_eq!(4960, header.width());
+    }
+
+    #[test]
+    fn negotiate_gray() {
+        let attrs = PrinterAttributes {
+            pwg_raster_document_type_supported: vec!["black_1".to_string(), "sgray_8".to_string()],
+            ..Default::default()
+        };
+        let settings = RasterSettings::negotiate(&attrs).unwrap();
+        assert_eq!("sgray_8", settings.raster_type);
+
+        let mut header = PageHeader::default();
+        settings.apply(&mut header).unwrap();
+        assert_eq!(8, header.bits_per_pixel());
+        assert_eq!(header.width(), header.bytes_per_line());
+    }
+
+    #[test]
+    fn negotiate_16bit() {
+        let attrs = PrinterAttributes {
+            pwg_raster_document_type_supported: vec!["sgray_16".to_string(), "srgb_16".to_string()],
+            ..Default::default()
+        };
+        let settings = RasterSettings::negotiate(&attrs).unwrap();
+        assert_eq!("srgb_16", settings.raster_type);
+
+        let mut header = PageHeader::default();
+        settings.apply(&mut header).unwrap();
+        assert_eq!(48, header.bits_per_pixel());
+        assert_eq!(header.width() * 6, header.bytes_per_line());
+    }
+
+    #[test]
+    fn negotiate_urf() {
+        let mut attrs = PrinterAttributes {
+            document_format_supported: vec!["image/jpeg".to_string(), "image/urf".to_string()],
+            urf_supported: ["V1.4", "W8", "SRGB24", "RS600-1200", "DM2"]
+                .map(|e| e.to_string())
+                .to_vec(),
+            ..Default::default()
+        };
+        let settings = RasterSettings::negotiate(&attrs).unwrap();
+        assert_eq!("image/urf", settings.document_format);
+        assert_eq!([600, 600], settings.resolution);
+        assert_eq!("srgb_8", settings.raster_type);
+        assert_eq!(SheetBack::Flipped, settings.sheet_back);
+
+        attrs.urf_supported.clear();
+        assert!(RasterSettings::negotiate(&attrs).is_err());
+    }
+
+    #[test]
+    fn negotiate_pclm() {
+        let mut attrs = PrinterAttributes {
+            document_format_supported: vec!["application/PCLm".to_string()],
+            pclm_source_resolution_supported: vec![dpi(600)],
+            pclm_compression_method_preferred: vec!["jpeg".to_string(), "flate".to_string()],
+            pclm_raster_back_side: Some("rotated".to_string()),
+            color_supported: Some(true),
+            ..Default::default()
+        };
+        let settings = RasterSettings::negotiate(&attrs).unwrap();
+        assert_eq!("application/PCLm", settings.document_format);
+        assert_eq!([600, 600], settings.resolution);
+        assert_eq!("srgb_8", settings.raster_type);
+        assert_eq!(SheetBack::Rotated, settings.sheet_back);
+
+        attrs.pclm_compression_method_preferred = vec!["jpeg".to_string()];
+        assert!(RasterSettings::negotiate(&attrs).is_err());
+    }
+
+    #[test]
+    fn negotiate_cmyk() {
+        let attrs = PrinterAttributes {
+            pwg_raster_document_type_supported: vec!["sgray_8".to_string(), "cmyk_8".to_string()],
+            ..Default::default()
+        };
+        let settings = RasterSettings::negotiate(&attrs).unwrap();
+        assert_eq!("cmyk_8", settings.raster_type);
+
+        let mut header = PageHeader::default();
+        settings.apply(&mut header).unwrap();
+        assert_eq!(Some(ColorSpace::Cmyk), header.color_space());
+        assert_eq!(header.width() * 4, header.bytes_per_line());
+    }
+
+    #[test]
+    fn negotiate_black() {
+        let attrs = PrinterAttributes {
+            pwg_raster_document_type_supported: vec!["black_1".to_string()],
+            ..Default::default()
+        };
+        let settings = RasterSettings::negotiate(&attrs).unwrap();
+        assert_eq!("black_1", settings.raster_type);
+
+        let mut header = PageHeader::default();
+        settings.apply(&mut header).unwrap();
+        assert_eq!(1, header.bits_per_pixel());
+        assert_eq!(header.width().div_ceil(8), header.bytes_per_line());
+    }
+
+    #[test]
+    fn negotiate_unsupported() {
+        let attrs = PrinterAttributes {
+            document_format_supported: vec!["application/pdf".to_string()],
+            ..Default::default()
+        };
+        assert!(RasterSettings::negotiate(&attrs).is_err());
+
+        let attrs = PrinterAttributes {
+            pwg_raster_document_type_supported: vec!["cmyk_16".to_string()],
+            ..Default::default()
+        };
+        assert!(RasterSettings::negotiate(&attrs).is_err());
+    }
+
+    #[test]
+    fn negotiate_color_mode() {
+        let mut attrs = PrinterAttributes {
+            pwg_raster_document_type_supported: ["srgb_8", "sgray_8", "black_1"]
+                .map(|e| e.to_string())
+                .to_vec(),
+            ..Default::default()
+        };
+        let raster_type = |attrs: &PrinterAttributes, mode| {
+            RasterSettings::negotiate_color(attrs, mode)
+                .unwrap()
+                .raster_type
+        };
+        assert_eq!("srgb_8", raster_type(&attrs, PrintColorMode::Color));
+        assert_eq!("sgray_8", raster_type(&attrs, PrintColorMode::Monochrome));
+        assert_eq!("black_1", raster_type(&attrs, PrintColorMode::BiLevel));
+
+        attrs.color_supported = Some(false);
+        assert_eq!("sgray_8", raster_type(&attrs, PrintColorMode::Color));
+        assert_eq!("sgray_8", raster_type(&attrs, PrintColorMode::Auto));
+
+        // Gray when bi-level isn't supported, and any type if not reported
+        attrs.pwg_raster_document_type_supported = vec!["sgray_8".to_string()];
+        assert_eq!("sgray_8", raster_type(&attrs, PrintColorMode::BiLevel));
+        let attrs = PrinterAttributes::default();
+        assert_eq!("srgb_8", raster_type(&attrs, PrintColorMode::Auto));
+        assert_eq!("black_1", raster_type(&attrs, PrintColorMode::BiLevel));
+
+        let attrs = PrinterAttributes {
+            pwg_raster_document_type_supported: vec!["srgb_8".to_string()],
+            ..Default::default()
+        };
+        assert!(RasterSettings::negotiate_color(&attrs, PrintColorMode::Monochrome).is_err());
+    }
+
+    #[test]
+    fn passthrough() {
+        let attrs = PrinterAttributes {
+            document_format_supported: vec![
+                "image/pwg-raster".to_string(),
+                "image/jpeg".to_string(),
+            ],
+            ..Default::default()
+        };
+        let jpeg = b"\xff\xd8\xff\xe0\x00\x10JFIF";
+        let png = b"\x89PNG\r\n\x1a\n\x00\x00\x00\x0dIHDR";
+        assert_eq!(Some("image/jpeg"), passthrough_format(jpeg, &attrs));
+        assert_eq!(None, passthrough_format(png, &attrs));
+        assert_eq!(None, passthrough_format(b"%PDF-1.7", &attrs));
+        assert_eq!(
+            None,
+            passthrough_format(jpeg, &PrinterAttributes::default())
+        );
+    }
+}
@@ -0,0 +1,349 @@
+//! PCLm, the raster format of Wi-Fi Direct Print and Mopria.
+//!
+//! A PCLm document is a restricted PDF in which each page is a column of image
+//! strips of the same height. Strips are compressed with FlateDecode; JPEG strips
+//! aren't generated.
+
+use crate::error::IppPrintError;
+use crate::pwgraster::{self, ColorSpace, PageHeader, PageWriter, Pixel, SheetBack};
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+use std::io::prelude::*;
+
+/// Rows per strip used when the printer has no preference.
+pub const DEFAULT_STRIP_HEIGHT: u32 = 16;
+
+/// Writes a PCLm document. finish() has to be called after the last page.
+pub struct PclmDocumentWriter<W> {
+    writer: W,
+    header: PageHeader,
+    sheet_back: SheetBack,
+    strip_height: u32,
+    /// Number of bytes written so far, for the cross-reference table.
+    offset: usize,
+    /// Byte offsets of objects, where objects[0] is object 1.
+    objects: Vec<usize>,
+    page_objects: Vec<usize>,
+}
+
+/// Object number of the catalog.
+const CATALOG: usize = 1;
+/// Object number of the page tree, which is written in finish().
+const PAGES: usize = 2;
+
+impl<W> PclmDocumentWriter<W>
+where
+    W: Write,
+{
+    /// Writes the file header. Every page uses geometry and color space of header,
+    /// which has to be srgb_8 or sgray_8.
+    pub fn new(
+        writer: W,
+        header: PageHeader,
+        sheet_back: SheetBack,
+    ) -> Result<Self, IppPrintError> {
+        color_space(&header)?;
+
+        let mut result = Self {
+            writer,
+            header,
+            sheet_back,
+            strip_height: DEFAULT_STRIP_HEIGHT,
+            offset: 0,
+            objects: vec![0; PAGES],
+            page_objects: vec![],
+        };
+        result.write(b"%PDF-1.7\n%PCLm 1.0\n")?;
+        result.write_object(
+            CATALOG,
+            format!("<</Type/Catalog/Pages {} 0 R>>", PAGES).as_bytes(),
+        )?;
+        Ok(result)
+    }
+
+    /// Sets rows per strip, e.g. from pclm-strip-height-preferred.
+    pub fn set_strip_height(&mut self, strip_height: u32) {
+        self.strip_height = strip_height.max(1);
+    }
+
+    /// Writes a page given as rows from the top of the front side.
+    /// The pixel type must match bits_per_pixel of the header.
+    pub fn write_page_rows<P, I, R>(&mut self, rows: I) -> Result<usize, IppPrintError>
+    where
+        P: Pixel,
+        I: IntoIterator<Item = R>,
+        R: AsRef<[P]>,
+    {
+        if P::BITS_PER_PIXEL != self.header.bits_per_pixel() {
+            return Err(IppPrintError::RasterError(format!(
+                "pixels have {} bits but the header says {}",
+                P::BITS_PER_PIXEL,
+                self.header.bits_per_pixel()
+            )));
+        }
+        let (width, height) = (self.header.width(), self.header.height());
+
+        let (cross_feed, feed) = pwgraster::back_side_transform(
+            self.header.sides(),
+            self.sheet_back,
+            self.page_objects.len() as u32,
+        );
+        let mut rows = rows.into_iter().collect::<Vec<_>>();
+        if rows.len() != height as usize {
+            return Err(IppPrintError::RasterError(format!(
+                "page has {} rows but the height is {}",
+                rows.len(),
+                height
+            )));
+        }
+        if feed < 0 {
+            rows.reverse();
+        }
+
+        let start = self.offset;
+        let page = self.objects.len() + 1;
+        let contents = page + 1;
+        let strip_count = height.div_ceil(self.strip_height) as usize;
+        let first_strip = contents + 1;
+
+        // Strips are placed from the top, and PDF coordinates start at the bottom.
+        let [x_dpi, y_dpi] = self.header.resolution();
+        let mut content = format!(
+            "q {:.4} 0 0 {:.4} 0 0 cm\n",
+            72.0 / x_dpi as f64,
+            72.0 / y_dpi as f64
+        );
+        let mut images = String::new();
+        for (i, strip) in rows.chunks(self.strip_height as usize).enumerate() {
+            let y = height as usize - i * self.strip_height as usize - strip.len();
+            content += &format!(
+                "q {} 0 0 {} 0 {} cm /Image{} Do Q\n",
+                width,
+                strip.len(),
+                y,
+                i
+            );
+            images += &format!("/Image{} {} 0 R", i, first_strip + i);
+        }
+        content += "Q\n";
+
+        let [page_width, page_height] = self.header.page_size();
+        self.write_object(
+            page,
+            format!(
+                "<</Type/Page/Parent {} 0 R/MediaBox[0 0 {} {}]\
+                 /Resources<</XObject<<{}>>>>/Contents {} 0 R>>",
+                PAGES, page_width, page_height, images, contents
+            )
+            .as_bytes(),
+        )?;
+        self.write_stream(contents, "", content.as_bytes())?;
+
+        let color_space = color_space(&self.header)?;
+        for (i, strip) in rows.chunks(self.strip_height as usize).enumerate() {
+            let mut bytes = vec![];
+            for row in strip {
+                let row = row.as_ref();
+                if row.len() != width as usize {
+                    return Err(IppPrintError::RasterError(format!(
+                        "row has {} pixels but the page width is {}",
+                        row.len(),
+                        width
+                    )));
+                }
+                if cross_feed < 0 {
+                    let row = row.iter().rev().cloned().collect::<Vec<_>>();
+                    P::write_row(&row, &mut bytes);
+                } else {
+                    P::write_row(row, &mut bytes);
+                }
+            }
+
+            let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+            encoder.write_all(&bytes)?;
+            let data = encoder.finish()?;
+            self.write_stream(
+                first_strip + i,
+                &format!(
+                    "/Type/XObject/Subtype/Image/Width {}/Height {}/ColorSpace/{}\
+                     /BitsPerComponent 8/Filter/FlateDecode",
+                    width,
+                    strip.len(),
+                    color_space
+                ),
+                &data,
+            )?;
+        }
+        debug_assert_eq!(first_strip + strip_count, self.objects.len() + 1);
+
+        self.page_objects.push(page);
+        Ok(self.offset - start)
+    }
+
+    /// Writes the page tree and the cross-reference table.
+    pub fn finish(mut self) -> Result<W, IppPrintError> {
+        let kids = self
+            .page_objects
+            .iter()
+            .map(|e| format!("{} 0 R", e))
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.write_object(
+            PAGES,
+            format!(
+                "<</Type/Pages/Kids[{}]/Count {}>>",
+                kids,
+                self.page_objects.len()
+            )
+            .as_bytes(),
+        )?;
+
+        let xref = self.offset;
+        let mut buf = format!("xref\n0 {}\n0000000000 65535 f \n", self.objects.len() + 1);
+        for offset in &self.objects {
+            buf += &format!("{:010} 00000 n \n", offset);
+        }
+        buf += &format!(
+            "trailer\n<</Size {}/Root {} 0 R>>\nstartxref\n{}\n%%EOF\n",
+            self.objects.len() + 1,
+            CATALOG,
+            xref
+        );
+        self.write(buf.as_bytes())?;
+        self.writer.flush()?;
+
+        Ok(self.writer)
+    }
+
+    fn write(&mut self, buf: &[u8]) -> Result<(), IppPrintError> {
+        self.writer.write_all(buf)?;
+        self.offset += buf.len();
+        Ok(())
+    }
+
+    /// Writes object number num, which must be the next one unless it was reserved.
+    fn write_object(&mut self, num: usize, body: &[u8]) -> Result<(), IppPrintError> {
+        if num > self.objects.len() {
+            self.objects.push(0);
+        }
+        self.objects[num - 1] = self.offset;
+        self.write(format!("{} 0 obj\n", num).as_bytes())?;
+        self.write(body)?;
+        self.write(b"\nendobj\n")
+    }
+
+    fn write_stream(&mut self, num: usize, dict: &str, data: &[u8]) -> Result<(), IppPrintError> {
+        let mut body = format!("<<{}/Length {}>>\nstream\n", dict, data.len()).into_bytes();
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\nendstream");
+        self.write_object(num, &body)
+    }
+}
+
+impl<W> PageWriter for PclmDocumentWriter<W>
+where
+    W: Write,
+{
+    fn write_page<P>(&mut self, pixels: &[P]) -> Result<usize, IppPrintError>
+    where
+        P: Pixel,
+    {
+        let (width, height) = (self.header.width() as usize, self.header.height() as usize);
+        if pixels.len() != width * height {
+            return Err(IppPrintError::RasterError(format!(
+                "page has {} pixels but the header says {}x{}",
+                pixels.len(),
+                width,
+                height
+            )));
+        }
+        self.write_page_rows(pixels.chunks(width.max(1)))
+    }
+}
+
+/// PDF color space of the cupsColorSpace in header.
+fn color_space(header: &PageHeader) -> Result<&'static str, IppPrintError> {
+    match (header.color_space(), header.bits_per_pixel()) {
+        (Some(ColorSpace::Sgray), 8) => Ok("DeviceGray"),
+        (Some(ColorSpace::Srgb), 24) => Ok("DeviceRGB"),
+        (color_space, bits_per_pixel) => Err(IppPrintError::Unsupported(format!(
+            "color space {:?} with {} bits per pixel in PCLm",
+            color_space, bits_per_pixel
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pwgraster::{GrayColor, SrgbColor};
+    use flate2::read::ZlibDecoder;
+
+    /// Finds the first occurrence of needle at or after from.
+    fn find(haystack: &[u8], needle: &[u8], from: usize) -> usize {
+        from + haystack[from..]
+            .windows(needle.len())
+            .position(|e| e == needle)
+            .unwrap()
+    }
+
+    #[test]
+    fn write_document() {
+        // 8x11 pixels
+        let mut header = PageHeader::default();
+        header.set_resolution(1, 1).unwrap();
+        header.set_raster_type("sgray_8").unwrap();
+        header.set_sides(crate::ipp::Sides::TwoSidedLongEdge);
+        let page = (0..88).map(GrayColor).collect::<Vec<_>>();
+
+        let mut writer = PclmDocumentWriter::new(Vec::new(), header, SheetBack::Rotated).unwrap();
+        writer.set_strip_height(4);
+        writer.write_page(&page).unwrap();
+        writer.write_page(&page).unwrap();
+        let out = writer.finish().unwrap();
+
+        assert!(out.starts_with(b"%PDF-1.7\n%PCLm 1.0\n"));
+        assert!(out.ends_with(b"%%EOF\n"));
+
+        // Every entry of the cross-reference table points to its object.
+        let xref = find(&out, b"xref\n", 0);
+        let table = String::from_utf8(out[xref..].to_vec()).unwrap();
+        let offsets = table
+            .lines()
+            .skip(3)
+            .take_while(|e| e.ends_with(" n "))
+            .map(|e| e[..10].parse::<usize>().unwrap())
+            .collect::<Vec<_>>();
+        // Catalog, pages, and page, contents, and 3 strips for each page
+        assert_eq!(2 + 2 * 5, offsets.len());
+        for (i, offset) in offsets.iter().enumerate() {
+            let obj = format!("{} 0 obj\n", i + 1);
+            assert!(out[*offset..].starts_with(obj.as_bytes()), "{}", obj);
+        }
+        assert!(table.contains("/Root 1 0 R"));
+        assert!(table.contains(&format!("startxref\n{}\n", xref)));
+
+        let pages = String::from_utf8_lossy(&out[offsets[1]..]);
+        assert!(pages.starts_with("2 0 obj\n<</Type/Pages/Kids[3 0 R 8 0 R]/Count 2>>"));
+
+        // The last strip of the back side is the top 3 rows rotated.
+        let strip = offsets[11];
+        let data = find(&out, b"stream\n", strip) + 7;
+        let mut decoded = vec![];
+        ZlibDecoder::new(&out[data..])
+            .read_to_end(&mut decoded)
+            .unwrap();
+        assert_eq!((0..24).rev().collect::<Vec<u8>>(), decoded);
+        assert!(String::from_utf8_lossy(&out[strip..data])
+            .contains("/Width 8/Height 3/ColorSpace/DeviceGray"));
+
+        let mut header = PageHeader::default();
+        header.set_raster_type("black_1").unwrap();
+        assert!(PclmDocumentWriter::new(Vec::new(), header, SheetBack::Normal).is_err());
+        let writer = PclmDocumentWriter::new(Vec::new(), PageHeader::default(), SheetBack::Normal);
+        assert!(writer
+            .unwrap()
+            .write_page(&[SrgbColor::new(0, 0, 0)])
+            .is_err());
+    }
+}
@@ -0,0 +1,106 @@
+//! PDF rendering with pdfium.
+//!
+//! The pdfium library is loaded at runtime, either from the given path or from
+//! the system libraries (e.g. libpdfium.so from
+//! <https://github.com/bblanchon/pdfium-binaries>).
+
+use crate::error::IppPrintError;
+use crate::layout::Image;
+use crate::pwgraster::{PageHeader, Pixel, SrgbColor};
+use pdfium_render::prelude::*;
+use std::io;
+use std::ops::RangeInclusive;
+use std::path::Path;
+
+/// Rasterizes PDF pages for a page header.
+pub struct PdfRenderer {
+    pdfium: Pdfium,
+}
+
+impl PdfRenderer {
+    /// Loads pdfium from library_path, or from the system libraries if None.
+    pub fn new(library_path: Option<&Path>) -> Result<Self, IppPrintError> {
+        let bindings = match library_path {
+            Some(path) => Pdfium::bind_to_library(path),
+            None => Pdfium::bind_to_system_library(),
+        }
+        .map_err(|e| io::Error::new(io::ErrorKind::NotFound, format!("pdfium: {}", e)))?;
+        Ok(Self {
+            pdfium: Pdfium::new(bindings),
+        })
+    }
+
+    /// Number of pages in a PDF document.
+    pub fn page_count(&self, pdf: &[u8]) -> Result<u32, IppPrintError> {
+        Ok(self.load(pdf)?.pages().len() as u32)
+    }
+
+    /// Renders the pages in page_ranges, counted from 1, or all pages if it is
+    /// empty. Pages are scaled to fit the page of header at its resolution and
+    /// centered, and landscape pages are rotated onto portrait pages.
+    pub fn render(
+        &self,
+        pdf: &[u8],
+        header: &PageHeader,
+        page_ranges: &[RangeInclusive<u32>],
+    ) -> Result<Vec<Image<SrgbColor>>, IppPrintError> {
+        let document = self.load(pdf)?;
+        let (width, height) = (header.width() as usize, header.height() as usize);
+        let config = PdfRenderConfig::new()
+            .set_target_width(width as Pixels)
+            .set_maximum_height(height as Pixels)
+            .rotate_if_landscape(PdfPageRenderRotation::Degrees90, true)
+            .render_form_data(true);
+
+        let mut pages = vec![];
+        for (i, page) in document.pages().iter().enumerate() {
+            let number = i as u32 + 1;
+            if !page_ranges.is_empty() && !page_ranges.iter().any(|e| e.contains(&number)) {
+                continue;
+            }
+
+            let bitmap = page.render_with_config(&config).map_err(pdfium_error)?;
+            let (bitmap_width, bitmap_height) = (bitmap.width() as usize, bitmap.height() as usize);
+            let pixels = bitmap
+                .as_rgba_bytes()
+                .chunks_exact(4)
+                .map(|e| SrgbColor::new(e[0], e[1], e[2]))
+                .collect();
+            let image = Image::new(bitmap_width, bitmap_height, pixels)?;
+
+            let mut sheet = Image::filled(width, height, SrgbColor::WHITE);
+            sheet.draw(
+                &image,
+                width.saturating_sub(bitmap_width) / 2,
+                height.saturating_sub(bitmap_height) / 2,
+            );
+            pages.push(sheet);
+        }
+        Ok(pages)
+    }
+
+    fn load<'a>(&'a self, pdf: &'a [u8]) -> Result<PdfDocument<'a>, IppPrintError> {
+        self.pdfium
+            .load_pdf_from_byte_slice(pdf, None)
+            .map_err(pdfium_error)
+    }
+}
+
+/// Errors from pdfium are about malformed documents once the library is loaded.
+fn pdfium_error(err: PdfiumError) -> IppPrintError {
+    io::Error::new(io::ErrorKind::InvalidData, format!("pdfium: {}", err)).into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn missing_library() {
+        let result = PdfRenderer::new(Some(Path::new("/nonexistent/libpdfium.so")));
+        assert!(matches!(
+            result,
+            Err(IppPrintError::IOError(e)) if e.kind() == io::ErrorKind::NotFound
+        ));
+    }
+}
@@ -0,0 +1,115 @@
+//! Binary PPM (Netpbm P6) images, to convert pages without the `image` feature.
+
+use crate::error::IppPrintError;
+use crate::layout::Image;
+use crate::pwgraster::SrgbColor;
+use std::io::prelude::*;
+
+/// Writes the image as P6 with 8-bit colors.
+pub fn write_ppm<W>(writer: &mut W, image: &Image<SrgbColor>) -> Result<(), IppPrintError>
+where
+    W: Write,
+{
+    write!(writer, "P6\n{} {}\n255\n", image.width(), image.height())?;
+    let buf = image
+        .pixels()
+        .iter()
+        .flat_map(|e| [e.r, e.g, e.b])
+        .collect::<Vec<_>>();
+    writer.write_all(&buf)?;
+    Ok(())
+}
+
+/// Reads a P6 image, scaling colors to 8 bits.
+pub fn read_ppm(data: &[u8]) -> Result<Image<SrgbColor>, IppPrintError> {
+    let invalid = |msg: &str| IppPrintError::RasterError(format!("PPM: {}", msg));
+    if !data.starts_with(b"P6") {
+        return Err(invalid("not a binary PPM image"));
+    }
+
+    // Width, height, and maxval, separated by whitespace and comments to the end of line.
+    let mut pos = 2;
+    let mut fields = [0usize; 3];
+    for field in &mut fields {
+        loop {
+            match data.get(pos) {
+                Some(b'#') => {
+                    while data.get(pos).is_some_and(|e| *e != b'\n') {
+                        pos += 1;
+                    }
+                }
+                Some(e) if e.is_ascii_whitespace() => pos += 1,
+                _ => break,
+            }
+        }
+        let start = pos;
+        while data.get(pos).is_some_and(|e| e.is_ascii_digit()) {
+            pos += 1;
+        }
+        *field = std::str::from_utf8(&data[start..pos])
+            .ok()
+            .and_then(|e| e.parse().ok())
+            .ok_or_else(|| invalid("malformed header"))?;
+    }
+    // A single whitespace character separates the header from the pixels.
+    pos += 1;
+
+    let [width, height, maxval] = fields;
+    let bytes_per_color = match maxval {
+        1..=255 => 1,
+        256..=65535 => 2,
+        _ => return Err(invalid("maxval out of range")),
+    };
+    let len = width
+        .checked_mul(height)
+        .and_then(|e| e.checked_mul(3 * bytes_per_color))
+        .ok_or_else(|| invalid("image too large"))?;
+    let pixels = data
+        .get(pos..)
+        .and_then(|e| e.get(..len))
+        .ok_or_else(|| invalid("truncated pixels"))?;
+
+    // 16-bit colors are big-endian.
+    let color = |value: &[u8]| {
+        let value = value.iter().fold(0, |acc, e| acc << 8 | *e as usize);
+        (value.min(maxval) * 255 / maxval) as u8
+    };
+    let pixels = pixels
+        .chunks(3 * bytes_per_color)
+        .map(|e| {
+            let mut components = e.chunks(bytes_per_color).map(color);
+            SrgbColor::new(
+                components.next().unwrap_or(0),
+                components.next().unwrap_or(0),
+                components.next().unwrap_or(0),
+            )
+        })
+        .collect();
+    Image::new(width, height, pixels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_and_read() {
+        let image = Image::new(
+            2,
+            1,
+            vec![SrgbColor::new(255, 0, 0), SrgbColor::new(1, 2, 3)],
+        )
+        .unwrap();
+        let mut buf = vec![];
+        write_ppm(&mut buf, &image).unwrap();
+        assert_eq!(b"P6\n2 1\n255\n\xff\x00\x00\x01\x02\x03", buf.as_slice());
+        assert_eq!(image, read_ppm(&buf).unwrap());
+
+        // Comments in the header, and a maxval other than 255
+        let image = read_ppm(b"P6 # made by hand\n1 1 # size\n15\n\x0f\x00\x05").unwrap();
+        assert_eq!(vec![SrgbColor::new(255, 0, 85)], image.pixels());
+
+        assert!(read_ppm(b"P6\n2 2\n255\n\x00").is_err());
+        assert!(read_ppm(b"P3\n1 1\n255\n0 0 0").is_err());
+    }
+}
@@ -1,8 +1,14 @@
-use std::error::Error;
-use std::fs::File;
+use crate::error::IppPrintError;
+use crate::ipp::{PrintQuality, Sides};
+use crate::layout::Image;
+use crate::media::MediaSize;
+use num::{FromPrimitive, ToPrimitive};
 use std::io::prelude::*;
+use std::io::BufWriter;
+use std::marker::PhantomData;
 
-#[derive(Debug)]
+/// Page header of PWG Raster (PWG5102.4 4.3).
+#[derive(Debug, Clone)]
 pub struct PageHeader {
     /// NUL-terminated string saying "PwgRaster".
     pwg_raster: [u8; 64],
@@ -138,9 +144,9 @@ pub struct PageHeader {
     /// For bi-level or monochrome page, use this color to print output the page.
     alternate_primary: u32,
     /// 0: Default
-    /// 1: Draft
-    /// 2: Normal
-    /// 3: High
+    /// 3: Draft
+    /// 4: Normal
+    /// 5: High
     print_quality: u32,
     reserved_9: [u8; 20],
     vendor_identifier: u32,
@@ -156,7 +162,7 @@ pub struct PageHeader {
 impl Default for PageHeader {
     fn default() -> Self {
         Self {
-            pwg_raster: [0; 64],
+            pwg_raster: *b"PwgRaster\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0\0",
             media_color: [0; 64],
             media_type: [0; 64],
             print_content_optimize: [0; 64],
@@ -210,693 +216,2062 @@ impl Default for PageHeader {
 }
 
 impl PageHeader {
-    pub fn write_to_stream<W>(&self, writer: &mut W) -> Result<usize, Box<dyn Error>>
+    /// Page width in pixels
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    /// Page height in pixels
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// Changes resolution, scaling pixel dimensions and the image box to keep the page size.
+    /// Fails if either resolution is 0, e.g. in a header read from a broken file.
+    pub fn set_resolution(&mut self, x_dpi: u32, y_dpi: u32) -> Result<(), IppPrintError> {
+        let [from_x, from_y] = self.hw_resolution;
+        if [from_x, from_y, x_dpi, y_dpi].contains(&0) {
+            return Err(IppPrintError::RasterError(format!(
+                "can't change resolution from {}x{} to {}x{}",
+                from_x, from_y, x_dpi, y_dpi
+            )));
+        }
+        let scale = |val: u32, from: u32, to: u32| (val as u64 * to as u64 / from as u64) as u32;
+
+        self.width = scale(self.width, from_x, x_dpi);
+        self.height = scale(self.height, from_y, y_dpi);
+        self.image_box_left = scale(self.image_box_left, from_x, x_dpi);
+        self.image_box_right = scale(self.image_box_right, from_x, x_dpi);
+        self.image_box_top = scale(self.image_box_top, from_y, y_dpi);
+        self.image_box_bottom = scale(self.image_box_bottom, from_y, y_dpi);
+        self.bytes_per_line = (self.width * self.bits_per_pixel).div_ceil(8);
+        self.hw_resolution = [x_dpi, y_dpi];
+        Ok(())
+    }
+
+    /// Sets page size and its name, computing pixel dimensions from hw_resolution.
+    pub fn set_media_size(&mut self, size: &MediaSize) {
+        self.page_size = size.points();
+        self.page_size_name = [0; 64];
+        let name = &size.name.as_bytes()[..size.name.len().min(63)];
+        self.page_size_name[..name.len()].copy_from_slice(name);
+
+        [self.width, self.height] = size.pixels(self.hw_resolution);
+        self.bytes_per_line = size.bytes_per_line(self.hw_resolution[0], self.bits_per_pixel);
+    }
+
+    /// Sets whether pages are printed on both sides, and which edge they are bound on.
+    pub fn set_sides(&mut self, sides: Sides) {
+        (self.duplex, self.tumble) = match sides {
+            Sides::OneSided => (0, 0),
+            Sides::TwoSidedLongEdge => (1, 0),
+            Sides::TwoSidedShortEdge => (1, 1),
+        };
+    }
+
+    pub fn set_cut_media(&mut self, cut_media: CutMedia) {
+        self.cut_media = cut_media as u32;
+    }
+
+    pub fn set_jog(&mut self, jog: Jog) {
+        self.jog = jog as u32;
+    }
+
+    pub fn set_leading_edge(&mut self, leading_edge: LeadingEdge) {
+        self.leading_edge = leading_edge as u32;
+    }
+
+    /// Fails for trays and rolls out of the numbers PWG Raster can express.
+    pub fn set_media_position(
+        &mut self,
+        media_position: MediaPosition,
+    ) -> Result<(), IppPrintError> {
+        self.media_position = media_position.to_u32().ok_or_else(|| {
+            IppPrintError::RasterError(format!("invalid media position: {:?}", media_position))
+        })?;
+        Ok(())
+    }
+
+    pub fn set_orientation(&mut self, orientation: Orientation) {
+        self.orientation = orientation as u32;
+    }
+
+    /// 0 leaves the number of copies to the job.
+    pub fn set_num_copies(&mut self, num_copies: u32) {
+        self.num_copies = num_copies;
+    }
+
+    /// None leaves the quality to the printer.
+    pub fn set_print_quality(&mut self, print_quality: Option<PrintQuality>) {
+        self.print_quality = print_quality.map_or(0, |e| e as u32);
+    }
+
+    /// Sets color space and bit depth from a pwg-raster-document-type-supported
+    /// keyword like "srgb_8" or "sgray_8".
+    pub fn set_raster_type(&mut self, raster_type: &str) -> Result<(), IppPrintError> {
+        let (color_space, num_colors, bits_per_color) = raster_type_params(raster_type)?;
+
+        self.color_space = color_space;
+        self.num_colors = num_colors;
+        self.bits_per_color = bits_per_color;
+        self.bits_per_pixel = num_colors * bits_per_color;
+        self.bytes_per_line = (self.width * self.bits_per_pixel).div_ceil(8);
+        Ok(())
+    }
+
+    /// Bits per pixel
+    pub fn bits_per_pixel(&self) -> u32 {
+        self.bits_per_pixel
+    }
+
+    /// Bits per color component, 8 or 16 except for black_1
+    pub fn bits_per_color(&self) -> u32 {
+        self.bits_per_color
+    }
+
+    /// Whether pages are printed on both sides, and which edge they are bound on.
+    pub fn sides(&self) -> Sides {
+        match (self.duplex != 0, self.tumble != 0) {
+            (false, _) => Sides::OneSided,
+            (true, false) => Sides::TwoSidedLongEdge,
+            (true, true) => Sides::TwoSidedShortEdge,
+        }
+    }
+
+    pub fn num_copies(&self) -> u32 {
+        self.num_copies
+    }
+
+    /// None for the printer default or an unknown value.
+    pub fn print_quality(&self) -> Option<PrintQuality> {
+        FromPrimitive::from_u32(self.print_quality)
+    }
+
+    /// None if the value isn't defined in PWG Raster. Same for the following getters.
+    pub fn cut_media(&self) -> Option<CutMedia> {
+        FromPrimitive::from_u32(self.cut_media)
+    }
+
+    pub fn jog(&self) -> Option<Jog> {
+        FromPrimitive::from_u32(self.jog)
+    }
+
+    pub fn leading_edge(&self) -> Option<LeadingEdge> {
+        FromPrimitive::from_u32(self.leading_edge)
+    }
+
+    pub fn media_position(&self) -> Option<MediaPosition> {
+        FromPrimitive::from_u32(self.media_position)
+    }
+
+    pub fn orientation(&self) -> Option<Orientation> {
+        FromPrimitive::from_u32(self.orientation)
+    }
+
+    /// Uncompressed bytes per line
+    pub fn bytes_per_line(&self) -> u32 {
+        self.bytes_per_line
+    }
+
+    /// cupsColorSpace, or None for one only used in CUPS raster
+    pub fn color_space(&self) -> Option<ColorSpace> {
+        FromPrimitive::from_u32(self.color_space)
+    }
+
+    /// [0]: Horizontal dpi
+    /// [1]: Vertical dpi
+    pub fn resolution(&self) -> [u32; 2] {
+        self.hw_resolution
+    }
+
+    /// Page width and height in points
+    pub fn page_size(&self) -> [u32; 2] {
+        self.page_size
+    }
+
+    /// MediaColor string, empty for the default
+    pub fn media_color(&self) -> String {
+        c_string(&self.media_color)
+    }
+
+    /// MediaType string, empty for the default
+    pub fn media_type(&self) -> String {
+        c_string(&self.media_type)
+    }
+
+    /// PrintContentOptimize string, e.g. "photo"
+    pub fn print_content_optimize(&self) -> String {
+        c_string(&self.print_content_optimize)
+    }
+
+    /// RenderingIntent string, e.g. "perceptual"
+    pub fn rendering_intent(&self) -> String {
+        c_string(&self.rendering_intent)
+    }
+
+    /// PageSizeName, a PWG5101.1 media name
+    pub fn page_size_name(&self) -> String {
+        c_string(&self.page_size_name)
+    }
+
+    /// Printable area of the front side as [left, top, right, bottom] in pixels,
+    /// where right and bottom are exclusive. The whole page if not set.
+    pub fn image_box(&self) -> [u32; 4] {
+        if self.image_box_right == 0 || self.image_box_bottom == 0 {
+            [0, 0, self.width, self.height]
+        } else {
+            [
+                self.image_box_left,
+                self.image_box_top,
+                self.image_box_right,
+                self.image_box_bottom,
+            ]
+        }
+    }
+
+    /// Sets MediaType, e.g. "stationery".
+    pub fn set_media_type(&mut self, media_type: &str) -> Result<(), IppPrintError> {
+        set_c_string(&mut self.media_type, "MediaType", media_type)
+    }
+
+    /// Sets MediaColor, e.g. "white".
+    pub fn set_media_color(&mut self, media_color: &str) -> Result<(), IppPrintError> {
+        set_c_string(&mut self.media_color, "MediaColor", media_color)
+    }
+
+    /// Sets PrintContentOptimize, e.g. "photo".
+    pub fn set_print_content_optimize(
+        &mut self,
+        print_content_optimize: &str,
+    ) -> Result<(), IppPrintError> {
+        set_c_string(
+            &mut self.print_content_optimize,
+            "PrintContentOptimize",
+            print_content_optimize,
+        )
+    }
+
+    /// Sets RenderingIntent, e.g. "perceptual".
+    pub fn set_rendering_intent(&mut self, rendering_intent: &str) -> Result<(), IppPrintError> {
+        set_c_string(
+            &mut self.rendering_intent,
+            "RenderingIntent",
+            rendering_intent,
+        )
+    }
+
+    /// Sets PageSizeName without changing dimensions. Use set_media_size to change both.
+    pub fn set_page_size_name(&mut self, page_size_name: &str) -> Result<(), IppPrintError> {
+        set_c_string(&mut self.page_size_name, "PageSizeName", page_size_name)
+    }
+
+    /// VendorIdentifier (USB vendor ID) and VendorData up to VendorLength.
+    pub fn vendor_data(&self) -> (u32, &[u8]) {
+        let len = (self.vendor_length as usize).min(self.vendor_data.len());
+        (self.vendor_identifier, &self.vendor_data[..len])
+    }
+
+    /// Sets VendorIdentifier and VendorData, which can be up to 1088 bytes.
+    pub fn set_vendor_data(&mut self, vendor_id: u32, data: &[u8]) -> Result<(), IppPrintError> {
+        if data.len() > self.vendor_data.len() {
+            return Err(IppPrintError::RasterError(format!(
+                "vendor data is {} bytes, longer than {}",
+                data.len(),
+                self.vendor_data.len()
+            )));
+        }
+        self.vendor_identifier = vendor_id;
+        self.vendor_length = data.len() as u32;
+        self.vendor_data = [0; 1088];
+        self.vendor_data[..data.len()].copy_from_slice(data);
+        Ok(())
+    }
+
+    /// Reads 1796-byte header written by write_to_stream. Synchronization word is not included.
+    pub fn read_from_stream<R>(reader: &mut R) -> Result<Self, IppPrintError>
     where
-        W: Write,
+        R: Read,
     {
-        let mut written = 0;
-
-        written += writer.write(&self.pwg_raster)?;
-        written += writer.write(&self.media_color)?;
-        written += writer.write(&self.media_type)?;
-        written += writer.write(&self.print_content_optimize)?;
-        written += writer.write(&self.reserved_0)?;
-        written += writer.write(&self.cut_media.to_be_bytes())?;
-        written += writer.write(&self.duplex.to_be_bytes())?;
-        written += writer.write(&self.hw_resolution[0].to_be_bytes())?;
-        written += writer.write(&self.hw_resolution[1].to_be_bytes())?;
-        written += writer.write(&self.reserved_1)?;
-        written += writer.write(&self.insert_sheet.to_be_bytes())?;
-        written += writer.write(&self.jog.to_be_bytes())?;
-        written += writer.write(&self.leading_edge.to_be_bytes())?;
-        written += writer.write(&self.reserved_2)?;
-        written += writer.write(&self.media_position.to_be_bytes())?;
-        written += writer.write(&self.media_weight_metric.to_be_bytes())?;
-        written += writer.write(&self.reserved_3)?;
-        written += writer.write(&self.num_copies.to_be_bytes())?;
-        written += writer.write(&self.orientation.to_be_bytes())?;
-        written += writer.write(&self.reserved_4)?;
-        written += writer.write(&self.page_size[0].to_be_bytes())?;
-        written += writer.write(&self.page_size[1].to_be_bytes())?;
-        written += writer.write(&self.reserved_5)?;
-        written += writer.write(&self.tumble.to_be_bytes())?;
-        written += writer.write(&self.width.to_be_bytes())?;
-        written += writer.write(&self.height.to_be_bytes())?;
-        written += writer.write(&self.reserved_6)?;
-        written += writer.write(&self.bits_per_color.to_be_bytes())?;
-        written += writer.write(&self.bits_per_pixel.to_be_bytes())?;
-        written += writer.write(&self.bytes_per_line.to_be_bytes())?;
-        written += writer.write(&self.color_order.to_be_bytes())?;
-        written += writer.write(&self.color_space.to_be_bytes())?;
-        written += writer.write(&self.reserved_7)?;
-        written += writer.write(&self.num_colors.to_be_bytes())?;
-        written += writer.write(&self.reserved_8)?;
-        written += writer.write(&self.total_page_count.to_be_bytes())?;
-        written += writer.write(&self.cross_feed_transform.to_be_bytes())?;
-        written += writer.write(&self.feed_transform.to_be_bytes())?;
-        written += writer.write(&self.image_box_left.to_be_bytes())?;
-        written += writer.write(&self.image_box_top.to_be_bytes())?;
-        written += writer.write(&self.image_box_right.to_be_bytes())?;
-        written += writer.write(&self.image_box_bottom.to_be_bytes())?;
-        written += writer.write(&self.alternate_primary.to_be_bytes())?;
-        written += writer.write(&self.print_quality.to_be_bytes())?;
-        written += writer.write(&self.reserved_9)?;
-        written += writer.write(&self.vendor_identifier.to_be_bytes())?;
-        written += writer.write(&self.vendor_length.to_be_bytes())?;
-        written += writer.write(&self.vendor_data)?;
-        written += writer.write(&self.reserved_10)?;
-        written += writer.write(&self.rendering_intent)?;
-        written += writer.write(&self.page_size_name)?;
+        let mut buf = [0u8; 1796];
+        reader.read_exact(&mut buf)?;
+        if c_string(&buf[..64]) != "PwgRaster" {
+            return Err(IppPrintError::RasterError(format!(
+                "not a PWG Raster page header: {:?}",
+                c_string(&buf[..64])
+            )));
+        }
+        PageHeader::from_bytes(&buf)
+    }
 
-        Ok(written)
+    /// Parses 1796-byte big-endian header in the layout of write_to_stream.
+    /// The first string isn't checked, since it's any MediaClass in CUPS raster.
+    fn from_bytes(buf: &[u8; 1796]) -> Result<Self, IppPrintError> {
+        let mut pos = 0;
+        let mut take = |len: usize| {
+            pos += len;
+            &buf[pos - len..pos]
+        };
+        let array = |bytes: &[u8]| {
+            let mut result = [0u8; 64];
+            result.copy_from_slice(bytes);
+            result
+        };
+        let u32_of = |bytes: &[u8]| u32::from_be_bytes(bytes.try_into().unwrap());
+        let i32_of = |bytes: &[u8]| i32::from_be_bytes(bytes.try_into().unwrap());
+
+        // Fields are evaluated in order, so the same order as the file works.
+        let header = Self {
+            pwg_raster: array(take(64)),
+            media_color: array(take(64)),
+            media_type: array(take(64)),
+            print_content_optimize: array(take(64)),
+            reserved_0: take(12).try_into().unwrap(),
+            cut_media: u32_of(take(4)),
+            duplex: u32_of(take(4)),
+            hw_resolution: [u32_of(take(4)), u32_of(take(4))],
+            reserved_1: take(16).try_into().unwrap(),
+            insert_sheet: u32_of(take(4)),
+            jog: u32_of(take(4)),
+            leading_edge: u32_of(take(4)),
+            reserved_2: take(12).try_into().unwrap(),
+            media_position: u32_of(take(4)),
+            media_weight_metric: u32_of(take(4)),
+            reserved_3: take(8).try_into().unwrap(),
+            num_copies: u32_of(take(4)),
+            orientation: u32_of(take(4)),
+            reserved_4: take(4).try_into().unwrap(),
+            page_size: [u32_of(take(4)), u32_of(take(4))],
+            reserved_5: take(8).try_into().unwrap(),
+            tumble: u32_of(take(4)),
+            width: u32_of(take(4)),
+            height: u32_of(take(4)),
+            reserved_6: take(4).try_into().unwrap(),
+            bits_per_color: u32_of(take(4)),
+            bits_per_pixel: u32_of(take(4)),
+            bytes_per_line: u32_of(take(4)),
+            color_order: u32_of(take(4)),
+            color_space: u32_of(take(4)),
+            reserved_7: take(16).try_into().unwrap(),
+            num_colors: u32_of(take(4)),
+            reserved_8: take(28).try_into().unwrap(),
+            total_page_count: u32_of(take(4)),
+            cross_feed_transform: i32_of(take(4)),
+            feed_transform: i32_of(take(4)),
+            image_box_left: u32_of(take(4)),
+            image_box_top: u32_of(take(4)),
+            image_box_right: u32_of(take(4)),
+            image_box_bottom: u32_of(take(4)),
+            alternate_primary: u32_of(take(4)),
+            print_quality: u32_of(take(4)),
+            reserved_9: take(20).try_into().unwrap(),
+            vendor_identifier: u32_of(take(4)),
+            vendor_length: u32_of(take(4)),
+            vendor_data: take(1088).try_into().unwrap(),
+            reserved_10: array(take(64)),
+            rendering_intent: array(take(64)),
+            page_size_name: array(take(64)),
+        };
+
+        if header.vendor_length as usize > header.vendor_data.len() {
+            return Err(IppPrintError::RasterError(format!(
+                "VendorLength {} is longer than VendorData",
+                header.vendor_length
+            )));
+        }
+        Ok(header)
     }
-}
 
-#[derive(Debug, PartialEq, Eq, Clone)]
-pub struct SrgbColor {
-    r: u8,
-    g: u8,
-    b: u8,
-}
+    /// Writes 1796-byte header. Synchronization word is not included.
+    pub fn write_to_stream<W>(&self, writer: &mut W) -> Result<usize, IppPrintError>
+    where
+        W: Write,
+    {
+        let buf = self.to_bytes();
+        writer.write_all(&buf)?;
+        Ok(buf.len())
+    }
 
-impl SrgbColor {
-    pub fn new(r: u8, g: u8, b: u8) -> Self {
-        Self { r, g, b }
+    /// Header in big-endian, assembled so that it can be written at once.
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(1796);
+
+        buf.extend_from_slice(&self.pwg_raster);
+        buf.extend_from_slice(&self.media_color);
+        buf.extend_from_slice(&self.media_type);
+        buf.extend_from_slice(&self.print_content_optimize);
+        buf.extend_from_slice(&self.reserved_0);
+        buf.extend_from_slice(&self.cut_media.to_be_bytes());
+        buf.extend_from_slice(&self.duplex.to_be_bytes());
+        buf.extend_from_slice(&self.hw_resolution[0].to_be_bytes());
+        buf.extend_from_slice(&self.hw_resolution[1].to_be_bytes());
+        buf.extend_from_slice(&self.reserved_1);
+        buf.extend_from_slice(&self.insert_sheet.to_be_bytes());
+        buf.extend_from_slice(&self.jog.to_be_bytes());
+        buf.extend_from_slice(&self.leading_edge.to_be_bytes());
+        buf.extend_from_slice(&self.reserved_2);
+        buf.extend_from_slice(&self.media_position.to_be_bytes());
+        buf.extend_from_slice(&self.media_weight_metric.to_be_bytes());
+        buf.extend_from_slice(&self.reserved_3);
+        buf.extend_from_slice(&self.num_copies.to_be_bytes());
+        buf.extend_from_slice(&self.orientation.to_be_bytes());
+        buf.extend_from_slice(&self.reserved_4);
+        buf.extend_from_slice(&self.page_size[0].to_be_bytes());
+        buf.extend_from_slice(&self.page_size[1].to_be_bytes());
+        buf.extend_from_slice(&self.reserved_5);
+        buf.extend_from_slice(&self.tumble.to_be_bytes());
+        buf.extend_from_slice(&self.width.to_be_bytes());
+        buf.extend_from_slice(&self.height.to_be_bytes());
+        buf.extend_from_slice(&self.reserved_6);
+        buf.extend_from_slice(&self.bits_per_color.to_be_bytes());
+        buf.extend_from_slice(&self.bits_per_pixel.to_be_bytes());
+        buf.extend_from_slice(&self.bytes_per_line.to_be_bytes());
+        buf.extend_from_slice(&self.color_order.to_be_bytes());
+        buf.extend_from_slice(&self.color_space.to_be_bytes());
+        buf.extend_from_slice(&self.reserved_7);
+        buf.extend_from_slice(&self.num_colors.to_be_bytes());
+        buf.extend_from_slice(&self.reserved_8);
+        buf.extend_from_slice(&self.total_page_count.to_be_bytes());
+        buf.extend_from_slice(&self.cross_feed_transform.to_be_bytes());
+        buf.extend_from_slice(&self.feed_transform.to_be_bytes());
+        buf.extend_from_slice(&self.image_box_left.to_be_bytes());
+        buf.extend_from_slice(&self.image_box_top.to_be_bytes());
+        buf.extend_from_slice(&self.image_box_right.to_be_bytes());
+        buf.extend_from_slice(&self.image_box_bottom.to_be_bytes());
+        buf.extend_from_slice(&self.alternate_primary.to_be_bytes());
+        buf.extend_from_slice(&self.print_quality.to_be_bytes());
+        buf.extend_from_slice(&self.reserved_9);
+        buf.extend_from_slice(&self.vendor_identifier.to_be_bytes());
+        buf.extend_from_slice(&self.vendor_length.to_be_bytes());
+        buf.extend_from_slice(&self.vendor_data);
+        buf.extend_from_slice(&self.reserved_10);
+        buf.extend_from_slice(&self.rendering_intent);
+        buf.extend_from_slice(&self.page_size_name);
+
+        buf
     }
 }
 
-impl From<u32> for SrgbColor {
-    fn from(value: u32) -> Self {
-        SrgbColor {
-            r: (value >> 16) as u8,
-            g: (value >> 8) as u8,
-            b: (value >> 0) as u8,
+/// cupsColorSpace, number of colors, and bits per color of a raster type keyword.
+fn raster_type_params(raster_type: &str) -> Result<(u32, u32, u32), IppPrintError> {
+    let (color_space, num_colors, bits_per_color) = match raster_type {
+        "black_1" => (ColorSpace::Black, 1, 1),
+        "sgray_8" => (ColorSpace::Sgray, 1, 8),
+        "sgray_16" => (ColorSpace::Sgray, 1, 16),
+        "srgb_8" => (ColorSpace::Srgb, 3, 8),
+        "srgb_16" => (ColorSpace::Srgb, 3, 16),
+        "adobe-rgb_8" => (ColorSpace::AdobeRgb, 3, 8),
+        "adobe-rgb_16" => (ColorSpace::AdobeRgb, 3, 16),
+        "cmyk_8" => (ColorSpace::Cmyk, 4, 8),
+        "cmyk_16" => (ColorSpace::Cmyk, 4, 16),
+        _ => {
+            return Err(IppPrintError::RasterError(format!(
+                "unknown raster type: {}",
+                raster_type
+            )))
         }
-    }
+    };
+    Ok((color_space.value(), num_colors, bits_per_color))
 }
 
-#[derive(Debug)]
-pub struct ImageEncoder {
+/// Builds a PageHeader from pixel dimensions or media size, resolution, and raster
+/// type, deriving the fields which depend on them so that they can't disagree.
+#[derive(Debug, Clone)]
+pub struct PageHeaderBuilder {
     width: u32,
     height: u32,
-    prev_row: Option<Vec<SrgbColor>>,
-    written_rows: u32,
-    comm_rows: u8,
+    media_size: Option<MediaSize>,
+    resolution: [u32; 2],
+    raster_type: String,
+    image_box: Option<[u32; 4]>,
 }
 
-impl ImageEncoder {
+impl PageHeaderBuilder {
+    /// A page of width x height pixels, at 300 dpi in srgb_8 unless changed.
     pub fn new(width: u32, height: u32) -> Self {
         Self {
             width,
             height,
-            prev_row: None,
-            written_rows: 0,
-            comm_rows: 0,
+            media_size: None,
+            resolution: [300, 300],
+            raster_type: "srgb_8".to_string(),
+            image_box: None,
         }
     }
 
-    fn do_encode_row<W>(writer: &mut W, row: Vec<SrgbColor>) -> Result<usize, Box<dyn Error>>
-    where
-        W: Write,
-    {
-        if row.is_empty() {
-            panic!("row mustn't be empty");
+    /// A page of the media size, whose pixel dimensions follow the resolution.
+    pub fn with_media_size(size: &MediaSize) -> Self {
+        Self {
+            media_size: Some(size.clone()),
+            ..PageHeaderBuilder::new(0, 0)
         }
+    }
 
-        let mut comm = vec![0i16; row.len()];
-        for x in (0..row.len() - 1).rev() {
-            comm[x] = if row[x + 1] == row[x] {
-                if comm[x + 1] < 0 {
-                    1
-                } else if comm[x + 1] == 127 {
-                    0
-                } else {
-                    comm[x + 1] + 1
-                }
-            } else {
-                if comm[x + 1] > 0 {
-                    0
-                } else if comm[x + 1] <= -128 {
-                    0
-                } else {
-                    comm[x + 1] - 1
-                }
-            }
-        }
+    pub fn resolution(mut self, x_dpi: u32, y_dpi: u32) -> Self {
+        self.resolution = [x_dpi, y_dpi];
+        self
+    }
 
-        let mut written = 0;
+    /// pwg-raster-document-type keyword like "srgb_8"
+    pub fn raster_type(mut self, raster_type: &str) -> Self {
+        self.raster_type = raster_type.to_string();
+        self
+    }
 
-        let mut x = 0;
-        while x < row.len() {
-            written += writer.write(&[comm[x] as u8])?;
-            if comm[x] < 0 {
-                for i in 0..(-comm[x] + 1) {
-                    written += writer.write(&[
-                        row[x + i as usize].r,
-                        row[x + i as usize].g,
-                        row[x + i as usize].b,
-                    ])?;
-                }
-                x += -comm[x] as usize + 1;
-            } else {
-                written += writer.write(&[row[x].r, row[x].g, row[x].b])?;
-                x += comm[x] as usize + 1;
+    /// Printable area in pixels at the resolution, as in PageHeader::image_box.
+    pub fn image_box(mut self, left: u32, top: u32, right: u32, bottom: u32) -> Self {
+        self.image_box = Some([left, top, right, bottom]);
+        self
+    }
+
+    /// Derives bits_per_pixel, bytes_per_line, num_colors, and page_size (in points),
+    /// failing if the combination can't be written as PWG Raster.
+    pub fn build(self) -> Result<PageHeader, IppPrintError> {
+        let (color_space, num_colors, bits_per_color) = raster_type_params(&self.raster_type)?;
+        let [x_dpi, y_dpi] = self.resolution;
+        if x_dpi == 0 || y_dpi == 0 {
+            return Err(IppPrintError::RasterError(format!(
+                "invalid resolution: {}x{}",
+                x_dpi, y_dpi
+            )));
+        }
+        let [width, height] = match &self.media_size {
+            Some(size) => size.pixels(self.resolution),
+            None => [self.width, self.height],
+        };
+        if width == 0 || height == 0 {
+            return Err(IppPrintError::RasterError(format!(
+                "empty page: {}x{}",
+                width, height
+            )));
+        }
+        let bits_per_pixel = num_colors * bits_per_color;
+        let bytes_per_line = (width as u64 * bits_per_pixel as u64).div_ceil(8);
+        if bytes_per_line.saturating_mul(height as u64) > MAX_PAGE_BYTES {
+            return Err(IppPrintError::RasterError(format!(
+                "page is too large: {}x{}",
+                width, height
+            )));
+        }
+        let to_points = |pixels: u32, dpi: u32| (pixels as u64 * 72).div_ceil(dpi as u64) as u32;
+
+        let mut header = PageHeader {
+            hw_resolution: self.resolution,
+            page_size: [to_points(width, x_dpi), to_points(height, y_dpi)],
+            width,
+            height,
+            bits_per_color,
+            bits_per_pixel,
+            bytes_per_line: bytes_per_line as u32,
+            color_space,
+            num_colors,
+            page_size_name: [0; 64],
+            ..Default::default()
+        };
+        if let Some(size) = &self.media_size {
+            header.page_size = size.points();
+            header.set_page_size_name(&size.name)?;
+        }
+        if let Some([left, top, right, bottom]) = self.image_box {
+            if left >= right || top >= bottom || right > width || bottom > height {
+                return Err(IppPrintError::RasterError(format!(
+                    "image box [{}, {}, {}, {}] doesn't fit in {}x{}",
+                    left, top, right, bottom, width, height
+                )));
             }
+            header.image_box_left = left;
+            header.image_box_top = top;
+            header.image_box_right = right;
+            header.image_box_bottom = bottom;
         }
+        Ok(header)
+    }
+}
 
-        Ok(written)
+/// Converts a header between big-endian and little-endian. Every field between
+/// the leading strings and vendor_data is a 32-bit number, including those which
+/// are reserved in PWG Raster but used by CUPS.
+fn swap_header_numbers(buf: &mut [u8]) {
+    for word in buf[256..516].chunks_exact_mut(4) {
+        word.reverse();
     }
+}
 
-    pub fn write_row<W>(
-        &mut self,
-        writer: &mut W,
-        row: Vec<SrgbColor>,
-    ) -> Result<usize, Box<dyn Error>>
-    where
-        W: Write,
-    {
-        if row.len() != self.width as usize {
-            panic!();
+/// Synchronization word at the start of a raster stream, telling how pages are stored.
+/// PWG Raster is always RaS2; the others are only used in CUPS raster.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SyncWord {
+    /// "RaS2": Big-endian with compressed page data (CUPS raster v2)
+    V2,
+    /// "RaS3": Big-endian with uncompressed page data (CUPS raster v3)
+    V3,
+    /// "2SaR": Little-endian with compressed page data
+    V2LittleEndian,
+    /// "3SaR": Little-endian with uncompressed page data
+    V3LittleEndian,
+}
+
+impl SyncWord {
+    pub fn from_bytes(bytes: &[u8; 4]) -> Option<Self> {
+        match bytes {
+            b"RaS2" => Some(Self::V2),
+            b"RaS3" => Some(Self::V3),
+            b"2SaR" => Some(Self::V2LittleEndian),
+            b"3SaR" => Some(Self::V3LittleEndian),
+            _ => None,
         }
-        if self.written_rows >= self.height {
-            panic!();
+    }
+
+    pub fn bytes(&self) -> &'static [u8; 4] {
+        match self {
+            Self::V2 => b"RaS2",
+            Self::V3 => b"RaS3",
+            Self::V2LittleEndian => b"2SaR",
+            Self::V3LittleEndian => b"3SaR",
         }
+    }
 
-        println!("{} {}", self.written_rows, self.height);
+    /// Whether page data is run-length encoded.
+    pub fn is_compressed(&self) -> bool {
+        matches!(self, Self::V2 | Self::V2LittleEndian)
+    }
 
-        if self.prev_row.is_none() {
-            self.written_rows += 1;
-            self.prev_row = Some(row);
-            return Ok(0);
-        }
+    /// Whether header fields and 16-bit color values are little-endian.
+    pub fn is_little_endian(&self) -> bool {
+        matches!(self, Self::V2LittleEndian | Self::V3LittleEndian)
+    }
+}
 
-        let mut written = 0;
+/// Text of a NUL-terminated string field.
+fn c_string(buf: &[u8]) -> String {
+    let len = buf.iter().position(|e| *e == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..len]).into_owned()
+}
 
-        if self.written_rows + 1 != self.height && self.prev_row.as_ref().unwrap() == &row {
-            self.comm_rows += 1;
-            self.written_rows += 1;
-            return Ok(0);
-        }
+/// Stores value in a string field, padded with NUL. Values which can't be read
+/// back as they are, i.e. too long to leave a terminator or containing NUL, are rejected.
+fn set_c_string(buf: &mut [u8; 64], name: &str, value: &str) -> Result<(), IppPrintError> {
+    if value.len() >= buf.len() || value.contains('\0') {
+        return Err(IppPrintError::RasterError(format!(
+            "invalid {}: {:?}",
+            name, value
+        )));
+    }
+    *buf = [0; 64];
+    buf[..value.len()].copy_from_slice(value.as_bytes());
+    Ok(())
+}
 
-        // TODO: handle comm rows longer than 128.
+/// CutMedia: when to cut roll media.
+#[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, Eq, Clone, Copy)]
+pub enum CutMedia {
+    Never = 0,
+    AfterFile = 1,
+    AfterJob = 2,
+    AfterSet = 3,
+    AfterPage = 4,
+}
 
-        let prev_row = self.prev_row.take().unwrap();
-        self.prev_row = Some(row);
+/// Jog: when to offset pages in the output bin.
+#[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, Eq, Clone, Copy)]
+pub enum Jog {
+    Never = 0,
+    AfterFile = 1,
+    AfterJob = 2,
+    AfterSet = 3,
+}
 
-        written += writer.write(&[self.comm_rows])?;
-        written += ImageEncoder::do_encode_row(writer, prev_row)?;
+/// LeadingEdge: which edge of the media is fed first.
+#[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, Eq, Clone, Copy)]
+pub enum LeadingEdge {
+    ShortEdgeFirst = 0,
+    LongEdgeFirst = 1,
+}
 
-        self.comm_rows = 0;
-        self.written_rows += 1;
+/// Orientation of the page content.
+#[derive(Debug, FromPrimitive, ToPrimitive, PartialEq, Eq, Clone, Copy)]
+pub enum Orientation {
+    Portrait = 0,
+    /// Rotated 90 degrees counter-clockwise
+    Landscape = 1,
+    /// Rotated 180 degrees
+    ReversePortrait = 2,
+    /// Rotated 90 degrees clockwise
+    ReverseLandscape = 3,
+}
 
-        Ok(written)
+/// MediaPosition: where the media is fed from.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MediaPosition {
+    Auto,
+    Main,
+    Alternate,
+    LargeCapacity,
+    Manual,
+    Envelope,
+    Disc,
+    Photo,
+    Hagaki,
+    MainRoll,
+    AlternateRoll,
+    Top,
+    Middle,
+    Bottom,
+    Side,
+    Left,
+    Right,
+    Center,
+    Rear,
+    ByPassTray,
+    /// Tray 1..=20
+    Tray(u8),
+    /// Roll 1..=10
+    Roll(u8),
+}
+
+/// Positions without a number, in the order of their values.
+const MEDIA_POSITIONS: [MediaPosition; 20] = [
+    MediaPosition::Auto,
+    MediaPosition::Main,
+    MediaPosition::Alternate,
+    MediaPosition::LargeCapacity,
+    MediaPosition::Manual,
+    MediaPosition::Envelope,
+    MediaPosition::Disc,
+    MediaPosition::Photo,
+    MediaPosition::Hagaki,
+    MediaPosition::MainRoll,
+    MediaPosition::AlternateRoll,
+    MediaPosition::Top,
+    MediaPosition::Middle,
+    MediaPosition::Bottom,
+    MediaPosition::Side,
+    MediaPosition::Left,
+    MediaPosition::Right,
+    MediaPosition::Center,
+    MediaPosition::Rear,
+    MediaPosition::ByPassTray,
+];
+
+/// media-source keywords (PWG5100.7) of MEDIA_POSITIONS.
+const MEDIA_SOURCES: [&str; 20] = [
+    "auto",
+    "main",
+    "alternate",
+    "large-capacity",
+    "manual",
+    "envelope",
+    "disc",
+    "photo",
+    "hagaki",
+    "main-roll",
+    "alternate-roll",
+    "top",
+    "middle",
+    "bottom",
+    "side",
+    "left",
+    "right",
+    "center",
+    "rear",
+    "by-pass-tray",
+];
+
+impl MediaPosition {
+    /// Position for a media-source keyword like "main" or "tray-2".
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        let number = |prefix: &str, max: u8| {
+            keyword
+                .strip_prefix(prefix)
+                .and_then(|e| e.parse::<u8>().ok())
+                .filter(|e| (1..=max).contains(e))
+        };
+        if let Some(n) = number("tray-", 20) {
+            Some(Self::Tray(n))
+        } else if let Some(n) = number("roll-", 10) {
+            Some(Self::Roll(n))
+        } else {
+            MEDIA_SOURCES
+                .iter()
+                .position(|e| *e == keyword)
+                .map(|e| MEDIA_POSITIONS[e])
+        }
+    }
+
+    /// media-source keyword
+    pub fn keyword(&self) -> String {
+        match self {
+            Self::Tray(n) => format!("tray-{}", n),
+            Self::Roll(n) => format!("roll-{}", n),
+            position => MEDIA_POSITIONS
+                .iter()
+                .position(|e| e == position)
+                .map_or_else(String::new, |e| MEDIA_SOURCES[e].to_string()),
+        }
     }
 }
 
-pub fn read_raster<R>(reader: &mut R) -> Result<(), Box<dyn Error>>
-where
-    R: Read,
-{
-    let mut n_read = 0;
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    println!(
-        "Synchronization Word={}",
-        String::from_utf8(buf.to_vec()).unwrap()
-    );
-
-    let mut buf = [0u8; 64];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("PwgRaster={}", String::from_utf8(buf.to_vec()).unwrap());
-
-    let mut buf = [0u8; 64];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    // if this is empty, default one is used
-    println!("MediaColor={}", String::from_utf8(buf.to_vec()).unwrap());
-
-    let mut buf = [0u8; 64];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("MediaType={}", String::from_utf8(buf.to_vec()).unwrap());
-
-    let mut buf = [0u8; 64];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!(
-        "PrintContentOptimize={}",
-        String::from_utf8(buf.to_vec()).unwrap()
-    );
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)AdvanceDistance={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)AdvanceMedia={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)Collate={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("CutMedia={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("Duplex={}", u32::from_be_bytes(buf));
-
-    // dpi
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("HWResolution[v]={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("HWResolution[h]={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)ImagingBoundingBox[l]={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)ImagingBoundingBox[b]={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)ImagingBoundingBox[r]={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)ImagingBoundingBox[t]={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("InsertSheet={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("Jog={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("LeadingEdge={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)Margins[l]={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)Margins[r]={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)ManualFeed={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("MediaPosition={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("MediaWeightMetric={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)MirrorPrint={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)NegativePrint={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("NumCopies={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("Orientation={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)OutputFaceUp={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("PageSize[width]={}", u32::from_be_bytes(buf));
-
-    // size in points
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("PageSize[length]={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)Separations={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)TraySwitch={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("Tumble={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("Width={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("Height={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)MediaType={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("BitsPerColor={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("BitsPerPixel={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("BytesPerLine={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("ColorOrder={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("ColorSpace={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)Compression={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)RowCount={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)RowFeed={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)RowStep={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("NumColors={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)BorderlessScalingFactor={}", f32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)pageSize[width]={}", f32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)pageSize[length]={}", f32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)ImagingBBox[left]={}", f32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)ImagingBBox[bottom]={}", f32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)ImagingBBox[right]={}", f32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("(cups)ImagingBBox[top]={}", f32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("TotalPageCount={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("CrossFeedTransform={}", i32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("FeedTransform={}", i32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("ImageBoxLeft={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("ImageBoxTop={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("ImageBoxRight={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("ImageBoxBottom={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("AlternatePrimary={:x}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("PrintQuality={}", u32::from_be_bytes(buf));
-
-    // reserved
-    let mut buf = [0u8; 20];
-    n_read += buf.len();
-    reader.read_exact(&mut buf)?;
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("VendorIdentifier={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 4];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("VendorLength={}", u32::from_be_bytes(buf));
-
-    let mut buf = [0u8; 1088];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("VendorData={:?}", buf);
-
-    // reserved
-    let mut buf = [0u8; 64];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-
-    let mut buf = [0u8; 64];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!("RenderingIntent={:?}", buf);
-
-    let mut buf = [0u8; 64];
-    reader.read_exact(&mut buf)?;
-    n_read += buf.len();
-    println!(
-        "PageSizeName={:?}",
-        String::from_utf8(buf.to_vec()).unwrap()
-    );
-
-    println!("{}", n_read);
-
-    let mut out = File::create("/tmp/out.ppm")?;
-    writeln!(out, "P3")?;
-    writeln!(out, "2480 3507")?;
-    writeln!(out, "255")?;
-
-    let mut written_rows: u32 = 0;
-    loop {
-        let mut buf = [0u8; 1];
-        if let Err(err) = reader.read_exact(&mut buf) {
-            panic!("{}", err);
-        }
-
-        let mut row = Vec::<u8>::new();
-
-        let mut x_written: u32 = 0;
+impl FromPrimitive for MediaPosition {
+    fn from_i64(n: i64) -> Option<Self> {
+        u64::try_from(n).ok().and_then(Self::from_u64)
+    }
 
-        loop {
-            let mut buf = [0u8; 1];
-            if let Err(err) = reader.read_exact(&mut buf) {
-                panic!("{}", err)
-            }
-            let run_len = buf[0] as i8;
-            if run_len >= 0 {
-                let mut color = [0u8; 3];
-                if let Err(err) = reader.read_exact(&mut color) {
-                    panic!("{}", err);
-                }
-                for _ in 0..=run_len {
-                    if x_written >= 2480 {
-                        println!(
-                            "warning: current line exceeded its size on line {}",
-                            written_rows
-                        );
-                        break;
-                    }
-                    write!(row, "{} {} {} ", color[0], color[1], color[2])?;
-                    x_written += 1;
-                }
-            } else {
-                let run_len = -run_len;
-                for _ in 0..=run_len {
-                    if x_written >= 2480 {
-                        println!(
-                            "warning: current line exceeded its size on line {}",
-                            written_rows
-                        );
-                        break;
-                    }
-                    let mut color = [0u8; 3];
-                    if let Err(err) = reader.read_exact(&mut color) {
-                        panic!("{}", err);
-                    }
-                    write!(row, "{} {} {} ", color[0], color[1], color[2])?;
-                    x_written += 1;
-                }
-            }
+    fn from_u64(n: u64) -> Option<Self> {
+        match n {
+            0..=19 => Some(MEDIA_POSITIONS[n as usize]),
+            20..=39 => Some(Self::Tray(n as u8 - 19)),
+            40..=49 => Some(Self::Roll(n as u8 - 39)),
+            _ => None,
+        }
+    }
+}
 
-            if x_written >= 2480 {
-                break;
-            }
+impl ToPrimitive for MediaPosition {
+    fn to_i64(&self) -> Option<i64> {
+        self.to_u64().map(|e| e as i64)
+    }
+
+    fn to_u64(&self) -> Option<u64> {
+        match self {
+            Self::Tray(n @ 1..=20) => Some(*n as u64 + 19),
+            Self::Roll(n @ 1..=10) => Some(*n as u64 + 39),
+            Self::Tray(_) | Self::Roll(_) => None,
+            position => MEDIA_POSITIONS
+                .iter()
+                .position(|e| e == position)
+                .map(|e| e as u64),
         }
-        writeln!(row)?;
+    }
+}
 
-        for _ in 0..=buf[0] {
-            if written_rows >= 3507 {
-                println!("warning: image too long!");
-                break;
-            }
-            out.write(row.as_slice())?;
-            written_rows += 1;
+/// cupsColorSpace values used in PWG Raster.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ColorSpace {
+    Rgb,
+    Black,
+    Cmyk,
+    /// Gray using sRGB gamma and white point
+    Sgray,
+    Srgb,
+    AdobeRgb,
+    /// CIE Lab with a hint for 1..=15 colors
+    Icc(u8),
+}
+
+impl ColorSpace {
+    /// Value of the header field. Icc out of 1..=15 is clamped into it.
+    pub fn value(&self) -> u32 {
+        match self {
+            Self::Rgb => 1,
+            Self::Black => 3,
+            Self::Cmyk => 6,
+            Self::Sgray => 18,
+            Self::Srgb => 19,
+            Self::AdobeRgb => 20,
+            Self::Icc(n) => 47 + (*n).clamp(1, 15) as u32,
         }
+    }
+}
+
+impl FromPrimitive for ColorSpace {
+    fn from_i64(n: i64) -> Option<Self> {
+        u64::try_from(n).ok().and_then(Self::from_u64)
+    }
 
-        if written_rows >= 3507 {
-            break;
+    fn from_u64(n: u64) -> Option<Self> {
+        match n {
+            1 => Some(Self::Rgb),
+            3 => Some(Self::Black),
+            6 => Some(Self::Cmyk),
+            18 => Some(Self::Sgray),
+            19 => Some(Self::Srgb),
+            20 => Some(Self::AdobeRgb),
+            48..=62 => Some(Self::Icc(n as u8 - 47)),
+            _ => None,
         }
     }
+}
 
-    Ok(())
+/// Media that need several header fields adjusted together.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum MediaPreset {
+    /// Japanese postcard (100x148mm)
+    Hagaki,
+    /// Japanese return postcard (148x200mm, unfolded)
+    OufukuHagaki,
+    /// DL envelope (110x220mm)
+    EnvelopeDl,
+    /// C5 envelope (162x229mm)
+    EnvelopeC5,
+    /// US #10 envelope (4.125x9.5in)
+    EnvelopeNumber10,
+    /// Japanese Chou 3 envelope (120x235mm)
+    EnvelopeChou3,
+    /// Japanese Chou 4 envelope (90x205mm)
+    EnvelopeChou4,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+impl MediaPreset {
+    /// Self-describing media name standardized in PWG5101.1
+    pub fn media_name(&self) -> &'static str {
+        match self {
+            Self::Hagaki => "jpn_hagaki_100x148mm",
+            Self::OufukuHagaki => "jpn_oufuku_148x200mm",
+            Self::EnvelopeDl => "iso_dl_110x220mm",
+            Self::EnvelopeC5 => "iso_c5_162x229mm",
+            Self::EnvelopeNumber10 => "na_number-10_4.125x9.5in",
+            Self::EnvelopeChou3 => "jpn_chou3_120x235mm",
+            Self::EnvelopeChou4 => "jpn_chou4_90x205mm",
+        }
+    }
 
-    #[test]
-    fn encode_row() {
-        let data = [
-            0xFFFF00, 0x0000FF, 0xFFFF00, 0xFFFFFF, 0xFFFFFF, 0xFFFFFF, 0x00FF00, 0xFFFFFF,
-        ]
-        .into_iter()
-        .map(|e| e.into())
-        .collect::<_>();
-        let mut out = Vec::new();
-        ImageEncoder::do_encode_row(&mut out, data).unwrap();
-        let expected_bytes = vec![
-            0xFE, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0x00, 0x02, 0xFF, 0xFF, 0xFF,
-            0xFF, 0x00, 0xFF, 0x00, 0xFF, 0xFF, 0xFF,
-        ];
-        assert_eq!(expected_bytes, out);
+    pub fn media_size(&self) -> MediaSize {
+        MediaSize::from_name(self.media_name()).unwrap()
     }
 
-    #[test]
-    fn encode_row_long_comm_pixels() {
-        let data = [0; 200].into_iter().map(|e| e.into()).collect::<_>();
-        let mut out = Vec::new();
-        ImageEncoder::do_encode_row(&mut out, data).unwrap();
-        let expected_bytes = vec![0x47, 0x00, 0x00, 0x00, 0x7F, 0x00, 0x00, 0x00];
-        assert_eq!(expected_bytes, out);
+    /// [0]: Width in hundredths of millimeters
+    /// [1]: Height in hundredths of millimeters
+    pub fn size(&self) -> [u32; 2] {
+        let size = self.media_size();
+        [size.width, size.height]
     }
 
-    #[test]
-    fn encode_row_long_diff_pixels() {
-        let data = (0..200).map(|e| e.into()).collect::<_>();
-        let mut out = Vec::new();
-        ImageEncoder::do_encode_row(&mut out, data).unwrap();
+    fn media_position(&self) -> MediaPosition {
+        match self {
+            Self::Hagaki | Self::OufukuHagaki => MediaPosition::Hagaki,
+            _ => MediaPosition::Envelope,
+        }
+    }
+
+    fn orientation(&self) -> Orientation {
+        match self {
+            // Western envelopes are addressed along the long edge.
+            Self::EnvelopeDl | Self::EnvelopeC5 | Self::EnvelopeNumber10 => Orientation::Landscape,
+            _ => Orientation::Portrait,
+        }
+    }
+
+    /// Margin on each side in hundredths of millimeters
+    fn margin(&self) -> u32 {
+        match self {
+            Self::Hagaki | Self::OufukuHagaki => 300,
+            _ => 500,
+        }
+    }
+
+    /// Sets media size and name, media position, orientation, and image box
+    /// of the header. Pixel dimensions are computed from hw_resolution.
+    pub fn apply(&self, header: &mut PageHeader) {
+        let to_pixels = |hmm: u32, dpi: u32| (hmm as u64 * dpi as u64 / 2540) as u32;
+
+        header.set_media_size(&self.media_size());
+
+        let margin = self.margin();
+        header.image_box_left = to_pixels(margin, header.hw_resolution[0]);
+        header.image_box_top = to_pixels(margin, header.hw_resolution[1]);
+        header.image_box_right = header.width - header.image_box_left;
+        header.image_box_bottom = header.height - header.image_box_top;
+
+        // Only positions without a number are used, which always have a value.
+        header.media_position = self.media_position().to_u32().unwrap_or(0);
+        header.set_orientation(self.orientation());
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SrgbColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl SrgbColor {
+    pub fn new(r: u8, g: u8, b: u8) -> Self {
+        Self { r, g, b }
+    }
+}
+
+impl From<u32> for SrgbColor {
+    fn from(value: u32) -> Self {
+        SrgbColor {
+            r: (value >> 16) as u8,
+            g: (value >> 8) as u8,
+            b: value as u8,
+        }
+    }
+}
+
+/// Pixel in the layout of page data, which is chunky (interleaved) and big-endian.
+pub trait Pixel: Clone + PartialEq {
+    /// Value of bits_per_pixel in the header.
+    const BITS_PER_PIXEL: u32;
+    /// Pixel printed without ink, used to fill margins.
+    const WHITE: Self;
+    /// Pixel printed black, used to draw borders.
+    const BLACK: Self;
+
+    /// Appends the pixel as written in page data.
+    fn write_bytes(&self, buf: &mut Vec<u8>);
+
+    /// Appends a row as written in page data before compression.
+    /// Pixels smaller than a byte have to override this to pack them.
+    fn write_row(row: &[Self], buf: &mut Vec<u8>) {
+        for pixel in row {
+            pixel.write_bytes(buf);
+        }
+    }
+}
+
+impl Pixel for SrgbColor {
+    const BITS_PER_PIXEL: u32 = 24;
+    const WHITE: Self = SrgbColor {
+        r: 255,
+        g: 255,
+        b: 255,
+    };
+    const BLACK: Self = SrgbColor { r: 0, g: 0, b: 0 };
+
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        buf.extend_from_slice(&[self.r, self.g, self.b]);
+    }
+}
+
+/// Pixel of srgb_16 and adobe-rgb_16 raster. Which one it is depends on the header.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Rgb16Color {
+    pub r: u16,
+    pub g: u16,
+    pub b: u16,
+}
+
+impl Rgb16Color {
+    pub fn new(r: u16, g: u16, b: u16) -> Self {
+        Self { r, g, b }
+    }
+}
+
+impl From<&SrgbColor> for Rgb16Color {
+    /// Scales 8-bit components so that 255 becomes 65535.
+    fn from(color: &SrgbColor) -> Self {
+        let scale = |v: u8| v as u16 * 257;
+        Self::new(scale(color.r), scale(color.g), scale(color.b))
+    }
+}
+
+impl Pixel for Rgb16Color {
+    const BITS_PER_PIXEL: u32 = 48;
+    const WHITE: Self = Rgb16Color {
+        r: u16::MAX,
+        g: u16::MAX,
+        b: u16::MAX,
+    };
+    const BLACK: Self = Rgb16Color { r: 0, g: 0, b: 0 };
+
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        for component in [self.r, self.g, self.b] {
+            buf.extend_from_slice(&component.to_be_bytes());
+        }
+    }
+}
+
+/// sGray pixel: 0 is black and 255 is white.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct GrayColor(pub u8);
+
+impl From<&SrgbColor> for GrayColor {
+    /// Luminance with Rec. 709 coefficients, which sRGB shares.
+    fn from(color: &SrgbColor) -> Self {
+        let y = color.r as u32 * 2126 + color.g as u32 * 7152 + color.b as u32 * 722;
+        GrayColor(((y + 5000) / 10000) as u8)
+    }
+}
+
+impl Pixel for GrayColor {
+    const BITS_PER_PIXEL: u32 = 8;
+    const WHITE: Self = GrayColor(255);
+    const BLACK: Self = GrayColor(0);
+
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        buf.push(self.0);
+    }
+}
+
+/// Pixel of black_1 raster: true marks the pixel black.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct BlackColor(pub bool);
+
+impl Pixel for BlackColor {
+    const BITS_PER_PIXEL: u32 = 1;
+    const WHITE: Self = BlackColor(false);
+    const BLACK: Self = BlackColor(true);
+
+    fn write_bytes(&self, buf: &mut Vec<u8>) {
+        buf.push(if self.0 { 0x80 } else { 0 });
+    }
+
+    /// Packs 8 pixels in a byte from the most significant bit, padding the last byte with 0.
+    fn write_row(row: &[Self], buf: &mut Vec<u8>) {
+        for pixels in row.chunks(8) {
+            let mut byte = 0;
+            for (i, pixel) in pixels.iter().enumerate() {
+                if pixel.0 {
+                    byte |= 0x80 >> i;
+                }
+            }
+            buf.push(byte);
+        }
+    }
+}
+
+/// Run-length encoder for page data (PWG5102.4 4.4).
+/// Rows must be given from top to bottom, exactly `height` times, followed by finish().
+#[derive(Debug)]
+pub struct ImageEncoder<P = SrgbColor> {
+    width: u32,
+    height: u32,
+    /// Pending row as written in page data, kept to count repeats.
+    prev_row: Option<Vec<u8>>,
+    written_rows: u32,
+    comm_rows: u8,
+    /// Longest run of different pixels in a record, as the negative count byte.
+    min_comm: i16,
+    _pixel: PhantomData<P>,
+}
+
+impl<P> ImageEncoder<P>
+where
+    P: Pixel,
+{
+    pub fn new(width: u32, height: u32) -> Self {
+        Self {
+            width,
+            height,
+            prev_row: None,
+            written_rows: 0,
+            comm_rows: 0,
+            min_comm: -128,
+            _pixel: PhantomData,
+        }
+    }
+
+    /// Encoder for URF, where the count byte 0x80 fills the rest of the row with
+    /// white instead of starting 129 different pixels.
+    pub(crate) fn for_urf(width: u32, height: u32) -> Self {
+        Self {
+            min_comm: -127,
+            ..Self::new(width, height)
+        }
+    }
+
+    /// Compresses a row given as bytes of page data.
+    /// Runs are counted in pixels, or in bytes for pixels smaller than a byte.
+    /// Runs of different pixels are at most 1 - min_comm long.
+    fn do_encode_row<W>(writer: &mut W, bytes: &[u8], min_comm: i16) -> Result<usize, IppPrintError>
+    where
+        W: Write,
+    {
+        if bytes.is_empty() {
+            return Err(IppPrintError::RasterError(
+                "row mustn't be empty".to_string(),
+            ));
+        }
+        let unit = (P::BITS_PER_PIXEL as usize / 8).max(1);
+        let row = bytes.chunks_exact(unit).collect::<Vec<_>>();
+        let mut comm = vec![0i16; row.len()];
+        for x in (0..row.len() - 1).rev() {
+            comm[x] = if row[x + 1] == row[x] {
+                if comm[x + 1] < 0 {
+                    1
+                } else if comm[x + 1] == 127 {
+                    0
+                } else {
+                    comm[x + 1] + 1
+                }
+            } else if comm[x + 1] > 0 || comm[x + 1] <= min_comm {
+                0
+            } else {
+                comm[x + 1] - 1
+            }
+        }
+
+        let mut buf = vec![];
+
+        let mut x = 0;
+        while x < row.len() {
+            buf.push(comm[x] as u8);
+            if comm[x] < 0 {
+                for pixel in &row[x..x + (-comm[x] + 1) as usize] {
+                    buf.extend_from_slice(pixel);
+                }
+                x += -comm[x] as usize + 1;
+            } else {
+                buf.extend_from_slice(row[x]);
+                x += comm[x] as usize + 1;
+            }
+        }
+
+        writer.write_all(&buf)?;
+        Ok(buf.len())
+    }
+
+    pub fn write_row<W>(&mut self, writer: &mut W, row: &[P]) -> Result<usize, IppPrintError>
+    where
+        W: Write,
+    {
+        if row.len() != self.width as usize {
+            return Err(IppPrintError::RasterError(format!(
+                "row has {} pixels but the page width is {}",
+                row.len(),
+                self.width
+            )));
+        }
+        if self.written_rows >= self.height {
+            return Err(IppPrintError::RasterError(format!(
+                "page already has {} rows",
+                self.height
+            )));
+        }
+
+        let mut bytes = vec![];
+        P::write_row(row, &mut bytes);
+
+        let mut written = 0;
+
+        match self.prev_row.take() {
+            // A record repeats a row at most 256 times, so longer runs are split.
+            Some(prev_row) if prev_row == bytes && self.comm_rows < u8::MAX => {
+                self.comm_rows += 1;
+                self.prev_row = Some(prev_row);
+            }
+            Some(prev_row) => {
+                writer.write_all(&[self.comm_rows])?;
+                written += 1 + ImageEncoder::<P>::do_encode_row(writer, &prev_row, self.min_comm)?;
+                self.comm_rows = 0;
+                self.prev_row = Some(bytes);
+            }
+            None => self.prev_row = Some(bytes),
+        }
+        self.written_rows += 1;
+
+        Ok(written)
+    }
+
+    /// Flushes the pending row. Fails if fewer than `height` rows were given.
+    pub fn finish<W>(mut self, writer: &mut W) -> Result<usize, IppPrintError>
+    where
+        W: Write,
+    {
+        if self.written_rows != self.height {
+            return Err(IppPrintError::RasterError(format!(
+                "page has {} rows but the height is {}",
+                self.written_rows, self.height
+            )));
+        }
+
+        match self.prev_row.take() {
+            Some(prev_row) => {
+                writer.write_all(&[self.comm_rows])?;
+                Ok(1 + ImageEncoder::<P>::do_encode_row(writer, &prev_row, self.min_comm)?)
+            }
+            None => Ok(0),
+        }
+    }
+}
+
+/// pwg-raster-document-sheet-back: How the printer expects back sides of duplex sheets.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SheetBack {
+    /// Back sides are printed as is.
+    Normal,
+    /// Back sides are flipped along the binding edge.
+    Flipped,
+    /// Back sides of long-edge duplex are rotated 180 degrees.
+    Rotated,
+    /// Back sides of short-edge duplex are rotated 180 degrees.
+    ManualTumble,
+}
+
+/// cross_feed_transform and feed_transform of the page after pages_written pages.
+pub(crate) fn back_side_transform(
+    sides: Sides,
+    sheet_back: SheetBack,
+    pages_written: u32,
+) -> (i32, i32) {
+    match sides {
+        _ if pages_written.is_multiple_of(2) => (1, 1),
+        Sides::OneSided => (1, 1),
+        Sides::TwoSidedLongEdge => sheet_back.transform(false),
+        Sides::TwoSidedShortEdge => sheet_back.transform(true),
+    }
+}
+
+impl SheetBack {
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        match keyword {
+            "normal" => Some(Self::Normal),
+            "flipped" => Some(Self::Flipped),
+            "rotated" => Some(Self::Rotated),
+            "manual-tumble" => Some(Self::ManualTumble),
+            _ => None,
+        }
+    }
+
+    /// cross_feed_transform and feed_transform for back sides.
+    pub(crate) fn transform(&self, tumble: bool) -> (i32, i32) {
+        match (self, tumble) {
+            (Self::Flipped, true) => (-1, 1),
+            (Self::Flipped, false) => (1, -1),
+            (Self::Rotated, false) | (Self::ManualTumble, true) => (-1, -1),
+            _ => (1, 1),
+        }
+    }
+}
+
+/// Lays out content of content_width pixels per row on a whole page of header,
+/// with its top-left corner at that of the image box and the rest white.
+/// Fails if content doesn't fit in the image box rather than getting clipped.
+pub fn place_in_image_box<P>(
+    header: &PageHeader,
+    content: &[P],
+    content_width: usize,
+) -> Result<Vec<P>, IppPrintError>
+where
+    P: Pixel,
+{
+    let [left, top, right, bottom] = header.image_box().map(|e| e as usize);
+    let content_height = content.len().checked_div(content_width).unwrap_or(0);
+    if content_width * content_height != content.len()
+        || content_width > right - left
+        || content_height > bottom - top
+    {
+        return Err(IppPrintError::RasterError(format!(
+            "content of {} pixels with width {} doesn't fit in the image box {}x{}",
+            content.len(),
+            content_width,
+            right - left,
+            bottom - top
+        )));
+    }
+
+    let width = header.width as usize;
+    let mut page = vec![P::WHITE; width * header.height as usize];
+    for (y, row) in content.chunks(content_width.max(1)).enumerate() {
+        let start = (top + y) * width + left;
+        page[start..start + content_width].clone_from_slice(row);
+    }
+    Ok(page)
+}
+
+/// Writer of a raster document, so that pages can be rendered regardless of the format.
+pub trait PageWriter {
+    /// Writes a page. pixels are in row-major order from the top-left corner of the front side.
+    /// The pixel type must match bits_per_pixel of the header.
+    fn write_page<P>(&mut self, pixels: &[P]) -> Result<usize, IppPrintError>
+    where
+        P: Pixel;
+}
+
+/// Writes a multi-page PWG Raster document.
+/// If the header says duplex, even pages (back sides) are transformed as the
+/// printer expects according to sheet_back.
+pub struct RasterDocumentWriter<W> {
+    writer: W,
+    header: PageHeader,
+    sheet_back: SheetBack,
+    sync_word: SyncWord,
+    pages: u32,
+}
+
+impl<W> RasterDocumentWriter<W>
+where
+    W: Write,
+{
+    /// Writes the synchronization word. Every page uses header.
+    pub fn new(
+        writer: W,
+        header: PageHeader,
+        sheet_back: SheetBack,
+    ) -> Result<Self, IppPrintError> {
+        RasterDocumentWriter::with_sync_word(writer, header, sheet_back, SyncWord::V2)
+    }
+
+    /// Writes a CUPS raster stream of the version and byte order of sync_word.
+    pub fn with_sync_word(
+        mut writer: W,
+        header: PageHeader,
+        sheet_back: SheetBack,
+        sync_word: SyncWord,
+    ) -> Result<Self, IppPrintError> {
+        // Pixel types write their components in big-endian.
+        if sync_word.is_little_endian() && header.bits_per_color > 8 {
+            return Err(IppPrintError::Unsupported(format!(
+                "{} bits per color in little-endian raster",
+                header.bits_per_color
+            )));
+        }
+        writer.write_all(sync_word.bytes())?;
+
+        Ok(Self {
+            writer,
+            header,
+            sheet_back,
+            sync_word,
+            pages: 0,
+        })
+    }
+
+    /// Writes a page given as rows from the top of the front side, e.g. borrowed
+    /// from a framebuffer. The pixel type must match bits_per_pixel of the header.
+    pub fn write_page_rows<P, I, R>(&mut self, rows: I) -> Result<usize, IppPrintError>
+    where
+        P: Pixel,
+        I: IntoIterator<Item = R>,
+        R: AsRef<[P]>,
+    {
+        let (header, cross_feed, feed) = self.next_header::<P>()?;
+
+        // Compressed rows can be a few bytes each.
+        let mut writer = BufWriter::new(&mut self.writer);
+        let mut written = write_header(&mut writer, &header, self.sync_word)?;
+        written += write_page_data(
+            &mut writer,
+            &header,
+            cross_feed,
+            feed,
+            rows,
+            self.sync_word
+                .is_compressed()
+                .then(|| ImageEncoder::new(header.width, header.height)),
+        )?;
+        writer.flush()?;
+        tracing::debug!(
+            page = self.pages + 1,
+            width = header.width,
+            height = header.height,
+            bytes = written,
+            "wrote raster page"
+        );
+
+        self.pages += 1;
+        Ok(written)
+    }
+
+    /// Like write_page, but compresses bands of rows on the rayon thread pool.
+    /// Repeated rows aren't merged across bands, so the output is slightly larger.
+    #[cfg(feature = "rayon")]
+    pub fn write_page_parallel<P>(&mut self, pixels: &[P]) -> Result<usize, IppPrintError>
+    where
+        P: Pixel + Sync,
+    {
+        use rayon::prelude::*;
+
+        if !self.sync_word.is_compressed() {
+            return self.write_page(pixels);
+        }
+
+        let (width, height) = (self.header.width as usize, self.header.height as usize);
+        if pixels.len() != width * height {
+            return Err(IppPrintError::RasterError(format!(
+                "page has {} pixels but the header says {}x{}",
+                pixels.len(),
+                width,
+                height
+            )));
+        }
+        let (header, cross_feed, feed) = self.next_header::<P>()?;
+
+        let mut rows = pixels.chunks(width.max(1)).collect::<Vec<_>>();
+        if feed < 0 {
+            rows.reverse();
+        }
+        let bands = rows
+            .par_chunks(PARALLEL_BAND_ROWS)
+            .map(|band| {
+                let mut buf = vec![];
+                let mut encoder = ImageEncoder::new(header.width, band.len() as u32);
+                for row in band {
+                    if cross_feed < 0 {
+                        let row = row.iter().rev().cloned().collect::<Vec<_>>();
+                        encoder.write_row(&mut buf, &row)?;
+                    } else {
+                        encoder.write_row(&mut buf, row)?;
+                    }
+                }
+                encoder.finish(&mut buf)?;
+                Ok(buf)
+            })
+            .collect::<Result<Vec<_>, IppPrintError>>()?;
+
+        let mut writer = BufWriter::new(&mut self.writer);
+        let mut written = write_header(&mut writer, &header, self.sync_word)?;
+        for band in bands {
+            writer.write_all(&band)?;
+            written += band.len();
+        }
+        writer.flush()?;
+        tracing::debug!(
+            page = self.pages + 1,
+            width = header.width,
+            height = header.height,
+            bytes = written,
+            "wrote raster page in parallel"
+        );
+
+        self.pages += 1;
+        Ok(written)
+    }
+
+    /// Header of the next page with the transform for its side.
+    /// Returns the header, cross_feed_transform, and feed_transform.
+    fn next_header<P>(&self) -> Result<(PageHeader, i32, i32), IppPrintError>
+    where
+        P: Pixel,
+    {
+        if P::BITS_PER_PIXEL != self.header.bits_per_pixel {
+            return Err(IppPrintError::RasterError(format!(
+                "pixels have {} bits but the header says {}",
+                P::BITS_PER_PIXEL,
+                self.header.bits_per_pixel
+            )));
+        }
+
+        let mut header = self.header.clone();
+        let (cross_feed, feed) = back_side_transform(header.sides(), self.sheet_back, self.pages);
+        header.cross_feed_transform = cross_feed;
+        header.feed_transform = feed;
+        if cross_feed < 0 && header.image_box_right != 0 {
+            let left = header.image_box_left;
+            header.image_box_left = header.width - header.image_box_right;
+            header.image_box_right = header.width - left;
+        }
+        if feed < 0 && header.image_box_bottom != 0 {
+            let top = header.image_box_top;
+            header.image_box_top = header.height - header.image_box_bottom;
+            header.image_box_bottom = header.height - top;
+        }
+
+        Ok((header, cross_feed, feed))
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W> PageWriter for RasterDocumentWriter<W>
+where
+    W: Write,
+{
+    fn write_page<P>(&mut self, pixels: &[P]) -> Result<usize, IppPrintError>
+    where
+        P: Pixel,
+    {
+        let (width, height) = (self.header.width as usize, self.header.height as usize);
+        if pixels.len() != width * height {
+            return Err(IppPrintError::RasterError(format!(
+                "page has {} pixels but the header says {}x{}",
+                pixels.len(),
+                width,
+                height
+            )));
+        }
+        self.write_page_rows(pixels.chunks(width.max(1)))
+    }
+}
+
+/// Writes a page header in the byte order of sync_word.
+fn write_header<W>(
+    writer: &mut W,
+    header: &PageHeader,
+    sync_word: SyncWord,
+) -> Result<usize, IppPrintError>
+where
+    W: Write,
+{
+    let mut buf = header.to_bytes();
+    if sync_word.is_little_endian() {
+        swap_header_numbers(&mut buf);
+    }
+    writer.write_all(&buf)?;
+    Ok(buf.len())
+}
+
+/// Writes rows given from the top of the front side, transforming them as
+/// cross_feed and feed say. Rows are compressed with encoder, or written as is
+/// without it for CUPS raster v3. Shared with URF, which compresses pages
+/// almost the same way.
+pub(crate) fn write_page_data<W, P, I, R>(
+    writer: &mut W,
+    header: &PageHeader,
+    cross_feed: i32,
+    feed: i32,
+    rows: I,
+    mut encoder: Option<ImageEncoder<P>>,
+) -> Result<usize, IppPrintError>
+where
+    W: Write,
+    P: Pixel,
+    I: IntoIterator<Item = R>,
+    R: AsRef<[P]>,
+{
+    let mut written = 0;
+    let mut raw_rows = 0;
+    let mut write_row = |row: &[P]| {
+        let reversed;
+        let row = if cross_feed < 0 {
+            reversed = row.iter().rev().cloned().collect::<Vec<_>>();
+            &reversed
+        } else {
+            row
+        };
+        if let Some(encoder) = &mut encoder {
+            return encoder.write_row(writer, row);
+        }
+
+        if row.len() != header.width as usize || raw_rows >= header.height {
+            return Err(IppPrintError::RasterError(format!(
+                "row {} with {} pixels doesn't fit in {}x{}",
+                raw_rows,
+                row.len(),
+                header.width,
+                header.height
+            )));
+        }
+        let mut bytes = vec![];
+        P::write_row(row, &mut bytes);
+        writer.write_all(&bytes)?;
+        raw_rows += 1;
+        Ok(bytes.len())
+    };
+    if feed < 0 {
+        // Only references are collected unless the caller gives owned rows.
+        for row in rows.into_iter().collect::<Vec<_>>().iter().rev() {
+            written += write_row(row.as_ref())?;
+        }
+    } else {
+        for row in rows {
+            written += write_row(row.as_ref())?;
+        }
+    }
+
+    if let Some(encoder) = encoder {
+        written += encoder.finish(writer)?;
+    } else if raw_rows != header.height {
+        return Err(IppPrintError::RasterError(format!(
+            "page has {} rows but the height is {}",
+            raw_rows, header.height
+        )));
+    }
+    Ok(written)
+}
+
+/// Rows per band of write_page_parallel. A record repeats a row at most 256
+/// times, so bands of this size lose at most one record of compression.
+#[cfg(feature = "rayon")]
+const PARALLEL_BAND_ROWS: usize = 256;
+
+/// Pages larger than this are rejected by PwgDocument, and rows by RasterReader,
+/// so that a corrupt header can't make the decoder allocate unbounded memory.
+const MAX_PAGE_BYTES: u64 = 1 << 30;
+
+/// A page of a decoded PWG Raster document.
+#[derive(Debug, Clone)]
+pub struct PwgPage {
+    pub header: PageHeader,
+    /// Uncompressed rows from top to bottom, bytes_per_line bytes each.
+    pub rows: Vec<Vec<u8>>,
+}
+
+impl PwgPage {
+    /// Converts to 8-bit sRGB, e.g. to view or re-encode the page. 16-bit colors
+    /// are truncated, and CMYK is converted without color management.
+    pub fn to_srgb(&self) -> Result<Image<SrgbColor>, IppPrintError> {
+        let header = &self.header;
+        let (width, height) = (header.width, header.height);
+        let bytes_per_color = (header.bits_per_color / 8).max(1) as usize;
+        let pixel = |row: &[u8], x: usize| -> [u8; 3] {
+            // Most significant byte of each color
+            let color = |i: usize| row[(x * header.num_colors as usize + i) * bytes_per_color];
+            match (header.color_space(), header.bits_per_color) {
+                (Some(ColorSpace::Black), 1) => {
+                    let black = row[x / 8] & (0x80 >> (x % 8)) != 0;
+                    [if black { 0 } else { 255 }; 3]
+                }
+                (Some(ColorSpace::Sgray), _) => [color(0); 3],
+                (Some(ColorSpace::Cmyk), _) => {
+                    let k = 255 - color(3) as u32;
+                    [0, 1, 2].map(|i| ((255 - color(i) as u32) * k / 255) as u8)
+                }
+                _ => [color(0), color(1), color(2)],
+            }
+        };
+        match (header.color_space(), header.bits_per_color) {
+            (Some(ColorSpace::Black), 1)
+            | (Some(ColorSpace::Sgray | ColorSpace::Cmyk), 8 | 16)
+            | (Some(ColorSpace::Srgb | ColorSpace::AdobeRgb | ColorSpace::Rgb), 8 | 16) => {}
+            (color_space, bits_per_color) => {
+                return Err(IppPrintError::Unsupported(format!(
+                    "conversion of {:?} with {} bits per color to RGB",
+                    color_space, bits_per_color
+                )))
+            }
+        }
+        if self.rows.len() != height as usize
+            || self
+                .rows
+                .iter()
+                .any(|e| e.len() != header.bytes_per_line as usize)
+        {
+            return Err(IppPrintError::RasterError(format!(
+                "rows don't match the header: {}x{}",
+                width, height
+            )));
+        }
+
+        let pixels = self
+            .rows
+            .iter()
+            .flat_map(|row| (0..width as usize).map(move |x| pixel(row, x)))
+            .map(|[r, g, b]| SrgbColor::new(r, g, b))
+            .collect();
+        Image::new(width as usize, height as usize, pixels)
+    }
+}
+
+#[cfg(feature = "image")]
+impl PwgPage {
+    /// Converts to 8-bit RGB for viewing, as with to_srgb.
+    pub fn to_rgb_image(&self) -> Result<image::RgbImage, IppPrintError> {
+        Ok(self.to_srgb()?.to_rgb_image())
+    }
+
+    /// Saves the page as a PNG file.
+    pub fn to_png<P>(&self, path: P) -> Result<(), IppPrintError>
+    where
+        P: AsRef<std::path::Path>,
+    {
+        self.to_srgb()?.to_png(path)
+    }
+}
+
+/// A decoded PWG Raster or CUPS raster document.
+#[derive(Debug, Clone)]
+pub struct PwgDocument {
+    /// How the document was stored. Decoded headers and rows are always big-endian.
+    pub sync_word: SyncWord,
+    pub pages: Vec<PwgPage>,
+}
+
+impl PwgDocument {
+    /// Decodes a whole document, starting with the synchronization word.
+    /// Headers and 16-bit colors of little-endian streams are read in that byte order.
+    pub fn read_from_stream<R>(reader: &mut R) -> Result<Self, IppPrintError>
+    where
+        R: Read,
+    {
+        let mut reader = RasterReader::new(reader)?;
+        let mut pages = vec![];
+        while let Some(header) = reader.next_page()? {
+            let header = header.clone();
+            if header.bytes_per_line as u64 * header.height as u64 > MAX_PAGE_BYTES {
+                return Err(IppPrintError::RasterError(format!(
+                    "page is too large: {}x{}",
+                    header.width, header.height
+                )));
+            }
+            let mut rows = Vec::with_capacity(header.height as usize);
+            while let Some(row) = reader.next_page_row()? {
+                rows.push(row.to_vec());
+            }
+            pages.push(PwgPage { header, rows });
+        }
+
+        Ok(Self {
+            sync_word: reader.sync_word(),
+            pages,
+        })
+    }
+}
+
+/// (page index, row index, row) returned by RasterReader::next_row.
+/// Both indices start from 0.
+pub type DocumentRow<'a> = (usize, usize, &'a [u8]);
+
+/// Decodes a PWG Raster or CUPS raster stream a row at a time, keeping only the
+/// current row in memory, for documents too large for PwgDocument.
+pub struct RasterReader<R> {
+    reader: R,
+    sync_word: SyncWord,
+    /// Header of the current page
+    header: Option<PageHeader>,
+    /// Number of page headers read so far
+    pages: usize,
+    /// Number of rows of the current page returned so far
+    rows: usize,
+    row: Vec<u8>,
+    /// How many more times the current row is returned before the next one is decoded
+    repeat: usize,
+}
+
+impl<R> RasterReader<R>
+where
+    R: Read,
+{
+    /// Reads the synchronization word.
+    pub fn new(mut reader: R) -> Result<Self, IppPrintError> {
+        let mut bytes = [0u8; 4];
+        reader.read_exact(&mut bytes)?;
+        let sync_word =
+            SyncWord::from_bytes(&bytes).ok_or(IppPrintError::InvalidSyncWord(bytes))?;
+
+        Ok(Self {
+            reader,
+            sync_word,
+            header: None,
+            pages: 0,
+            rows: 0,
+            row: vec![],
+            repeat: 0,
+        })
+    }
+
+    pub fn sync_word(&self) -> SyncWord {
+        self.sync_word
+    }
+
+    /// The underlying reader, e.g. to see how far the stream has been read.
+    pub fn get_ref(&self) -> &R {
+        &self.reader
+    }
+
+    /// Header of the current page, None before the first one.
+    pub fn header(&self) -> Option<&PageHeader> {
+        self.header.as_ref()
+    }
+
+    /// Skips the rest of the current page and reads the header of the next one.
+    /// Returns None at the end of the stream.
+    pub fn next_page(&mut self) -> Result<Option<&PageHeader>, IppPrintError> {
+        while self.decode_row()? {}
+
+        let mut buf = [0u8; 1796];
+        // A document ends where the next header would start.
+        let len = read_fully(&mut self.reader, &mut buf)?;
+        if len == 0 {
+            self.header = None;
+            return Ok(None);
+        }
+        if len < buf.len() {
+            return Err(IppPrintError::RasterError(format!(
+                "page {} has a truncated header",
+                self.pages + 1
+            )));
+        }
+
+        if self.sync_word.is_little_endian() {
+            swap_header_numbers(&mut buf);
+        }
+        let header = PageHeader::from_bytes(&buf)?;
+        check_geometry(&header)?;
+        self.pages += 1;
+        tracing::debug!(
+            page = self.pages,
+            width = header.width,
+            height = header.height,
+            bits_per_pixel = header.bits_per_pixel,
+            color_space = header.color_space,
+            resolution = ?header.hw_resolution,
+            page_size_name = %header.page_size_name(),
+            "read raster page header"
+        );
+        self.header = Some(header);
+        self.rows = 0;
+        self.repeat = 0;
+        Ok(self.header.as_ref())
+    }
+
+    /// Next row of the current page, or None after its last row.
+    /// Rows are bytes_per_line bytes with 16-bit colors in big-endian.
+    pub fn next_page_row(&mut self) -> Result<Option<&[u8]>, IppPrintError> {
+        if self.decode_row()? {
+            Ok(Some(&self.row))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Next row of the document, moving on to next pages.
+    pub fn next_row(&mut self) -> Result<Option<DocumentRow<'_>>, IppPrintError> {
+        loop {
+            if self.decode_row()? {
+                return Ok(Some((self.pages - 1, self.rows - 1, &self.row)));
+            }
+            if self.next_page()?.is_none() {
+                return Ok(None);
+            }
+        }
+    }
+
+    /// Decodes the next row of the current page into row, which is run-length
+    /// encoded (PWG5102.4 4.4) unless the stream is CUPS raster v3.
+    /// Returns false if there are no more rows.
+    fn decode_row(&mut self) -> Result<bool, IppPrintError> {
+        let Some(header) = &self.header else {
+            return Ok(false);
+        };
+        let height = header.height as usize;
+        let bytes_per_line = header.bytes_per_line as usize;
+        // Pixels smaller than a byte are compressed by bytes.
+        let unit = (header.bits_per_pixel as usize / 8).max(1);
+        let swap_bytes = self.sync_word.is_little_endian() && header.bits_per_color == 16;
+        if self.rows >= height {
+            return Ok(false);
+        }
+        if self.repeat > 0 {
+            self.repeat -= 1;
+            self.rows += 1;
+            return Ok(true);
+        }
+
+        let reader = &mut self.reader;
+        let row = &mut self.row;
+        row.clear();
+        let mut byte = [0u8; 1];
+        if self.sync_word.is_compressed() {
+            reader.read_exact(&mut byte)?;
+            let repeat = byte[0] as usize;
+            if self.rows + repeat + 1 > height {
+                return Err(IppPrintError::RasterError(format!(
+                    "page has more than {} rows",
+                    height
+                )));
+            }
+            self.repeat = repeat;
+
+            while row.len() < bytes_per_line {
+                reader.read_exact(&mut byte)?;
+                let count = if byte[0] < 128 {
+                    byte[0] as usize + 1
+                } else {
+                    257 - byte[0] as usize
+                };
+                if row.len() + count * unit > bytes_per_line {
+                    return Err(IppPrintError::RasterError(format!(
+                        "row {} is longer than {} bytes",
+                        self.rows, bytes_per_line
+                    )));
+                }
+
+                let start = row.len();
+                if byte[0] < 128 {
+                    row.resize(start + unit, 0);
+                    reader.read_exact(&mut row[start..])?;
+                    for _ in 1..count {
+                        row.extend_from_within(start..start + unit);
+                    }
+                } else {
+                    row.resize(start + count * unit, 0);
+                    reader.read_exact(&mut row[start..])?;
+                }
+            }
+        } else {
+            row.resize(bytes_per_line, 0);
+            reader.read_exact(row)?;
+        }
+
+        if swap_bytes {
+            for sample in row.chunks_exact_mut(2) {
+                sample.swap(0, 1);
+            }
+        }
+        self.rows += 1;
+        Ok(true)
+    }
+}
+
+/// Rejects headers whose pages can't be decoded.
+fn check_geometry(header: &PageHeader) -> Result<(), IppPrintError> {
+    let bits_per_pixel = header.bits_per_pixel;
+    if header.width == 0
+        || !matches!(bits_per_pixel, 1 | 2 | 4)
+            && (bits_per_pixel == 0 || !bits_per_pixel.is_multiple_of(8))
+    {
+        return Err(IppPrintError::RasterError(format!(
+            "unsupported page geometry: width={} bits_per_pixel={}",
+            header.width, bits_per_pixel
+        )));
+    }
+    if header.bytes_per_line as u64 != (header.width as u64 * bits_per_pixel as u64).div_ceil(8) {
+        return Err(IppPrintError::RasterError(format!(
+            "bytes_per_line {} doesn't match width {} and bits_per_pixel {}",
+            header.bytes_per_line, header.width, bits_per_pixel
+        )));
+    }
+    if header.bytes_per_line as u64 > MAX_PAGE_BYTES {
+        return Err(IppPrintError::RasterError(format!(
+            "row is too large: {} pixels",
+            header.width
+        )));
+    }
+    Ok(())
+}
+
+/// Reads until buf is full or the end of the stream. Returns the number of bytes read.
+fn read_fully<R>(reader: &mut R, buf: &mut [u8]) -> Result<usize, IppPrintError>
+where
+    R: Read,
+{
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(err) if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(err.into()),
+        }
+    }
+    Ok(len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode_row_of(row: &[SrgbColor]) -> Vec<u8> {
+        let mut bytes = vec![];
+        SrgbColor::write_row(row, &mut bytes);
+        let mut out = Vec::new();
+        ImageEncoder::<SrgbColor>::do_encode_row(&mut out, &bytes, -128).unwrap();
+        out
+    }
+
+    #[test]
+    fn encode_row() {
+        let data = [
+            0xFFFF00, 0x0000FF, 0xFFFF00, 0xFFFFFF, 0xFFFFFF, 0xFFFFFF, 0x00FF00, 0xFFFFFF,
+        ]
+        .into_iter()
+        .map(SrgbColor::from)
+        .collect::<Vec<_>>();
+        let out = encode_row_of(&data);
+        let expected_bytes = vec![
+            0xFE, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0x00, 0x02, 0xFF, 0xFF, 0xFF,
+            0xFF, 0x00, 0xFF, 0x00, 0xFF, 0xFF, 0xFF,
+        ];
+        assert_eq!(expected_bytes, out);
+    }
+
+    #[test]
+    fn encode_row_long_comm_pixels() {
+        let data = [0; 200].map(SrgbColor::from);
+        let out = encode_row_of(&data);
+        let expected_bytes = vec![0x47, 0x00, 0x00, 0x00, 0x7F, 0x00, 0x00, 0x00];
+        assert_eq!(expected_bytes, out);
+    }
+
+    #[test]
+    fn encode_row_long_diff_pixels() {
+        let data = (0..200).map(SrgbColor::from).collect::<Vec<_>>();
+        let out = encode_row_of(&data);
         let expected_bytes = vec![
             186, 0, 0, 0, 0, 0, 1, 0, 0, 2, 0, 0, 3, 0, 0, 4, 0, 0, 5, 0, 0, 6, 0, 0, 7, 0, 0, 8,
             0, 0, 9, 0, 0, 10, 0, 0, 11, 0, 0, 12, 0, 0, 13, 0, 0, 14, 0, 0, 15, 0, 0, 16, 0, 0,
@@ -927,11 +2302,86 @@ mod tests {
         assert_eq!(expected_bytes, out);
     }
 
+    #[test]
+    fn set_media_size() {
+        let mut hdr = PageHeader::default();
+        hdr.set_media_size(&MediaSize::from_name("na_letter_8.5x11in").unwrap());
+        assert_eq!([612, 792], hdr.page_size);
+        assert_eq!(b"na_letter_8.5x11in\0", &hdr.page_size_name[..19]);
+        assert_eq!(2550, hdr.width());
+        assert_eq!(3300, hdr.height());
+        assert_eq!(2550 * 3, hdr.bytes_per_line);
+    }
+
+    #[test]
+    fn set_resolution_and_raster_type() {
+        let mut hdr = PageHeader::default();
+        hdr.set_resolution(600, 600).unwrap();
+        assert_eq!([600, 600], hdr.hw_resolution);
+        assert_eq!(4960, hdr.width());
+        assert_eq!(7014, hdr.height());
+        assert_eq!(4960 * 3, hdr.bytes_per_line);
+
+        hdr.set_raster_type("sgray_8").unwrap();
+        assert_eq!(Some(ColorSpace::Sgray), hdr.color_space());
+        assert_eq!(8, hdr.bits_per_pixel);
+        assert_eq!(4960, hdr.bytes_per_line);
+
+        hdr.set_raster_type("black_1").unwrap();
+        assert_eq!(620, hdr.bytes_per_line);
+
+        assert!(hdr.set_raster_type("rgb_8").is_err());
+
+        // Rows of black_1 end with a partial byte unless the width is a multiple of 8.
+        let mut hdr = PageHeader::default();
+        hdr.set_raster_type("black_1").unwrap();
+        hdr.set_media_size(&MediaSize::from_name("iso_a4_210x297mm").unwrap());
+        hdr.set_resolution(203, 203).unwrap();
+        assert_eq!(1678, hdr.width());
+        assert_eq!(210, hdr.bytes_per_line);
+
+        hdr.hw_resolution = [0, 0];
+        assert!(hdr.set_resolution(300, 300).is_err());
+        assert_eq!(1678, hdr.width());
+    }
+
+    #[test]
+    fn apply_media_preset() {
+        let mut hdr = PageHeader::default();
+        MediaPreset::Hagaki.apply(&mut hdr);
+
+        assert_eq!([283, 420], hdr.page_size);
+        assert_eq!(b"jpn_hagaki_100x148mm\0", &hdr.page_size_name[..21]);
+        assert_eq!(1181, hdr.width);
+        assert_eq!(1748, hdr.height);
+        assert_eq!(1181 * 3, hdr.bytes_per_line);
+        assert_eq!(Some(MediaPosition::Hagaki), hdr.media_position());
+        assert_eq!(Some(Orientation::Portrait), hdr.orientation());
+        assert_eq!(
+            [35, 35, 1146, 1713],
+            [
+                hdr.image_box_left,
+                hdr.image_box_top,
+                hdr.image_box_right,
+                hdr.image_box_bottom,
+            ]
+        );
+
+        MediaPreset::EnvelopeDl.apply(&mut hdr);
+        assert_eq!([312, 624], hdr.page_size);
+        assert_eq!(b"iso_dl_110x220mm\0\0\0\0\0", &hdr.page_size_name[..21]);
+        assert_eq!(Some(MediaPosition::Envelope), hdr.media_position());
+        assert_eq!(Some(Orientation::Landscape), hdr.orientation());
+
+        let mut out = Vec::new();
+        assert_eq!(1796, hdr.write_to_stream(&mut out).unwrap());
+    }
+
     #[test]
     fn encode_image() {
         // test with sample sRGB bitmap described in the spec.
 
-        let mut encoder = ImageEncoder::new(8, 8);
+        let mut encoder = ImageEncoder::<SrgbColor>::new(8, 8);
 
         #[rustfmt::skip]
         let image_data = [
@@ -948,9 +2398,10 @@ mod tests {
         let mut out = Vec::new();
         for row in image_data {
             encoder
-                .write_row(&mut out, row.into_iter().map(|e| e.into()).collect::<_>())
+                .write_row(&mut out, &row.map(SrgbColor::from))
                 .unwrap();
         }
+        encoder.finish(&mut out).unwrap();
         let expected_bytes = vec![
             0x00, 0x00, 0xFF, 0xFF, 0xFF, 0x02, 0xFF, 0xFF, 0x00, 0x03, 0xFF, 0xFF, 0xFF, 0x00,
             0xFE, 0xFF, 0xFF, 0x00, 0x00, 0x00, 0xFF, 0xFF, 0xFF, 0x00, 0x02, 0xFF, 0xFF, 0xFF,
@@ -962,4 +2413,823 @@ mod tests {
         ];
         assert_eq!(expected_bytes, out);
     }
+
+    #[test]
+    fn encode_long_repeated_rows() {
+        for (height, records) in [(256, 1), (257, 2), (3000, 12)] {
+            let mut encoder = ImageEncoder::new(2, height);
+            let mut out = Vec::new();
+            for _ in 0..height {
+                encoder
+                    .write_row(&mut out, &[GrayColor(0), GrayColor(0)])
+                    .unwrap();
+            }
+            encoder.finish(&mut out).unwrap();
+
+            // Each record is the repeat count followed by a run of 2 pixels.
+            assert_eq!(records * 3, out.len());
+            let repeats = out.chunks(3).map(|e| e[0] as u32 + 1).collect::<Vec<_>>();
+            assert_eq!(height, repeats.iter().sum::<u32>());
+            assert!(repeats[..records - 1].iter().all(|e| *e == 256));
+
+            let header = PageHeader {
+                width: 2,
+                height,
+                bits_per_pixel: 8,
+                bytes_per_line: 2,
+                ..Default::default()
+            };
+            let mut stream = b"RaS2".to_vec();
+            header.write_to_stream(&mut stream).unwrap();
+            stream.extend_from_slice(&out);
+            let rows = PwgDocument::read_from_stream(&mut stream.as_slice())
+                .unwrap()
+                .pages
+                .remove(0)
+                .rows;
+            assert_eq!(vec![vec![0, 0]; height as usize], rows);
+        }
+    }
+
+    #[test]
+    fn sheet_back_transform() {
+        assert_eq!((1, 1), SheetBack::Normal.transform(false));
+        assert_eq!((1, -1), SheetBack::Flipped.transform(false));
+        assert_eq!((-1, 1), SheetBack::Flipped.transform(true));
+        assert_eq!((-1, -1), SheetBack::Rotated.transform(false));
+        assert_eq!((1, 1), SheetBack::Rotated.transform(true));
+        assert_eq!((1, 1), SheetBack::ManualTumble.transform(false));
+        assert_eq!((-1, -1), SheetBack::ManualTumble.transform(true));
+    }
+
+    #[test]
+    fn write_duplex_document() {
+        let mut header = PageHeader {
+            width: 2,
+            height: 2,
+            bytes_per_line: 6,
+            ..Default::default()
+        };
+        header.set_sides(Sides::TwoSidedLongEdge);
+        let page = [0x000000, 0xFF0000, 0x00FF00, 0x0000FF]
+            .into_iter()
+            .map(SrgbColor::from)
+            .collect::<Vec<_>>();
+
+        let mut writer =
+            RasterDocumentWriter::new(Vec::new(), header.clone(), SheetBack::Rotated).unwrap();
+        writer.write_page(&page).unwrap();
+        writer.write_page(&page).unwrap();
+        let out = writer.into_inner();
+
+        let page_len = 1796 + 2 * 8;
+        assert_eq!(4 + page_len * 2, out.len());
+
+        // The front side is written as is.
+        let mut expected = Vec::new();
+        let mut encoder = ImageEncoder::<SrgbColor>::new(2, 2);
+        for row in page.chunks(2) {
+            encoder.write_row(&mut expected, row).unwrap();
+        }
+        encoder.finish(&mut expected).unwrap();
+        assert_eq!(expected, out[4 + 1796..4 + page_len]);
+
+        // The back side is rotated 180 degrees.
+        let mut expected = Vec::new();
+        let mut encoder = ImageEncoder::<SrgbColor>::new(2, 2);
+        for row in page.chunks(2).rev() {
+            encoder
+                .write_row(
+                    &mut expected,
+                    &row.iter().rev().cloned().collect::<Vec<_>>(),
+                )
+                .unwrap();
+        }
+        encoder.finish(&mut expected).unwrap();
+        assert_eq!(expected, out[4 + page_len + 1796..]);
+
+        // CrossFeedTransform and FeedTransform
+        let back_header = &out[4 + page_len..];
+        assert_eq!([0xFF; 4], back_header[456..460]);
+        assert_eq!([0xFF; 4], back_header[460..464]);
+    }
+
+    #[test]
+    fn write_page_rows() {
+        let mut header = PageHeader {
+            width: 2,
+            height: 3,
+            ..Default::default()
+        };
+        header.set_raster_type("sgray_8").unwrap();
+        header.set_sides(Sides::TwoSidedLongEdge);
+        let framebuffer = [[1, 2], [3, 4], [5, 6]].map(|row| row.map(GrayColor));
+
+        let mut writer =
+            RasterDocumentWriter::new(Vec::new(), header.clone(), SheetBack::Rotated).unwrap();
+        for _ in 0..2 {
+            writer.write_page_rows(framebuffer.iter()).unwrap();
+        }
+        let out = writer.into_inner();
+
+        let mut writer = RasterDocumentWriter::new(Vec::new(), header, SheetBack::Rotated).unwrap();
+        for _ in 0..2 {
+            writer.write_page(framebuffer.as_flattened()).unwrap();
+        }
+        assert_eq!(writer.into_inner(), out);
+
+        let doc = PwgDocument::read_from_stream(&mut out.as_slice()).unwrap();
+        assert_eq!(vec![vec![6, 5], vec![4, 3], vec![2, 1]], doc.pages[1].rows);
+    }
+
+    #[cfg(feature = "rayon")]
+    #[test]
+    fn write_page_parallel() {
+        let mut header = PageHeader {
+            width: 3,
+            height: 600,
+            ..Default::default()
+        };
+        header.set_raster_type("sgray_8").unwrap();
+        header.set_sides(Sides::TwoSidedLongEdge);
+        let pixels = (0..3 * 600)
+            .map(|i| GrayColor((i / 3 / 100) as u8))
+            .collect::<Vec<_>>();
+
+        let mut writer =
+            RasterDocumentWriter::new(Vec::new(), header.clone(), SheetBack::Rotated).unwrap();
+        for _ in 0..2 {
+            writer.write_page_parallel(&pixels).unwrap();
+        }
+        let parallel = PwgDocument::read_from_stream(&mut writer.into_inner().as_slice()).unwrap();
+
+        let mut writer = RasterDocumentWriter::new(Vec::new(), header, SheetBack::Rotated).unwrap();
+        for _ in 0..2 {
+            writer.write_page(&pixels).unwrap();
+        }
+        let serial = PwgDocument::read_from_stream(&mut writer.into_inner().as_slice()).unwrap();
+
+        for (parallel, serial) in parallel.pages.iter().zip(&serial.pages) {
+            assert_eq!(serial.rows, parallel.rows);
+        }
+        assert_eq!(vec![5; 3], parallel.pages[1].rows[0]);
+    }
+
+    #[test]
+    fn write_invalid_rows() {
+        let mut encoder = ImageEncoder::<SrgbColor>::new(2, 1);
+        let mut out = Vec::new();
+
+        assert!(matches!(
+            encoder.write_row(&mut out, &vec![0.into(); 3]),
+            Err(IppPrintError::RasterError(_))
+        ));
+        encoder.write_row(&mut out, &vec![0.into(); 2]).unwrap();
+        assert!(matches!(
+            encoder.write_row(&mut out, &vec![0.into(); 2]),
+            Err(IppPrintError::RasterError(_))
+        ));
+        // Nothing is written until the encoder is finished.
+        assert!(out.is_empty());
+        assert_eq!(5, encoder.finish(&mut out).unwrap());
+        assert_eq!(vec![0x00, 0x01, 0, 0, 0], out);
+    }
+
+    #[test]
+    fn finish_missing_rows() {
+        let mut encoder = ImageEncoder::<SrgbColor>::new(2, 3);
+        let mut out = Vec::new();
+        encoder.write_row(&mut out, &vec![0.into(); 2]).unwrap();
+        encoder.write_row(&mut out, &vec![0.into(); 2]).unwrap();
+        assert!(matches!(
+            encoder.finish(&mut out),
+            Err(IppPrintError::RasterError(_))
+        ));
+    }
+
+    #[test]
+    fn read_document() {
+        let header = PageHeader {
+            width: 3,
+            height: 4,
+            bytes_per_line: 9,
+            ..Default::default()
+        };
+        let pages = [
+            [0xFF0000, 0xFF0000, 0x00FF00, 0x0000FF],
+            [0xFFFFFF, 0x000000, 0x000000, 0x000000],
+        ]
+        .map(|colors| {
+            colors
+                .iter()
+                .flat_map(|e| std::iter::repeat_n(SrgbColor::from(*e), 3))
+                .collect::<Vec<_>>()
+        });
+
+        let mut writer = RasterDocumentWriter::new(Vec::new(), header, SheetBack::Normal).unwrap();
+        for page in &pages {
+            writer.write_page(page).unwrap();
+        }
+        let out = writer.into_inner();
+
+        let doc = PwgDocument::read_from_stream(&mut out.as_slice()).unwrap();
+        assert_eq!(2, doc.pages.len());
+        for (page, pixels) in doc.pages.iter().zip(&pages) {
+            assert_eq!(3, page.header.width());
+            assert_eq!(9, page.header.bytes_per_line());
+            let expected = pixels
+                .chunks(3)
+                .map(|row| row.iter().flat_map(|e| [e.r, e.g, e.b]).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            assert_eq!(expected, page.rows);
+        }
+
+        // Truncated page data
+        assert!(PwgDocument::read_from_stream(&mut &out[..out.len() - 1]).is_err());
+        assert!(matches!(
+            PwgDocument::read_from_stream(&mut &b"RaS4"[..]),
+            Err(IppPrintError::InvalidSyncWord(bytes)) if &bytes == b"RaS4"
+        ));
+    }
+
+    #[test]
+    fn set_string_fields() {
+        let mut header = PageHeader::default();
+        header.set_media_color("white").unwrap();
+        header.set_print_content_optimize("photo").unwrap();
+        header.set_rendering_intent("perceptual").unwrap();
+        header.set_page_size_name("custom_min_10x15mm").unwrap();
+        assert_eq!("white", header.media_color());
+        assert_eq!("photo", header.print_content_optimize());
+        assert_eq!("perceptual", header.rendering_intent());
+        assert_eq!("custom_min_10x15mm", header.page_size_name());
+
+        // A shorter value doesn't leave the rest of the previous one.
+        header.set_media_color("red").unwrap();
+        assert_eq!(b"red\0\0\0", &header.media_color[..6]);
+
+        assert!(header.set_media_type(&"a".repeat(63)).is_ok());
+        assert!(header.set_media_type(&"a".repeat(64)).is_err());
+        assert!(header.set_media_type("a\0b").is_err());
+        assert_eq!("a".repeat(63), header.media_type());
+    }
+
+    #[test]
+    fn read_header() {
+        let mut header = PageHeader::default();
+        header.set_media_size(&MediaSize::from_name("na_letter_8.5x11in").unwrap());
+        header.set_media_type("stationery").unwrap();
+        header.set_vendor_data(0x04b8, &[1, 2, 3]).unwrap();
+
+        let mut buf = vec![];
+        header.write_to_stream(&mut buf).unwrap();
+        let read = PageHeader::read_from_stream(&mut buf.as_slice()).unwrap();
+        assert_eq!("PwgRaster", c_string(&read.pwg_raster));
+        assert_eq!("stationery", read.media_type());
+        assert_eq!("", read.media_color());
+        assert_eq!("na_letter_8.5x11in", read.page_size_name());
+        assert_eq!((0x04b8, &[1u8, 2, 3][..]), read.vendor_data());
+        assert_eq!([612, 792], read.page_size);
+        assert_eq!(header.height(), read.height());
+
+        let mut out = vec![];
+        read.write_to_stream(&mut out).unwrap();
+        assert_eq!(buf, out);
+
+        let mut long_vendor_data = buf.clone();
+        long_vendor_data[512..516].copy_from_slice(&1089u32.to_be_bytes());
+        assert!(PageHeader::read_from_stream(&mut long_vendor_data.as_slice()).is_err());
+
+        buf[..9].copy_from_slice(b"CupsRastr");
+        assert!(PageHeader::read_from_stream(&mut buf.as_slice()).is_err());
+    }
+
+    #[test]
+    fn write_gray_page() {
+        assert_eq!(
+            GrayColor(255),
+            GrayColor::from(&SrgbColor::new(255, 255, 255))
+        );
+        assert_eq!(GrayColor(54), GrayColor::from(&SrgbColor::new(255, 0, 0)));
+
+        let mut header = PageHeader {
+            width: 4,
+            height: 1,
+            ..Default::default()
+        };
+        header.set_raster_type("sgray_8").unwrap();
+        assert_eq!(4, header.bytes_per_line());
+
+        let page = [GrayColor(0), GrayColor(0), GrayColor(10), GrayColor(20)];
+        let mut writer =
+            RasterDocumentWriter::new(Vec::new(), header.clone(), SheetBack::Normal).unwrap();
+        writer.write_page(&page).unwrap();
+        let out = writer.into_inner();
+        assert_eq!([0x00, 0x01, 0x00, 0xff, 10, 20], out[4 + 1796..]);
+
+        let doc = PwgDocument::read_from_stream(&mut out.as_slice()).unwrap();
+        assert_eq!(vec![vec![0, 0, 10, 20]], doc.pages[0].rows);
+
+        // sRGB pixels don't fit in the sGray header.
+        let mut writer = RasterDocumentWriter::new(Vec::new(), header, SheetBack::Normal).unwrap();
+        assert!(writer
+            .write_page(&vec![SrgbColor::new(0, 0, 0); 4])
+            .is_err());
+    }
+
+    #[test]
+    fn write_black_page() {
+        let mut header = PageHeader {
+            width: 10,
+            height: 2,
+            ..Default::default()
+        };
+        header.set_raster_type("black_1").unwrap();
+        assert_eq!(2, header.bytes_per_line());
+
+        let page = [
+            true, false, false, false, false, false, false, true, true, true, // row 0
+            true, false, false, false, false, false, false, true, true, true, // row 1
+        ]
+        .map(BlackColor);
+        let mut writer = RasterDocumentWriter::new(Vec::new(), header, SheetBack::Normal).unwrap();
+        writer.write_page(&page).unwrap();
+        let out = writer.into_inner();
+        // One repeated row with 2 literal bytes, the last one padded.
+        assert_eq!([0x01, 0xff, 0x81, 0xc0], out[4 + 1796..]);
+
+        let doc = PwgDocument::read_from_stream(&mut out.as_slice()).unwrap();
+        assert_eq!(vec![vec![0x81, 0xc0]; 2], doc.pages[0].rows);
+    }
+
+    #[test]
+    fn write_16bit_page() {
+        let mut header = PageHeader {
+            width: 3,
+            height: 2,
+            ..Default::default()
+        };
+        header.set_raster_type("adobe-rgb_16").unwrap();
+        assert_eq!(Some(ColorSpace::AdobeRgb), header.color_space());
+        assert_eq!(16, header.bits_per_color());
+        assert_eq!(18, header.bytes_per_line());
+
+        // Components differing only in the low byte must not be merged.
+        let page = [
+            Rgb16Color::new(0x0102, 0x0304, 0x0506),
+            Rgb16Color::new(0x0102, 0x0304, 0x0506),
+            Rgb16Color::new(0x0102, 0x0304, 0x0507),
+        ];
+        let page = [page, page].concat();
+        let mut writer = RasterDocumentWriter::new(Vec::new(), header, SheetBack::Normal).unwrap();
+        writer.write_page(&page).unwrap();
+        let out = writer.into_inner();
+        assert_eq!(
+            [0x01, 0x01, 1, 2, 3, 4, 5, 6, 0x00, 1, 2, 3, 4, 5, 7],
+            out[4 + 1796..]
+        );
+
+        let doc = PwgDocument::read_from_stream(&mut out.as_slice()).unwrap();
+        let mut expected = vec![];
+        Rgb16Color::write_row(&page[..3], &mut expected);
+        assert_eq!(vec![expected; 2], doc.pages[0].rows);
+
+        assert_eq!(
+            Rgb16Color::new(0xffff, 0x8080, 0),
+            Rgb16Color::from(&SrgbColor::new(255, 128, 0))
+        );
+    }
+
+    #[test]
+    fn cups_raster_sync_words() {
+        let mut header = PageHeader {
+            width: 4,
+            height: 2,
+            ..Default::default()
+        };
+        header.set_raster_type("sgray_8").unwrap();
+        let page = [10, 10, 10, 20, 30, 30, 40, 50].map(GrayColor);
+
+        for sync_word in [
+            SyncWord::V2,
+            SyncWord::V3,
+            SyncWord::V2LittleEndian,
+            SyncWord::V3LittleEndian,
+        ] {
+            let mut writer = RasterDocumentWriter::with_sync_word(
+                Vec::new(),
+                header.clone(),
+                SheetBack::Normal,
+                sync_word,
+            )
+            .unwrap();
+            writer.write_page(&page).unwrap();
+            let out = writer.into_inner();
+            assert_eq!(sync_word.bytes(), &out[..4]);
+            let width = if sync_word.is_little_endian() {
+                4u32.to_le_bytes()
+            } else {
+                4u32.to_be_bytes()
+            };
+            assert_eq!(width, out[4 + 372..4 + 376]);
+            if !sync_word.is_compressed() {
+                assert_eq!([10, 10, 10, 20, 30, 30, 40, 50], out[4 + 1796..]);
+            }
+
+            let doc = PwgDocument::read_from_stream(&mut out.as_slice()).unwrap();
+            assert_eq!(sync_word, doc.sync_word);
+            assert_eq!(header.to_bytes(), doc.pages[0].header.to_bytes());
+            assert_eq!(
+                vec![vec![10, 10, 10, 20], vec![30, 30, 40, 50]],
+                doc.pages[0].rows
+            );
+        }
+    }
+
+    #[test]
+    fn read_little_endian_16bit_page() {
+        let mut header = PageHeader {
+            width: 1,
+            height: 1,
+            ..Default::default()
+        };
+        header.set_raster_type("sgray_16").unwrap();
+        assert!(RasterDocumentWriter::with_sync_word(
+            Vec::new(),
+            header.clone(),
+            SheetBack::Normal,
+            SyncWord::V3LittleEndian
+        )
+        .is_err());
+
+        let mut out = b"3SaR".to_vec();
+        let mut buf = header.to_bytes();
+        swap_header_numbers(&mut buf);
+        out.extend_from_slice(&buf);
+        out.extend_from_slice(&0x1234u16.to_le_bytes());
+        let doc = PwgDocument::read_from_stream(&mut out.as_slice()).unwrap();
+        assert_eq!(16, doc.pages[0].header.bits_per_color());
+        assert_eq!(vec![vec![0x12, 0x34]], doc.pages[0].rows);
+    }
+
+    #[test]
+    fn build_header() {
+        let header = PageHeaderBuilder::new(850, 1100)
+            .resolution(100, 100)
+            .raster_type("black_1")
+            .build()
+            .unwrap();
+        assert_eq!([100, 100], header.resolution());
+        assert_eq!(1, header.bits_per_pixel());
+        assert_eq!(107, header.bytes_per_line());
+        assert_eq!(Some(ColorSpace::Black), header.color_space());
+        assert_eq!([612, 792], header.page_size());
+        assert_eq!("", header.page_size_name());
+
+        let header = PageHeaderBuilder::new(3, 2).build().unwrap();
+        assert_eq!(24, header.bits_per_pixel());
+        assert_eq!(9, header.bytes_per_line());
+        assert_eq!(3, header.num_colors);
+
+        assert!(PageHeaderBuilder::new(3, 2)
+            .raster_type("rgb_10")
+            .build()
+            .is_err());
+        assert!(PageHeaderBuilder::new(0, 2).build().is_err());
+        assert!(PageHeaderBuilder::new(3, 2)
+            .resolution(0, 300)
+            .build()
+            .is_err());
+        assert!(PageHeaderBuilder::new(u32::MAX, u32::MAX).build().is_err());
+
+        let a5 = MediaSize::from_name("iso_a5_148x210mm").unwrap();
+        let header = PageHeaderBuilder::with_media_size(&a5)
+            .resolution(600, 600)
+            .raster_type("sgray_8")
+            .build()
+            .unwrap();
+        assert_eq!([3496, 4960], [header.width(), header.height()]);
+        assert_eq!(3496, header.bytes_per_line());
+        assert_eq!([420, 595], header.page_size());
+        assert_eq!("iso_a5_148x210mm", header.page_size_name());
+        assert_eq!([0, 0, 3496, 4960], header.image_box());
+
+        let header = PageHeaderBuilder::new(10, 10)
+            .image_box(1, 2, 9, 8)
+            .build()
+            .unwrap();
+        assert_eq!([1, 2, 9, 8], header.image_box());
+        assert!(PageHeaderBuilder::new(10, 10)
+            .image_box(1, 2, 11, 8)
+            .build()
+            .is_err());
+        assert!(PageHeaderBuilder::new(10, 10)
+            .image_box(5, 2, 5, 8)
+            .build()
+            .is_err());
+    }
+
+    #[test]
+    fn place_content_in_image_box() {
+        let header = PageHeaderBuilder::new(4, 3)
+            .raster_type("sgray_8")
+            .image_box(1, 1, 4, 3)
+            .build()
+            .unwrap();
+        let content = [1, 2, 3, 4].map(GrayColor);
+        let page = place_in_image_box(&header, &content, 2).unwrap();
+        assert_eq!(
+            [255, 255, 255, 255, 255, 1, 2, 255, 255, 3, 4, 255].map(GrayColor),
+            page[..]
+        );
+
+        // Content wider than the image box would be clipped by the printer.
+        assert!(place_in_image_box(&header, &content, 4).is_err());
+        assert!(place_in_image_box(&header, &content, 3).is_err());
+        assert!(place_in_image_box(&header, &[GrayColor(0); 12], 4).is_err());
+    }
+
+    #[test]
+    fn set_vendor_data() {
+        let mut header = PageHeader::default();
+        assert_eq!((0, &[][..]), header.vendor_data());
+
+        header.set_vendor_data(0x0922, &[0xab; 1088]).unwrap();
+        assert_eq!((0x0922, &[0xab; 1088][..]), header.vendor_data());
+        header.set_vendor_data(0x0922, &[1, 2]).unwrap();
+        assert_eq!((0x0922, &[1u8, 2][..]), header.vendor_data());
+        assert_eq!([0, 0], header.vendor_data[2..4]);
+
+        assert!(header.set_vendor_data(0x0922, &[0; 1089]).is_err());
+        assert_eq!((0x0922, &[1u8, 2][..]), header.vendor_data());
+    }
+
+    #[test]
+    fn typed_fields() {
+        let mut header = PageHeader::default();
+        assert_eq!(Sides::OneSided, header.sides());
+        assert_eq!(None, header.print_quality());
+        assert_eq!(Some(MediaPosition::Auto), header.media_position());
+
+        header.set_sides(Sides::TwoSidedShortEdge);
+        header.set_cut_media(CutMedia::AfterPage);
+        header.set_jog(Jog::AfterJob);
+        header.set_leading_edge(LeadingEdge::LongEdgeFirst);
+        header.set_orientation(Orientation::ReverseLandscape);
+        header.set_print_quality(Some(PrintQuality::High));
+        header.set_media_position(MediaPosition::Tray(3)).unwrap();
+        assert!(header.set_media_position(MediaPosition::Roll(11)).is_err());
+        assert_eq!([1, 1, 4, 2, 1, 3, 5, 22], {
+            let h = &header;
+            [
+                h.duplex,
+                h.tumble,
+                h.cut_media,
+                h.jog,
+                h.leading_edge,
+                h.orientation,
+                h.print_quality,
+                h.media_position,
+            ]
+        });
+
+        let mut buf = vec![];
+        header.write_to_stream(&mut buf).unwrap();
+        let read = PageHeader::read_from_stream(&mut buf.as_slice()).unwrap();
+        assert_eq!(Sides::TwoSidedShortEdge, read.sides());
+        assert_eq!(Some(CutMedia::AfterPage), read.cut_media());
+        assert_eq!(Some(Jog::AfterJob), read.jog());
+        assert_eq!(Some(LeadingEdge::LongEdgeFirst), read.leading_edge());
+        assert_eq!(Some(Orientation::ReverseLandscape), read.orientation());
+        assert_eq!(Some(PrintQuality::High), read.print_quality());
+        assert_eq!(Some(MediaPosition::Tray(3)), read.media_position());
+        assert_eq!(Some(ColorSpace::Srgb), read.color_space());
+
+        for value in 0..50 {
+            let position = MediaPosition::from_u32(value).unwrap();
+            assert_eq!(Some(value), position.to_u32());
+        }
+        assert_eq!(None, MediaPosition::from_u32(50));
+        for position in MEDIA_POSITIONS
+            .into_iter()
+            .chain([MediaPosition::Tray(20), MediaPosition::Roll(1)])
+        {
+            assert_eq!(
+                Some(position),
+                MediaPosition::from_keyword(&position.keyword())
+            );
+        }
+        assert_eq!(None, MediaPosition::from_keyword("tray-21"));
+        assert_eq!(None, MediaPosition::from_keyword("tray"));
+        assert_eq!(Some(ColorSpace::Icc(15)), ColorSpace::from_u32(62));
+        assert_eq!(62, ColorSpace::Icc(15).value());
+        assert_eq!(None, ColorSpace::from_u32(0));
+    }
+    #[cfg(feature = "image")]
+    #[test]
+    fn page_to_rgb_image() {
+        let mut header = PageHeader {
+            width: 2,
+            height: 1,
+            ..Default::default()
+        };
+        let decode = |header: &PageHeader, write: &dyn Fn(&mut RasterDocumentWriter<Vec<u8>>)| {
+            let mut writer =
+                RasterDocumentWriter::new(Vec::new(), header.clone(), SheetBack::Normal).unwrap();
+            write(&mut writer);
+            let out = writer.into_inner();
+            let doc = PwgDocument::read_from_stream(&mut out.as_slice()).unwrap();
+            doc.pages[0].to_rgb_image().unwrap().into_raw()
+        };
+
+        header.set_raster_type("srgb_8").unwrap();
+        let page = [SrgbColor::new(1, 2, 3), SrgbColor::new(4, 5, 6)];
+        assert_eq!(
+            vec![1, 2, 3, 4, 5, 6],
+            decode(&header, &|w| {
+                w.write_page(&page).unwrap();
+            })
+        );
+
+        header.set_raster_type("black_1").unwrap();
+        assert_eq!(
+            vec![0, 0, 0, 255, 255, 255],
+            decode(&header, &|w| {
+                w.write_page(&[BlackColor(true), BlackColor(false)])
+                    .unwrap();
+            })
+        );
+
+        header.set_raster_type("srgb_16").unwrap();
+        let page = [Rgb16Color::new(0x1234, 0, 0xffff), Rgb16Color::WHITE];
+        assert_eq!(
+            vec![0x12, 0, 0xff, 255, 255, 255],
+            decode(&header, &|w| {
+                w.write_page(&page).unwrap();
+            })
+        );
+
+        header.set_raster_type("cmyk_8").unwrap();
+        let page = [
+            crate::color::CmykColor::new(255, 0, 0, 0),
+            crate::color::CmykColor::new(0, 0, 0, 255),
+        ];
+        assert_eq!(
+            vec![0, 255, 255, 0, 0, 0],
+            decode(&header, &|w| {
+                w.write_page(&page).unwrap();
+            })
+        );
+
+        let page = PwgPage {
+            header,
+            rows: vec![],
+        };
+        assert!(page.to_rgb_image().is_err());
+    }
+    /// Pages up to 300 pixels wide as rows of values 0..4, with runs around 128
+    /// pixels, where the encoder switches to another run, and repeated rows.
+    fn random_page() -> impl proptest::strategy::Strategy<Value = Vec<Vec<u8>>> {
+        use proptest::prelude::*;
+
+        (1usize..=300, 1usize..=8)
+            .prop_flat_map(|(width, height)| {
+                let run = (
+                    0u8..4,
+                    prop_oneof![1usize..=4, 126usize..=130, 1usize..=300],
+                );
+                let row = prop::collection::vec(run, 1..8).prop_map(move |runs| {
+                    let pixels = runs
+                        .iter()
+                        .flat_map(|(value, len)| std::iter::repeat_n(*value, *len))
+                        .collect::<Vec<_>>();
+                    pixels
+                        .iter()
+                        .cycle()
+                        .take(width)
+                        .cloned()
+                        .collect::<Vec<_>>()
+                });
+                prop::collection::vec((row, any::<bool>()), height)
+            })
+            .prop_map(|rows| {
+                let mut page: Vec<Vec<u8>> = vec![];
+                for (row, repeat) in rows {
+                    match page.last() {
+                        Some(last) if repeat => page.push(last.clone()),
+                        _ => page.push(row),
+                    }
+                }
+                page
+            })
+    }
+
+    /// Encodes rows of pixels and decodes them, returning decoded and expected rows.
+    fn round_trip<P>(raster_type: &str, rows: &[Vec<P>]) -> (Vec<Vec<u8>>, Vec<Vec<u8>>)
+    where
+        P: Pixel,
+    {
+        let header = PageHeaderBuilder::new(rows[0].len() as u32, rows.len() as u32)
+            .raster_type(raster_type)
+            .build()
+            .unwrap();
+        let mut writer = RasterDocumentWriter::new(Vec::new(), header, SheetBack::Normal).unwrap();
+        writer.write_page_rows(rows).unwrap();
+        let out = writer.into_inner();
+        let doc = PwgDocument::read_from_stream(&mut out.as_slice()).unwrap();
+
+        let expected = rows
+            .iter()
+            .map(|row| {
+                let mut bytes = vec![];
+                P::write_row(row, &mut bytes);
+                bytes
+            })
+            .collect();
+        (doc.pages[0].rows.clone(), expected)
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn round_trip_gray(page in random_page()) {
+            let rows = page
+                .iter()
+                .map(|row| row.iter().map(|e| GrayColor(e * 85)).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            let (decoded, expected) = round_trip("sgray_8", &rows);
+            proptest::prop_assert_eq!(expected, decoded);
+        }
+
+        #[test]
+        fn round_trip_srgb(page in random_page()) {
+            let rows = page
+                .iter()
+                .map(|row| row.iter().map(|e| SrgbColor::new(*e, 0, e * 85)).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            let (decoded, expected) = round_trip("srgb_8", &rows);
+            proptest::prop_assert_eq!(expected, decoded);
+        }
+
+        #[test]
+        fn round_trip_black(page in random_page()) {
+            let rows = page
+                .iter()
+                .map(|row| row.iter().map(|e| BlackColor(e % 2 == 1)).collect::<Vec<_>>())
+                .collect::<Vec<_>>();
+            let (decoded, expected) = round_trip("black_1", &rows);
+            proptest::prop_assert_eq!(expected, decoded);
+        }
+    }
+
+    #[test]
+    fn read_rows_lazily() {
+        let mut header = PageHeader {
+            width: 2,
+            height: 3,
+            ..Default::default()
+        };
+        header.set_raster_type("sgray_8").unwrap();
+        let mut writer =
+            RasterDocumentWriter::new(Vec::new(), header.clone(), SheetBack::Normal).unwrap();
+        writer
+            .write_page(&[1, 1, 1, 1, 2, 3].map(GrayColor))
+            .unwrap();
+        writer
+            .write_page(&[4, 5, 6, 7, 8, 9].map(GrayColor))
+            .unwrap();
+        let out = writer.into_inner();
+
+        let mut reader = RasterReader::new(out.as_slice()).unwrap();
+        let mut rows = vec![];
+        while let Some((page, row, data)) = reader.next_row().unwrap() {
+            rows.push((page, row, data.to_vec()));
+        }
+        assert_eq!(
+            vec![
+                (0, 0, vec![1, 1]),
+                (0, 1, vec![1, 1]),
+                (0, 2, vec![2, 3]),
+                (1, 0, vec![4, 5]),
+                (1, 1, vec![6, 7]),
+                (1, 2, vec![8, 9]),
+            ],
+            rows
+        );
+        assert!(reader.header().is_none());
+
+        // The rest of a page is skipped by next_page.
+        let mut reader = RasterReader::new(out.as_slice()).unwrap();
+        assert_eq!(3, reader.next_page().unwrap().unwrap().height());
+        assert_eq!(Some(&[1u8, 1][..]), reader.next_page_row().unwrap());
+        assert!(reader.next_page().unwrap().is_some());
+        assert_eq!(Some(&[4u8, 5][..]), reader.next_page_row().unwrap());
+        assert!(reader.next_page().unwrap().is_none());
+
+        // Errors come when the broken row is reached.
+        let mut reader = RasterReader::new(&out[..out.len() - 1]).unwrap();
+        for _ in 0..5 {
+            assert!(reader.next_row().unwrap().is_some());
+        }
+        assert!(reader.next_row().is_err());
+    }
 }
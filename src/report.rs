@@ -0,0 +1,289 @@
+//! Human-readable summary of what a printer supports, from Get-Printer-Attributes.
+
+use crate::ipp::{AttributeValue, PrinterAttributes, Resolution};
+use std::fmt;
+
+/// finishings enum values (PWG5100.1) and their keywords.
+const FINISHINGS: &[(i32, &str)] = &[
+    (3, "none"),
+    (4, "staple"),
+    (5, "punch"),
+    (6, "cover"),
+    (7, "bind"),
+    (8, "saddle-stitch"),
+    (9, "edge-stitch"),
+    (10, "fold"),
+    (11, "trim"),
+    (12, "bale"),
+    (13, "booklet-maker"),
+    (14, "jog-offset"),
+    (15, "coat"),
+    (16, "laminate"),
+    (20, "staple-top-left"),
+    (21, "staple-bottom-left"),
+    (22, "staple-top-right"),
+    (23, "staple-bottom-right"),
+    (24, "edge-stitch-left"),
+    (25, "edge-stitch-top"),
+    (26, "edge-stitch-right"),
+    (27, "edge-stitch-bottom"),
+    (28, "staple-dual-left"),
+    (29, "staple-dual-top"),
+    (30, "staple-dual-right"),
+    (31, "staple-dual-bottom"),
+    (50, "bind-left"),
+    (51, "bind-top"),
+    (52, "bind-right"),
+    (53, "bind-bottom"),
+    (70, "punch-top-left"),
+    (71, "punch-bottom-left"),
+    (72, "punch-top-right"),
+    (73, "punch-bottom-right"),
+    (74, "punch-dual-left"),
+    (75, "punch-dual-top"),
+    (76, "punch-dual-right"),
+    (77, "punch-dual-bottom"),
+    (93, "fold-half"),
+    (96, "fold-letter"),
+    (100, "fold-z"),
+];
+
+/// print-quality enum values (RFC 8011 5.2.13).
+const QUALITIES: &[(i32, &str)] = &[(3, "draft"), (4, "normal"), (5, "high")];
+
+/// Formats printer attributes as a list of capabilities, one per line.
+pub struct CapabilityReport<'a>(pub &'a PrinterAttributes);
+
+impl CapabilityReport<'_> {
+    fn strings(&self, name: &str) -> Vec<String> {
+        self.0
+            .other
+            .get(name)
+            .map(|value| {
+                value
+                    .values()
+                    .iter()
+                    .filter_map(|e| e.as_str().map(|e| e.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Keywords of enum values, or the number for unknown values.
+    fn enums(&self, name: &str, keywords: &[(i32, &str)]) -> Vec<String> {
+        let Some(value) = self.0.other.get(name) else {
+            return vec![];
+        };
+        value
+            .values()
+            .iter()
+            .filter_map(|e| match e {
+                AttributeValue::Enum(val) => Some(
+                    keywords
+                        .iter()
+                        .find(|(k, _)| k == val)
+                        .map_or_else(|| val.to_string(), |(_, keyword)| keyword.to_string()),
+                ),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn media(&self) -> Vec<String> {
+        let attrs = self.0;
+        attrs
+            .media_supported
+            .iter()
+            .map(|media| {
+                let mut notes = vec![];
+                if attrs.media_default.as_ref() == Some(media) {
+                    notes.push("default");
+                }
+                if attrs.media_ready.contains(media) {
+                    notes.push("ready");
+                }
+                if notes.is_empty() {
+                    media.clone()
+                } else {
+                    format!("{} ({})", media, notes.join(", "))
+                }
+            })
+            .collect()
+    }
+
+    /// printer-resolution-supported, or the resolutions for PWG Raster if the
+    /// printer doesn't list them.
+    fn resolutions(&self) -> Vec<String> {
+        let attrs = self.0;
+        let resolutions = if attrs.printer_resolution_supported.is_empty() {
+            &attrs.pwg_raster_document_resolution_supported
+        } else {
+            &attrs.printer_resolution_supported
+        };
+        let mut result = vec![];
+        for (x, y) in resolutions.iter().map(Resolution::to_dpi) {
+            let resolution = format!("{}x{}dpi", x, y);
+            if !result.contains(&resolution) {
+                result.push(resolution);
+            }
+        }
+        result
+    }
+
+    fn color_modes(&self) -> Vec<String> {
+        let modes = self.strings("print-color-mode-supported");
+        if !modes.is_empty() {
+            modes
+        } else if self.0.color_supported == Some(true) {
+            vec!["color".to_string(), "monochrome".to_string()]
+        } else {
+            vec!["monochrome".to_string()]
+        }
+    }
+
+    /// printer-firmware-name with the version of the same index.
+    fn firmware(&self) -> Vec<String> {
+        let versions = self.strings("printer-firmware-string-version");
+        self.strings("printer-firmware-name")
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| match versions.get(i) {
+                Some(version) => format!("{} {}", name, version),
+                None => name,
+            })
+            .collect()
+    }
+}
+
+fn write_list(f: &mut fmt::Formatter, label: &str, values: &[String]) -> fmt::Result {
+    if values.is_empty() {
+        writeln!(f, "{}: unknown", label)
+    } else {
+        writeln!(f, "{}: {}", label, values.join(", "))
+    }
+}
+
+impl fmt::Display for CapabilityReport<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let attrs = self.0;
+        writeln!(
+            f,
+            "{} ({})",
+            attrs.printer_name.as_deref().unwrap_or("unknown"),
+            attrs.printer_make_and_model.as_deref().unwrap_or("unknown")
+        )?;
+        write_list(f, "Firmware", &self.firmware())?;
+        writeln!(f, "Media:")?;
+        for media in self.media() {
+            writeln!(f, "    {}", media)?;
+        }
+        write_list(f, "Trays", &self.strings("media-source-supported"))?;
+        write_list(f, "Resolutions", &self.resolutions())?;
+        write_list(f, "Color modes", &self.color_modes())?;
+
+        let duplex = attrs
+            .sides_supported
+            .iter()
+            .filter(|e| e.starts_with("two-sided"))
+            .cloned()
+            .collect::<Vec<_>>();
+        if duplex.is_empty() {
+            writeln!(f, "Duplex: no")?;
+        } else {
+            writeln!(f, "Duplex: yes ({})", duplex.join(", "))?;
+        }
+
+        write_list(
+            f,
+            "Quality",
+            &self.enums("print-quality-supported", QUALITIES),
+        )?;
+        let finishings = self
+            .enums("finishings-supported", FINISHINGS)
+            .into_iter()
+            .filter(|e| e != "none")
+            .collect::<Vec<_>>();
+        if finishings.is_empty() {
+            writeln!(f, "Finishings: none")?;
+        } else {
+            write_list(f, "Finishings", &finishings)?;
+        }
+        write_list(f, "Formats", &attrs.document_format_supported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_report() {
+        let keywords = |values: &[&str]| {
+            AttributeValue::VectorAttribute(
+                values
+                    .iter()
+                    .map(|e| AttributeValue::Keyword(e.to_string()))
+                    .collect(),
+            )
+        };
+        let mut attrs = PrinterAttributes {
+            printer_name: Some("office".to_string()),
+            printer_make_and_model: Some("Example Printer".to_string()),
+            document_format_supported: vec!["image/pwg-raster".to_string()],
+            media_default: Some("iso_a4_210x297mm".to_string()),
+            media_supported: vec![
+                "iso_a4_210x297mm".to_string(),
+                "jpn_hagaki_100x148mm".to_string(),
+            ],
+            media_ready: vec!["iso_a4_210x297mm".to_string()],
+            sides_supported: vec!["one-sided".to_string()],
+            printer_resolution_supported: vec![
+                Resolution {
+                    resolution_cross_feed: 300,
+                    resolution_feed: 300,
+                    units: Resolution::DOTS_PER_INCH,
+                },
+                Resolution {
+                    resolution_cross_feed: 236,
+                    resolution_feed: 236,
+                    units: Resolution::DOTS_PER_CENTIMETER,
+                },
+            ],
+            ..Default::default()
+        };
+        attrs.other.insert(
+            "media-source-supported".to_string(),
+            keywords(&["main", "manual"]),
+        );
+        attrs.other.insert(
+            "finishings-supported".to_string(),
+            AttributeValue::VectorAttribute(vec![
+                AttributeValue::Enum(3),
+                AttributeValue::Enum(20),
+                AttributeValue::Enum(1000),
+            ]),
+        );
+        attrs.other.insert(
+            "printer-firmware-name".to_string(),
+            AttributeValue::NameWithoutLanguage("main".to_string()),
+        );
+        attrs.other.insert(
+            "printer-firmware-string-version".to_string(),
+            AttributeValue::TextWithoutLanguage("1.2.3".to_string()),
+        );
+
+        assert_eq!(
+            "office (Example Printer)\n\
+             Firmware: main 1.2.3\n\
+             Media:\n    iso_a4_210x297mm (default, ready)\n    jpn_hagaki_100x148mm\n\
+             Trays: main, manual\n\
+             Resolutions: 300x300dpi, 599x599dpi\n\
+             Color modes: monochrome\n\
+             Duplex: no\n\
+             Quality: unknown\n\
+             Finishings: staple-top-left, 1000\n\
+             Formats: image/pwg-raster\n",
+            CapabilityReport(&attrs).to_string()
+        );
+    }
+}
@@ -0,0 +1,221 @@
+//! Levels of ink, toner, and other supplies from the marker-* printer attributes
+//! (PWG5100.13, with the values of prtMarkerSuppliesLevel in RFC 3805).
+
+use crate::ipp::{AttributeValue, PrinterAttributes};
+use std::fmt;
+
+/// Printer attributes describing supplies, to request only them.
+pub const SUPPLY_ATTRIBUTES: &[&str] = &[
+    "marker-names",
+    "marker-types",
+    "marker-colors",
+    "marker-levels",
+    "marker-low-levels",
+    "marker-high-levels",
+];
+
+/// How much of a supply is left.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "kebab-case")
+)]
+pub enum MarkerLevel {
+    /// 0 to 100
+    Percent(u8),
+    /// -1: the printer places no restriction on the level, like for some waste tanks.
+    Other,
+    /// -2, or a level the printer didn't report.
+    Unknown,
+    /// -3: some is left, but the printer doesn't know how much.
+    SomeRemaining,
+}
+
+impl MarkerLevel {
+    pub fn from_value(value: i32) -> Self {
+        match value {
+            0..=100 => Self::Percent(value as u8),
+            -1 => Self::Other,
+            -3 => Self::SomeRemaining,
+            _ => Self::Unknown,
+        }
+    }
+}
+
+impl fmt::Display for MarkerLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Percent(level) => write!(f, "{}%", level),
+            Self::Other => write!(f, "n/a"),
+            Self::Unknown => write!(f, "unknown"),
+            Self::SomeRemaining => write!(f, "some remaining"),
+        }
+    }
+}
+
+/// A supply like a toner cartridge.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize),
+    serde(rename_all = "kebab-case")
+)]
+pub struct Marker {
+    /// Name from marker-names like "Black Toner"
+    pub name: String,
+    /// marker-types keyword like "toner" or "ink-cartridge"
+    #[cfg_attr(feature = "serde", serde(rename = "type"))]
+    pub kind: Option<String>,
+    /// marker-colors value like "#000000", or "none"; colors of a
+    /// multi-color cartridge are joined like "#00FFFF#FF00FF#FFFF00"
+    pub color: Option<String>,
+    pub level: MarkerLevel,
+    /// Level in percent at or below which the supply is low
+    pub low_level: Option<i32>,
+    /// Level in percent at or above which the supply is full, or a waste tank
+    /// needs emptying
+    pub high_level: Option<i32>,
+}
+
+impl Marker {
+    /// Whether the level is at or below the low level.
+    pub fn is_low(&self) -> bool {
+        match (self.level, self.low_level) {
+            (MarkerLevel::Percent(level), Some(low)) => i32::from(level) <= low,
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Display for Marker {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.name, self.level)?;
+        if self.is_low() {
+            write!(f, " (low)")?;
+        }
+        Ok(())
+    }
+}
+
+/// Supplies of a printer, in the order of marker-names.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Supplies {
+    pub markers: Vec<Marker>,
+}
+
+impl Supplies {
+    /// The marker-* attributes are parallel lists indexed like marker-names.
+    pub fn from_attributes(attrs: &PrinterAttributes) -> Self {
+        let values = |name: &str| {
+            attrs
+                .other
+                .get(name)
+                .map(|e| e.values().to_vec())
+                .unwrap_or_default()
+        };
+        let string = |values: &[AttributeValue], i: usize| {
+            values
+                .get(i)
+                .and_then(|e| e.as_str())
+                .map(|e| e.to_string())
+        };
+        let integer = |values: &[AttributeValue], i: usize| match values.get(i) {
+            Some(AttributeValue::Integer(value)) => Some(*value),
+            _ => None,
+        };
+
+        let names = values("marker-names");
+        let types = values("marker-types");
+        let colors = values("marker-colors");
+        let levels = values("marker-levels");
+        let low_levels = values("marker-low-levels");
+        let high_levels = values("marker-high-levels");
+        let markers = (0..names.len())
+            .filter_map(|i| {
+                Some(Marker {
+                    name: string(&names, i)?,
+                    kind: string(&types, i),
+                    color: string(&colors, i),
+                    level: integer(&levels, i)
+                        .map_or(MarkerLevel::Unknown, MarkerLevel::from_value),
+                    low_level: integer(&low_levels, i),
+                    high_level: integer(&high_levels, i),
+                })
+            })
+            .collect();
+        Self { markers }
+    }
+
+    /// Supplies at or below their low level.
+    pub fn low(&self) -> impl Iterator<Item = &Marker> {
+        self.markers.iter().filter(|e| e.is_low())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_supplies() {
+        let list = |values: Vec<AttributeValue>| AttributeValue::VectorAttribute(values);
+        let names = |values: &[&str]| {
+            list(
+                values
+                    .iter()
+                    .map(|e| AttributeValue::NameWithoutLanguage(e.to_string()))
+                    .collect(),
+            )
+        };
+        let integers =
+            |values: &[i32]| list(values.iter().map(|e| AttributeValue::Integer(*e)).collect());
+        let mut attrs = PrinterAttributes::default();
+        for (name, value) in [
+            (
+                "marker-names",
+                names(&["Black Toner", "Cyan Toner", "Waste"]),
+            ),
+            (
+                "marker-types",
+                list(
+                    ["toner", "toner", "waste-toner"]
+                        .iter()
+                        .map(|e| AttributeValue::Keyword(e.to_string()))
+                        .collect(),
+                ),
+            ),
+            ("marker-colors", names(&["#000000", "#00FFFF", "none"])),
+            ("marker-levels", integers(&[45, 5, -3])),
+            ("marker-low-levels", integers(&[10, 10, 0])),
+            ("marker-high-levels", integers(&[100, 100, 100])),
+        ] {
+            attrs.other.insert(name.to_string(), value);
+        }
+
+        let supplies = Supplies::from_attributes(&attrs);
+        assert_eq!(3, supplies.markers.len());
+        assert_eq!(
+            Marker {
+                name: "Black Toner".to_string(),
+                kind: Some("toner".to_string()),
+                color: Some("#000000".to_string()),
+                level: MarkerLevel::Percent(45),
+                low_level: Some(10),
+                high_level: Some(100),
+            },
+            supplies.markers[0]
+        );
+        assert_eq!(MarkerLevel::SomeRemaining, supplies.markers[2].level);
+        assert_eq!(
+            vec!["Cyan Toner: 5% (low)"],
+            supplies.low().map(|e| e.to_string()).collect::<Vec<_>>()
+        );
+
+        // A printer without supplies, or which doesn't report them.
+        assert!(Supplies::from_attributes(&PrinterAttributes::default())
+            .markers
+            .is_empty());
+    }
+}
@@ -0,0 +1,103 @@
+//! SVG rendering with resvg.
+//!
+//! SVG is rendered in its physical size at the resolution of the page, so that
+//! labels and tickets designed in millimeters come out in the same size.
+
+use crate::error::IppPrintError;
+use crate::layout::Image;
+use crate::pwgraster::{PageHeader, SrgbColor};
+use resvg::tiny_skia::{Color, Pixmap, Transform};
+use resvg::usvg::{Options, Tree};
+use std::io;
+
+/// CSS pixels per inch, the unit of SVG user space.
+const CSS_DPI: f32 = 96.0;
+
+/// Rasterizes SVG documents for a page header.
+pub struct SvgRenderer {
+    options: Options<'static>,
+}
+
+impl SvgRenderer {
+    /// Loads the system fonts for text in SVG.
+    pub fn new() -> Self {
+        let mut options = Options::default();
+        options.fontdb_mut().load_system_fonts();
+        Self { options }
+    }
+
+    /// Renders svg centered on the page of header. It is scaled down to fit the
+    /// page if it is larger.
+    pub fn render(
+        &self,
+        svg: &[u8],
+        header: &PageHeader,
+    ) -> Result<Image<SrgbColor>, IppPrintError> {
+        let tree = Tree::from_data(svg, &self.options)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("svg: {}", e)))?;
+
+        let (width, height) = (header.width(), header.height());
+        let [x_dpi, y_dpi] = header.resolution().map(|e| e as f32);
+        let size = tree.size();
+        let (mut scale_x, mut scale_y) = (x_dpi / CSS_DPI, y_dpi / CSS_DPI);
+        let fit = (width as f32 / (size.width() * scale_x))
+            .min(height as f32 / (size.height() * scale_y))
+            .min(1.0);
+        scale_x *= fit;
+        scale_y *= fit;
+        let left = (width as f32 - size.width() * scale_x) / 2.0;
+        let top = (height as f32 - size.height() * scale_y) / 2.0;
+
+        let mut pixmap = Pixmap::new(width, height).ok_or_else(|| {
+            IppPrintError::RasterError(format!("can't render SVG on {}x{}", width, height))
+        })?;
+        pixmap.fill(Color::WHITE);
+        resvg::render(
+            &tree,
+            Transform::from_row(scale_x, 0.0, 0.0, scale_y, left, top),
+            &mut pixmap.as_mut(),
+        );
+
+        // The background is opaque, so colors aren't premultiplied in effect.
+        let pixels = pixmap
+            .pixels()
+            .iter()
+            .map(|e| SrgbColor::new(e.red(), e.green(), e.blue()))
+            .collect();
+        Image::new(width as usize, height as usize, pixels)
+    }
+}
+
+impl Default for SvgRenderer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pwgraster::PageHeaderBuilder;
+
+    #[test]
+    fn render_in_physical_size() {
+        // Half an inch square on a page of an inch square at 300 dpi
+        let svg = br##"<svg xmlns="http://www.w3.org/2000/svg" width="0.5in" height="0.5in">
+            <rect width="100%" height="100%" fill="#000000"/></svg>"##;
+        let header = PageHeaderBuilder::new(300, 300).build().unwrap();
+        let page = SvgRenderer::new().render(svg, &header).unwrap();
+
+        let pixel = |x: usize, y: usize| page.pixels()[y * 300 + x].clone();
+        assert_eq!(SrgbColor::new(255, 255, 255), pixel(70, 150));
+        assert_eq!(SrgbColor::new(0, 0, 0), pixel(80, 80));
+        assert_eq!(SrgbColor::new(0, 0, 0), pixel(219, 219));
+        assert_eq!(SrgbColor::new(255, 255, 255), pixel(230, 150));
+
+        // Scaled down to fit
+        let header = PageHeaderBuilder::new(60, 60).build().unwrap();
+        let page = SvgRenderer::new().render(svg, &header).unwrap();
+        assert!(page.pixels().iter().all(|e| *e == SrgbColor::new(0, 0, 0)));
+
+        assert!(SvgRenderer::new().render(b"<svg", &header).is_err());
+    }
+}
@@ -0,0 +1,1026 @@
+//! In-process fake printer for testing code built on this crate without a real device.
+//!
+//! [`MockPrinter`] serves IPP over HTTP on a local port and implements enough of
+//! an IPP Everywhere printer to run a whole job: Get-Printer-Attributes, Validate-Job,
+//! Create-Job, Send-Document, Print-Job, Get-Jobs, Get-Job-Attributes, Cancel-Job,
+//! Hold-Job, Release-Job, and Restart-Job.
+//! Jobs complete as soon as their last document arrives, and documents are kept
+//! for inspection.
+
+use crate::ipp::{
+    AttributeGroup, AttributeValue, Compression, DelimiterOrValueTag, IPPRequest, IPPResponse,
+    JobState, PrintClient, PrinterOperation, PrinterState, Resolution, StatusCode,
+};
+use crate::transport::{HttpTransport, PrinterUri};
+use flate2::read::{DeflateDecoder, GzDecoder};
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// A document received with Send-Document or Print-Job.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MockDocument {
+    pub document_format: Option<String>,
+    pub data: Vec<u8>,
+}
+
+/// A job created on the mock printer.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct MockJob {
+    pub id: i32,
+    pub state: JobState,
+    /// job-state-reasons set with set_job_state, instead of the usual ones for state
+    pub reasons: Vec<String>,
+    /// requesting-user-name of the creating request
+    pub user: Option<String>,
+    /// Job template attributes of the creating request, like copies or sides.
+    pub attrs: Vec<(String, AttributeValue)>,
+    pub documents: Vec<MockDocument>,
+}
+
+#[derive(Default)]
+struct MockState {
+    printer_attributes: Vec<(String, AttributeValue)>,
+    /// Statuses returned instead of processing the operation.
+    canned_statuses: Vec<(PrinterOperation, StatusCode)>,
+    jobs: Vec<MockJob>,
+    requests: Vec<IPPRequest>,
+}
+
+/// Fake IPP printer listening on 127.0.0.1. Stops when dropped.
+pub struct MockPrinter {
+    addr: SocketAddr,
+    state: Arc<Mutex<MockState>>,
+    shutdown: Arc<AtomicBool>,
+}
+
+fn keywords<S>(values: &[S]) -> AttributeValue
+where
+    S: AsRef<str>,
+{
+    AttributeValue::VectorAttribute(
+        values
+            .iter()
+            .map(|e| AttributeValue::Keyword(e.as_ref().to_string()))
+            .collect(),
+    )
+}
+
+/// Attributes of a typical color duplex IPP Everywhere printer.
+fn default_printer_attributes() -> Vec<(String, AttributeValue)> {
+    let operations = [
+        PrinterOperation::PrintJob,
+        PrinterOperation::ValidateJob,
+        PrinterOperation::CreateJob,
+        PrinterOperation::SendDocument,
+        PrinterOperation::CancelJob,
+        PrinterOperation::HoldJob,
+        PrinterOperation::ReleaseJob,
+        PrinterOperation::RestartJob,
+        PrinterOperation::GetJobAttributes,
+        PrinterOperation::GetJobs,
+        PrinterOperation::GetPrinterAttributes,
+    ];
+
+    vec![
+        (
+            "printer-name".to_string(),
+            AttributeValue::NameWithoutLanguage("mock".to_string()),
+        ),
+        (
+            "printer-uuid".to_string(),
+            AttributeValue::Uri("urn:uuid:4d6f636b-0000-4000-8000-000000000001".to_string()),
+        ),
+        (
+            "printer-config-change-time".to_string(),
+            AttributeValue::Integer(1),
+        ),
+        (
+            "printer-make-and-model".to_string(),
+            AttributeValue::TextWithoutLanguage("ipp-print Mock Printer".to_string()),
+        ),
+        (
+            "printer-state".to_string(),
+            AttributeValue::Enum(PrinterState::Idle as i32),
+        ),
+        ("printer-state-reasons".to_string(), keywords(&["none"])),
+        (
+            "ipp-versions-supported".to_string(),
+            keywords(&["1.1", "2.0"]),
+        ),
+        (
+            "operations-supported".to_string(),
+            AttributeValue::VectorAttribute(
+                operations
+                    .iter()
+                    .map(|op| AttributeValue::Enum(u16::from(*op) as i32))
+                    .collect(),
+            ),
+        ),
+        (
+            "document-format-supported".to_string(),
+            AttributeValue::VectorAttribute(
+                ["application/octet-stream", "image/pwg-raster"]
+                    .iter()
+                    .map(|e| AttributeValue::MimeMediaType(e.to_string()))
+                    .collect(),
+            ),
+        ),
+        (
+            "media-default".to_string(),
+            AttributeValue::Keyword("iso_a4_210x297mm".to_string()),
+        ),
+        (
+            "media-supported".to_string(),
+            keywords(&["iso_a4_210x297mm", "na_letter_8.5x11in"]),
+        ),
+        (
+            "sides-supported".to_string(),
+            keywords(&["one-sided", "two-sided-long-edge", "two-sided-short-edge"]),
+        ),
+        ("color-supported".to_string(), AttributeValue::Boolean(true)),
+        (
+            "multiple-document-jobs-supported".to_string(),
+            AttributeValue::Boolean(true),
+        ),
+        (
+            "compression-supported".to_string(),
+            keywords(&["none", "gzip", "deflate"]),
+        ),
+        (
+            "pwg-raster-document-resolution-supported".to_string(),
+            AttributeValue::Resolution(Resolution {
+                resolution_cross_feed: 300,
+                resolution_feed: 300,
+                units: Resolution::DOTS_PER_INCH,
+            }),
+        ),
+        (
+            "pwg-raster-document-type-supported".to_string(),
+            keywords(&["sgray_8", "srgb_8"]),
+        ),
+        (
+            "pwg-raster-document-sheet-back".to_string(),
+            AttributeValue::Keyword("normal".to_string()),
+        ),
+    ]
+}
+
+impl MockPrinter {
+    /// Starts serving on a free port.
+    pub fn start() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(Mutex::new(MockState {
+            printer_attributes: default_printer_attributes(),
+            ..Default::default()
+        }));
+        let shutdown = Arc::new(AtomicBool::new(false));
+
+        let thread_state = state.clone();
+        let thread_shutdown = shutdown.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                if thread_shutdown.load(Ordering::SeqCst) {
+                    break;
+                }
+                let Ok(stream) = stream else {
+                    continue;
+                };
+                let state = thread_state.clone();
+                std::thread::spawn(move || {
+                    // The connection is just closed on errors, like a real printer.
+                    let _ = serve_connection(stream, addr, &state);
+                });
+            }
+        });
+
+        Ok(Self {
+            addr,
+            state,
+            shutdown,
+        })
+    }
+
+    /// Address like "127.0.0.1:12345", usable as PRINTER_ADDR.
+    pub fn addr(&self) -> String {
+        self.addr.to_string()
+    }
+
+    /// Client connected to this printer over HTTP.
+    pub fn client(&self) -> PrintClient {
+        PrintClient::with_transport(Box::new(HttpTransport::with_uri(PrinterUri {
+            secure: false,
+            host: self.addr.ip().to_string(),
+            port: self.addr.port(),
+            path: PrinterUri::DEFAULT_PATH.to_string(),
+        })))
+    }
+
+    /// Adds or replaces a printer attribute returned by Get-Printer-Attributes.
+    pub fn set_printer_attribute(&self, name: &str, value: AttributeValue) {
+        let mut state = self.state.lock().unwrap();
+        match state
+            .printer_attributes
+            .iter_mut()
+            .find(|(key, _)| key == name)
+        {
+            Some((_, old)) => *old = value,
+            None => state.printer_attributes.push((name.to_string(), value)),
+        }
+    }
+
+    /// Makes the printer answer the operation with the status, without processing it.
+    pub fn set_status(&self, operation: PrinterOperation, status_code: StatusCode) {
+        let mut state = self.state.lock().unwrap();
+        state.canned_statuses.retain(|(op, _)| *op != operation);
+        state.canned_statuses.push((operation, status_code));
+    }
+
+    /// Changes the state of the job, e.g. to stop it with reasons like "media-jam".
+    /// The reasons replace the usual ones for the state until they are set again.
+    pub fn set_job_state(&self, job_id: i32, job_state: JobState, reasons: &[&str]) {
+        let mut state = self.state.lock().unwrap();
+        if let Some(job) = state.jobs.iter_mut().find(|e| e.id == job_id) {
+            job.state = job_state;
+            job.reasons = reasons.iter().map(|e| e.to_string()).collect();
+        }
+    }
+
+    /// Jobs in the order of creation.
+    pub fn jobs(&self) -> Vec<MockJob> {
+        self.state.lock().unwrap().jobs.clone()
+    }
+
+    /// Every request received so far, including document data.
+    pub fn requests(&self) -> Vec<IPPRequest> {
+        self.state.lock().unwrap().requests.clone()
+    }
+}
+
+impl Drop for MockPrinter {
+    fn drop(&mut self) {
+        self.shutdown.store(true, Ordering::SeqCst);
+        // Wake up the accept loop.
+        let _ = TcpStream::connect(self.addr);
+    }
+}
+
+fn read_line<R>(reader: &mut R) -> io::Result<Option<String>>
+where
+    R: BufRead,
+{
+    let mut line = String::new();
+    if reader.read_line(&mut line)? == 0 {
+        return Ok(None);
+    }
+    Ok(Some(line.trim_end().to_string()))
+}
+
+/// Reads the body of an HTTP request. Returns None when the client closed the connection.
+fn read_request<R>(reader: &mut R) -> io::Result<Option<Vec<u8>>>
+where
+    R: BufRead,
+{
+    if read_line(reader)?.is_none() {
+        return Ok(None);
+    }
+
+    let mut content_length = 0;
+    let mut chunked = false;
+    while let Some(line) = read_line(reader)? {
+        if line.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = line.split_once(':') {
+            match name.trim().to_ascii_lowercase().as_str() {
+                "content-length" => content_length = value.trim().parse().unwrap_or(0),
+                "transfer-encoding" => chunked = value.contains("chunked"),
+                _ => {}
+            }
+        }
+    }
+
+    if !chunked {
+        let mut body = vec![0; content_length];
+        reader.read_exact(&mut body)?;
+        return Ok(Some(body));
+    }
+
+    let mut body = vec![];
+    loop {
+        let size = read_line(reader)?.unwrap_or_default();
+        let size = usize::from_str_radix(size.split(';').next().unwrap().trim(), 16)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "invalid chunk size"))?;
+        if size == 0 {
+            while !read_line(reader)?.unwrap_or_default().is_empty() {}
+            return Ok(Some(body));
+        }
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+        read_line(reader)?;
+    }
+}
+
+fn serve_connection(
+    stream: TcpStream,
+    addr: SocketAddr,
+    state: &Mutex<MockState>,
+) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+
+    while let Some(body) = read_request(&mut reader)? {
+        let response = match IPPRequest::read_from_stream(&mut body.as_slice()) {
+            Ok(request) => handle(&mut state.lock().unwrap(), addr, request),
+            Err(_) => IPPResponse {
+                version_major: 1,
+                version_minor: 1,
+                status_code: StatusCode::ClientErrorBadRequest,
+                request_id: 0,
+                attrs: vec![(DelimiterOrValueTag::OperationAttributesTag, vec![])],
+                data: vec![],
+            },
+        };
+
+        let mut buf = vec![];
+        response
+            .write_to_stream(&mut buf)
+            .map_err(io::Error::other)?;
+        write!(
+            writer,
+            "HTTP/1.1 200 OK\r\nContent-Type: application/ipp\r\nContent-Length: {}\r\n\r\n",
+            buf.len()
+        )?;
+        writer.write_all(&buf)?;
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+fn integer_of(request: &IPPRequest, name: &str) -> Option<i32> {
+    match request.get_attribute(name) {
+        Some(AttributeValue::Integer(value)) => Some(*value),
+        _ => None,
+    }
+}
+
+fn job_attrs(addr: SocketAddr, job: &MockJob) -> Vec<(String, AttributeValue)> {
+    let reasons = match job.state {
+        _ if !job.reasons.is_empty() => keywords(&job.reasons),
+        JobState::Completed => keywords(&["job-completed-successfully"]),
+        JobState::Canceled => keywords(&["job-canceled-by-user"]),
+        _ => keywords(&["none"]),
+    };
+    let mut attrs = vec![
+        ("job-id".to_string(), AttributeValue::Integer(job.id)),
+        (
+            "job-uri".to_string(),
+            AttributeValue::Uri(format!("ipp://{}/ipp/print/{}", addr, job.id)),
+        ),
+        (
+            "job-state".to_string(),
+            AttributeValue::Enum(job.state as i32),
+        ),
+        ("job-state-reasons".to_string(), reasons),
+    ];
+    if let Some(user) = &job.user {
+        attrs.push((
+            "job-originating-user-name".to_string(),
+            AttributeValue::NameWithoutLanguage(user.clone()),
+        ));
+    }
+    attrs
+}
+
+/// Adds a document to the job, completing it if it is the last one.
+/// Compressed documents are kept decompressed, or as they are if they can't be.
+fn add_document(job: &mut MockJob, request: &IPPRequest, last: bool) {
+    let compression = request
+        .get_attribute("compression")
+        .and_then(|e| e.as_str())
+        .and_then(Compression::from_keyword);
+    let data = match compression {
+        Some(compression) => {
+            let mut data = vec![];
+            let result = match compression {
+                Compression::Gzip => GzDecoder::new(request.data.as_slice()).read_to_end(&mut data),
+                Compression::Deflate => {
+                    DeflateDecoder::new(request.data.as_slice()).read_to_end(&mut data)
+                }
+            };
+            result.map_or_else(|_| request.data.clone(), |_| data)
+        }
+        None => request.data.clone(),
+    };
+    job.documents.push(MockDocument {
+        document_format: request
+            .get_attribute("document-format")
+            .and_then(|e| e.as_str())
+            .map(|e| e.to_string()),
+        data,
+    });
+    if last {
+        job.state = JobState::Completed;
+    }
+}
+
+fn handle(state: &mut MockState, addr: SocketAddr, request: IPPRequest) -> IPPResponse {
+    let mut groups: Vec<AttributeGroup> = vec![];
+    let canned = state
+        .canned_statuses
+        .iter()
+        .find(|(op, _)| *op == request.operation_id)
+        .map(|(_, status)| *status);
+
+    let status_code = match canned {
+        Some(status) => status,
+        None => match request.operation_id {
+            PrinterOperation::GetPrinterAttributes => {
+                let requested = request
+                    .get_attribute("requested-attributes")
+                    .map(|e| {
+                        e.values()
+                            .iter()
+                            .filter_map(|e| e.as_str())
+                            .collect::<Vec<_>>()
+                    })
+                    .unwrap_or_default();
+                let attrs = state
+                    .printer_attributes
+                    .iter()
+                    .filter(|(name, _)| {
+                        requested.is_empty()
+                            || requested.contains(&"all")
+                            || requested.contains(&name.as_str())
+                    })
+                    .cloned()
+                    .collect();
+                groups.push((DelimiterOrValueTag::PrinterAttributesTag, attrs));
+                StatusCode::SuccessfulOk
+            }
+            PrinterOperation::ValidateJob => StatusCode::SuccessfulOk,
+            PrinterOperation::CreateJob
+            | PrinterOperation::PrintJob
+            | PrinterOperation::PrintURI => {
+                let mut job = MockJob {
+                    id: state.jobs.len() as i32 + 1,
+                    state: JobState::Pending,
+                    reasons: vec![],
+                    user: request
+                        .get_attribute("requesting-user-name")
+                        .and_then(|e| e.as_str())
+                        .map(|e| e.to_string()),
+                    attrs: request
+                        .attrs
+                        .iter()
+                        .filter(|(tag, _)| *tag == DelimiterOrValueTag::JobAttributesTag)
+                        .flat_map(|(_, attrs)| attrs.iter().cloned())
+                        .collect(),
+                    documents: vec![],
+                };
+                if request.operation_id != PrinterOperation::CreateJob {
+                    add_document(&mut job, &request, true);
+                }
+                groups.push((DelimiterOrValueTag::JobAttributesTag, job_attrs(addr, &job)));
+                state.jobs.push(job);
+                StatusCode::SuccessfulOk
+            }
+            PrinterOperation::SendDocument
+            | PrinterOperation::SendURI
+            | PrinterOperation::GetJobAttributes
+            | PrinterOperation::CancelJob
+            | PrinterOperation::HoldJob
+            | PrinterOperation::ReleaseJob
+            | PrinterOperation::RestartJob => {
+                let job = integer_of(&request, "job-id")
+                    .and_then(|id| state.jobs.iter_mut().find(|job| job.id == id));
+                match job {
+                    Some(job) => {
+                        match request.operation_id {
+                            PrinterOperation::SendDocument | PrinterOperation::SendURI => {
+                                let last = matches!(
+                                    request.get_attribute("last-document"),
+                                    Some(AttributeValue::Boolean(true))
+                                );
+                                add_document(job, &request, last);
+                            }
+                            PrinterOperation::CancelJob => job.state = JobState::Canceled,
+                            PrinterOperation::HoldJob => job.state = JobState::PendingHeld,
+                            PrinterOperation::ReleaseJob => job.state = JobState::Pending,
+                            // The restarted job is held only if job-hold-until is given.
+                            PrinterOperation::RestartJob => {
+                                job.state = match request.get_attribute("job-hold-until") {
+                                    Some(_) => JobState::PendingHeld,
+                                    None => JobState::Pending,
+                                }
+                            }
+                            _ => {}
+                        }
+                        groups.push((DelimiterOrValueTag::JobAttributesTag, job_attrs(addr, job)));
+                        StatusCode::SuccessfulOk
+                    }
+                    None => StatusCode::ClientErrorNotFound,
+                }
+            }
+            PrinterOperation::GetJobs => {
+                for job in &state.jobs {
+                    groups.push((DelimiterOrValueTag::JobAttributesTag, job_attrs(addr, job)));
+                }
+                StatusCode::SuccessfulOk
+            }
+            // Jobs are kept for MockPrinter::jobs instead of being deleted.
+            PrinterOperation::PurgeJobs => {
+                for job in state.jobs.iter_mut().filter(|e| !e.state.is_terminal()) {
+                    job.state = JobState::Canceled;
+                }
+                StatusCode::SuccessfulOk
+            }
+            _ => StatusCode::ServerErrorOperationNotSupported,
+        },
+    };
+
+    let operation_attrs = vec![
+        (
+            "attributes-charset".to_string(),
+            AttributeValue::Charset("utf-8".to_string()),
+        ),
+        (
+            "attributes-natural-language".to_string(),
+            AttributeValue::NaturalLanguage("en".to_string()),
+        ),
+    ];
+    groups.insert(
+        0,
+        (DelimiterOrValueTag::OperationAttributesTag, operation_attrs),
+    );
+
+    let response = IPPResponse {
+        version_major: request.version_major,
+        version_minor: request.version_minor,
+        status_code,
+        request_id: request.request_id,
+        attrs: groups,
+        data: vec![],
+    };
+    state.requests.push(request);
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::AttributeCache;
+    use crate::error::IppPrintError;
+    use crate::ipp::{Document, GetJobsOptions, JobTemplate, Sides};
+    use std::time::Duration;
+
+    #[test]
+    fn print_to_mock_printer() {
+        let printer = MockPrinter::start().unwrap();
+        let mut client = printer.client();
+
+        let attrs = client.printer_attributes().unwrap();
+        assert_eq!(Some("mock".to_string()), attrs.printer_name);
+        assert!(client
+            .supports_operation(PrinterOperation::CreateJob)
+            .unwrap());
+
+        let template = JobTemplate {
+            sides: Some(Sides::TwoSidedLongEdge),
+            ..Default::default()
+        };
+        let job_id = client.create_job(&template).unwrap();
+        client
+            .send_document(job_id, "image/pwg-raster", b"RaS2".to_vec())
+            .unwrap();
+        let status = client
+            .wait_for_completion(job_id, Duration::from_millis(10))
+            .unwrap();
+        assert_eq!(JobState::Completed, status.state);
+
+        let jobs = printer.jobs();
+        assert_eq!(1, jobs.len());
+        assert_eq!(
+            vec![MockDocument {
+                document_format: Some("image/pwg-raster".to_string()),
+                data: b"RaS2".to_vec(),
+            }],
+            jobs[0].documents
+        );
+        assert!(jobs[0].attrs.contains(&(
+            "sides".to_string(),
+            AttributeValue::Keyword("two-sided-long-edge".to_string())
+        )));
+
+        let resp = client.get_jobs(&GetJobsOptions::default()).unwrap();
+        assert_eq!(
+            Some(&AttributeValue::Integer(1)),
+            resp.get_attribute("job-id")
+        );
+    }
+
+    #[test]
+    fn print_with_print_job() {
+        let printer = MockPrinter::start().unwrap();
+        let operations = |printer: &MockPrinter| {
+            printer
+                .requests()
+                .iter()
+                .map(|e| e.operation_id)
+                .filter(|e| *e != PrinterOperation::GetPrinterAttributes)
+                .collect::<Vec<_>>()
+        };
+
+        let job_id = printer
+            .client()
+            .print(
+                "image/pwg-raster",
+                b"RaS2".to_vec(),
+                &JobTemplate::default(),
+            )
+            .unwrap();
+        assert_eq!(1, job_id);
+        assert_eq!(
+            vec![PrinterOperation::ValidateJob, PrinterOperation::PrintJob],
+            operations(&printer)
+        );
+
+        // Printers without Print-Job get Create-Job and Send-Document instead.
+        printer.set_printer_attribute(
+            "operations-supported",
+            AttributeValue::VectorAttribute(
+                [
+                    PrinterOperation::ValidateJob,
+                    PrinterOperation::CreateJob,
+                    PrinterOperation::SendDocument,
+                ]
+                .iter()
+                .map(|op| AttributeValue::Enum(u16::from(*op) as i32))
+                .collect(),
+            ),
+        );
+        let job_id = printer
+            .client()
+            .print(
+                "image/pwg-raster",
+                b"RaS2".to_vec(),
+                &JobTemplate::default(),
+            )
+            .unwrap();
+        assert_eq!(2, job_id);
+        assert_eq!(
+            vec![
+                PrinterOperation::ValidateJob,
+                PrinterOperation::PrintJob,
+                PrinterOperation::ValidateJob,
+                PrinterOperation::CreateJob,
+                PrinterOperation::SendDocument
+            ],
+            operations(&printer)
+        );
+
+        let jobs = printer.jobs();
+        assert!(jobs.iter().all(|e| e.state == JobState::Completed));
+        assert!(jobs.iter().all(|e| e.documents[0].data == b"RaS2"));
+    }
+
+    #[test]
+    fn report_stopped_job() {
+        let printer = MockPrinter::start().unwrap();
+        let mut client = printer.client();
+        let job_id = client.create_job(&JobTemplate::default()).unwrap();
+        printer.set_job_state(job_id, JobState::ProcessingStopped, &["printer-stopped"]);
+        printer.set_printer_attribute(
+            "printer-state",
+            AttributeValue::Enum(PrinterState::Stopped as i32),
+        );
+        printer.set_printer_attribute("printer-state-reasons", keywords(&["media-jam-error"]));
+
+        let reported = Arc::new(Mutex::new(vec![]));
+        let hook_reported = reported.clone();
+        client.set_stopped_hook(Box::new(move |status, printer_reasons| {
+            hook_reported
+                .lock()
+                .unwrap()
+                .push((status.reasons.clone(), printer_reasons.to_vec()));
+        }));
+        std::thread::scope(|scope| {
+            // The jam is cleared some polls after it was reported.
+            scope.spawn(|| {
+                while reported.lock().unwrap().is_empty() {
+                    std::thread::sleep(Duration::from_millis(10));
+                }
+                std::thread::sleep(Duration::from_millis(50));
+                printer.set_printer_attribute(
+                    "printer-state",
+                    AttributeValue::Enum(PrinterState::Idle as i32),
+                );
+                printer.set_job_state(job_id, JobState::Completed, &[]);
+            });
+            let status = client
+                .wait_for_completion(job_id, Duration::from_millis(10))
+                .unwrap();
+            assert_eq!(JobState::Completed, status.state);
+        });
+
+        assert_eq!(
+            vec![(
+                vec!["printer-stopped".to_string()],
+                vec!["media-jam-error".to_string()]
+            )],
+            *reported.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn cancel_job() {
+        let printer = MockPrinter::start().unwrap();
+        let mut client = printer.client();
+        let job_id = client.create_job(&JobTemplate::default()).unwrap();
+
+        client.cancel_job(job_id).unwrap();
+        assert_eq!(JobState::Canceled, printer.jobs()[0].state);
+        let request = printer.requests().pop().unwrap();
+        assert_eq!(PrinterOperation::CancelJob, request.operation_id);
+        assert_eq!(
+            Some(&AttributeValue::Integer(job_id)),
+            request.get_attribute("job-id")
+        );
+
+        assert!(matches!(
+            client.cancel_job(job_id + 1),
+            Err(IppPrintError::StatusError(
+                StatusCode::ClientErrorNotFound,
+                _
+            ))
+        ));
+    }
+
+    #[test]
+    fn hold_release_and_restart_job() {
+        let printer = MockPrinter::start().unwrap();
+        let mut client = printer.client();
+        let job_id = client.create_job(&JobTemplate::default()).unwrap();
+        let hold_until = |printer: &MockPrinter| {
+            printer
+                .requests()
+                .pop()
+                .unwrap()
+                .get_attribute("job-hold-until")
+                .cloned()
+        };
+
+        client.hold_job(job_id, None).unwrap();
+        assert_eq!(JobState::PendingHeld, printer.jobs()[0].state);
+        assert_eq!(None, hold_until(&printer));
+        client.release_job(job_id).unwrap();
+        assert_eq!(JobState::Pending, printer.jobs()[0].state);
+        client.hold_job(job_id, Some("night")).unwrap();
+        assert_eq!(
+            Some(AttributeValue::Keyword("night".to_string())),
+            hold_until(&printer)
+        );
+        client.release_job(job_id).unwrap();
+
+        client
+            .send_document(job_id, "image/pwg-raster", b"RaS2".to_vec())
+            .unwrap();
+        client.restart_job(job_id, None).unwrap();
+        assert_eq!(JobState::Pending, printer.jobs()[0].state);
+        client.restart_job(job_id, Some("indefinite")).unwrap();
+        assert_eq!(JobState::PendingHeld, printer.jobs()[0].state);
+        assert_eq!(
+            Some(AttributeValue::Keyword("indefinite".to_string())),
+            hold_until(&printer)
+        );
+
+        assert!(matches!(
+            client.release_job(job_id + 1),
+            Err(IppPrintError::StatusError(
+                StatusCode::ClientErrorNotFound,
+                _
+            ))
+        ));
+    }
+
+    #[test]
+    fn cancel_jobs_of_user() {
+        let printer = MockPrinter::start().unwrap();
+        let mut client = printer.client();
+        for user in ["alice", "bob", "alice"] {
+            client.set_user_name(user);
+            client.create_job(&JobTemplate::default()).unwrap();
+        }
+        let job_id = client.create_job(&JobTemplate::default()).unwrap();
+        client
+            .send_document(job_id, "image/pwg-raster", b"RaS2".to_vec())
+            .unwrap();
+
+        // The completed job of alice is left as it is.
+        assert_eq!(vec![1, 3], client.cancel_all_jobs(Some("alice")).unwrap());
+        assert_eq!(
+            vec![
+                JobState::Canceled,
+                JobState::Pending,
+                JobState::Canceled,
+                JobState::Completed
+            ],
+            printer.jobs().iter().map(|e| e.state).collect::<Vec<_>>()
+        );
+        assert_eq!(vec![2], client.cancel_all_jobs(None).unwrap());
+
+        // Purge-Jobs isn't in operations-supported of the mock printer.
+        assert!(!client
+            .supports_operation(PrinterOperation::PurgeJobs)
+            .unwrap());
+        client.purge_jobs().unwrap();
+    }
+
+    #[test]
+    fn cached_attributes() {
+        let printer = MockPrinter::start().unwrap();
+        let mut client = printer.client();
+        client.set_attribute_cache(AttributeCache::new(Duration::from_secs(60)));
+        let full_requests = || {
+            printer
+                .requests()
+                .iter()
+                .filter(|e| e.get_attribute("requested-attributes").is_none())
+                .count()
+        };
+
+        assert_eq!(
+            Some("mock".to_string()),
+            client.printer_attributes().unwrap().printer_name
+        );
+        client.printer_attributes().unwrap();
+        assert_eq!(1, full_requests());
+
+        // Changing settings of the printer makes the cached attributes stale.
+        printer.set_printer_attribute("printer-config-change-time", AttributeValue::Integer(2));
+        printer.set_printer_attribute(
+            "printer-name",
+            AttributeValue::NameWithoutLanguage("renamed".to_string()),
+        );
+        assert_eq!(
+            Some("renamed".to_string()),
+            client.printer_attributes().unwrap().printer_name
+        );
+        assert_eq!(2, full_requests());
+    }
+
+    #[test]
+    fn unwritable_attribute_cache() {
+        let printer = MockPrinter::start().unwrap();
+        let mut client = printer.client();
+        // A directory can't be created under a file, even by root.
+        let file = std::env::temp_dir().join(format!("ipp-print-not-dir-{}", std::process::id()));
+        std::fs::write(&file, b"").unwrap();
+        client.set_attribute_cache(AttributeCache::with_dir(
+            Duration::from_secs(60),
+            file.join("printers"),
+        ));
+
+        let attrs = client.printer_attributes();
+        std::fs::remove_file(&file).unwrap();
+        assert_eq!(Some("mock".to_string()), attrs.unwrap().printer_name);
+    }
+
+    #[test]
+    fn compressed_document() {
+        let printer = MockPrinter::start().unwrap();
+        let mut client = printer.client();
+        let attrs = client.printer_attributes().unwrap();
+        let compression = Compression::choose(&attrs.compression_supported);
+        assert_eq!(Some(Compression::Gzip), compression);
+        client.set_compression(compression);
+
+        let data = b"RaS2".repeat(1000);
+        let job_id = client.create_job(&JobTemplate::default()).unwrap();
+        client
+            .send_document_stream(job_id, "image/pwg-raster", io::Cursor::new(data.clone()))
+            .unwrap();
+        client.set_compression(Some(Compression::Deflate));
+        client
+            .print_job("image/pwg-raster", data.clone(), &JobTemplate::default())
+            .unwrap();
+
+        // Documents are sent compressed and received as they were.
+        let documents = printer
+            .requests()
+            .into_iter()
+            .filter(|e| !e.data.is_empty())
+            .collect::<Vec<_>>();
+        assert_eq!(2, documents.len());
+        for (job, request) in printer.jobs().iter().zip(&documents) {
+            assert_eq!(data, job.documents[0].data);
+            assert!(request.data.len() < 100);
+        }
+        assert_eq!(
+            Some(&AttributeValue::Keyword("deflate".to_string())),
+            printer
+                .requests()
+                .last()
+                .unwrap()
+                .get_attribute("compression")
+        );
+        assert_eq!(None, Compression::choose(&["none".to_string()]));
+    }
+
+    #[test]
+    fn multiple_documents() {
+        let printer = MockPrinter::start().unwrap();
+        let mut client = printer.client();
+        let mut job = client.start_job(&JobTemplate::default()).unwrap();
+        job.send_document(
+            Document::new("image/pwg-raster", io::Cursor::new(b"RaS2")).with_name("a.txt"),
+            false,
+        )
+        .unwrap();
+        let job_id = job.id();
+        assert_eq!(
+            JobState::Pending,
+            client.get_job_status(job_id).unwrap().state
+        );
+
+        client
+            .job(job_id)
+            .send_document(
+                Document::new("image/jpeg", io::Cursor::new(b"\xff\xd8\xff")),
+                true,
+            )
+            .unwrap();
+        let jobs = printer.jobs();
+        assert_eq!(JobState::Completed, jobs[0].state);
+        assert_eq!(
+            vec![Some("image/pwg-raster"), Some("image/jpeg")],
+            jobs[0]
+                .documents
+                .iter()
+                .map(|e| e.document_format.as_deref())
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            Some(&AttributeValue::NameWithoutLanguage("a.txt".to_string())),
+            printer.requests()[1].get_attribute("document-name")
+        );
+    }
+
+    #[test]
+    fn print_by_reference() {
+        let printer = MockPrinter::start().unwrap();
+        let mut client = printer.client();
+        let uri = "http://192.0.2.10/report.pdf";
+
+        let mut attrs = client.printer_attributes().unwrap();
+        assert!(attrs.check_reference_uri(uri).is_err());
+        attrs.reference_uri_schemes_supported = vec!["http".to_string(), "ftp".to_string()];
+        attrs.check_reference_uri(uri).unwrap();
+        assert!(attrs.check_reference_uri("HTTP://192.0.2.10/a").is_ok());
+        assert!(attrs.check_reference_uri("https://192.0.2.10/a").is_err());
+        assert!(attrs.check_reference_uri("report.pdf").is_err());
+
+        client
+            .print_uri(uri, Some("application/pdf"), &JobTemplate::default())
+            .unwrap();
+        let mut job = client.start_job(&JobTemplate::default()).unwrap();
+        job.send_uri(uri, None, true).unwrap();
+
+        let jobs = printer.jobs();
+        assert_eq!(
+            vec![JobState::Completed, JobState::Completed],
+            jobs.iter().map(|e| e.state).collect::<Vec<_>>()
+        );
+        let requests = printer.requests();
+        for request in [&requests[1], &requests[3]] {
+            assert_eq!(
+                Some(&AttributeValue::Uri(uri.to_string())),
+                request.get_attribute("document-uri")
+            );
+        }
+        assert_eq!(PrinterOperation::SendURI, requests[3].operation_id);
+    }
+
+    #[test]
+    fn canned_status() {
+        let printer = MockPrinter::start().unwrap();
+        printer.set_status(
+            PrinterOperation::CreateJob,
+            StatusCode::ServerErrorNotAcceptingJobs,
+        );
+
+        let mut client = printer.client();
+        assert!(client.create_job(&JobTemplate::default()).is_err());
+        assert!(printer.jobs().is_empty());
+        assert_eq!(
+            PrinterOperation::CreateJob,
+            printer.requests().last().unwrap().operation_id
+        );
+    }
+}
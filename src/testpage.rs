@@ -0,0 +1,210 @@
+//! Built-in page for checking a printer: a border at the edges of the media,
+//! alignment crosses, color bars, a gray ramp, and resolution wedges.
+
+use crate::layout::Image;
+use crate::pwgraster::{PageHeader, Pixel, SrgbColor};
+
+/// Process colors, their overprints, and black.
+const COLOR_BARS: &[u32] = &[
+    0x00ffff, 0xff00ff, 0xffff00, 0xff0000, 0x00ff00, 0x0000ff, 0x000000,
+];
+
+/// Steps of the gray ramp, from white to black.
+const GRAY_STEPS: u32 = 11;
+
+/// Distance from the edges of the media to the content, in millimeters.
+const MARGIN: f64 = 15.0;
+
+struct Canvas {
+    image: Image<SrgbColor>,
+    /// [0]: Horizontal dpi
+    /// [1]: Vertical dpi
+    dpi: [u32; 2],
+}
+
+impl Canvas {
+    fn x(&self, mm: f64) -> usize {
+        (mm * self.dpi[0] as f64 / 25.4).round() as usize
+    }
+
+    fn y(&self, mm: f64) -> usize {
+        (mm * self.dpi[1] as f64 / 25.4).round() as usize
+    }
+
+    /// Fills the rectangle in pixels, clipped to the page.
+    fn fill(&mut self, x: usize, y: usize, width: usize, height: usize, color: &SrgbColor) {
+        let page_width = self.image.width();
+        let right = (x + width).min(page_width);
+        let bottom = (y + height).min(self.image.height());
+        for row in y.min(bottom)..bottom {
+            self.image.pixels_mut()[row * page_width + x.min(right)..row * page_width + right]
+                .fill(color.clone());
+        }
+    }
+
+    /// Lines at the edges of the media, which are cut off where the printer
+    /// can't print, and a thinner frame 5 mm inside them.
+    fn draw_border(&mut self) {
+        let (width, height) = (self.image.width(), self.image.height());
+        for (inset, thickness) in [(0.0, 0.5), (5.0, 0.2)] {
+            let (x, y) = (self.x(inset), self.y(inset));
+            let (tx, ty) = (self.x(thickness).max(1), self.y(thickness).max(1));
+            let (inner_width, inner_height) =
+                (width.saturating_sub(2 * x), height.saturating_sub(2 * y));
+            self.fill(x, y, inner_width, ty, &SrgbColor::BLACK);
+            self.fill(
+                x,
+                (y + inner_height).saturating_sub(ty),
+                inner_width,
+                ty,
+                &SrgbColor::BLACK,
+            );
+            self.fill(x, y, tx, inner_height, &SrgbColor::BLACK);
+            self.fill(
+                (x + inner_width).saturating_sub(tx),
+                y,
+                tx,
+                inner_height,
+                &SrgbColor::BLACK,
+            );
+        }
+    }
+
+    /// A cross centered at (x, y) in pixels.
+    fn draw_cross(&mut self, x: usize, y: usize) {
+        let (arm_x, arm_y) = (self.x(4.0), self.y(4.0));
+        let (tx, ty) = (self.x(0.2).max(1), self.y(0.2).max(1));
+        self.fill(
+            x.saturating_sub(arm_x),
+            y.saturating_sub(ty / 2),
+            2 * arm_x,
+            ty,
+            &SrgbColor::BLACK,
+        );
+        self.fill(
+            x.saturating_sub(tx / 2),
+            y.saturating_sub(arm_y),
+            tx,
+            2 * arm_y,
+            &SrgbColor::BLACK,
+        );
+    }
+
+    /// Crosses near the corners and at the center, to check how the page is
+    /// placed and whether the two sides of duplex sheets line up.
+    fn draw_crosses(&mut self) {
+        let (width, height) = (self.image.width(), self.image.height());
+        let (x, y) = (self.x(MARGIN / 2.0), self.y(MARGIN / 2.0));
+        for (cx, cy) in [
+            (x, y),
+            (width.saturating_sub(x), y),
+            (x, height.saturating_sub(y)),
+            (width.saturating_sub(x), height.saturating_sub(y)),
+            (width / 2, height / 2),
+        ] {
+            self.draw_cross(cx, cy);
+        }
+    }
+
+    /// Fills top to top + height with patches of the colors side by side
+    /// between the margins.
+    fn draw_patches(&mut self, top: usize, height: usize, colors: &[SrgbColor]) {
+        let left = self.x(MARGIN);
+        let width = self.image.width().saturating_sub(2 * left);
+        for (i, color) in colors.iter().enumerate() {
+            let x = left + width * i / colors.len();
+            let next = left + width * (i + 1) / colors.len();
+            self.fill(x, top, next - x, height, color);
+        }
+    }
+
+    /// Patches of lines 1 to 4 pixels wide with gaps of the same width,
+    /// vertical lines above horizontal ones, to see the smallest lines the
+    /// printer resolves.
+    fn draw_wedges(&mut self, top: usize) {
+        let (size_x, size_y) = (self.x(12.0), self.y(12.0));
+        let (gap_x, gap_y) = (self.x(5.0), self.y(5.0));
+        for line in 1..=4 {
+            let x = self.x(MARGIN) + (line - 1) * (size_x + gap_x);
+            for i in (0..size_x).step_by(2 * line) {
+                self.fill(x + i, top, line.min(size_x - i), size_y, &SrgbColor::BLACK);
+            }
+            let y = top + size_y + gap_y;
+            for i in (0..size_y).step_by(2 * line) {
+                self.fill(x, y + i, size_x, line.min(size_y - i), &SrgbColor::BLACK);
+            }
+        }
+    }
+}
+
+/// Renders the test page in the size and resolution of the header. Parts
+/// which don't fit on small media are clipped.
+pub fn render(header: &PageHeader) -> Image<SrgbColor> {
+    let (width, height) = (header.width() as usize, header.height() as usize);
+    let mut canvas = Canvas {
+        image: Image::filled(width, height, SrgbColor::WHITE),
+        dpi: header.resolution(),
+    };
+
+    canvas.draw_border();
+    canvas.draw_crosses();
+
+    let colors = COLOR_BARS
+        .iter()
+        .map(|e| SrgbColor::from(*e))
+        .collect::<Vec<_>>();
+    let top = canvas.y(MARGIN + 10.0);
+    canvas.draw_patches(top, canvas.y(12.0), &colors);
+
+    let grays = (0..GRAY_STEPS)
+        .map(|i| {
+            let level = (255 - 255 * i / (GRAY_STEPS - 1)) as u8;
+            SrgbColor::new(level, level, level)
+        })
+        .collect::<Vec<_>>();
+    let top = canvas.y(MARGIN + 25.0);
+    canvas.draw_patches(top, canvas.y(8.0), &grays);
+
+    let top = canvas.y(MARGIN + 38.0);
+    canvas.draw_wedges(top);
+
+    canvas.image
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::media::MediaSize;
+    use crate::pwgraster::PageHeaderBuilder;
+
+    #[test]
+    fn render_test_page() {
+        // jpn_hagaki at 100 dpi is 393 x 582 pixels.
+        let header = PageHeaderBuilder::with_media_size(
+            &MediaSize::from_name("jpn_hagaki_100x148mm").unwrap(),
+        )
+        .resolution(100, 100)
+        .build()
+        .unwrap();
+        let page = render(&header);
+        assert_eq!((393, 582), (page.width(), page.height()));
+
+        let pixel = |x: usize, y: usize| page.pixels()[y * page.width() + x].clone();
+        // Border at the edges
+        assert_eq!(SrgbColor::BLACK, pixel(0, 0));
+        assert_eq!(SrgbColor::BLACK, pixel(392, 581));
+        assert_eq!(SrgbColor::WHITE, pixel(5, 5));
+        // Cross at the center
+        assert_eq!(SrgbColor::BLACK, pixel(196, 291));
+        assert_eq!(SrgbColor::WHITE, pixel(190, 285));
+        // First color bar is cyan, and the gray ramp starts at white.
+        assert_eq!(SrgbColor::new(0, 255, 255), pixel(70, 110));
+        assert_eq!(SrgbColor::WHITE, pixel(62, 170));
+        assert_eq!(SrgbColor::BLACK, pixel(330, 170));
+        // Wedge of 1 pixel lines
+        assert_eq!(
+            (SrgbColor::BLACK, SrgbColor::WHITE),
+            (pixel(59, 225), pixel(60, 225))
+        );
+    }
+}
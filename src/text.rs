@@ -0,0 +1,241 @@
+//! Plain text rendering with a TrueType or OpenType font.
+//!
+//! Text is laid out on the pages of a page header like lpr does, wrapping long
+//! lines and starting a new page on a form feed or when a page is full. Lines
+//! break after spaces, and between any two CJK characters except before closing
+//! punctuation and small kana.
+
+use crate::error::IppPrintError;
+use crate::layout::Image;
+use crate::pwgraster::{GrayColor, PageHeader, Pixel};
+use fontdue::{Font, FontSettings};
+use std::io;
+
+/// Characters which can't start a line (kinsoku).
+const NO_BREAK_BEFORE: &str =
+    "、。，．・：；？！ー）］｝」』】〉》〕ゝゞぁぃぅぇぉっゃゅょゎァィゥェォッャュョヮヵヶ";
+
+/// Renders text onto pages in grayscale.
+pub struct TextRenderer {
+    font: Font,
+    /// Font size in points
+    pub size: f32,
+    /// Left, top, right, and bottom margins in points. Text also stays in the
+    /// image box of the page.
+    pub margins: [f32; 4],
+    /// Tab stops every this many columns
+    pub tab_width: usize,
+}
+
+impl TextRenderer {
+    /// Loads a font file, which should have glyphs for every script in the text.
+    /// For a font collection, the first font is used.
+    pub fn new(font: &[u8]) -> Result<Self, IppPrintError> {
+        let font = Font::from_bytes(font, FontSettings::default())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        Ok(Self {
+            font,
+            size: 10.0,
+            margins: [36.0; 4],
+            tab_width: 8,
+        })
+    }
+
+    /// Lays out text and renders each page in the size of header. Glyphs are
+    /// rasterized for the vertical resolution.
+    pub fn render(
+        &self,
+        text: &str,
+        header: &PageHeader,
+    ) -> Result<Vec<Image<GrayColor>>, IppPrintError> {
+        let (width, height) = (header.width() as usize, header.height() as usize);
+        let [x_dpi, y_dpi] = header.resolution().map(|e| e as f32);
+        let px = self.size * y_dpi / 72.0;
+
+        let [left, top, right, bottom] = header.image_box().map(|e| e as f32);
+        let [margin_left, margin_top, margin_right, margin_bottom] = self.margins;
+        let left = left.max(margin_left * x_dpi / 72.0);
+        let top = top.max(margin_top * y_dpi / 72.0);
+        let right = right.min(width as f32 - margin_right * x_dpi / 72.0);
+        let bottom = bottom.min(height as f32 - margin_bottom * y_dpi / 72.0);
+
+        let (ascent, line_height) = match self.font.horizontal_line_metrics(px) {
+            Some(metrics) => (metrics.ascent, metrics.new_line_size),
+            None => (px, px * 1.2),
+        };
+        let lines_per_page = ((bottom - top) / line_height).floor();
+        if right - left < px || lines_per_page < 1.0 {
+            return Err(IppPrintError::RasterError(format!(
+                "no room for {}pt text within the margins",
+                self.size
+            )));
+        }
+
+        let pages = paginate(
+            text,
+            right - left,
+            lines_per_page as usize,
+            self.tab_width,
+            |c| self.font.metrics(c, px).advance_width,
+        );
+        Ok(pages
+            .iter()
+            .map(|lines| {
+                let mut page = Image::filled(width, height, GrayColor::WHITE);
+                for (i, line) in lines.iter().enumerate() {
+                    let baseline = top + i as f32 * line_height + ascent;
+                    self.draw_line(&mut page, line, left, baseline, px);
+                }
+                page
+            })
+            .collect())
+    }
+
+    fn draw_line(&self, page: &mut Image<GrayColor>, line: &str, x: f32, baseline: f32, px: f32) {
+        let (width, height) = (page.width() as i64, page.height() as i64);
+        let mut pen = x;
+        for c in line.chars() {
+            let (metrics, coverage) = self.font.rasterize(c, px);
+            let left = (pen + metrics.xmin as f32).round() as i64;
+            let top = (baseline - (metrics.ymin + metrics.height as i32) as f32).round() as i64;
+            for (i, alpha) in coverage.iter().enumerate() {
+                let x = left + (i % metrics.width.max(1)) as i64;
+                let y = top + (i / metrics.width.max(1)) as i64;
+                if *alpha > 0 && (0..width).contains(&x) && (0..height).contains(&y) {
+                    let pixel = &mut page.pixels_mut()[(y * width + x) as usize];
+                    pixel.0 = pixel.0.min(255 - alpha);
+                }
+            }
+            pen += metrics.advance_width;
+        }
+    }
+}
+
+/// Splits text into pages of lines no wider than width, where advance gives the
+/// width of a character.
+fn paginate<F>(
+    text: &str,
+    width: f32,
+    lines_per_page: usize,
+    tab_width: usize,
+    advance: F,
+) -> Vec<Vec<String>>
+where
+    F: Fn(char) -> f32,
+{
+    let text = text.strip_suffix('\x0c').unwrap_or(text);
+    let mut pages = vec![];
+    for page in text.split('\x0c') {
+        let lines = page
+            .lines()
+            .flat_map(|e| wrap(&expand_tabs(e, tab_width), width, &advance))
+            .collect::<Vec<_>>();
+        if lines.is_empty() {
+            pages.push(vec![]);
+        }
+        pages.extend(lines.chunks(lines_per_page).map(<[String]>::to_vec));
+    }
+    pages
+}
+
+fn expand_tabs(line: &str, tab_width: usize) -> String {
+    let mut result = String::new();
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = tab_width.max(1) - column % tab_width.max(1);
+            result.extend(std::iter::repeat_n(' ', spaces));
+            column += spaces;
+        } else {
+            result.push(c);
+            column += 1;
+        }
+    }
+    result
+}
+
+/// Wraps a line without newlines. A word wider than width is broken anywhere.
+fn wrap<F>(line: &str, width: f32, advance: &F) -> Vec<String>
+where
+    F: Fn(char) -> f32,
+{
+    let chars = line.chars().collect::<Vec<_>>();
+    let mut lines = vec![];
+    let mut start = 0;
+    while start < chars.len() || lines.is_empty() {
+        // At least one character for progress
+        let mut end = start;
+        let mut x = 0.0;
+        while end < chars.len() && (end == start || x + advance(chars[end]) <= width) {
+            x += advance(chars[end]);
+            end += 1;
+        }
+        if end < chars.len() {
+            if let Some(e) = (start + 1..=end)
+                .rev()
+                .find(|&i| can_break(chars[i - 1], chars[i]))
+            {
+                end = e;
+            }
+        }
+
+        lines.push(
+            chars[start..end]
+                .iter()
+                .collect::<String>()
+                .trim_end()
+                .to_string(),
+        );
+        start = end;
+        while start < chars.len() && chars[start] == ' ' {
+            start += 1;
+        }
+    }
+    lines
+}
+
+fn can_break(before: char, after: char) -> bool {
+    if after.is_whitespace() || NO_BREAK_BEFORE.contains(after) {
+        false
+    } else {
+        before.is_whitespace() || is_cjk(before) || is_cjk(after)
+    }
+}
+
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{2e80}'..='\u{9fff}'
+        | '\u{ac00}'..='\u{d7af}'
+        | '\u{f900}'..='\u{faff}'
+        | '\u{ff00}'..='\u{ffef}'
+        | '\u{20000}'..='\u{2ffff}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wrap_lines() {
+        // Every character is 1 wide.
+        let wrap = |line| wrap(line, 10.0, &|_| 1.0);
+        assert_eq!(vec![""], wrap(""));
+        assert_eq!(vec!["hello", "world"], wrap("hello world"));
+        assert_eq!(vec!["a quick", "brown fox"], wrap("a quick brown fox"));
+        assert_eq!(vec!["abcdefghij", "klm"], wrap("abcdefghijklm"));
+        // "、" doesn't start a line.
+        assert_eq!(
+            vec!["あいうえ", "お、かきく", "けこ"],
+            super::wrap("あいうえお、かきくけこ", 5.0, &|_| 1.0)
+        );
+    }
+
+    #[test]
+    fn paginate_text() {
+        let pages = paginate("a\tb\n1\n2\n3\x0cnext\n\x0c\x0c", 10.0, 2, 4, |_| 1.0);
+        assert_eq!(
+            vec![vec!["a   b", "1"], vec!["2", "3"], vec!["next"], vec![]],
+            pages
+        );
+    }
+}
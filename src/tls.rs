@@ -0,0 +1,147 @@
+use crate::error::IppPrintError;
+use reqwest::blocking::ClientBuilder;
+
+/// How certificates of ipps:// printers are checked.
+/// Printers usually come with self-signed certificates, so Verify only works
+/// for printers with a certificate issued by a trusted CA.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub enum TlsVerification {
+    /// Verify the certificate chain and host name with the system roots.
+    #[default]
+    Verify,
+    /// Accept any certificate. The connection is encrypted but not authenticated.
+    AcceptInvalidCerts,
+    /// Accept only the certificate with this SHA-256 fingerprint.
+    /// Requires the rustls feature.
+    Fingerprint([u8; 32]),
+}
+
+impl TlsVerification {
+    /// Parses a SHA-256 fingerprint in hex, optionally separated with colons
+    /// like "AB:CD:...", as printed by `openssl x509 -fingerprint -sha256`.
+    pub fn parse_fingerprint(fingerprint: &str) -> Option<Self> {
+        let hex = fingerprint.replace(':', "");
+        if hex.len() != 64 || !hex.is_ascii() {
+            return None;
+        }
+
+        let mut result = [0u8; 32];
+        for (i, e) in result.iter_mut().enumerate() {
+            *e = u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()?;
+        }
+        Some(Self::Fingerprint(result))
+    }
+}
+
+#[cfg(feature = "rustls")]
+mod pinning {
+    use rustls::client::{ServerCertVerified, ServerCertVerifier};
+    use rustls::{Certificate, ServerName};
+    use sha2::{Digest, Sha256};
+    use std::time::SystemTime;
+
+    /// Accepts only the certificate with the fingerprint, ignoring its issuer and names.
+    pub struct FingerprintVerifier {
+        pub fingerprint: [u8; 32],
+    }
+
+    impl ServerCertVerifier for FingerprintVerifier {
+        fn verify_server_cert(
+            &self,
+            end_entity: &Certificate,
+            _intermediates: &[Certificate],
+            _server_name: &ServerName,
+            _scts: &mut dyn Iterator<Item = &[u8]>,
+            _ocsp_response: &[u8],
+            _now: SystemTime,
+        ) -> Result<ServerCertVerified, rustls::Error> {
+            if Sha256::digest(&end_entity.0)[..] == self.fingerprint {
+                Ok(ServerCertVerified::assertion())
+            } else {
+                Err(rustls::Error::InvalidCertificate(
+                    rustls::CertificateError::ApplicationVerificationFailure,
+                ))
+            }
+        }
+    }
+}
+
+/// Configures the HTTP client to check certificates as verification says.
+pub(crate) fn configure(
+    builder: ClientBuilder,
+    verification: &TlsVerification,
+) -> Result<ClientBuilder, IppPrintError> {
+    Ok(match verification {
+        TlsVerification::Verify => builder,
+        #[cfg(any(feature = "rustls", feature = "native-tls"))]
+        TlsVerification::AcceptInvalidCerts => builder.danger_accept_invalid_certs(true),
+        #[cfg(not(any(feature = "rustls", feature = "native-tls")))]
+        TlsVerification::AcceptInvalidCerts => {
+            return Err(IppPrintError::NotAvailable(
+                "ipps:// requires the rustls or native-tls feature".to_string(),
+            ))
+        }
+        #[cfg(feature = "rustls")]
+        TlsVerification::Fingerprint(fingerprint) => {
+            let config = rustls::ClientConfig::builder()
+                .with_safe_defaults()
+                .with_custom_certificate_verifier(std::sync::Arc::new(
+                    pinning::FingerprintVerifier {
+                        fingerprint: *fingerprint,
+                    },
+                ))
+                .with_no_client_auth();
+            builder.use_preconfigured_tls(config)
+        }
+        #[cfg(not(feature = "rustls"))]
+        TlsVerification::Fingerprint(_) => {
+            return Err(IppPrintError::NotAvailable(
+                "certificate pinning requires the rustls feature".to_string(),
+            ))
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_fingerprint() {
+        let mut expected = [0u8; 32];
+        expected[0] = 0xab;
+        expected[31] = 0x01;
+        let hex = format!("AB:{}:01", ["00"; 30].join(":"));
+        assert_eq!(
+            Some(TlsVerification::Fingerprint(expected)),
+            TlsVerification::parse_fingerprint(&hex)
+        );
+        assert_eq!(
+            Some(TlsVerification::Fingerprint(expected)),
+            TlsVerification::parse_fingerprint(&hex.replace(':', "").to_lowercase())
+        );
+
+        assert_eq!(None, TlsVerification::parse_fingerprint("AB:CD"));
+        assert_eq!(
+            None,
+            TlsVerification::parse_fingerprint(&hex.replace("AB", "XY"))
+        );
+    }
+
+    #[test]
+    fn build_clients() {
+        let build = |verification| {
+            configure(reqwest::blocking::Client::builder(), &verification)
+                .and_then(|builder| Ok(builder.build()?))
+        };
+        assert!(build(TlsVerification::Verify).is_ok());
+        assert_eq!(
+            cfg!(any(feature = "rustls", feature = "native-tls")),
+            build(TlsVerification::AcceptInvalidCerts).is_ok()
+        );
+        assert_eq!(
+            cfg!(feature = "rustls"),
+            build(TlsVerification::Fingerprint([0; 32])).is_ok()
+        );
+    }
+}
@@ -0,0 +1,668 @@
+use crate::auth::AuthProvider;
+use crate::error::IppPrintError;
+use crate::tls::{self, TlsVerification};
+use reqwest::blocking::{Client, ClientBuilder};
+use std::io;
+use std::io::prelude::*;
+use std::io::BufReader;
+use std::time::Duration;
+
+/// Carries encoded IPP messages between PrintClient and a printer.
+/// Implement this to talk to printers over something other than HTTP,
+/// or to answer requests without a printer in tests.
+pub trait Transport: Send {
+    /// printer-uri to put in requests, like "ipp://192.0.2.1:631/ipp/print".
+    fn printer_uri(&self) -> String;
+
+    /// Sends an encoded IPP request and returns a reader of the encoded response.
+    fn send_ipp(&mut self, request: Vec<u8>) -> Result<Box<dyn Read>, IppPrintError>;
+
+    /// Sends an encoded IPP request followed by data.
+    /// The default implementation reads all of data into memory; override this
+    /// if the transport can send data as it is read.
+    fn send_ipp_stream(
+        &mut self,
+        request: Vec<u8>,
+        mut data: Box<dyn Read + Send>,
+    ) -> Result<Box<dyn Read>, IppPrintError> {
+        let mut request = request;
+        data.read_to_end(&mut request)?;
+        self.send_ipp(request)
+    }
+}
+
+/// Reader calling back with the number of bytes read so far, to show the
+/// progress of [`crate::ipp::PrintClient::send_document_stream`]. Transports
+/// which stream read data as it is sent, so this follows the upload.
+pub struct ProgressReader<R, F> {
+    inner: R,
+    read: u64,
+    callback: F,
+}
+
+impl<R, F> ProgressReader<R, F>
+where
+    R: Read,
+    F: FnMut(u64),
+{
+    pub fn new(inner: R, callback: F) -> Self {
+        Self {
+            inner,
+            read: 0,
+            callback,
+        }
+    }
+}
+
+impl<R, F> Read for ProgressReader<R, F>
+where
+    R: Read,
+    F: FnMut(u64),
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        if len > 0 {
+            self.read += len as u64;
+            (self.callback)(self.read);
+        }
+        Ok(len)
+    }
+}
+
+/// Printer URI with ipp or ipps scheme (RFC 3510, RFC 7472).
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct PrinterUri {
+    /// Whether to use TLS (ipps://).
+    pub secure: bool,
+    /// Host name or IP address, with brackets for IPv6 like "[2001:db8::1]"
+    pub host: String,
+    pub port: u16,
+    /// Resource path like "/ipp/print"
+    pub path: String,
+}
+
+impl PrinterUri {
+    pub const DEFAULT_PORT: u16 = 631;
+    /// Path of IPP Everywhere printers, used when the path is omitted.
+    pub const DEFAULT_PATH: &'static str = "/ipp/print";
+
+    /// Parses a URI like "ipps://printer.local/ipp/print", or an address like
+    /// "192.0.2.1:631" which is taken as ipp. The port defaults to 631 and the
+    /// path to /ipp/print.
+    pub fn parse(uri: &str) -> Result<Self, IppPrintError> {
+        let invalid = || IppPrintError::InvalidUri(uri.to_string());
+
+        let (secure, rest) = match uri.split_once("://") {
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("ipps") => (true, rest),
+            Some((scheme, rest)) if scheme.eq_ignore_ascii_case("ipp") => (false, rest),
+            Some(_) => return Err(invalid()),
+            None => (false, uri),
+        };
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], &rest[i..]),
+            None => (rest, ""),
+        };
+
+        // The port is after the closing bracket of IPv6 addresses.
+        let port_start = authority.rfind(']').unwrap_or(0);
+        let (host, port) = match authority[port_start..].rfind(':') {
+            Some(i) => {
+                let (host, port) = authority.split_at(port_start + i);
+                (host, port[1..].parse::<u16>().map_err(|_| invalid())?)
+            }
+            None => (authority, Self::DEFAULT_PORT),
+        };
+        if host.is_empty() || host.contains(['@', '?', '#']) {
+            return Err(invalid());
+        }
+
+        Ok(Self {
+            secure,
+            host: host.to_string(),
+            port,
+            path: match path {
+                "" => Self::DEFAULT_PATH.to_string(),
+                path => path.to_string(),
+            },
+        })
+    }
+
+    /// URL to post IPP requests to.
+    pub fn http_url(&self) -> String {
+        let scheme = if self.secure { "https" } else { "http" };
+        format!("{}://{}:{}{}", scheme, self.host, self.port, self.path)
+    }
+}
+
+impl std::str::FromStr for PrinterUri {
+    type Err = IppPrintError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s)
+    }
+}
+
+impl std::fmt::Display for PrinterUri {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let scheme = if self.secure { "ipps" } else { "ipp" };
+        write!(f, "{}://{}:{}{}", scheme, self.host, self.port, self.path)
+    }
+}
+
+/// IPP over HTTP (RFC 8010 4), or HTTPS for ipps:// printers.
+pub struct HttpTransport {
+    client: Client,
+    uri: PrinterUri,
+    auth: Option<Box<dyn AuthProvider>>,
+}
+
+impl HttpTransport {
+    /// printer_addr is an address like "192.0.2.1:631", or a URI with ipp or ipps scheme
+    /// like "ipps://192.0.2.1:631/ipp/print". TLS is used for ipps.
+    /// Fails with InvalidUri if printer_addr can't be parsed.
+    pub fn new(printer_addr: &str) -> Result<Self, IppPrintError> {
+        Ok(Self::with_uri(PrinterUri::parse(printer_addr)?))
+    }
+
+    pub fn with_uri(uri: PrinterUri) -> Self {
+        Self {
+            // Default settings never fail to build.
+            client: HttpTransport::client_builder().build().unwrap(),
+            uri,
+            auth: None,
+        }
+    }
+
+    /// Keeps one connection to the printer open across operations of a job,
+    /// because some printers are slow to accept new connections.
+    fn client_builder() -> ClientBuilder {
+        Client::builder()
+            .http1_only()
+            .pool_max_idle_per_host(1)
+            .pool_idle_timeout(Duration::from_secs(60))
+            .tcp_keepalive(Duration::from_secs(30))
+            .tcp_nodelay(true)
+    }
+
+    /// Sends credentials from auth with every request.
+    pub fn set_auth(&mut self, auth: Box<dyn AuthProvider>) {
+        self.auth = Some(auth);
+    }
+
+    /// Changes how the certificate of the printer is checked.
+    pub fn set_tls_verification(
+        &mut self,
+        verification: &TlsVerification,
+    ) -> Result<(), IppPrintError> {
+        self.client = tls::configure(HttpTransport::client_builder(), verification)?.build()?;
+        Ok(())
+    }
+
+    /// Reads the whole body, so that the connection goes back to the pool
+    /// even if the caller stops reading halfway.
+    fn read_body(response: reqwest::blocking::Response) -> Result<Box<dyn Read>, IppPrintError> {
+        let body = response.error_for_status()?.bytes()?;
+        Ok(Box::new(io::Cursor::new(body)))
+    }
+
+    fn post<B>(&mut self, body: B) -> Result<reqwest::blocking::Response, IppPrintError>
+    where
+        B: Into<reqwest::blocking::Body>,
+    {
+        let mut request = self
+            .client
+            .post(self.uri.http_url())
+            .header("Content-Type", "application/ipp")
+            .body(body);
+        if let Some(auth) = self.auth.as_mut() {
+            request = request.header("Authorization", auth.authorization()?);
+        }
+
+        Ok(request.send()?)
+    }
+}
+
+impl Transport for HttpTransport {
+    fn printer_uri(&self) -> String {
+        self.uri.to_string()
+    }
+
+    fn send_ipp(&mut self, request: Vec<u8>) -> Result<Box<dyn Read>, IppPrintError> {
+        let mut response = self.post(request.clone())?;
+        if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+            if let Some(auth) = self.auth.as_mut() {
+                if auth.refresh()? {
+                    response = self.post(request)?;
+                }
+            }
+        }
+
+        HttpTransport::read_body(response)
+    }
+
+    fn send_ipp_stream(
+        &mut self,
+        request: Vec<u8>,
+        data: Box<dyn Read + Send>,
+    ) -> Result<Box<dyn Read>, IppPrintError> {
+        // The length is unknown, so this is sent with chunked transfer encoding.
+        // The body can't be sent again, so credentials are not refreshed on 401.
+        let body = reqwest::blocking::Body::new(io::Cursor::new(request).chain(data));
+        let response = self.post(body)?;
+
+        HttpTransport::read_body(response)
+    }
+}
+
+/// IPP over HTTP/1.1 on an already connected byte stream, like a Unix domain socket
+/// of the local CUPS or a USB printer interface. The connection is kept alive across requests.
+pub struct StreamTransport<S> {
+    stream: BufReader<S>,
+    printer_uri: String,
+    /// Path of printer_uri, used as the request target.
+    resource: String,
+}
+
+impl<S> StreamTransport<S>
+where
+    S: Read + Write,
+{
+    /// printer_uri is sent in requests, like "ipp://localhost/printers/office".
+    pub fn new(stream: S, printer_uri: &str) -> Self {
+        let resource = printer_uri
+            .split_once("://")
+            .and_then(|(_, rest)| rest.find('/').map(|i| rest[i..].to_string()))
+            .unwrap_or_else(|| "/".to_string());
+
+        Self {
+            stream: BufReader::new(stream),
+            printer_uri: printer_uri.to_string(),
+            resource,
+        }
+    }
+
+    fn read_line(&mut self) -> Result<String, IppPrintError> {
+        let mut line = String::new();
+        if self.stream.read_line(&mut line)? == 0 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(line.trim_end().to_string())
+    }
+
+    /// Reads status line and headers, skipping interim 1xx responses.
+    /// Returns the status code and headers with lowercase names.
+    fn read_head(&mut self) -> Result<(u16, Vec<(String, String)>), IppPrintError> {
+        loop {
+            let status_line = self.read_line()?;
+            let status = status_line
+                .split(' ')
+                .nth(1)
+                .and_then(|e| e.parse::<u16>().ok())
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("invalid HTTP status line: {}", status_line),
+                    )
+                })?;
+
+            let mut headers = vec![];
+            loop {
+                let line = self.read_line()?;
+                if line.is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = line.split_once(':') {
+                    headers.push((name.trim().to_ascii_lowercase(), value.trim().to_string()));
+                }
+            }
+
+            if !(100..200).contains(&status) {
+                return Ok((status, headers));
+            }
+        }
+    }
+
+    fn read_response(&mut self) -> Result<Box<dyn Read>, IppPrintError> {
+        let (status, headers) = self.read_head()?;
+        let header = |name: &str| {
+            headers
+                .iter()
+                .find(|(key, _)| key == name)
+                .map(|(_, value)| value.as_str())
+        };
+
+        let body = if header("transfer-encoding").is_some_and(|e| e.contains("chunked")) {
+            self.read_chunked_body()?
+        } else if let Some(len) = header("content-length") {
+            let len = len.parse::<usize>().map_err(|_| {
+                io::Error::new(io::ErrorKind::InvalidData, "invalid Content-Length")
+            })?;
+            if len > MAX_BODY_LEN {
+                return Err(body_too_large().into());
+            }
+            let mut body = vec![0; len];
+            self.stream.read_exact(&mut body)?;
+            body
+        } else {
+            let mut body = vec![];
+            (&mut self.stream)
+                .take(MAX_BODY_LEN as u64 + 1)
+                .read_to_end(&mut body)?;
+            if body.len() > MAX_BODY_LEN {
+                return Err(body_too_large().into());
+            }
+            body
+        };
+
+        if status != 200 {
+            return Err(
+                io::Error::other(format!("printer returned HTTP status {}", status)).into(),
+            );
+        }
+
+        Ok(Box::new(io::Cursor::new(body)))
+    }
+
+    fn read_chunked_body(&mut self) -> Result<Vec<u8>, IppPrintError> {
+        let mut body = vec![];
+        loop {
+            let size_line = self.read_line()?;
+            let size = size_line.split(';').next().unwrap_or("");
+            let size = usize::from_str_radix(size.trim(), 16).map_err(|_| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("invalid chunk size: {}", size_line),
+                )
+            })?;
+            if size == 0 {
+                // Skip trailers.
+                while !self.read_line()?.is_empty() {}
+                return Ok(body);
+            }
+
+            let start = body.len();
+            let end = start
+                .checked_add(size)
+                .filter(|e| *e <= MAX_BODY_LEN)
+                .ok_or_else(body_too_large)?;
+            body.resize(end, 0);
+            self.stream.read_exact(&mut body[start..])?;
+            self.read_line()?;
+        }
+    }
+}
+
+/// Limit on response bodies, so that a bogus Content-Length or chunk size makes
+/// reading fail instead of exhausting memory. Responses only have attributes,
+/// which are limited to less than this when decoded.
+const MAX_BODY_LEN: usize = 64 * 1024 * 1024;
+
+fn body_too_large() -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, "response body is too large")
+}
+
+impl<S> Transport for StreamTransport<S>
+where
+    S: Read + Write + Send,
+{
+    fn printer_uri(&self) -> String {
+        self.printer_uri.clone()
+    }
+
+    fn send_ipp(&mut self, request: Vec<u8>) -> Result<Box<dyn Read>, IppPrintError> {
+        let head = format!(
+            "POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/ipp\r\nContent-Length: {}\r\n\r\n",
+            self.resource,
+            request.len()
+        );
+        let stream = self.stream.get_mut();
+        stream.write_all(head.as_bytes())?;
+        stream.write_all(&request)?;
+        stream.flush()?;
+
+        self.read_response()
+    }
+
+    fn send_ipp_stream(
+        &mut self,
+        request: Vec<u8>,
+        mut data: Box<dyn Read + Send>,
+    ) -> Result<Box<dyn Read>, IppPrintError> {
+        let head = format!(
+            "POST {} HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/ipp\r\nTransfer-Encoding: chunked\r\n\r\n",
+            self.resource,
+        );
+        let stream = self.stream.get_mut();
+        stream.write_all(head.as_bytes())?;
+        write!(stream, "{:x}\r\n", request.len())?;
+        stream.write_all(&request)?;
+        stream.write_all(b"\r\n")?;
+
+        let mut buf = vec![0u8; 65536];
+        loop {
+            let len = data.read(&mut buf)?;
+            if len == 0 {
+                break;
+            }
+            write!(stream, "{:x}\r\n", len)?;
+            stream.write_all(&buf[..len])?;
+            stream.write_all(b"\r\n")?;
+        }
+        stream.write_all(b"0\r\n\r\n")?;
+        stream.flush()?;
+
+        self.read_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn report_progress() {
+        let mut progress = vec![];
+        let mut reader = ProgressReader::new(&[0u8; 10][..], |e| progress.push(e));
+        let mut buf = [0u8; 4];
+        while reader.read(&mut buf).unwrap() > 0 {}
+        assert_eq!(vec![4, 8, 10], progress);
+    }
+
+    #[test]
+    fn printer_uri_scheme() {
+        let transport = HttpTransport::new("192.0.2.1:631").unwrap();
+        assert_eq!("ipp://192.0.2.1:631/ipp/print", transport.printer_uri());
+        assert_eq!("http://192.0.2.1:631/ipp/print", transport.uri.http_url());
+
+        let transport = HttpTransport::new("ipp://192.0.2.1/printers/office").unwrap();
+        assert_eq!(
+            "ipp://192.0.2.1:631/printers/office",
+            transport.printer_uri()
+        );
+        assert_eq!(
+            "http://192.0.2.1:631/printers/office",
+            transport.uri.http_url()
+        );
+
+        let transport = HttpTransport::new("ipps://printer.local:443").unwrap();
+        assert_eq!(
+            "ipps://printer.local:443/ipp/print",
+            transport.printer_uri()
+        );
+        assert_eq!(
+            "https://printer.local:443/ipp/print",
+            transport.uri.http_url()
+        );
+
+        assert!(matches!(
+            HttpTransport::new("http://printer.local"),
+            Err(IppPrintError::InvalidUri(_))
+        ));
+    }
+
+    #[test]
+    fn parse_printer_uri() {
+        assert_eq!(
+            PrinterUri {
+                secure: true,
+                host: "[2001:db8::1]".to_string(),
+                port: 8631,
+                path: "/ipp/print/2".to_string(),
+            },
+            PrinterUri::parse("IPPS://[2001:db8::1]:8631/ipp/print/2").unwrap()
+        );
+        assert_eq!(631, PrinterUri::parse("ipp://[2001:db8::1]/").unwrap().port);
+        assert_eq!("/", PrinterUri::parse("printer.local/").unwrap().path);
+
+        for uri in [
+            "http://printer.local",
+            "ipp://",
+            "printer.local:x",
+            "ipp://a@b",
+        ] {
+            assert!(matches!(
+                PrinterUri::parse(uri),
+                Err(IppPrintError::InvalidUri(e)) if e == uri
+            ));
+        }
+    }
+
+    /// Replays canned bytes and records what was written.
+    struct FakeStream {
+        input: io::Cursor<Vec<u8>>,
+        output: Vec<u8>,
+    }
+
+    impl Read for FakeStream {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for FakeStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn fake_transport(input: &[u8]) -> StreamTransport<FakeStream> {
+        StreamTransport::new(
+            FakeStream {
+                input: io::Cursor::new(input.to_vec()),
+                output: vec![],
+            },
+            "ipp://localhost/ipp/print",
+        )
+    }
+
+    fn read_all(mut reader: Box<dyn Read>) -> Vec<u8> {
+        let mut buf = vec![];
+        reader.read_to_end(&mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn stream_transport() {
+        let mut transport = fake_transport(
+            b"HTTP/1.1 100 Continue\r\n\r\n\
+              HTTP/1.1 200 OK\r\nContent-Length: 3\r\n\r\nabc\
+              HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nde\r\n1;x=y\r\nf\r\n0\r\n\r\n\
+              HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\n\r\n",
+        );
+
+        assert_eq!(
+            b"abc",
+            &read_all(transport.send_ipp(b"req1".to_vec()).unwrap())[..]
+        );
+        assert_eq!(
+            b"def",
+            &read_all(transport.send_ipp(b"req2".to_vec()).unwrap())[..]
+        );
+        assert!(transport.send_ipp(b"req3".to_vec()).is_err());
+
+        let written = String::from_utf8(transport.stream.get_ref().output.clone()).unwrap();
+        assert!(written.starts_with("POST /ipp/print HTTP/1.1\r\n"));
+        assert!(written.contains("Content-Length: 4\r\n\r\nreq1POST"));
+    }
+
+    #[test]
+    fn stream_transport_bogus_sizes() {
+        for response in [
+            &b"HTTP/1.1 200 OK\r\nContent-Length: 18446744073709551615\r\n\r\n"[..],
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\nffffffffffffffff\r\n",
+            b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+              1\r\na\r\nffffffffffffffff\r\n",
+        ] {
+            let mut transport = fake_transport(response);
+            assert!(transport.send_ipp(b"req".to_vec()).is_err());
+        }
+    }
+
+    #[test]
+    fn stream_transport_chunked_request() {
+        let mut transport = fake_transport(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok");
+
+        let data = io::Cursor::new(b"document".to_vec());
+        let resp = transport
+            .send_ipp_stream(b"req".to_vec(), Box::new(data))
+            .unwrap();
+        assert_eq!(b"ok", &read_all(resp)[..]);
+
+        let written = String::from_utf8(transport.stream.get_ref().output.clone()).unwrap();
+        assert!(written.contains("Transfer-Encoding: chunked\r\n\r\n"));
+        assert!(written.ends_with("\r\n\r\n3\r\nreq\r\n8\r\ndocument\r\n0\r\n\r\n"));
+    }
+
+    #[test]
+    fn http_transport_reuses_connection() {
+        use std::net::TcpListener;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let connections = Arc::new(AtomicUsize::new(0));
+
+        let counter = connections.clone();
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let mut stream = BufReader::new(stream.unwrap());
+                'requests: loop {
+                    let mut len = 0;
+                    loop {
+                        let mut line = String::new();
+                        if stream.read_line(&mut line).unwrap() == 0 {
+                            break 'requests;
+                        }
+                        if let Some(value) =
+                            line.to_ascii_lowercase().strip_prefix("content-length:")
+                        {
+                            len = value.trim().parse().unwrap();
+                        }
+                        if line == "\r\n" {
+                            break;
+                        }
+                    }
+                    let mut body = vec![0; len];
+                    stream.read_exact(&mut body).unwrap();
+                    stream
+                        .get_mut()
+                        .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok")
+                        .unwrap();
+                }
+            }
+        });
+
+        let mut transport = HttpTransport::new(&addr.to_string()).unwrap();
+        for _ in 0..3 {
+            let resp = transport.send_ipp(b"req".to_vec()).unwrap();
+            assert_eq!(b"ok", &read_all(resp)[..]);
+        }
+        assert_eq!(1, connections.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,278 @@
+//! Apple Raster (URF), the raster format of AirPrint.
+//!
+//! Pages are compressed the same way as PWG Raster, but a document starts with
+//! the number of pages and each page has a 32-byte header.
+
+use crate::error::IppPrintError;
+use crate::ipp::Sides;
+use crate::pwgraster::{self, ColorSpace, ImageEncoder, PageHeader, PageWriter, Pixel, SheetBack};
+use std::io::prelude::*;
+use std::io::BufWriter;
+
+/// urf-supported keywords for color spaces and the pwg-raster-document-type
+/// each corresponds to.
+const RASTER_TYPES: &[(&str, &str)] = &[
+    ("W8", "sgray_8"),
+    ("W16", "sgray_16"),
+    ("SRGB24", "srgb_8"),
+    ("SRGB48", "srgb_16"),
+    ("ADOBERGB24", "adobe-rgb_8"),
+    ("ADOBERGB48", "adobe-rgb_16"),
+    ("DEVCMYK32", "cmyk_8"),
+];
+
+/// Capabilities in urf-supported, like ["V1.4", "W8", "SRGB24", "RS300-600", "DM1"].
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+pub struct UrfSupported {
+    /// Supported color spaces as pwg-raster-document-type keywords like "srgb_8".
+    pub raster_types: Vec<String>,
+    /// Supported resolutions in dpi, same in both directions.
+    pub resolutions: Vec<u32>,
+    /// How back sides of duplex sheets should be transformed, if duplex is supported.
+    pub sheet_back: Option<SheetBack>,
+}
+
+impl UrfSupported {
+    /// Parses keywords of urf-supported. Unknown keywords are ignored.
+    pub fn parse<S>(keywords: &[S]) -> Self
+    where
+        S: AsRef<str>,
+    {
+        let mut result = Self::default();
+
+        for keyword in keywords.iter().map(|e| e.as_ref()) {
+            if let Some((_, raster_type)) = RASTER_TYPES.iter().find(|(e, _)| *e == keyword) {
+                result.raster_types.push(raster_type.to_string());
+            } else if let Some(resolutions) = keyword.strip_prefix("RS") {
+                result
+                    .resolutions
+                    .extend(resolutions.split('-').filter_map(|e| e.parse::<u32>().ok()));
+            } else if let Some(mode) = keyword.strip_prefix("DM") {
+                result.sheet_back = match mode {
+                    "1" => Some(SheetBack::Normal),
+                    "2" => Some(SheetBack::Flipped),
+                    "3" => Some(SheetBack::Rotated),
+                    "4" => Some(SheetBack::ManualTumble),
+                    _ => result.sheet_back,
+                };
+            }
+        }
+
+        result
+    }
+}
+
+/// Writes a URF document. The number of pages has to be known in advance, since
+/// it comes first in the document.
+pub struct UrfDocumentWriter<W> {
+    writer: W,
+    header: PageHeader,
+    sheet_back: SheetBack,
+    pages: u32,
+}
+
+impl<W> UrfDocumentWriter<W>
+where
+    W: Write,
+{
+    /// Writes the file header. Every page uses geometry and color space of header,
+    /// which has to be a raster type URF supports.
+    pub fn new(
+        mut writer: W,
+        header: PageHeader,
+        sheet_back: SheetBack,
+        page_count: u32,
+    ) -> Result<Self, IppPrintError> {
+        color_space(&header)?;
+
+        writer.write_all(b"UNIRAST\0")?;
+        writer.write_all(&page_count.to_be_bytes())?;
+
+        Ok(Self {
+            writer,
+            header,
+            sheet_back,
+            pages: 0,
+        })
+    }
+
+    /// Writes a page given as rows from the top of the front side.
+    /// The pixel type must match bits_per_pixel of the header.
+    pub fn write_page_rows<P, I, R>(&mut self, rows: I) -> Result<usize, IppPrintError>
+    where
+        P: Pixel,
+        I: IntoIterator<Item = R>,
+        R: AsRef<[P]>,
+    {
+        if P::BITS_PER_PIXEL != self.header.bits_per_pixel() {
+            return Err(IppPrintError::RasterError(format!(
+                "pixels have {} bits but the header says {}",
+                P::BITS_PER_PIXEL,
+                self.header.bits_per_pixel()
+            )));
+        }
+
+        let (cross_feed, feed) =
+            pwgraster::back_side_transform(self.header.sides(), self.sheet_back, self.pages);
+
+        let mut writer = BufWriter::new(&mut self.writer);
+        let page_header = page_header(&self.header)?;
+        writer.write_all(&page_header)?;
+        let mut written = page_header.len();
+        let encoder = ImageEncoder::for_urf(self.header.width(), self.header.height());
+        written += pwgraster::write_page_data(
+            &mut writer,
+            &self.header,
+            cross_feed,
+            feed,
+            rows,
+            Some(encoder),
+        )?;
+        writer.flush()?;
+
+        self.pages += 1;
+        Ok(written)
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<W> PageWriter for UrfDocumentWriter<W>
+where
+    W: Write,
+{
+    fn write_page<P>(&mut self, pixels: &[P]) -> Result<usize, IppPrintError>
+    where
+        P: Pixel,
+    {
+        let (width, height) = (self.header.width() as usize, self.header.height() as usize);
+        if pixels.len() != width * height {
+            return Err(IppPrintError::RasterError(format!(
+                "page has {} pixels but the header says {}x{}",
+                pixels.len(),
+                width,
+                height
+            )));
+        }
+        self.write_page_rows(pixels.chunks(width.max(1)))
+    }
+}
+
+/// URF color space of the cupsColorSpace in header.
+fn color_space(header: &PageHeader) -> Result<u8, IppPrintError> {
+    match header.color_space() {
+        Some(ColorSpace::Sgray) => Ok(0),
+        Some(ColorSpace::Srgb) => Ok(1),
+        Some(ColorSpace::AdobeRgb) => Ok(3),
+        Some(ColorSpace::Cmyk) => Ok(6),
+        color_space => Err(IppPrintError::Unsupported(format!(
+            "color space {:?} in URF",
+            color_space
+        ))),
+    }
+}
+
+/// 32-byte page header.
+fn page_header(header: &PageHeader) -> Result<[u8; 32], IppPrintError> {
+    let mut buf = [0u8; 32];
+    buf[0] = header.bits_per_pixel() as u8;
+    buf[1] = color_space(header)?;
+    // 1: Simplex, 2: Short-edge duplex, 3: Long-edge duplex
+    buf[2] = match header.sides() {
+        Sides::OneSided => 1,
+        Sides::TwoSidedShortEdge => 2,
+        Sides::TwoSidedLongEdge => 3,
+    };
+    buf[3] = header.print_quality().map_or(0, |e| e as u8);
+    buf[12..16].copy_from_slice(&header.width().to_be_bytes());
+    buf[16..20].copy_from_slice(&header.height().to_be_bytes());
+    buf[20..24].copy_from_slice(&header.resolution()[0].to_be_bytes());
+    Ok(buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pwgraster::{GrayColor, PwgDocument, RasterDocumentWriter};
+
+    #[test]
+    fn parse_urf_supported() {
+        let supported =
+            UrfSupported::parse(&["V1.4", "CP1", "W8", "SRGB24", "RS300-600", "DM3", "IS1"]);
+        assert_eq!(vec!["sgray_8", "srgb_8"], supported.raster_types);
+        assert_eq!(vec![300, 600], supported.resolutions);
+        assert_eq!(Some(SheetBack::Rotated), supported.sheet_back);
+    }
+
+    #[test]
+    fn write_document() {
+        let mut header = PageHeader::default();
+        header.set_media_size(&crate::media::MediaSize::from_name("iso_a6_105x148mm").unwrap());
+        header.set_raster_type("sgray_8").unwrap();
+        let (width, height) = (header.width(), header.height());
+        let page = vec![GrayColor(0x80); (width * height) as usize];
+
+        let mut writer =
+            UrfDocumentWriter::new(Vec::new(), header.clone(), SheetBack::Normal, 1).unwrap();
+        writer.write_page(&page).unwrap();
+        let out = writer.into_inner();
+
+        assert_eq!(b"UNIRAST\0\0\0\0\x01", &out[..12]);
+        let page_header = &out[12..44];
+        assert_eq!([8, 0, 1, 0], page_header[..4]);
+        assert_eq!(width.to_be_bytes(), page_header[12..16]);
+        assert_eq!(height.to_be_bytes(), page_header[16..20]);
+        assert_eq!(300u32.to_be_bytes(), page_header[20..24]);
+
+        // Page data is the same as PWG Raster.
+        let mut pwg = RasterDocumentWriter::new(Vec::new(), header, SheetBack::Normal).unwrap();
+        pwg.write_page(&page).unwrap();
+        let pwg = pwg.into_inner();
+        assert_eq!(pwg[4 + 1796..], out[44..]);
+        assert!(PwgDocument::read_from_stream(&mut pwg.as_slice()).is_ok());
+
+        let mut header = PageHeader::default();
+        header.set_raster_type("black_1").unwrap();
+        assert!(UrfDocumentWriter::new(Vec::new(), header, SheetBack::Normal, 1).is_err());
+    }
+
+    #[test]
+    fn write_long_literal_runs() {
+        let mut header = PageHeader::default();
+        header.set_media_size(&crate::media::MediaSize::from_name("iso_a6_105x148mm").unwrap());
+        header.set_raster_type("sgray_8").unwrap();
+        let (width, height) = (header.width() as usize, header.height() as usize);
+        // No two neighbouring pixels are the same, as in a photo.
+        let row = (0..width)
+            .map(|x| GrayColor((x % 251) as u8))
+            .collect::<Vec<_>>();
+
+        let mut writer = UrfDocumentWriter::new(Vec::new(), header, SheetBack::Normal, 1).unwrap();
+        writer.write_page_rows(vec![row; height]).unwrap();
+        let out = writer.into_inner();
+
+        // 0x80 would fill the rest of the row with white, so it must not appear.
+        let mut data = &out[44..];
+        let mut rows = 0;
+        while !data.is_empty() {
+            rows += data[0] as usize + 1;
+            data = &data[1..];
+            let mut x = 0;
+            while x < width {
+                let op = data[0];
+                assert_ne!(0x80, op);
+                let (pixels, len) = if op < 0x80 {
+                    (op as usize + 1, 1)
+                } else {
+                    (257 - op as usize, 257 - op as usize)
+                };
+                x += pixels;
+                data = &data[1 + len..];
+            }
+            assert_eq!(width, x);
+        }
+        assert_eq!(height, rows);
+    }
+}
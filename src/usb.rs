@@ -0,0 +1,179 @@
+//! IPP over USB (USB-IF IPP USB 1.0) using the printer class interface with protocol 0x04.
+//!
+//! Devices expose at least two such interfaces so that several HTTP connections can be
+//! open at once. Each [`UsbStream`] claims one of them.
+
+use crate::error::IppPrintError;
+use crate::transport::StreamTransport;
+use rusb::{Device, DeviceHandle, Direction, GlobalContext, TransferType};
+use std::io;
+use std::io::prelude::*;
+use std::time::Duration;
+
+const CLASS_PRINTER: u8 = 7;
+const SUBCLASS_PRINTER: u8 = 1;
+const PROTOCOL_IPP_USB: u8 = 4;
+
+/// How long to wait for a single bulk transfer.
+/// Printers can be slow to answer while warming up, so this is long.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// An IPP-USB interface with its bulk endpoints.
+#[derive(Debug, Clone)]
+pub struct UsbInterface {
+    pub device: Device<GlobalContext>,
+    pub interface: u8,
+    pub alt_setting: u8,
+    endpoint_in: u8,
+    endpoint_out: u8,
+}
+
+/// Lists IPP-USB interfaces of all connected devices.
+pub fn find_interfaces() -> Result<Vec<UsbInterface>, IppPrintError> {
+    let mut result = vec![];
+
+    for device in rusb::devices().map_err(usb_error)?.iter() {
+        let Ok(config) = device.active_config_descriptor() else {
+            continue;
+        };
+        for interface in config.interfaces() {
+            for desc in interface.descriptors() {
+                if (
+                    desc.class_code(),
+                    desc.sub_class_code(),
+                    desc.protocol_code(),
+                ) != (CLASS_PRINTER, SUBCLASS_PRINTER, PROTOCOL_IPP_USB)
+                {
+                    continue;
+                }
+
+                let bulk = |direction| {
+                    desc.endpoint_descriptors()
+                        .find(|e| {
+                            e.transfer_type() == TransferType::Bulk && e.direction() == direction
+                        })
+                        .map(|e| e.address())
+                };
+                if let (Some(endpoint_in), Some(endpoint_out)) =
+                    (bulk(Direction::In), bulk(Direction::Out))
+                {
+                    result.push(UsbInterface {
+                        device: device.clone(),
+                        interface: desc.interface_number(),
+                        alt_setting: desc.setting_number(),
+                        endpoint_in,
+                        endpoint_out,
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+fn usb_error(err: rusb::Error) -> IppPrintError {
+    IppPrintError::IOError(io::Error::other(err))
+}
+
+/// Byte stream over the bulk endpoints of a claimed IPP-USB interface.
+pub struct UsbStream {
+    handle: DeviceHandle<GlobalContext>,
+    interface: u8,
+    endpoint_in: u8,
+    endpoint_out: u8,
+    /// Data read from the device but not consumed yet.
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl UsbStream {
+    /// Opens the device and claims the interface, detaching the kernel driver if needed.
+    pub fn open(interface: &UsbInterface) -> Result<Self, IppPrintError> {
+        let handle = interface.device.open().map_err(usb_error)?;
+        // Not supported on every platform, in which case the driver just stays attached.
+        let _ = handle.set_auto_detach_kernel_driver(true);
+        handle
+            .claim_interface(interface.interface)
+            .map_err(usb_error)?;
+        handle
+            .set_alternate_setting(interface.interface, interface.alt_setting)
+            .map_err(usb_error)?;
+
+        Ok(Self {
+            handle,
+            interface: interface.interface,
+            endpoint_in: interface.endpoint_in,
+            endpoint_out: interface.endpoint_out,
+            buf: vec![],
+            pos: 0,
+        })
+    }
+
+    /// Opens the first IPP-USB interface found.
+    pub fn open_first() -> Result<Self, IppPrintError> {
+        match find_interfaces()?.first() {
+            Some(interface) => UsbStream::open(interface),
+            None => Err(IppPrintError::NotAvailable(
+                "no IPP-USB printer is connected".to_string(),
+            )),
+        }
+    }
+}
+
+impl Read for UsbStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.buf.len() {
+            // Read a whole transfer at once. Reading less than a packet overflows.
+            self.buf.resize(16384, 0);
+            let len = loop {
+                match self
+                    .handle
+                    .read_bulk(self.endpoint_in, &mut self.buf, TIMEOUT)
+                {
+                    // Devices send zero-length packets between responses.
+                    Ok(0) => continue,
+                    Ok(len) => break len,
+                    Err(err) => {
+                        self.buf.clear();
+                        self.pos = 0;
+                        return Err(io::Error::other(err));
+                    }
+                }
+            };
+            self.buf.truncate(len);
+            self.pos = 0;
+        }
+
+        let len = buf.len().min(self.buf.len() - self.pos);
+        buf[..len].copy_from_slice(&self.buf[self.pos..self.pos + len]);
+        self.pos += len;
+        Ok(len)
+    }
+}
+
+impl Write for UsbStream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.handle
+            .write_bulk(self.endpoint_out, buf, TIMEOUT)
+            .map_err(io::Error::other)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for UsbStream {
+    fn drop(&mut self) {
+        let _ = self.handle.release_interface(self.interface);
+    }
+}
+
+/// Transport for the first IPP-USB printer found, usable with PrintClient::with_transport.
+pub fn transport() -> Result<StreamTransport<UsbStream>, IppPrintError> {
+    Ok(StreamTransport::new(
+        UsbStream::open_first()?,
+        "ipp://localhost/ipp/print",
+    ))
+}